@@ -1,24 +1,68 @@
-use mg_core::*;
+use mg_core::vfs;
+#[cfg(not(target_arch = "wasm32"))]
+use mg_render::{assets::Reloaded, hot_reload::HotReload};
 use mg_render::{
-    geometry::Geometry, gltf_loader, graphics::Graphics, material, material::Material,
-    texture::Texture, mesh::Mesh, mesh
+    assets::{AssetServer, Handle, LoadState, Loading},
+    graphics::Graphics,
+    gltf_loader::LoadOptions,
+    model::Model,
+    scene::Scene,
+    Renderer,
 };
 
 pub struct Assets {
-    defaults: material::Defaults,
-    pub meshes: Vec<Mesh>,
+    server: AssetServer,
+    pub model: Loading<Model>,
+    // the model in the scene, once it's loaded
+    placed: Option<Handle<Model>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    hot_reload: HotReload,
 }
 
 impl Assets {
     pub fn new(graphics: &Graphics) -> Assets {
-        let defaults = material::Defaults::new(graphics);
-        let meshes = gltf_loader::meshes_from_separated(
-            graphics,
-            &defaults,
-            "../../assets/PKG_A_Curtains/",
-            "NewSponza_Curtains_glTF",
+        // the repo's assets, beside the native build or served by the web server
+        #[cfg(not(target_arch = "wasm32"))]
+        vfs::VFS.mount("assets", vfs::Dir::new("../../assets"));
+        #[cfg(target_arch = "wasm32")]
+        vfs::VFS.mount("assets", vfs::Http::new("assets"));
+
+        let server = AssetServer::new(graphics);
+        let model = server.load_model_async(
+            "assets/PKG_A_Curtains/NewSponza_Curtains_glTF.gltf",
+            &LoadOptions::default(),
         );
+        Self {
+            server,
+            model,
+            placed: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            hot_reload: HotReload::default(),
+        }
+    }
+
+    // Uploads a few loaded textures, places the model once it's ready and
+    // reloads edited shaders and assets on native builds
+    pub fn update(&mut self, graphics: &Graphics, renderer: &mut Renderer, scene: &mut Scene) {
+        self.server.update(graphics);
+        if self.placed.is_none() && self.model.state() == LoadState::Ready {
+            let model = self.model.handle().unwrap();
+            scene.instantiate_model(graphics, &model);
+            self.placed = Some(model);
+        }
 
-        Self { defaults, meshes }
+        #[cfg(not(target_arch = "wasm32"))]
+        for reloaded in self
+            .hot_reload
+            .update(graphics, renderer, &self.server, scene)
+        {
+            if let Reloaded::Model { old, new } = reloaded {
+                if self.placed.as_ref() == Some(&old) {
+                    self.placed = Some(new);
+                }
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = renderer;
     }
 }
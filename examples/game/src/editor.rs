@@ -60,26 +60,23 @@ impl Editor {
     }
     pub fn start_edit(&mut self, graphics: &Graphics) {
         let res = graphics
-            .window
-            .borrow_mut()
+            .window()
             .set_cursor_grab(winit::window::CursorGrabMode::Confined);
         res.or_else(|_e| {
             graphics
-                .window
-                .borrow_mut()
+                .window()
                 .set_cursor_grab(winit::window::CursorGrabMode::Locked)
         })
         .unwrap();
-        graphics.window.borrow_mut().set_cursor_visible(false);
+        graphics.window().set_cursor_visible(false);
         self.state = State::Edit;
     }
     pub fn start_menu(&mut self, graphics: &Graphics) {
         graphics
-            .window
-            .borrow_mut()
+            .window()
             .set_cursor_grab(winit::window::CursorGrabMode::None)
             .unwrap();
-        graphics.window.borrow_mut().set_cursor_visible(true);
+        graphics.window().set_cursor_visible(true);
         self.state = State::Menu;
     }
 }
@@ -8,7 +8,7 @@ use assets::Assets;
 use bitflags::bitflags;
 use editor::Editor;
 use mg_core::*;
-use mg_render::{graphics::Graphics, instance, scene::Scene};
+use mg_render::{graphics::Graphics, scene::Scene};
 use winit::{
     event::{ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent},
     event_loop::ControlFlow,
@@ -56,20 +56,8 @@ impl App {
         let graphics = Graphics::new(event_loop).await;
         let renderer = mg_render::Renderer::new(&graphics);
         let egui = ui::Egui::new(&graphics);
-        let mut scene = Scene::new(&graphics);
+        let scene = Scene::new(&graphics);
         let assets = Assets::new(&graphics);
-        for mesh in assets.meshes.iter() {
-            scene.instantiate_mesh(
-                &graphics,
-                mesh.clone(),
-                instance::Params {
-                    amt: 1,
-                    bin: None,
-                    buffer: None,
-                    range: None,
-                },
-            );
-        }
         App {
             graphics,
             renderer,
@@ -93,8 +81,11 @@ impl App {
     }
 
     pub fn update(&mut self) {
+        self.assets
+            .update(&self.graphics, &mut self.renderer, &mut self.scene);
         self.update_ui();
-        self.graphics.window.borrow().request_redraw();
+        self.scene.update(&self.graphics);
+        self.graphics.window().request_redraw();
     }
     fn update_ui(&mut self) {
         match &mut self.state {
@@ -109,6 +100,10 @@ impl App {
                             if ui.button("editor").clicked() {
                                 self.state = State::Editor(Editor::new());
                             }
+                            if !self.assets.model.is_done() {
+                                let progress = self.assets.model.progress();
+                                ui.add(egui::ProgressBar::new(progress).text("loading"));
+                            }
                         });
                 });
             }
@@ -145,6 +140,7 @@ impl App {
             .graphics
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.renderer.deform_pass(&mut encoder, &self.scene);
         self.renderer.geometry_pass(&mut encoder, &self.scene);
         self.renderer.ray_pass(&mut encoder, &mut self.scene);
         self.graphics.queue.submit(Some(encoder.finish()));
@@ -153,16 +149,13 @@ impl App {
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-        let output = self.graphics.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let frame = self.graphics.frame()?;
         self.egui.update_buffers(&mut encoder, &self.graphics);
         {
             let mut comp_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("comp pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view: &frame.view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
@@ -173,12 +166,12 @@ impl App {
                 timestamp_writes: None,
                 occlusion_query_set: None,
             });
-            self.renderer.compose_pass(&mut comp_pass);
+            self.renderer.compose_pass(&mut comp_pass, &self.scene);
             self.egui.render(&mut comp_pass);
         }
 
         self.graphics.queue.submit(Some(encoder.finish()));
-        output.present();
+        frame.present();
 
         self.scene.camera.update(&self.graphics);
         Ok(())
@@ -1,11 +1,5 @@
-struct CameraUniform {
-    view_proj: mat4x4<f32>,
-}
-
-@group(0) @binding(0) var<uniform> camera: CameraUniform;
-
-@group(1) @binding(0) var albedo_tx: texture_3d<f32>;
-@group(1) @binding(1) var albedo_s: sampler;
+#include "camera.wgsl"
+#include "volume.wgsl"
 
 struct VertexInput {
   @location(0) pos: vec2f,
@@ -76,7 +70,7 @@ struct FragmentOutput {
 @fragment
 fn fs_main(in: VertexOutput) -> FragmentOutput {
   let view_position = in.pos.xyz / in.pos.w;
-  let albedo = textureSample(albedo_tx, albedo_s, in.tx_coords);
+  let albedo = textureSample(volume_tx, volume_s, in.tx_coords);
   var out: FragmentOutput;
   out.albedo = albedo;
   out.position = in.pos;
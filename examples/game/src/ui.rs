@@ -45,10 +45,10 @@ impl Egui {
         // Tell egui to use these fonts:
         egui.set_fonts(fonts);
         let winit =
-            egui_winit::State::new(egui.viewport_id(), &*graphics.window.borrow(), None, None);
+            egui_winit::State::new(egui.viewport_id(), &*graphics.window(), None, None);
         let screen_descriptor = ScreenDescriptor {
             size_in_pixels: [graphics.width, graphics.height],
-            pixels_per_point: egui_winit::pixels_per_point(&egui, &*graphics.window.borrow()),
+            pixels_per_point: egui_winit::pixels_per_point(&egui, &*graphics.window()),
         };
         let renderer = Renderer::new(&graphics.device, graphics.tx_format_surface, None, 1);
         Egui {
@@ -76,10 +76,10 @@ impl Egui {
     where
         F: FnOnce(&egui::Context),
     {
-        let raw_input = self.winit.take_egui_input(&*graphics.window.borrow());
+        let raw_input = self.winit.take_egui_input(&*graphics.window());
         let full_output = self.egui.run(raw_input.clone(), closure);
         self.winit.handle_platform_output(
-            &*graphics.window.borrow(),
+            &*graphics.window(),
             &self.egui,
             full_output.platform_output,
         );
@@ -88,7 +88,7 @@ impl Egui {
             .tessellate(full_output.shapes, full_output.pixels_per_point);
         self.screen_descriptor = egui_wgpu::renderer::ScreenDescriptor {
             size_in_pixels: [graphics.width, graphics.height],
-            pixels_per_point: egui_winit::pixels_per_point(&self.egui, &*graphics.window.borrow()),
+            pixels_per_point: egui_winit::pixels_per_point(&self.egui, &*graphics.window()),
         };
         for (id, image_delta) in &full_output.textures_delta.set {
             self.renderer
@@ -0,0 +1,133 @@
+use mg_core::*;
+use mg_render::{
+    cooked::{self, CookOptions},
+    gltf_loader::{self, GltfSrc, LoadOptions, NormalMode},
+};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str =
+    "usage: mg_cook [--compress] [--smooth-normals] [--out <dir>] <gltf or package dir>...
+
+Cooks each .gltf and .glb, or every one inside a package directory, into a
+.mgc beside it, or under --out keeping its path relative to the argument.
+Loading a .mgc with the AssetServer skips parsing and mipmapping.
+
+  --compress        store 8 bit textures as BC1, or BC3 with alpha
+  --smooth-normals  average generated normals across faces";
+
+struct Args {
+    cook: CookOptions,
+    load: LoadOptions,
+    out: Option<PathBuf>,
+    inputs: Vec<PathBuf>,
+}
+
+fn parse_args() -> Result<Args> {
+    let mut args = Args {
+        cook: CookOptions::default(),
+        load: LoadOptions::default(),
+        out: None,
+        inputs: vec![],
+    };
+    let mut argv = std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--compress" => args.cook.compress = true,
+            "--smooth-normals" => args.load.normals = NormalMode::Smooth,
+            "--out" => {
+                let dir = argv
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--out needs a dir"))?;
+                args.out = Some(dir.into());
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ if arg.starts_with('-') => anyhow::bail!("unknown option {}", arg),
+            _ => args.inputs.push(arg.into()),
+        }
+    }
+    if args.inputs.is_empty() {
+        anyhow::bail!("nothing to cook");
+    }
+    Ok(args)
+}
+
+fn is_gltf(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "gltf" || ext == "glb")
+}
+
+// glTF files under dir, sorted so output is stable
+fn find_gltfs(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            find_gltfs(&path, found)?;
+        } else if is_gltf(&path) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn cook_file(args: &Args, source: &Path, out: &Path) -> Result<()> {
+    let path = source
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("{} isn't utf-8", source.display()))?;
+    let prepared = gltf_loader::prepare(GltfSrc::Path(path), &args.load)?;
+    let bytes = cooked::cook(&prepared, &args.cook)?;
+    if let Some(dir) = out.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(out, &bytes)?;
+    log::info!("{} -> {} ({} bytes)", path, out.display(), bytes.len());
+    Ok(())
+}
+
+fn main() {
+    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let mut failed = false;
+    for input in &args.inputs {
+        let mut sources = vec![];
+        let base = match input.is_dir() {
+            true => {
+                if let Err(err) = find_gltfs(input, &mut sources) {
+                    log::error!("{}: {}", input.display(), err);
+                    failed = true;
+                }
+                input.as_path()
+            }
+            false => {
+                sources.push(input.clone());
+                input.parent().unwrap_or(Path::new(""))
+            }
+        };
+        for source in sources {
+            let cooked = source.with_extension(cooked::EXTENSION);
+            let out = match &args.out {
+                Some(dir) => dir.join(cooked.strip_prefix(base).unwrap_or(&cooked)),
+                None => cooked,
+            };
+            if let Err(err) = cook_file(&args, &source, &out) {
+                log::error!("{}: {:#}", source.display(), err);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,96 @@
+use std::fmt;
+
+// Asset loading failures, each naming the file or glTF element at fault
+#[derive(Debug)]
+pub enum AssetError {
+    MissingFile {
+        path: String,
+        source: std::io::Error,
+    },
+    InvalidGltf {
+        asset: String,
+        reason: String,
+    },
+    InvalidUri {
+        asset: String,
+        uri: String,
+    },
+    UnsupportedAccessor {
+        asset: String,
+        accessor: String,
+        reason: String,
+    },
+    BufferViewOutOfRange {
+        asset: String,
+        view: usize,
+        end: usize,
+        len: usize,
+    },
+    BadImage {
+        asset: String,
+        reason: String,
+    },
+    InvalidCooked {
+        asset: String,
+        reason: String,
+    },
+    InvalidArchive {
+        archive: String,
+        source: zip::result::ZipError,
+    },
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::MissingFile { path, source } => {
+                write!(f, "can't read \"{}\": {}", path, source)
+            }
+            AssetError::InvalidGltf { asset, reason } => {
+                write!(f, "invalid glTF \"{}\": {}", asset, reason)
+            }
+            AssetError::InvalidUri { asset, uri } => {
+                let uri = uri.get(..64).unwrap_or(uri);
+                write!(f, "can't decode uri \"{}\" in \"{}\"", uri, asset)
+            }
+            AssetError::UnsupportedAccessor {
+                asset,
+                accessor,
+                reason,
+            } => write!(
+                f,
+                "unsupported accessor {} in \"{}\": {}",
+                accessor, asset, reason
+            ),
+            AssetError::BufferViewOutOfRange {
+                asset,
+                view,
+                end,
+                len,
+            } => write!(
+                f,
+                "buffer view {} in \"{}\" ends at {} past buffer length {}",
+                view, asset, end, len
+            ),
+            AssetError::BadImage { asset, reason } => {
+                write!(f, "can't decode image \"{}\": {}", asset, reason)
+            }
+            AssetError::InvalidCooked { asset, reason } => {
+                write!(f, "invalid cooked asset \"{}\": {}", asset, reason)
+            }
+            AssetError::InvalidArchive { archive, source } => {
+                write!(f, "invalid archive \"{}\": {}", archive, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AssetError::MissingFile { source, .. } => Some(source),
+            AssetError::InvalidArchive { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use crate::{AssetError, Lazy, Mutex, Result};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+
+pub type ReadFuture<'a> = Pin<Box<dyn Future<Output = io::Result<Vec<u8>>> + 'a>>;
+
+// A source of files addressed by slash separated paths relative to where
+// it's mounted
+pub trait Backend: Send + Sync {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>>;
+
+    // Backends that can't block override this, web requests use fetch
+    fn read_async<'a>(&'a self, path: &'a str) -> ReadFuture<'a> {
+        Box::pin(async move { self.read(path) })
+    }
+
+    // Where the file lives on disk, for watching it
+    fn native_path(&self, _path: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+// Collapses ".", ".." and repeated or back slashes. Absolute paths keep
+// their leading slash and ".." past the start of a relative path is kept
+pub fn normalize(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut parts: Vec<&str> = vec![];
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." if parts.last().is_some_and(|last| *last != "..") => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    let joined = parts.join("/");
+    match path.starts_with('/') {
+        true => format!("/{}", joined),
+        false => joined,
+    }
+}
+
+// Files under a native directory, an empty root resolves paths as given
+pub struct Dir {
+    root: PathBuf,
+}
+
+impl Dir {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    // Paths under a root can't climb out of it with ".." or replace it
+    // with an absolute path
+    fn join(&self, path: &str) -> io::Result<PathBuf> {
+        if self.root.as_os_str().is_empty() {
+            return Ok(PathBuf::from(path));
+        }
+        let path = normalize(path);
+        match Path::new(&path)
+            .components()
+            .all(|part| matches!(part, Component::Normal(_)))
+        {
+            true => Ok(self.root.join(path)),
+            false => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is outside {}", path, self.root.display()),
+            )),
+        }
+    }
+}
+
+impl Backend for Dir {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        std::fs::read(self.join(path)?)
+    }
+
+    fn native_path(&self, path: &str) -> Option<PathBuf> {
+        let path = self.join(path).ok()?;
+        path.is_file().then_some(path)
+    }
+}
+
+// Files held in memory, such as a bundle of include_bytes! assets
+#[derive(Default)]
+pub struct Memory {
+    files: HashMap<String, Cow<'static, [u8]>>,
+}
+
+impl Memory {
+    pub fn with(mut self, path: &str, bytes: impl Into<Cow<'static, [u8]>>) -> Self {
+        self.files.insert(normalize(path), bytes.into());
+        self
+    }
+}
+
+impl Backend for Memory {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        match self.files.get(path) {
+            Some(bytes) => Ok(bytes.to_vec()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+}
+
+// Files in a zip archive, .pak files are zips under another name
+pub struct Archive {
+    zip: Mutex<zip::ZipArchive<io::Cursor<Vec<u8>>>>,
+}
+
+impl Archive {
+    pub fn new(name: &str, bytes: Vec<u8>) -> Result<Self> {
+        let zip = zip::ZipArchive::new(io::Cursor::new(bytes)).map_err(|source| {
+            AssetError::InvalidArchive {
+                archive: name.to_string(),
+                source,
+            }
+        })?;
+        Ok(Self {
+            zip: Mutex::new(zip),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.zip.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Backend for Archive {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        let mut zip = self.zip.lock();
+        let mut file = zip.by_name(path).map_err(|err| match err {
+            zip::result::ZipError::FileNotFound => io::ErrorKind::NotFound.into(),
+            zip::result::ZipError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        })?;
+        let mut bytes = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+// Files served over http relative to a base url, an empty base is
+// relative to the page
+pub struct Http {
+    base: String,
+}
+
+impl Http {
+    pub fn new(base: &str) -> Self {
+        Self {
+            base: base.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        match self.base.is_empty() {
+            true => path.to_string(),
+            false => format!("{}/{}", self.base, path),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use super::*;
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_futures::JsFuture;
+
+    fn js_error(value: JsValue) -> io::Error {
+        io::Error::new(io::ErrorKind::Other, format!("{:?}", value))
+    }
+
+    fn status_error(url: &str, status: u16) -> io::Error {
+        match status {
+            404 => io::ErrorKind::NotFound.into(),
+            _ => io::Error::new(io::ErrorKind::Other, format!("{} returned {}", url, status)),
+        }
+    }
+
+    pub async fn fetch(url: &str) -> io::Result<Vec<u8>> {
+        let global = js_sys::global();
+        // loads run on the page and on wasm_thread workers
+        let promise = match global.dyn_ref::<web_sys::Window>() {
+            Some(window) => window.fetch_with_str(url),
+            None => global
+                .unchecked_ref::<web_sys::WorkerGlobalScope>()
+                .fetch_with_str(url),
+        };
+        let response: web_sys::Response = JsFuture::from(promise)
+            .await
+            .map_err(js_error)?
+            .unchecked_into();
+        if !response.ok() {
+            return Err(status_error(url, response.status()));
+        }
+        let buffer = JsFuture::from(response.array_buffer().map_err(js_error)?)
+            .await
+            .map_err(js_error)?;
+        Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+    }
+
+    // Blocking loaders can't await fetch, a synchronous request is allowed
+    // on workers and only returns text, one byte per char
+    pub fn request(url: &str) -> io::Result<Vec<u8>> {
+        let request = web_sys::XmlHttpRequest::new().map_err(js_error)?;
+        request
+            .open_with_async("GET", url, false)
+            .map_err(js_error)?;
+        request
+            .override_mime_type("text/plain; charset=x-user-defined")
+            .map_err(js_error)?;
+        request.send().map_err(js_error)?;
+        match request.status().map_err(js_error)? {
+            200 => {
+                let text = request
+                    .response_text()
+                    .map_err(js_error)?
+                    .unwrap_or_default();
+                Ok(text.encode_utf16().map(|c| c as u8).collect())
+            }
+            status => Err(status_error(url, status)),
+        }
+    }
+}
+
+impl Backend for Http {
+    #[cfg(target_arch = "wasm32")]
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        web::request(&self.url(path))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("can't request {} outside the web", self.url(path)),
+        ))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn read_async<'a>(&'a self, path: &'a str) -> ReadFuture<'a> {
+        Box::pin(async move { web::fetch(&self.url(path)).await })
+    }
+}
+
+// Mounted backends by path prefix. Later mounts shadow earlier ones and
+// a file missing from one falls through to the next
+pub struct Vfs {
+    mounts: Mutex<Vec<(String, Arc<dyn Backend>)>>,
+}
+
+impl Default for Vfs {
+    // Paths resolve as they always did, against the working directory
+    // natively and the page on the web
+    fn default() -> Self {
+        let vfs = Self {
+            mounts: Default::default(),
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        vfs.mount("", Dir::new(""));
+        #[cfg(target_arch = "wasm32")]
+        vfs.mount("", Http::new(""));
+        vfs
+    }
+}
+
+pub static VFS: Lazy<Vfs> = Lazy::new(Vfs::default);
+
+impl Vfs {
+    pub fn mount(&self, prefix: &str, backend: impl Backend + 'static) {
+        self.mounts
+            .lock()
+            .push((normalize(prefix), Arc::new(backend)));
+    }
+
+    // Removes the latest mount at the prefix
+    pub fn unmount(&self, prefix: &str) -> bool {
+        let prefix = normalize(prefix);
+        let mut mounts = self.mounts.lock();
+        match mounts.iter().rposition(|(p, _)| *p == prefix) {
+            Some(i) => {
+                mounts.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Backends that may hold the path with the path relative to each
+    fn resolve(&self, path: &str) -> Vec<(Arc<dyn Backend>, String)> {
+        let mounts = self.mounts.lock();
+        mounts
+            .iter()
+            .rev()
+            .filter_map(|(prefix, backend)| {
+                let relative = match prefix.is_empty() {
+                    true => path,
+                    false => path.strip_prefix(prefix.as_str())?.strip_prefix('/')?,
+                };
+                Some((backend.clone(), relative.to_string()))
+            })
+            .collect()
+    }
+
+    fn missing(path: &str) -> anyhow::Error {
+        AssetError::MissingFile {
+            path: path.to_string(),
+            source: io::ErrorKind::NotFound.into(),
+        }
+        .into()
+    }
+
+    pub fn read(&self, path: &str) -> Result<Vec<u8>> {
+        for (backend, relative) in self.resolve(&normalize(path)) {
+            match backend.read(&relative) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                result => {
+                    return result.map_err(|source| {
+                        AssetError::MissingFile {
+                            path: path.to_string(),
+                            source,
+                        }
+                        .into()
+                    })
+                }
+            }
+        }
+        Err(Self::missing(path))
+    }
+
+    // The file a native mount would read the path from
+    pub fn native_path(&self, path: &str) -> Option<PathBuf> {
+        self.resolve(&normalize(path))
+            .into_iter()
+            .find_map(|(backend, relative)| backend.native_path(&relative))
+    }
+
+    pub async fn read_async(&self, path: &str) -> Result<Vec<u8>> {
+        for (backend, relative) in self.resolve(&normalize(path)) {
+            match backend.read_async(&relative).await {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                result => {
+                    return result.map_err(|source| {
+                        AssetError::MissingFile {
+                            path: path.to_string(),
+                            source,
+                        }
+                        .into()
+                    })
+                }
+            }
+        }
+        Err(Self::missing(path))
+    }
+}
@@ -0,0 +1,119 @@
+use mg_core::vfs::{self, Archive, Backend, Dir, Memory, Vfs};
+use mg_core::*;
+use std::io::Write;
+
+fn empty() -> Vfs {
+    let vfs = Vfs::default();
+    vfs.unmount("");
+    vfs
+}
+
+fn missing(result: Result<Vec<u8>>) -> bool {
+    matches!(
+        result.unwrap_err().downcast_ref::<AssetError>(),
+        Some(AssetError::MissingFile { .. })
+    )
+}
+
+#[test]
+fn normalize() {
+    assert_eq!(vfs::normalize("a/./b//c"), "a/b/c");
+    assert_eq!(vfs::normalize("a/b/../c"), "a/c");
+    assert_eq!(vfs::normalize("../../assets/x.png"), "../../assets/x.png");
+    assert_eq!(vfs::normalize("/tmp/a/../b"), "/tmp/b");
+    assert_eq!(vfs::normalize("a\\b"), "a/b");
+}
+
+#[test]
+fn mounts() {
+    let vfs = empty();
+    vfs.mount("data", Memory::default().with("a.txt", b"low".as_slice()));
+    assert_eq!(vfs.read("data/a.txt").unwrap(), b"low");
+    assert_eq!(vfs.read("./data/x/../a.txt").unwrap(), b"low");
+    assert!(missing(vfs.read("a.txt")));
+    assert!(missing(vfs.read("database/a.txt")));
+
+    // later mounts shadow earlier ones, missing files fall through
+    vfs.mount("data", Memory::default().with("b.txt", b"high".as_slice()));
+    vfs.mount("data", Memory::default().with("a.txt", b"high".as_slice()));
+    assert_eq!(vfs.read("data/a.txt").unwrap(), b"high");
+    assert_eq!(vfs.read("data/b.txt").unwrap(), b"high");
+    assert!(vfs.unmount("data"));
+    assert_eq!(vfs.read("data/a.txt").unwrap(), b"low");
+    assert!(!vfs.unmount("other"));
+}
+
+#[test]
+fn dir() {
+    let root = std::env::temp_dir().join("mg_vfs_dir");
+    fs::create_dir_all(root.join("textures")).unwrap();
+    fs::write(root.join("textures/a.txt"), b"native").unwrap();
+    let vfs = empty();
+    vfs.mount("assets", Dir::new(&root));
+    assert_eq!(vfs.read("assets/textures/a.txt").unwrap(), b"native");
+    assert!(missing(vfs.read("assets/textures/b.txt")));
+
+    // a mounted directory can't be climbed out of
+    fs::write(root.join("secret.txt"), b"secret").unwrap();
+    vfs.mount("assets/textures", Dir::new(root.join("textures")));
+    assert_eq!(vfs.read("assets/textures/a.txt").unwrap(), b"native");
+    assert!(vfs.read("assets/textures/../secret.txt").is_ok());
+    assert!(vfs.read("assets/textures/../../secret.txt").is_err());
+    let textures = Dir::new(root.join("textures"));
+    assert!(textures.read("../secret.txt").is_err());
+    assert!(textures.read("a/../../secret.txt").is_err());
+    assert!(textures
+        .read(root.join("secret.txt").to_str().unwrap())
+        .is_err());
+    assert!(textures.native_path("../secret.txt").is_none());
+    vfs.mount("", Dir::new(&root));
+    assert!(vfs.read("../etc/passwd").is_err());
+
+    // the default root mount reads paths as given
+    let path = root.join("textures/a.txt");
+    assert_eq!(
+        Vfs::default().read(path.to_str().unwrap()).unwrap(),
+        b"native"
+    );
+}
+
+#[test]
+fn archive() {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+    for (name, method) in [
+        ("stored.txt", zip::CompressionMethod::Stored),
+        ("dir/deflated.txt", zip::CompressionMethod::Deflated),
+    ] {
+        let options = zip::write::FileOptions::default().compression_method(method);
+        zip.start_file(name, options).unwrap();
+        zip.write_all(name.repeat(8).as_bytes()).unwrap();
+    }
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let archive = Archive::new("game.pak", bytes).unwrap();
+    assert_eq!(archive.len(), 2);
+    let vfs = empty();
+    vfs.mount("pak", archive);
+    assert_eq!(
+        vfs.read("pak/stored.txt").unwrap(),
+        "stored.txt".repeat(8).as_bytes()
+    );
+    assert_eq!(
+        vfs.read("pak/dir/deflated.txt").unwrap(),
+        "dir/deflated.txt".repeat(8).as_bytes()
+    );
+    assert!(missing(vfs.read("pak/other.txt")));
+    let err = Archive::new("bad.pak", vec![0; 16]).err().unwrap();
+    assert!(matches!(
+        err.downcast_ref::<AssetError>(),
+        Some(AssetError::InvalidArchive { archive, .. }) if archive == "bad.pak"
+    ));
+}
+
+#[test]
+fn read_async() {
+    let vfs = empty();
+    vfs.mount("data", Memory::default().with("a.txt", b"async".as_slice()));
+    let bytes = pollster::block_on(vfs.read_async("data/a.txt")).unwrap();
+    assert_eq!(bytes, b"async");
+}
@@ -0,0 +1,429 @@
+use crate::{model::Model, scene::Scene};
+use mg_core::*;
+
+// A node transform split so it can be animated and blended part by part
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3f,
+    pub rotation: Quat,
+    pub scale: Vec3f,
+}
+
+impl Transform {
+    // Assumes no shear, which glTF forbids on animated nodes
+    pub fn from_matrix(m: &Mat4) -> Transform {
+        let translation = m.fixed_view::<3, 1>(0, 3).into_owned();
+        let linear = m.fixed_view::<3, 3>(0, 0).into_owned();
+        let mut scale = Vec3f::new(
+            linear.column(0).norm(),
+            linear.column(1).norm(),
+            linear.column(2).norm(),
+        );
+        if linear.determinant() < 0.0 {
+            scale.x = -scale.x;
+        }
+        let unscale = scale.map(|s| if s == 0.0 { 0.0 } else { 1.0 / s });
+        let rotation = linear * na::Matrix3::from_diagonal(&unscale);
+        Transform {
+            translation,
+            rotation: Quat::from_rotation_matrix(&na::Rotation3::from_matrix_unchecked(rotation)),
+            scale,
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * Mat4::new_nonuniform_scaling(&self.scale)
+    }
+
+    pub fn blend(&self, other: &Transform, t: f32) -> Transform {
+        let [x, y, z, w] = slerp(self.rotation.coords.into(), other.rotation.coords.into(), t);
+        Transform {
+            translation: self.translation.lerp(&other.translation, t),
+            rotation: quat([x, y, z, w]),
+            scale: self.scale.lerp(&other.scale, t),
+        }
+    }
+}
+
+// glTF and nalgebra both keep quaternions as xyzw
+fn quat(xyzw: [f32; 4]) -> Quat {
+    Quat::new_normalize(na::Quaternion::from(na::Vector4::from(xyzw)))
+}
+
+// Spherical interpolation along the shorter arc
+fn slerp(a: [f32; 4], mut b: [f32; 4], t: f32) -> [f32; 4] {
+    let mut dot: f32 = (0..4).map(|i| a[i] * b[i]).sum();
+    if dot < 0.0 {
+        b = b.map(|c| -c);
+        dot = -dot;
+    }
+    // nearly parallel, lerping is as good and avoids dividing by ~0
+    let (wa, wb) = match dot > 0.9995 {
+        true => (1.0 - t, t),
+        false => {
+            let theta = dot.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        }
+    };
+    std::array::from_fn(|i| a[i] * wa + b[i] * wb)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Property {
+    Translation,
+    Rotation,
+    Scale,
+    // morph target weights, as many per key as the mesh has targets
+    Weights,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    CubicSpline,
+}
+
+// Keyframes animating one property of a model node
+#[derive(Clone, Debug)]
+pub struct Channel {
+    pub node: usize,
+    pub property: Property,
+    pub interpolation: Interpolation,
+    // seconds, increasing
+    pub times: Vec<f32>,
+    // width floats per key, cubic splines keep an in tangent, the value
+    // and an out tangent per key
+    pub values: Vec<f32>,
+}
+
+impl Channel {
+    fn stride(&self) -> usize {
+        match self.interpolation {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        }
+    }
+
+    // Floats per sampled value
+    pub fn width(&self) -> usize {
+        match self.property {
+            Property::Translation | Property::Scale => 3,
+            Property::Rotation => 4,
+            Property::Weights => self.values.len() / (self.times.len() * self.stride()).max(1),
+        }
+    }
+
+    // Why the keyframes can't be sampled, None when they can
+    pub fn check(&self) -> Option<String> {
+        let keys = self.times.len() * self.stride();
+        if self.times.is_empty() {
+            return Some("has no keyframes".to_string());
+        }
+        if self.times.windows(2).any(|pair| pair[0] > pair[1]) {
+            return Some("times decrease".to_string());
+        }
+        let width = self.width();
+        if width == 0 || self.values.len() != keys * width {
+            return Some(format!("{} values for {} keys", self.values.len(), keys));
+        }
+        None
+    }
+
+    fn value(&self, key: usize, part: usize) -> &[f32] {
+        let width = self.width();
+        let start = (key * self.stride() + part) * width;
+        &self.values[start..start + width]
+    }
+
+    // Writes the value at time into out, holding the first and last keys
+    // outside of the keyframes
+    pub fn sample(&self, time: f32, out: &mut [f32]) {
+        let cubic = self.interpolation == Interpolation::CubicSpline;
+        let value = if cubic { 1 } else { 0 };
+        let next = self.times.partition_point(|&t| t <= time);
+        if next == 0 || next == self.times.len() {
+            let key = next.saturating_sub(1);
+            out.copy_from_slice(self.value(key, value));
+            return;
+        }
+        let key = next - 1;
+        let span = self.times[next] - self.times[key];
+        let t = (time - self.times[key]) / span;
+        let (a, b) = (self.value(key, value), self.value(next, value));
+        match self.interpolation {
+            Interpolation::Step => out.copy_from_slice(a),
+            Interpolation::Linear if self.property == Property::Rotation => {
+                let to_array = |v: &[f32]| [v[0], v[1], v[2], v[3]];
+                out.copy_from_slice(&slerp(to_array(a), to_array(b), t));
+            }
+            Interpolation::Linear => {
+                for (i, out) in out.iter_mut().enumerate() {
+                    *out = a[i] + (b[i] - a[i]) * t;
+                }
+            }
+            Interpolation::CubicSpline => {
+                // hermite basis, tangents are per second so scale by the span
+                let (t2, t3) = (t * t, t * t * t);
+                let out_tangent = self.value(key, 2);
+                let in_tangent = self.value(next, 0);
+                for (i, out) in out.iter_mut().enumerate() {
+                    *out = (2.0 * t3 - 3.0 * t2 + 1.0) * a[i]
+                        + (t3 - 2.0 * t2 + t) * span * out_tangent[i]
+                        + (-2.0 * t3 + 3.0 * t2) * b[i]
+                        + (t3 - t2) * span * in_tangent[i];
+                }
+                if self.property == Property::Rotation {
+                    let normal = out.iter().map(|c| c * c).sum::<f32>().sqrt();
+                    out.iter_mut().for_each(|c| *c /= normal);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Clip {
+    pub name: String,
+    pub channels: Vec<Channel>,
+    // the last keyframe time of any channel
+    pub duration: f32,
+}
+
+impl Clip {
+    pub fn new(name: String, channels: Vec<Channel>) -> Clip {
+        let duration = channels
+            .iter()
+            .filter_map(|channel| channel.times.last().copied())
+            .fold(0.0, f32::max);
+        Clip {
+            name,
+            channels,
+            duration,
+        }
+    }
+
+    // Overwrites the parts of pose the clip animates with their values at time
+    pub fn sample(&self, time: f32, pose: &mut Pose) {
+        let mut out = [0.0; 4];
+        for channel in &self.channels {
+            let Some(transform) = pose.transforms.get_mut(channel.node) else {
+                continue;
+            };
+            match channel.property {
+                Property::Translation => {
+                    channel.sample(time, &mut out[..3]);
+                    transform.translation = Vec3f::new(out[0], out[1], out[2]);
+                }
+                Property::Rotation => {
+                    channel.sample(time, &mut out);
+                    transform.rotation = quat(out);
+                }
+                Property::Scale => {
+                    channel.sample(time, &mut out[..3]);
+                    transform.scale = Vec3f::new(out[0], out[1], out[2]);
+                }
+                Property::Weights => {
+                    let weights = &mut pose.weights[channel.node];
+                    weights.resize(channel.width(), 0.0);
+                    channel.sample(time, weights);
+                }
+            }
+        }
+    }
+}
+
+// Transforms and morph weights of each of a model's nodes, weights are
+// empty for nodes without morph targets until a clip animates them
+#[derive(Clone, Debug)]
+pub struct Pose {
+    pub transforms: Vec<Transform>,
+    pub weights: Vec<Vec<f32>>,
+}
+
+impl Pose {
+    // The model's nodes as they were loaded, weighted by their mesh's
+    // default weights
+    pub fn rest(model: &Model) -> Pose {
+        Pose {
+            transforms: model
+                .nodes
+                .iter()
+                .map(|node| Transform::from_matrix(&node.tsf))
+                .collect(),
+            weights: model
+                .nodes
+                .iter()
+                .map(|node| {
+                    node.meshes
+                        .first()
+                        .map_or(vec![], |&mesh| model.meshes[mesh].weights.clone())
+                })
+                .collect(),
+        }
+    }
+
+    // Moves the pose t of the way to other
+    pub fn blend(&mut self, other: &Pose, t: f32) {
+        for (transform, other) in self.transforms.iter_mut().zip(&other.transforms) {
+            *transform = transform.blend(other, t);
+        }
+        for (weights, other) in self.weights.iter_mut().zip(&other.weights) {
+            weights.resize(weights.len().max(other.len()), 0.0);
+            for (i, weight) in weights.iter_mut().enumerate() {
+                *weight += (other.get(i).copied().unwrap_or(0.0) - *weight) * t;
+            }
+        }
+    }
+}
+
+// Where a clip is in its playback
+#[derive(Clone, Debug)]
+pub struct Track {
+    pub clip: Arc<Clip>,
+    // seconds into the clip
+    pub time: f32,
+    // negative plays backwards
+    pub speed: f32,
+    pub looping: bool,
+}
+
+impl Track {
+    pub fn new(clip: Arc<Clip>) -> Track {
+        Track {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+        }
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        self.time += dt * self.speed;
+        let duration = self.clip.duration;
+        self.time = match self.looping && duration > 0.0 {
+            true => self.time.rem_euclid(duration),
+            false => self.time.clamp(0.0, duration),
+        };
+    }
+
+    // Stopped at an end of a clip that doesn't loop
+    pub fn finished(&self) -> bool {
+        match self.speed < 0.0 {
+            true => !self.looping && self.time <= 0.0,
+            false => !self.looping && self.time >= self.clip.duration,
+        }
+    }
+}
+
+struct Fade {
+    from: Track,
+    elapsed: f32,
+    duration: f32,
+}
+
+// Plays a model's clips on the scene nodes it was instantiated as
+pub struct Animator {
+    // scene node of each model node, as Scene::instantiate_model returns
+    nodes: Vec<usize>,
+    rest: Pose,
+    pose: Pose,
+    // nodes the sampled clips move, and ones they morph
+    animated: Vec<bool>,
+    weighted: Vec<bool>,
+    pub track: Option<Track>,
+    fade: Option<Fade>,
+}
+
+impl Animator {
+    pub fn new(model: &Model, nodes: Vec<usize>) -> Animator {
+        let rest = Pose::rest(model);
+        Animator {
+            animated: vec![false; nodes.len()],
+            weighted: vec![false; nodes.len()],
+            nodes,
+            pose: rest.clone(),
+            rest,
+            track: None,
+            fade: None,
+        }
+    }
+
+    // Switches to clip from its start
+    pub fn play(&mut self, clip: &Arc<Clip>) -> &mut Track {
+        self.fade = None;
+        self.track.insert(Track::new(clip.clone()))
+    }
+
+    // Blends from the playing clip to clip over duration seconds. A fade
+    // already underway is cut short
+    pub fn cross_fade(&mut self, clip: &Arc<Clip>, duration: f32) -> &mut Track {
+        self.fade = self.track.take().map(|from| Fade {
+            from,
+            elapsed: 0.0,
+            duration,
+        });
+        self.track.insert(Track::new(clip.clone()))
+    }
+
+    pub fn pose(&self) -> &Pose {
+        &self.pose
+    }
+
+    // Advances playback by dt seconds and samples the pose
+    pub fn update(&mut self, dt: f32) {
+        if let Some(track) = &mut self.track {
+            track.advance(dt);
+        }
+        if let Some(fade) = &mut self.fade {
+            fade.from.advance(dt);
+            fade.elapsed += dt;
+            if fade.elapsed >= fade.duration {
+                self.fade = None;
+            }
+        }
+
+        self.pose.clone_from(&self.rest);
+        self.animated
+            .iter_mut()
+            .chain(self.weighted.iter_mut())
+            .for_each(|animated| *animated = false);
+        let mut sample = |track: &Track, pose: &mut Pose| {
+            track.clip.sample(track.time, pose);
+            for channel in &track.clip.channels {
+                let animated = match channel.property {
+                    Property::Weights => self.weighted.get_mut(channel.node),
+                    _ => self.animated.get_mut(channel.node),
+                };
+                if let Some(animated) = animated {
+                    *animated = true;
+                }
+            }
+        };
+        if let Some(track) = &self.track {
+            sample(track, &mut self.pose);
+        }
+        if let Some(fade) = &self.fade {
+            let mut from = self.rest.clone();
+            sample(&fade.from, &mut from);
+            from.blend(&self.pose, fade.elapsed / fade.duration);
+            self.pose = from;
+        }
+    }
+
+    // Sets the transforms and weights of the scene nodes the clips move
+    // and morph
+    pub fn apply(&self, scene: &mut Scene) {
+        for (i, &node) in self.nodes.iter().enumerate() {
+            if self.animated[i] {
+                scene.set_node_tsf(node, self.pose.transforms[i].matrix());
+            }
+            if self.weighted[i] {
+                scene.set_node_weights(node, &self.pose.weights[i]);
+            }
+        }
+    }
+}
@@ -0,0 +1,599 @@
+use crate::{
+    cooked,
+    gltf_loader::{self, GltfSrc, LoadOptions, Prepared},
+    graphics::Graphics,
+    material::{self, Bindings, Material},
+    mesh::Mesh,
+    model::Model,
+    texture::{ContentKey, Texture, TextureOptions},
+};
+use mg_core::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{mpsc, Weak};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+#[cfg(target_arch = "wasm32")]
+use wasm_thread as thread;
+
+// Textures uploaded per update, keeps loading from stalling frames
+pub const UPLOADS_PER_FRAME: usize = 4;
+
+// Shared reference to a loaded asset. The asset and its gpu resources
+// are freed when the last handle, or Arc taken from one, drops
+pub struct Handle<T>(Arc<T>);
+
+impl<T> Handle<T> {
+    // Unique among live assets, reused once an asset is freed
+    pub fn id(&self) -> usize {
+        Arc::as_ptr(&self.0) as *const () as usize
+    }
+
+    pub fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
+
+    pub fn arc(&self) -> Arc<T> {
+        self.0.clone()
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> std::ops::Deref for Handle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
+impl<T> From<Arc<T>> for Handle<T> {
+    fn from(asset: Arc<T>) -> Self {
+        Self(asset)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Key {
+    Path(String),
+    Content(ContentKey),
+}
+
+impl Key {
+    fn path(path: &str) -> Self {
+        Key::Path(vfs::normalize(path))
+    }
+
+    fn content(bytes: &[u8]) -> Self {
+        Key::Content(ContentKey::new(bytes))
+    }
+}
+
+// Live assets by key, entries don't keep assets alive
+struct Store<K, T> {
+    assets: HashMap<K, Weak<T>>,
+}
+
+impl<K, T> Default for Store<K, T> {
+    fn default() -> Self {
+        Self {
+            assets: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, T> Store<K, T> {
+    fn get(&self, key: &K) -> Option<Handle<T>> {
+        self.assets.get(key).and_then(Weak::upgrade).map(Handle)
+    }
+
+    fn insert(&mut self, key: K, asset: Arc<T>) -> Handle<T> {
+        self.assets.retain(|_, asset| asset.strong_count() > 0);
+        self.assets.insert(key, Arc::downgrade(&asset));
+        Handle(asset)
+    }
+
+    // Keys can share an asset, each is counted once
+    fn len(&self) -> usize {
+        self.assets
+            .values()
+            .filter(|asset| asset.strong_count() > 0)
+            .map(|asset| asset.as_ptr() as *const () as usize)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadState {
+    // reading files and decoding images on a worker thread
+    Preparing,
+    // waiting on AssetServer::update to upload it
+    Uploading,
+    Ready,
+    Failed(String),
+}
+
+struct Progress<T> {
+    state: LoadState,
+    progress: f32,
+    asset: Option<Handle<T>>,
+}
+
+// A background load, polled for a loading screen until it's ready
+pub struct Loading<T>(Arc<Mutex<Progress<T>>>);
+
+impl<T> Clone for Loading<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Loading<T> {
+    fn new(state: LoadState, progress: f32, asset: Option<Handle<T>>) -> Self {
+        Self(Arc::new(Mutex::new(Progress {
+            state,
+            progress,
+            asset,
+        })))
+    }
+
+    fn set(&self, state: LoadState, progress: f32) {
+        let mut loading = self.0.lock();
+        loading.state = state;
+        loading.progress = progress;
+    }
+
+    pub fn state(&self) -> LoadState {
+        self.0.lock().state.clone()
+    }
+
+    // From 0 to 1, in uploaded textures
+    pub fn progress(&self) -> f32 {
+        self.0.lock().progress
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.state(), LoadState::Ready | LoadState::Failed(_))
+    }
+
+    pub fn handle(&self) -> Option<Handle<T>> {
+        self.0.lock().asset.clone()
+    }
+}
+
+type ModelKey = (Key, LoadOptions);
+type TextureKey = (Key, TextureOptions);
+// a document read and decoded off the main thread
+type PreparedModel = (ModelKey, Loading<Model>, Result<Read>);
+
+enum Read {
+    Gltf(Box<Prepared>),
+    // cooked models need no preparing, only their bytes
+    Cooked { path: String, bytes: Arc<[u8]> },
+}
+
+fn is_cooked_path(path: &str) -> bool {
+    std::path::Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext == cooked::EXTENSION)
+}
+
+fn read_model(path: &str, options: &LoadOptions) -> Result<Read> {
+    match is_cooked_path(path) {
+        true => Ok(Read::Cooked {
+            path: path.to_string(),
+            bytes: read_file_to_end(path)?.into(),
+        }),
+        false => Ok(Read::Gltf(Box::new(gltf_loader::prepare(
+            GltfSrc::Path(path),
+            options,
+        )?))),
+    }
+}
+
+// A prepared model being uploaded over several updates
+struct Upload {
+    key: ModelKey,
+    loading: Loading<Model>,
+    prepared: Prepared,
+}
+
+impl Upload {
+    fn progress(&self) -> f32 {
+        let total = self.prepared.texture_count() + 1;
+        (total - self.prepared.pending_textures() - 1) as f32 / total as f32
+    }
+}
+
+// A loaded asset read from a file
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Source {
+    Model(ModelKey),
+    Texture(TextureKey),
+}
+
+// An asset loaded again after its files changed. Handles to the old one
+// keep it alive, see Scene::swap_model and Scene::swap_texture
+pub enum Reloaded {
+    Model { old: Handle<Model>, new: Handle<Model> },
+    Texture { old: Handle<Texture>, new: Handle<Texture> },
+}
+
+// Assets alive in an AssetServer
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct AssetCounts {
+    pub models: usize,
+    pub textures: usize,
+    pub materials: usize,
+    pub meshes: usize,
+}
+
+// Materials with the same factors, textures and samplers are the same
+#[derive(PartialEq, Eq, Hash)]
+struct BindingsKey {
+    params: Box<[u8]>,
+    resources: [usize; 10],
+}
+
+fn bindings_key(bindings: &Bindings) -> BindingsKey {
+    BindingsKey {
+        params: bytemuck::bytes_of(&bindings.params).into(),
+        resources: [
+            Arc::as_ptr(&bindings.albedo_tx) as usize,
+            Arc::as_ptr(&bindings.emission_tx) as usize,
+            Arc::as_ptr(&bindings.metallic_roughness_tx) as usize,
+            Arc::as_ptr(&bindings.normal_tx) as usize,
+            Arc::as_ptr(&bindings.occlusion_tx) as usize,
+            Arc::as_ptr(&bindings.albedo_sampler) as usize,
+            Arc::as_ptr(&bindings.emission_sampler) as usize,
+            Arc::as_ptr(&bindings.metallic_roughness_sampler) as usize,
+            Arc::as_ptr(&bindings.normal_sampler) as usize,
+            Arc::as_ptr(&bindings.occlusion_sampler) as usize,
+        ],
+    }
+}
+
+// Hands out handles to models, textures, materials and meshes, loading
+// each path once while a handle to it is alive. Image contents are
+// deduplicated by Graphics::image_texture, so documents sharing images
+// share textures and then identical materials
+pub struct AssetServer {
+    pub defaults: material::Defaults,
+    models: Mutex<Store<ModelKey, Model>>,
+    textures: Mutex<Store<TextureKey, Texture>>,
+    materials: Mutex<Store<BindingsKey, Material>>,
+    meshes: Mutex<Store<(usize, usize), Mesh>>,
+    loading: Mutex<HashMap<ModelKey, Loading<Model>>>,
+    prepared_sender: mpsc::Sender<PreparedModel>,
+    prepared: Mutex<mpsc::Receiver<PreparedModel>>,
+    uploads: Mutex<VecDeque<Upload>>,
+    // assets by the files they were read from
+    sources: Mutex<HashMap<String, HashSet<Source>>>,
+}
+
+impl AssetServer {
+    pub fn new(graphics: &Graphics) -> Self {
+        let (prepared_sender, prepared) = mpsc::channel();
+        Self {
+            defaults: material::Defaults::new(graphics),
+            models: Default::default(),
+            textures: Default::default(),
+            materials: Default::default(),
+            meshes: Default::default(),
+            loading: Default::default(),
+            prepared_sender,
+            prepared: Mutex::new(prepared),
+            uploads: Default::default(),
+            sources: Default::default(),
+        }
+    }
+
+    pub fn load_model(&self, graphics: &Graphics, path: &str) -> Result<Handle<Model>> {
+        self.load_model_with(graphics, path, &LoadOptions::default())
+    }
+
+    pub fn load_model_with(
+        &self,
+        graphics: &Graphics,
+        path: &str,
+        options: &LoadOptions,
+    ) -> Result<Handle<Model>> {
+        let key = (Key::path(path), options.clone());
+        if let Some(model) = self.models.lock().get(&key) {
+            return Ok(model);
+        }
+        let model = self.load_read(graphics, &key, read_model(path, options)?)?;
+        Ok(self.models.lock().insert(key, Arc::new(model)))
+    }
+
+    // .gltf, .glb or cooked contents, keyed on their hash and length
+    pub fn load_model_slice(
+        &self,
+        graphics: &Graphics,
+        bytes: &[u8],
+        options: &LoadOptions,
+    ) -> Result<Handle<Model>> {
+        let key = (Key::content(bytes), options.clone());
+        if let Some(model) = self.models.lock().get(&key) {
+            return Ok(model);
+        }
+        let model = match cooked::is_cooked(bytes) {
+            true => cooked::load(graphics, &self.defaults, "cooked slice", bytes.into())?,
+            false => gltf_loader::model_from_source_with(
+                graphics,
+                &self.defaults,
+                GltfSrc::Slice(bytes),
+                options,
+            )?,
+        };
+        let model = self.intern_materials(model);
+        Ok(self.models.lock().insert(key, Arc::new(model)))
+    }
+
+    // Reads and decodes the model on another thread, update uploads it.
+    // Loading a path already loading shares its progress
+    pub fn load_model_async(&self, path: &str, options: &LoadOptions) -> Loading<Model> {
+        let key = (Key::path(path), options.clone());
+        if let Some(model) = self.models.lock().get(&key) {
+            return Loading::new(LoadState::Ready, 1.0, Some(model));
+        }
+        let mut loading = self.loading.lock();
+        if let Some(model) = loading.get(&key) {
+            return model.clone();
+        }
+        let model = Loading::new(LoadState::Preparing, 0.0, None);
+        loading.insert(key.clone(), model.clone());
+
+        let (sender, path, options) = (
+            self.prepared_sender.clone(),
+            path.to_string(),
+            options.clone(),
+        );
+        let job = (key, model.clone());
+        thread::spawn(move || {
+            let read = read_model(&path, &options);
+            // the server may have been dropped meanwhile
+            let _ = sender.send((job.0, job.1, read));
+        });
+        model
+    }
+
+    // Background loads still preparing or uploading
+    pub fn pending_loads(&self) -> usize {
+        self.loading.lock().len()
+    }
+
+    // Moves background loads along, call once a frame
+    pub fn update(&self, graphics: &Graphics) {
+        self.update_with_budget(graphics, UPLOADS_PER_FRAME)
+    }
+
+    // Uploads up to budget textures, finishing a model takes one more
+    pub fn update_with_budget(&self, graphics: &Graphics, mut budget: usize) {
+        let mut uploads = self.uploads.lock();
+        for (key, loading, read) in self.prepared.lock().try_iter() {
+            match read {
+                Ok(Read::Gltf(prepared)) => {
+                    self.track(prepared.files(), Source::Model(key.clone()));
+                    let upload = Upload {
+                        key,
+                        loading,
+                        prepared: *prepared,
+                    };
+                    upload.loading.set(LoadState::Uploading, upload.progress());
+                    uploads.push_back(upload);
+                }
+                // uploaded as is, a cooked model is quick to finish
+                Ok(read @ Read::Cooked { .. }) => {
+                    let model = self.load_read(graphics, &key, read);
+                    self.finish(&key, &loading, model);
+                }
+                Err(err) => self.finish(&key, &loading, Err(err)),
+            }
+        }
+
+        while budget > 0 {
+            let Some(mut upload) = uploads.pop_front() else {
+                break;
+            };
+            while budget > 0 && upload.prepared.pending_textures() > 0 {
+                budget -= 1;
+                if let Err(err) = upload.prepared.upload_texture(graphics) {
+                    self.finish(&upload.key, &upload.loading, Err(err));
+                    break;
+                }
+                upload.loading.set(LoadState::Uploading, upload.progress());
+            }
+            if upload.loading.is_done() {
+                continue;
+            }
+            if budget == 0 {
+                uploads.push_front(upload);
+                break;
+            }
+            budget -= 1;
+            let model = gltf_loader::model_from_prepared(graphics, &self.defaults, upload.prepared)
+                .map(|model| self.intern_materials(model));
+            self.finish(&upload.key, &upload.loading, model);
+        }
+    }
+
+    fn finish(&self, key: &ModelKey, loading: &Loading<Model>, model: Result<Model>) {
+        self.loading.lock().remove(key);
+        match model {
+            Ok(model) => {
+                let model = self.models.lock().insert(key.clone(), Arc::new(model));
+                loading.0.lock().asset = Some(model);
+                loading.set(LoadState::Ready, 1.0);
+            }
+            Err(err) => {
+                log::error!("{:#}", err);
+                loading.set(LoadState::Failed(format!("{:#}", err)), 1.0);
+            }
+        }
+    }
+
+    pub fn load_texture(
+        &self,
+        graphics: &Graphics,
+        path: &str,
+        options: &TextureOptions,
+    ) -> Result<Handle<Texture>> {
+        let key = (Key::path(path), *options);
+        if let Some(texture) = self.textures.lock().get(&key) {
+            return Ok(texture);
+        }
+        self.track(&[path.to_string()], Source::Texture(key.clone()));
+        let bytes = read_file_to_end(path)?;
+        let texture = graphics.image_texture(path, &bytes, options)?;
+        Ok(self.textures.lock().insert(key, texture))
+    }
+
+    fn load_read(&self, graphics: &Graphics, key: &ModelKey, read: Read) -> Result<Model> {
+        let model = match read {
+            Read::Gltf(prepared) => {
+                self.track(prepared.files(), Source::Model(key.clone()));
+                gltf_loader::model_from_prepared(graphics, &self.defaults, *prepared)?
+            }
+            Read::Cooked { path, bytes } => {
+                self.track(std::slice::from_ref(&path), Source::Model(key.clone()));
+                cooked::load(graphics, &self.defaults, &path, bytes)?
+            }
+        };
+        Ok(self.intern_materials(model))
+    }
+
+    fn track(&self, files: &[String], source: Source) {
+        let mut sources = self.sources.lock();
+        for file in files {
+            sources
+                .entry(vfs::normalize(file))
+                .or_default()
+                .insert(source.clone());
+        }
+    }
+
+    // Files live assets were read from, for a watcher to poll
+    pub fn source_files(&self) -> Vec<String> {
+        let (models, textures) = (self.models.lock(), self.textures.lock());
+        let mut sources = self.sources.lock();
+        sources.retain(|_, assets| {
+            assets.retain(|source| match source {
+                Source::Model(key) => models.get(key).is_some(),
+                Source::Texture(key) => textures.get(key).is_some(),
+            });
+            !assets.is_empty()
+        });
+        sources.keys().cloned().collect()
+    }
+
+    // Loads the live assets read from the file again. Later loads get the
+    // new assets, ones that fail to load are logged and kept
+    pub fn reload(&self, graphics: &Graphics, file: &str) -> Vec<Reloaded> {
+        let sources = match self.sources.lock().get(&vfs::normalize(file)) {
+            Some(sources) => sources.iter().cloned().collect::<Vec<_>>(),
+            None => return vec![],
+        };
+        let mut reloaded = vec![];
+        for source in sources {
+            let result = match source {
+                Source::Model(key) => self.reload_model(graphics, key),
+                Source::Texture(key) => self.reload_texture(graphics, key),
+            };
+            match result {
+                Ok(Some(asset)) => reloaded.push(asset),
+                Ok(None) => {}
+                Err(err) => log::error!("{:#}", err),
+            }
+        }
+        reloaded
+    }
+
+    fn reload_model(&self, graphics: &Graphics, key: ModelKey) -> Result<Option<Reloaded>> {
+        let (Some(old), Key::Path(path)) = (self.models.lock().get(&key), &key.0) else {
+            return Ok(None);
+        };
+        let model = self.load_read(graphics, &key, read_model(path, &key.1)?)?;
+        let new = self.models.lock().insert(key, Arc::new(model));
+        Ok(Some(Reloaded::Model { old, new }))
+    }
+
+    fn reload_texture(&self, graphics: &Graphics, key: TextureKey) -> Result<Option<Reloaded>> {
+        let (Some(old), Key::Path(path)) = (self.textures.lock().get(&key), &key.0) else {
+            return Ok(None);
+        };
+        let bytes = read_file_to_end(path)?;
+        let texture = graphics.image_texture(path, &bytes, &key.1)?;
+        let new = self.textures.lock().insert(key, texture);
+        Ok(Some(Reloaded::Texture { old, new }))
+    }
+
+    // An existing material when one with the same bindings is alive
+    pub fn material(
+        &self,
+        graphics: &Graphics,
+        name: Option<&str>,
+        bindings: Bindings,
+    ) -> Handle<Material> {
+        let key = bindings_key(&bindings);
+        let mut materials = self.materials.lock();
+        match materials.get(&key) {
+            Some(material) => material,
+            None => materials.insert(key, Arc::new(Material::new(graphics, name, bindings))),
+        }
+    }
+
+    pub fn mesh(&self, mesh: Mesh) -> Handle<Mesh> {
+        let key = (
+            Arc::as_ptr(&mesh.geometry) as usize,
+            Arc::as_ptr(&mesh.material) as usize,
+        );
+        let mut meshes = self.meshes.lock();
+        match meshes.get(&key) {
+            Some(mesh) => mesh,
+            None => meshes.insert(key, Arc::new(mesh)),
+        }
+    }
+
+    pub fn counts(&self) -> AssetCounts {
+        AssetCounts {
+            models: self.models.lock().len(),
+            textures: self.textures.lock().len(),
+            materials: self.materials.lock().len(),
+            meshes: self.meshes.lock().len(),
+        }
+    }
+
+    // Swaps each mesh's material for a live duplicate from an earlier load
+    fn intern_materials(&self, mut model: Model) -> Model {
+        let mut materials = self.materials.lock();
+        for mesh in &mut model.meshes {
+            let key = bindings_key(&mesh.material.bindings);
+            mesh.material = match materials.get(&key) {
+                Some(material) => material.arc(),
+                None => materials.insert(key, mesh.material.clone()).arc(),
+            };
+        }
+        model
+    }
+}
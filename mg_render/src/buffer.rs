@@ -1,4 +1,36 @@
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
 pub struct Buffer {
-    pub bin: Box<[u8]>,
+    pub bin: Bin,
     pub gpu_buffer: wgpu::Buffer,
 }
+
+// A buffer's bytes on the cpu. A cooked model's are a range of its file,
+// shared rather than copied out
+pub struct Bin {
+    bytes: Arc<[u8]>,
+    range: Range<usize>,
+}
+
+impl Bin {
+    pub fn new(bytes: Arc<[u8]>, range: Range<usize>) -> Bin {
+        assert!(range.start <= range.end && range.end <= bytes.len());
+        Bin { bytes, range }
+    }
+}
+
+impl From<Vec<u8>> for Bin {
+    fn from(bytes: Vec<u8>) -> Bin {
+        let range = 0..bytes.len();
+        Bin::new(bytes.into(), range)
+    }
+}
+
+impl Deref for Bin {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes[self.range.clone()]
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{
+    g_buffer::GBuffer,
+    graphics::{Graphics, Target},
+    texture::Texture,
+};
+use image::RgbaImage;
+use mg_core::*;
+use std::path::Path;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Attachment {
+    Albedo,
+    Emission,
+    Position,
+    Normal,
+    Depth,
+}
+
+impl Attachment {
+    pub const ALL: [Attachment; 5] = [
+        Attachment::Albedo,
+        Attachment::Emission,
+        Attachment::Position,
+        Attachment::Normal,
+        Attachment::Depth,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Attachment::Albedo => "albedo",
+            Attachment::Emission => "emission",
+            Attachment::Position => "position",
+            Attachment::Normal => "normal",
+            Attachment::Depth => "depth",
+        }
+    }
+}
+
+impl GBuffer {
+    pub fn attachment(&self, attachment: Attachment) -> &Texture {
+        match attachment {
+            Attachment::Albedo => &self.albedo_texture,
+            Attachment::Emission => &self.emission_texture,
+            Attachment::Position => &self.position_texture,
+            Attachment::Normal => &self.normal_texture,
+            Attachment::Depth => &self.depth_texture,
+        }
+    }
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((bits >> 10) & 0x1f) as i32;
+    let frac = (bits & 0x3ff) as f32;
+    match exp {
+        0 => sign * frac * 2f32.powi(-24),
+        0x1f if frac == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + frac / 1024.0) * 2f32.powi(exp - 15),
+    }
+}
+
+fn unorm8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn texel_to_rgba(format: wgpu::TextureFormat, texel: &[u8]) -> Result<[u8; 4]> {
+    Ok(match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => {
+            [texel[0], texel[1], texel[2], texel[3]]
+        }
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+            [texel[2], texel[1], texel[0], texel[3]]
+        }
+        wgpu::TextureFormat::Rgba16Float => {
+            let c = |i: usize| unorm8(f16_to_f32(u16::from_le_bytes([texel[i], texel[i + 1]])));
+            [c(0), c(2), c(4), c(6)]
+        }
+        wgpu::TextureFormat::Depth32Float => {
+            let d = unorm8(f32::from_le_bytes([texel[0], texel[1], texel[2], texel[3]]));
+            [d, d, d, 255]
+        }
+        _ => anyhow::bail!("can't capture texture format {:?}", format),
+    })
+}
+
+// Copies a texture back to the cpu, blocking until the gpu is done.
+// Float and depth formats are clamped to 0..1
+pub fn capture_texture(graphics: &Graphics, texture: &wgpu::Texture) -> Result<RgbaImage> {
+    capture_texture_level(graphics, texture, 0)
+}
+
+pub fn capture_texture_level(
+    graphics: &Graphics,
+    texture: &wgpu::Texture,
+    mip_level: u32,
+) -> Result<RgbaImage> {
+    capture_texture_layer(graphics, texture, mip_level, 0)
+}
+
+// One array layer or cube face of a mip level
+pub fn capture_texture_layer(
+    graphics: &Graphics,
+    texture: &wgpu::Texture,
+    mip_level: u32,
+    layer: u32,
+) -> Result<RgbaImage> {
+    let format = texture.format();
+    let size = wgpu::Extent3d {
+        depth_or_array_layers: 1,
+        ..texture
+            .size()
+            .mip_level_size(mip_level, texture.dimension())
+    };
+    if layer >= texture.depth_or_array_layers() {
+        anyhow::bail!("layer {} out of {}", layer, texture.depth_or_array_layers());
+    }
+    let (width, height) = (size.width, size.height);
+    let texel_size = format.block_size(None).unwrap_or(0);
+    // bail on unsupported formats before copying
+    texel_to_rgba(format, &[0; 8])?;
+    if format.is_depth_stencil_format()
+        && !graphics
+            .adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::DEPTH_TEXTURE_AND_BUFFER_COPIES)
+    {
+        anyhow::bail!("adapter can't copy depth textures to buffers");
+    }
+
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let unpadded_row = width * texel_size;
+    let padded_row = unpadded_row.div_ceil(align) * align;
+    let buffer = graphics.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("capture buffer"),
+        size: (padded_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = graphics
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("capture encoder"),
+        });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            aspect: wgpu::TextureAspect::All,
+            texture,
+            mip_level,
+            origin: wgpu::Origin3d {
+                x: 0,
+                y: 0,
+                z: layer,
+            },
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row),
+                rows_per_image: Some(height),
+            },
+        },
+        size,
+    );
+    graphics.queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |res| {
+        let _ = sender.send(res);
+    });
+    graphics.device.poll(wgpu::Maintain::Wait);
+    receiver.recv()??;
+
+    let mut image = RgbaImage::new(width, height);
+    {
+        let data = slice.get_mapped_range();
+        for (y, row) in data.chunks(padded_row as usize).enumerate() {
+            for (x, texel) in row[..unpadded_row as usize]
+                .chunks(texel_size as usize)
+                .enumerate()
+            {
+                let rgba = texel_to_rgba(format, texel)?;
+                image.put_pixel(x as u32, y as u32, image::Rgba(rgba));
+            }
+        }
+    }
+    buffer.unmap();
+    Ok(image)
+}
+
+// Reads back the offscreen target of headless graphics
+pub fn capture_frame(graphics: &Graphics) -> Result<RgbaImage> {
+    match &graphics.target {
+        Target::Offscreen(texture) => capture_texture(graphics, &texture.texture),
+        Target::Surface { .. } => anyhow::bail!("only offscreen targets can be captured"),
+    }
+}
+
+pub fn capture_attachment(
+    graphics: &Graphics,
+    g_buffer: &GBuffer,
+    attachment: Attachment,
+) -> Result<RgbaImage> {
+    capture_texture(graphics, &g_buffer.attachment(attachment).texture)
+}
+
+pub fn save_png<P: AsRef<Path>>(image: &RgbaImage, path: P) -> Result<()> {
+    image.save_with_format(path, image::ImageFormat::Png)?;
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct ImageDiff {
+    pub max_channel_diff: u8,
+    pub mismatched_pixels: usize,
+    pub pixel_count: usize,
+}
+
+impl ImageDiff {
+    pub fn mismatched_fraction(&self) -> f32 {
+        self.mismatched_pixels as f32 / self.pixel_count.max(1) as f32
+    }
+}
+
+// Counts pixels with any channel differing by more than tolerance
+pub fn diff_images(a: &RgbaImage, b: &RgbaImage, tolerance: u8) -> Result<ImageDiff> {
+    if a.dimensions() != b.dimensions() {
+        anyhow::bail!(
+            "image sizes differ {:?} != {:?}",
+            a.dimensions(),
+            b.dimensions()
+        );
+    }
+    let mut diff = ImageDiff {
+        pixel_count: (a.width() * a.height()) as usize,
+        ..Default::default()
+    };
+    for (pa, pb) in a.pixels().zip(b.pixels()) {
+        let max =
+            pa.0.iter()
+                .zip(pb.0.iter())
+                .map(|(ca, cb)| ca.abs_diff(*cb))
+                .max()
+                .unwrap_or(0);
+        diff.max_channel_diff = diff.max_channel_diff.max(max);
+        if max > tolerance {
+            diff.mismatched_pixels += 1;
+        }
+    }
+    Ok(diff)
+}
@@ -0,0 +1,1018 @@
+use crate::{
+    animation::{Channel, Clip, Interpolation, Property},
+    buffer::{Bin, Buffer},
+    geometry::{self, Geometry},
+    gltf_loader::{self, Prepared},
+    graphics::Graphics,
+    ktx,
+    material::{self, Material},
+    mesh::Mesh,
+    mipmap,
+    model::{Light, LightKind, Model, Node, Skin},
+    sampler::SamplerDesc,
+    texture::{self, ColorSpace, DecodedImage, Texels, TextureOptions},
+};
+use bytemuck::{Pod, Zeroable};
+use mg_core::*;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::num::NonZeroU32;
+use std::ops::Range;
+use wgpu::util::DeviceExt;
+
+// A cooked model is a Header whose spans locate everything else: the
+// vertex and index bin laid out as Geometry ranges, texture levels in
+// their gpu format, names, and tables of little endian repr(C) records.
+// Loading uploads the bin and levels in place and only checks the tables
+pub const MAGIC: [u8; 8] = *b"MGCOOKED";
+pub const VERSION: u32 = 7;
+pub const EXTENSION: &str = "mgc";
+
+// An index to nothing, like a material slot left to the default texture
+const NONE: u32 = u32::MAX;
+
+// Bytes of the file, or records of a table
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+struct Span {
+    start: u32,
+    end: u32,
+}
+
+impl Span {
+    fn range(self) -> Range<usize> {
+        self.start as usize..self.end as usize
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct Header {
+    magic: [u8; 8],
+    version: u32,
+    bin: Span,
+    // tables
+    levels: Span,
+    textures: Span,
+    materials: Span,
+    meshes: Span,
+    nodes: Span,
+    skins: Span,
+    lights: Span,
+    clips: Span,
+    channels: Span,
+    // u32s nodes, skins and the roots refer to
+    indices: Span,
+    // records of indices
+    roots: Span,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct TextureRecord {
+    name: Span,
+    // the KTX2 and vulkan number of the format
+    vk_format: u32,
+    width: u32,
+    height: u32,
+    // records of the level table, largest first
+    levels: Span,
+}
+
+const ADDRESS_MODES: [wgpu::AddressMode; 4] = [
+    wgpu::AddressMode::ClampToEdge,
+    wgpu::AddressMode::Repeat,
+    wgpu::AddressMode::MirrorRepeat,
+    wgpu::AddressMode::ClampToBorder,
+];
+const FILTER_MODES: [wgpu::FilterMode; 2] = [wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear];
+
+// SamplerDesc modes as indices into ADDRESS_MODES and FILTER_MODES
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct SamplerRecord {
+    address_modes: [u32; 3],
+    filters: [u32; 3],
+}
+
+impl SamplerRecord {
+    fn new(desc: &SamplerDesc) -> Self {
+        let address = |mode| ADDRESS_MODES.iter().position(|&m| m == mode).unwrap() as u32;
+        let filter = |mode| FILTER_MODES.iter().position(|&m| m == mode).unwrap() as u32;
+        Self {
+            address_modes: [
+                address(desc.address_mode_u),
+                address(desc.address_mode_v),
+                address(desc.address_mode_w),
+            ],
+            filters: [
+                filter(desc.mag_filter),
+                filter(desc.min_filter),
+                filter(desc.mipmap_filter),
+            ],
+        }
+    }
+
+    fn desc(&self) -> Option<SamplerDesc> {
+        let [u, v, w] = self
+            .address_modes
+            .map(|i| ADDRESS_MODES.get(i as usize).copied());
+        let [mag, min, mipmap] = self.filters.map(|i| FILTER_MODES.get(i as usize).copied());
+        Some(SamplerDesc {
+            address_mode_u: u?,
+            address_mode_v: v?,
+            address_mode_w: w?,
+            mag_filter: mag?,
+            min_filter: min?,
+            mipmap_filter: mipmap?,
+        })
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct Slot {
+    texture: u32,
+    sampler: SamplerRecord,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct MaterialRecord {
+    name: Span,
+    params: material::Params,
+    // in Bindings::textures order
+    slots: [Slot; 5],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct MeshRecord {
+    name: Span,
+    material: u32,
+    elm_amt: u32,
+    index_size: u32,
+    // bytes of the bin
+    ranges: geometry::Ranges,
+    // bytes of one f32 per morph target
+    weights: Span,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct NodeRecord {
+    name: Span,
+    tsf: [[f32; 4]; 4],
+    parent: u32,
+    // records of indices
+    children: Span,
+    meshes: Span,
+    skin: u32,
+    light: u32,
+    // bytes of one matrix per instance
+    instances: Span,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct SkinRecord {
+    name: Span,
+    // records of indices
+    joints: Span,
+    // bytes of one matrix per joint
+    inverse_binds: Span,
+}
+
+const DIRECTIONAL: u32 = 0;
+const POINT: u32 = 1;
+const SPOT: u32 = 2;
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct LightRecord {
+    name: Span,
+    kind: u32,
+    color: [f32; 3],
+    intensity: f32,
+    // zero when unlimited
+    range: f32,
+    // spot lights only
+    inner_cone: f32,
+    outer_cone: f32,
+}
+
+const PROPERTIES: [Property; 4] = [
+    Property::Translation,
+    Property::Rotation,
+    Property::Scale,
+    Property::Weights,
+];
+const INTERPOLATIONS: [Interpolation; 3] = [
+    Interpolation::Step,
+    Interpolation::Linear,
+    Interpolation::CubicSpline,
+];
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct ClipRecord {
+    name: Span,
+    // records of the channel table
+    channels: Span,
+}
+
+// property and interpolation index PROPERTIES and INTERPOLATIONS
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct ChannelRecord {
+    node: u32,
+    property: u32,
+    interpolation: u32,
+    // bytes of f32s
+    times: Span,
+    values: Span,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CookOptions {
+    // BC1, or BC3 when there's alpha, for 8 bit textures whose size is a
+    // multiple of 4. Devices without BC support decode them when loading
+    pub compress: bool,
+}
+
+struct Writer<'a> {
+    asset: &'a str,
+    out: Vec<u8>,
+    names: HashMap<String, Span>,
+}
+
+impl Writer<'_> {
+    // Spans are u32 so nothing can end past 4 GiB
+    fn bytes(&mut self, bytes: &[u8]) -> Result<Span> {
+        self.out.resize(self.out.len().next_multiple_of(4), 0);
+        let start = self.out.len();
+        self.out.extend_from_slice(bytes);
+        match (u32::try_from(start), u32::try_from(self.out.len())) {
+            (Ok(start), Ok(end)) => Ok(Span { start, end }),
+            _ => anyhow::bail!("{} cooks to more than 4 GiB", self.asset),
+        }
+    }
+
+    fn table<T: Pod>(&mut self, records: &[T]) -> Result<Span> {
+        self.bytes(bytemuck::cast_slice(records))
+    }
+
+    fn name(&mut self, name: &str) -> Result<Span> {
+        if let Some(&span) = self.names.get(name) {
+            return Ok(span);
+        }
+        let span = self.bytes(name.as_bytes())?;
+        self.names.insert(name.to_string(), span);
+        Ok(span)
+    }
+}
+
+fn push_indices(indices: &mut Vec<u32>, values: &[usize]) -> Span {
+    let start = indices.len() as u32;
+    indices.extend(values.iter().map(|&v| v as u32));
+    Span {
+        start,
+        end: indices.len() as u32,
+    }
+}
+
+// An image's levels in the format loading uploads
+struct CookedTexture {
+    vk_format: ktx2::Format,
+    width: u32,
+    height: u32,
+    levels: Vec<Vec<u8>>,
+}
+
+fn level_count(width: u32, height: u32, mipmaps: bool) -> u32 {
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    match mipmaps {
+        true => size.max_mips(wgpu::TextureDimension::D2),
+        false => 1,
+    }
+}
+
+fn cook_rgba(
+    rgba: &image::RgbaImage,
+    srgb: bool,
+    mipmaps: bool,
+    options: &CookOptions,
+) -> CookedTexture {
+    use ktx2::Format as K;
+    let (width, height) = rgba.dimensions();
+    let mut chain = vec![rgba.clone()];
+    chain.extend(mipmap::generate_cpu(
+        rgba,
+        srgb,
+        level_count(width, height, mipmaps),
+    ));
+    let compress = options.compress && width.is_multiple_of(4) && height.is_multiple_of(4);
+    let alpha = rgba.pixels().any(|p| p[3] < u8::MAX);
+    let vk_format = match (compress, alpha, srgb) {
+        (false, _, false) => K::R8G8B8A8_UNORM,
+        (false, _, true) => K::R8G8B8A8_SRGB,
+        (true, false, false) => K::BC1_RGBA_UNORM_BLOCK,
+        (true, false, true) => K::BC1_RGBA_SRGB_BLOCK,
+        (true, true, false) => K::BC3_UNORM_BLOCK,
+        (true, true, true) => K::BC3_SRGB_BLOCK,
+    };
+    let format = ktx::wgpu_format(vk_format).unwrap();
+    let levels = chain
+        .into_iter()
+        .map(|level| match compress {
+            true => ktx::encode(format, &level).unwrap(),
+            false => level.into_raw(),
+        })
+        .collect();
+    CookedTexture {
+        vk_format,
+        width,
+        height,
+        levels,
+    }
+}
+
+// Half floats like Texture::create_float_texture, each level filtered
+// down from the first
+fn cook_float(rgba: &image::Rgba32FImage, mipmaps: bool) -> CookedTexture {
+    let (width, height) = rgba.dimensions();
+    let levels = (0..level_count(width, height, mipmaps))
+        .map(|level| match level {
+            0 => texture::f16_bytes(rgba),
+            _ => {
+                let (w, h) = ((width >> level).max(1), (height >> level).max(1));
+                let filter = image::imageops::FilterType::Triangle;
+                texture::f16_bytes(&image::imageops::resize(rgba, w, h, filter))
+            }
+        })
+        .collect();
+    CookedTexture {
+        vk_format: ktx2::Format::R16G16B16A16_SFLOAT,
+        width,
+        height,
+        levels,
+    }
+}
+
+fn cook_texture(
+    name: &str,
+    image: &DecodedImage,
+    color_space: ColorSpace,
+    mipmaps: bool,
+    options: &CookOptions,
+) -> Result<CookedTexture> {
+    let srgb = color_space == ColorSpace::Srgb;
+    match &image.texels {
+        Texels::Ktx2(bytes) => {
+            // basis textures are transcoded like cook_rgba compresses
+            let features = match options.compress {
+                true => wgpu::Features::TEXTURE_COMPRESSION_BC,
+                false => wgpu::Features::empty(),
+            };
+            let ktx::Levels {
+                vk_format,
+                format,
+                size,
+                levels,
+            } = ktx::read_levels(name, bytes, features)?;
+            // a lone uncompressed level gets a mip chain like other images
+            if levels.len() == 1 && mipmaps && !format.is_compressed() {
+                if let Some(rgba) = ktx::decode(format, size.width, size.height, &levels[0]) {
+                    return Ok(cook_rgba(&rgba, format.is_srgb(), mipmaps, options));
+                }
+            }
+            Ok(CookedTexture {
+                vk_format,
+                width: size.width,
+                height: size.height,
+                levels: levels.into_iter().map(Cow::into_owned).collect(),
+            })
+        }
+        Texels::Rgba8(rgba) => Ok(cook_rgba(rgba, srgb, mipmaps, options)),
+        Texels::Float(rgba) => Ok(cook_float(rgba, mipmaps)),
+    }
+}
+
+// Lays out a prepared glTF the way load reads it. Only the bin ranges
+// meshes use are kept and textures are mipped ahead of time
+pub fn cook(prepared: &Prepared, options: &CookOptions) -> Result<Vec<u8>> {
+    let doc = &prepared.doc;
+    let mut writer = Writer {
+        asset: &prepared.asset,
+        out: vec![0; size_of::<Header>()],
+        names: HashMap::new(),
+    };
+
+    let mut bin = vec![];
+    let mut moved = HashMap::new();
+    let mut compact = |range: [u32; 2]| {
+        *moved.entry(range).or_insert_with(|| {
+            let bytes = &prepared.bin[range[0] as usize..range[1] as usize];
+            gltf_loader::append(&mut bin, bytes)
+        })
+    };
+    let mut meshes = vec![];
+    let mut mesh_ranges = vec![];
+    for (mesh, layouts) in doc.meshes().zip(&prepared.layouts) {
+        let start = meshes.len();
+        for (p, layout) in mesh.primitives().zip(layouts) {
+            let ranges = layout.ranges;
+            meshes.push(MeshRecord {
+                name: writer.name(mesh.name().unwrap_or(""))?,
+                material: p.material().index().map_or(NONE, |i| i as u32),
+                elm_amt: layout.elm_amt,
+                index_size: match layout.index_format {
+                    wgpu::IndexFormat::Uint16 => 2,
+                    wgpu::IndexFormat::Uint32 => 4,
+                },
+                ranges: geometry::Ranges {
+                    index: compact(ranges.index),
+                    vertex: compact(ranges.vertex),
+                    uv: compact(ranges.uv),
+                    normal: compact(ranges.normal),
+                    tangent: compact(ranges.tangent),
+                    joints: compact(ranges.joints),
+                    weights: compact(ranges.weights),
+                    targets: compact(ranges.targets),
+                },
+                weights: writer.table(&gltf_loader::mesh_weights(&mesh, &ranges))?,
+            });
+        }
+        mesh_ranges.push(start..meshes.len());
+    }
+    let mut header = Header {
+        magic: MAGIC,
+        version: VERSION,
+        bin: writer.bytes(&bin)?,
+        ..Zeroable::zeroed()
+    };
+
+    let uses = gltf_loader::texture_uses(doc);
+    let mut levels = vec![];
+    let mut textures = vec![];
+    for &(index, color_space) in &uses {
+        let t = doc.textures().nth(index).unwrap();
+        let (images, mipmaps) = gltf_loader::texture_images(&t, prepared.images.len());
+        let cook_image = |image: usize| match &prepared.images[image] {
+            (name, Ok(image)) => cook_texture(name, image, color_space, mipmaps, options),
+            (name, Err(reason)) => Err(AssetError::BadImage {
+                asset: name.clone(),
+                reason: reason.clone(),
+            }
+            .into()),
+        };
+        let cooked = match &images[..] {
+            [basisu, source] => cook_image(*basisu).or_else(|err| {
+                log::warn!("{:#}, falling back to image {}", err, source);
+                cook_image(*source)
+            })?,
+            _ => cook_image(images[0])?,
+        };
+        let first = levels.len() as u32;
+        for level in &cooked.levels {
+            levels.push(writer.bytes(level)?);
+        }
+        let name = &prepared.images[*images.last().unwrap()].0;
+        textures.push(TextureRecord {
+            name: writer.name(name)?,
+            vk_format: cooked.vk_format.0.get(),
+            width: cooked.width,
+            height: cooked.height,
+            levels: Span {
+                start: first,
+                end: levels.len() as u32,
+            },
+        });
+    }
+
+    let materials = doc
+        .materials()
+        .map(|m| {
+            let slots = gltf_loader::material_textures(&m).map(|slot| match slot {
+                Some((t, _, color_space)) => Slot {
+                    texture: uses
+                        .iter()
+                        .position(|&u| u == (t.index(), color_space))
+                        .unwrap() as u32,
+                    sampler: SamplerRecord::new(&gltf_loader::sampler_desc(&t.sampler())),
+                },
+                None => Slot {
+                    texture: NONE,
+                    sampler: SamplerRecord::new(&SamplerDesc::default()),
+                },
+            });
+            Ok(MaterialRecord {
+                name: writer.name(m.name().unwrap_or(""))?,
+                params: gltf_loader::material_params(&m),
+                slots,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (nodes, roots) = gltf_loader::parse_nodes(doc, &mesh_ranges, &prepared.instances);
+    // parents are cooked before their children so loading can rule out cycles
+    let (order, new_index) = parents_first(&nodes)
+        .ok_or_else(|| anyhow::anyhow!("{} has nodes that aren't a tree", prepared.asset))?;
+    let remap = |nodes: &[usize]| {
+        nodes
+            .iter()
+            .map(|&node| new_index[node])
+            .collect::<Vec<_>>()
+    };
+    let mut indices = vec![];
+    let nodes = order
+        .iter()
+        .map(|&node| {
+            let node = &nodes[node];
+            let instances: Vec<[[f32; 4]; 4]> = node.instances.iter().map(|&m| m.into()).collect();
+            Ok(NodeRecord {
+                name: writer.name(&node.name)?,
+                tsf: node.tsf.into(),
+                parent: node.parent.map_or(NONE, |parent| new_index[parent] as u32),
+                children: push_indices(&mut indices, &remap(&node.children)),
+                meshes: push_indices(&mut indices, &node.meshes),
+                skin: node.skin.map_or(NONE, |skin| skin as u32),
+                light: node.light.map_or(NONE, |light| light as u32),
+                instances: writer.table(&instances)?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    header.roots = push_indices(&mut indices, &remap(&roots));
+    let skins = prepared
+        .skins
+        .iter()
+        .map(|skin| {
+            let inverse_binds: Vec<[[f32; 4]; 4]> =
+                skin.inverse_binds.iter().map(|&m| m.into()).collect();
+            Ok(SkinRecord {
+                name: writer.name(&skin.name)?,
+                joints: push_indices(&mut indices, &remap(&skin.joints)),
+                inverse_binds: writer.table(&inverse_binds)?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let lights = gltf_loader::parse_lights(doc)
+        .iter()
+        .map(|light| {
+            let (kind, [inner_cone, outer_cone]) = match light.kind {
+                LightKind::Directional => (DIRECTIONAL, [0.0; 2]),
+                LightKind::Point => (POINT, [0.0; 2]),
+                LightKind::Spot {
+                    inner_cone,
+                    outer_cone,
+                } => (SPOT, [inner_cone, outer_cone]),
+            };
+            Ok(LightRecord {
+                name: writer.name(&light.name)?,
+                kind,
+                color: light.color.into(),
+                intensity: light.intensity,
+                range: light.range.unwrap_or(0.0),
+                inner_cone,
+                outer_cone,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut channels = vec![];
+    let clips = prepared
+        .animations
+        .iter()
+        .map(|clip| {
+            let start = channels.len() as u32;
+            for channel in &clip.channels {
+                let property = PROPERTIES.iter().position(|&p| p == channel.property);
+                let interpolation = INTERPOLATIONS
+                    .iter()
+                    .position(|&i| i == channel.interpolation);
+                channels.push(ChannelRecord {
+                    node: new_index[channel.node] as u32,
+                    property: property.unwrap() as u32,
+                    interpolation: interpolation.unwrap() as u32,
+                    times: writer.table(&channel.times)?,
+                    values: writer.table(&channel.values)?,
+                });
+            }
+            Ok(ClipRecord {
+                name: writer.name(&clip.name)?,
+                channels: Span {
+                    start,
+                    end: channels.len() as u32,
+                },
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    header.levels = writer.table(&levels)?;
+    header.textures = writer.table(&textures)?;
+    header.materials = writer.table(&materials)?;
+    header.meshes = writer.table(&meshes)?;
+    header.nodes = writer.table(&nodes)?;
+    header.skins = writer.table(&skins)?;
+    header.lights = writer.table(&lights)?;
+    header.clips = writer.table(&clips)?;
+    header.channels = writer.table(&channels)?;
+    header.indices = writer.table(&indices)?;
+    writer.out[..size_of::<Header>()].copy_from_slice(bytemuck::bytes_of(&header));
+    Ok(writer.out)
+}
+
+// The order nodes are cooked in, each after its parent, and each node's
+// index in it. None when the parents loop or a child is listed twice
+fn parents_first(nodes: &[Node]) -> Option<(Vec<usize>, Vec<usize>)> {
+    let mut order = vec![];
+    let mut new_index = vec![usize::MAX; nodes.len()];
+    for node in 0..nodes.len() {
+        let mut ancestors = vec![];
+        let mut next = Some(node);
+        while let Some(node) = next.filter(|&node| new_index[node] == usize::MAX) {
+            if ancestors.len() == nodes.len() {
+                return None;
+            }
+            ancestors.push(node);
+            next = nodes[node].parent;
+        }
+        for &node in ancestors.iter().rev() {
+            new_index[node] = order.len();
+            order.push(node);
+        }
+    }
+    // a child listed by a node other than its parent could still come first
+    let ordered = order.iter().enumerate().all(|(i, &node)| {
+        nodes[node]
+            .children
+            .iter()
+            .all(|&child| new_index[child] > i)
+    });
+    ordered.then_some((order, new_index))
+}
+
+pub fn is_cooked(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+// Records in place when the bytes are aligned for them
+fn table<T: Pod>(bytes: &[u8], span: Span) -> Option<Cow<'_, [T]>> {
+    let bytes = bytes.get(span.range())?;
+    if !bytes.len().is_multiple_of(size_of::<T>()) {
+        return None;
+    }
+    Some(match bytemuck::try_cast_slice(bytes) {
+        Ok(records) => Cow::Borrowed(records),
+        Err(_) => Cow::Owned(
+            bytes
+                .chunks_exact(size_of::<T>())
+                .map(bytemuck::pod_read_unaligned)
+                .collect(),
+        ),
+    })
+}
+
+fn level_size(size: wgpu::Extent3d, level: u32, format: wgpu::TextureFormat) -> Option<usize> {
+    let size = size
+        .mip_level_size(level, wgpu::TextureDimension::D2)
+        .physical_size(format);
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_size(None).unwrap_or(0);
+    [
+        size.width / block_width,
+        size.height / block_height,
+        block_size,
+    ]
+    .iter()
+    .try_fold(1usize, |len, &n| len.checked_mul(n as usize))
+}
+
+// The model's buffer keeps the file, its bin shared rather than copied
+pub fn load(
+    graphics: &Graphics,
+    defaults: &material::Defaults,
+    asset: &str,
+    file: Arc<[u8]>,
+) -> Result<Model> {
+    let bytes = &file[..];
+    let invalid = |reason: String| -> anyhow::Error {
+        AssetError::InvalidCooked {
+            asset: asset.to_string(),
+            reason,
+        }
+        .into()
+    };
+    let header: Header = match bytes.get(..size_of::<Header>()) {
+        Some(header) if is_cooked(header) => bytemuck::pod_read_unaligned(header),
+        _ => return Err(invalid("not a cooked model".to_string())),
+    };
+    if header.version != VERSION {
+        return Err(invalid(format!(
+            "version {}, this build reads {}",
+            header.version, VERSION
+        )));
+    }
+    let out_of_range = |what: &str| invalid(format!("{} out of range", what));
+    let name = |span: Span| {
+        bytes
+            .get(span.range())
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or_else(|| out_of_range("name"))
+    };
+    let bin = bytes
+        .get(header.bin.range())
+        .ok_or_else(|| out_of_range("bin"))?;
+    let levels = table::<Span>(bytes, header.levels).ok_or_else(|| out_of_range("levels"))?;
+    let indices = table::<u32>(bytes, header.indices).ok_or_else(|| out_of_range("indices"))?;
+    let index_span = |span: Span, len: usize| {
+        let indices = indices
+            .get(span.range())
+            .ok_or_else(|| out_of_range("index span"))?;
+        match indices.iter().all(|&i| (i as usize) < len) {
+            true => Ok(indices.iter().map(|&i| i as usize).collect::<Vec<_>>()),
+            false => Err(out_of_range("index")),
+        }
+    };
+
+    let textures = table::<TextureRecord>(bytes, header.textures)
+        .ok_or_else(|| out_of_range("textures"))?
+        .iter()
+        .map(|record| {
+            let name = name(record.name)?;
+            let format = NonZeroU32::new(record.vk_format)
+                .and_then(|format| ktx::wgpu_format(ktx2::Format(format)))
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "texture \"{}\" has format {}",
+                        name, record.vk_format
+                    ))
+                })?;
+            let size = wgpu::Extent3d {
+                width: record.width,
+                height: record.height,
+                depth_or_array_layers: 1,
+            };
+            let level_spans = levels
+                .get(record.levels.range())
+                .filter(|spans| {
+                    let max = size.max_mips(wgpu::TextureDimension::D2) as usize;
+                    record.width > 0 && record.height > 0 && (1..=max).contains(&spans.len())
+                })
+                .ok_or_else(|| invalid(format!("texture \"{}\" has bad levels", name)))?;
+            let data = level_spans
+                .iter()
+                .enumerate()
+                .map(|(level, span)| {
+                    bytes
+                        .get(span.range())
+                        .filter(|data| Some(data.len()) == level_size(size, level as u32, format))
+                        .ok_or_else(|| invalid(format!("texture \"{}\" level {}", name, level)))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            // the levels are final, a lone one isn't mipped
+            let options = TextureOptions {
+                mipmaps: false,
+                color_space: ColorSpace::Linear,
+            };
+            let texture = ktx::upload_levels(graphics, name, format, size, &data, &options)?;
+            Ok(Arc::new(texture))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let default_textures = defaults.material.bindings.textures();
+    let materials = table::<MaterialRecord>(bytes, header.materials)
+        .ok_or_else(|| out_of_range("materials"))?
+        .iter()
+        .map(|record| {
+            let name = name(record.name)?;
+            for (i, slot) in record.slots.iter().enumerate() {
+                let texture = slot.texture == NONE || (slot.texture as usize) < textures.len();
+                if !texture || slot.sampler.desc().is_none() {
+                    return Err(invalid(format!("material \"{}\" slot {}", name, i)));
+                }
+            }
+            let slots = std::array::from_fn(|i| match record.slots[i] {
+                Slot { texture: NONE, .. } => default_textures[i].clone(),
+                Slot { texture, sampler } => (
+                    textures[texture as usize].clone(),
+                    graphics.sampler(sampler.desc().unwrap()),
+                ),
+            });
+            let bindings = material::Bindings::from_textures(record.params, slots);
+            let name = (!name.is_empty()).then_some(name);
+            Ok(Arc::new(Material::new(graphics, name, bindings)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let gpu_buffer = graphics
+        .device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(format!("{} buffer", asset).as_str()),
+            contents: bin,
+            usage: wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::INDEX
+                | wgpu::BufferUsages::STORAGE,
+        });
+    // the bin is shared with the file rather than copied out of it
+    let buffer = Arc::new(Buffer {
+        bin: Bin::new(file.clone(), header.bin.range()),
+        gpu_buffer,
+    });
+    let meshes = table::<MeshRecord>(bytes, header.meshes)
+        .ok_or_else(|| out_of_range("meshes"))?
+        .iter()
+        .map(|record| {
+            let name = name(record.name)?;
+            let r = record.ranges;
+            let in_bin = [
+                r.index, r.vertex, r.uv, r.normal, r.tangent, r.joints, r.weights, r.targets,
+            ]
+            .iter()
+            .all(|&[start, end]| start <= end && end as usize <= bin.len());
+            let index_format = match record.index_size {
+                2 => wgpu::IndexFormat::Uint16,
+                4 => wgpu::IndexFormat::Uint32,
+                _ => return Err(invalid(format!("mesh \"{}\" index size", name))),
+            };
+            let indexed = record.elm_amt as u64 * record.index_size as u64;
+            if !in_bin || indexed > r.index().end - r.index().start {
+                return Err(invalid(format!("mesh \"{}\" ranges", name)));
+            }
+            // ranges are bound and read as whole, aligned elements
+            let range = |[start, end]: [u32; 2]| &bin[start as usize..end as usize];
+            let indices_aligned = match index_format {
+                wgpu::IndexFormat::Uint16 => {
+                    bytemuck::try_cast_slice::<_, u16>(range(r.index)).is_ok()
+                }
+                wgpu::IndexFormat::Uint32 => {
+                    bytemuck::try_cast_slice::<_, u32>(range(r.index)).is_ok()
+                }
+            };
+            let aligned = [
+                r.vertex, r.uv, r.normal, r.tangent, r.joints, r.weights, r.targets,
+            ]
+            .iter()
+            .all(|&floats| bytemuck::try_cast_slice::<_, u32>(range(floats)).is_ok());
+            if !indices_aligned || !aligned {
+                return Err(invalid(format!("mesh \"{}\" ranges aren't aligned", name)));
+            }
+            let weights = table::<f32>(bytes, record.weights)
+                .filter(|weights| weights.len() == r.target_amt() as usize)
+                .ok_or_else(|| out_of_range("weights"))?;
+            let material = match record.material {
+                NONE => defaults.material.clone(),
+                material => materials
+                    .get(material as usize)
+                    .ok_or_else(|| invalid(format!("mesh \"{}\" material", name)))?
+                    .clone(),
+            };
+            Ok(Mesh {
+                name: name.to_string(),
+                geometry: Arc::new(Geometry {
+                    elm_amt: record.elm_amt,
+                    ranges: record.ranges,
+                    index_format,
+                    buffer: buffer.clone(),
+                    g_pipeline: None,
+                    ray_pipeline: None,
+                }),
+                material,
+                weights: weights.to_vec(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let node_records =
+        table::<NodeRecord>(bytes, header.nodes).ok_or_else(|| out_of_range("nodes"))?;
+    let skins = table::<SkinRecord>(bytes, header.skins)
+        .ok_or_else(|| out_of_range("skins"))?
+        .iter()
+        .map(|record| {
+            let joints = index_span(record.joints, node_records.len())?;
+            let inverse_binds = table::<[[f32; 4]; 4]>(bytes, record.inverse_binds)
+                .filter(|matrices| matrices.len() == joints.len())
+                .ok_or_else(|| out_of_range("inverse binds"))?;
+            Ok(Skin {
+                name: name(record.name)?.to_string(),
+                joints,
+                inverse_binds: inverse_binds.iter().map(|&m| Mat4::from(m)).collect(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let lights = table::<LightRecord>(bytes, header.lights)
+        .ok_or_else(|| out_of_range("lights"))?
+        .iter()
+        .map(|record| {
+            let name = name(record.name)?;
+            Ok(Light {
+                name: name.to_string(),
+                kind: match record.kind {
+                    DIRECTIONAL => LightKind::Directional,
+                    POINT => LightKind::Point,
+                    SPOT => LightKind::Spot {
+                        inner_cone: record.inner_cone,
+                        outer_cone: record.outer_cone,
+                    },
+                    _ => return Err(invalid(format!("light \"{}\" kind", name))),
+                },
+                color: Vec3f::from(record.color),
+                intensity: record.intensity,
+                range: Some(record.range).filter(|&range| range > 0.0),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let nodes = node_records
+        .iter()
+        .enumerate()
+        .map(|(i, record)| {
+            let parent = match record.parent {
+                NONE => None,
+                parent if (parent as usize) < node_records.len() => Some(parent as usize),
+                _ => return Err(out_of_range("parent")),
+            };
+            // nodes come after their parents, so the hierarchy can't loop
+            let children = index_span(record.children, node_records.len())?;
+            if parent.is_some_and(|parent| parent >= i) || children.iter().any(|&child| child <= i)
+            {
+                return Err(invalid(format!("node {} comes before its parent", i)));
+            }
+            let skin = match record.skin {
+                NONE => None,
+                skin if (skin as usize) < skins.len() => Some(skin as usize),
+                _ => return Err(out_of_range("skin")),
+            };
+            let light = match record.light {
+                NONE => None,
+                light if (light as usize) < lights.len() => Some(light as usize),
+                _ => return Err(out_of_range("light")),
+            };
+            let instances = table::<[[f32; 4]; 4]>(bytes, record.instances)
+                .ok_or_else(|| out_of_range("instances"))?;
+            Ok(Node {
+                name: name(record.name)?.to_string(),
+                tsf: Mat4::from(record.tsf),
+                parent,
+                children,
+                meshes: index_span(record.meshes, meshes.len())?,
+                skin,
+                light,
+                instances: instances.iter().map(|&m| Mat4::from(m)).collect(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let roots = index_span(header.roots, nodes.len())?;
+
+    let channel_records =
+        table::<ChannelRecord>(bytes, header.channels).ok_or_else(|| out_of_range("channels"))?;
+    let animations = table::<ClipRecord>(bytes, header.clips)
+        .ok_or_else(|| out_of_range("clips"))?
+        .iter()
+        .map(|record| {
+            let name = name(record.name)?;
+            let channels = channel_records
+                .get(record.channels.range())
+                .ok_or_else(|| out_of_range("clip channels"))?
+                .iter()
+                .enumerate()
+                .map(|(i, record)| {
+                    let bad = |reason: &str| {
+                        invalid(format!("clip \"{}\" channel {} {}", name, i, reason))
+                    };
+                    let floats = |span| table::<f32>(bytes, span).map(Cow::into_owned);
+                    let channel = Channel {
+                        node: record.node as usize,
+                        property: *PROPERTIES
+                            .get(record.property as usize)
+                            .ok_or_else(|| bad("property"))?,
+                        interpolation: *INTERPOLATIONS
+                            .get(record.interpolation as usize)
+                            .ok_or_else(|| bad("interpolation"))?,
+                        times: floats(record.times).ok_or_else(|| out_of_range("times"))?,
+                        values: floats(record.values).ok_or_else(|| out_of_range("values"))?,
+                    };
+                    if channel.node >= nodes.len() {
+                        return Err(out_of_range("channel node"));
+                    }
+                    match channel.check() {
+                        Some(reason) => Err(bad(&reason)),
+                        None => Ok(channel),
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Arc::new(Clip::new(name.to_string(), channels)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Model {
+        meshes,
+        nodes,
+        roots,
+        skins,
+        lights,
+        animations,
+    })
+}
@@ -0,0 +1,244 @@
+use crate::{geometry::Geometry, graphics::Graphics};
+use mg_core::*;
+use std::mem::size_of;
+use std::ops::Range;
+use wgpu::util::DeviceExt;
+
+// A model's skin instantiated for one scene node whose meshes it deforms
+pub struct Skin {
+    // scene nodes
+    pub joints: Vec<usize>,
+    pub inverse_binds: Vec<Mat4>,
+    pub node: usize,
+    // each joint's transform relative to node, posed by Scene::update
+    pub palette: Vec<Mat4>,
+    pub(crate) palette_buffer: wgpu::Buffer,
+}
+
+fn palette_buffer(graphics: &Graphics, joint_amt: usize) -> wgpu::Buffer {
+    graphics.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("palette buffer"),
+        size: (joint_amt.max(1) * size_of::<[[f32; 4]; 4]>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+impl Skin {
+    pub fn new(
+        graphics: &Graphics,
+        joints: Vec<usize>,
+        inverse_binds: Vec<Mat4>,
+        node: usize,
+    ) -> Skin {
+        Skin {
+            palette: vec![Mat4::identity(); joints.len()],
+            palette_buffer: palette_buffer(graphics, joints.len()),
+            joints,
+            inverse_binds,
+            node,
+        }
+    }
+
+    // Skinned vertices stay in the node's space so the instance transform
+    // still places them
+    pub(crate) fn pose(&mut self, world: impl Fn(usize) -> Mat4) {
+        let to_node = world(self.node)
+            .try_inverse()
+            .unwrap_or_else(Mat4::identity);
+        for ((matrix, &joint), inverse_bind) in self
+            .palette
+            .iter_mut()
+            .zip(&self.joints)
+            .zip(&self.inverse_binds)
+        {
+            *matrix = to_node * world(joint) * inverse_bind;
+        }
+    }
+
+    pub(crate) fn write(&self, graphics: &Graphics) {
+        let palette: Vec<[[f32; 4]; 4]> = self.palette.iter().map(|&m| m.into()).collect();
+        graphics
+            .queue
+            .write_buffer(&self.palette_buffer, 0, bytemuck::cast_slice(&palette));
+    }
+}
+
+// mirrors DeformRanges in deform.wgsl, byte offsets into the mesh buffer
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DeformRanges {
+    vertex: u32,
+    normal: u32,
+    tangent: u32,
+    joints: u32,
+    weights: u32,
+    targets: u32,
+    target_amt: u32,
+    vertex_amt: u32,
+    skinned: u32,
+}
+
+// An instance's positions, normals and tangents morphed then skinned by
+// the deform pass into a buffer of its own, in that order
+pub struct Deformed {
+    // index into Scene::skins
+    pub skin: Option<usize>,
+    pub vertex_amt: u32,
+    // one per morph target
+    pub(crate) weights: Vec<f32>,
+    pub(crate) weights_dirty: bool,
+    weights_buffer: wgpu::Buffer,
+    pub(crate) output: wgpu::Buffer,
+    pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl Deformed {
+    pub fn new(
+        graphics: &Graphics,
+        geometry: &Geometry,
+        skin: Option<usize>,
+        mut weights: Vec<f32>,
+        skins: &[Skin],
+    ) -> Deformed {
+        let vertex_amt = geometry.vertex_amt();
+        weights.resize(geometry.target_amt() as usize, 0.0);
+        let r = geometry.ranges;
+        let ranges = DeformRanges {
+            vertex: r.vertex[0],
+            normal: r.normal[0],
+            tangent: r.tangent[0],
+            joints: r.joints[0],
+            weights: r.weights[0],
+            targets: r.targets[0],
+            target_amt: weights.len() as u32,
+            vertex_amt,
+            skinned: skin.is_some() as u32,
+        };
+        let ranges_uniform =
+            graphics
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("deform ranges buffer"),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                    contents: bytemuck::bytes_of(&ranges),
+                });
+        let weights_buffer = graphics.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("morph weights buffer"),
+            size: (weights.len().max(1) * size_of::<f32>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // unskinned instances bind an unused palette
+        let unskinned;
+        let palette = match skin {
+            Some(skin) => &skins[skin].palette_buffer,
+            None => {
+                unskinned = palette_buffer(graphics, 0);
+                &unskinned
+            }
+        };
+        let output = graphics.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("deformed buffer"),
+            size: vertex_amt as u64 * size_of::<[f32; 10]>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+        let bind_group = graphics
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("deform bind group"),
+                layout: &bind_group_layout(graphics),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: ranges_uniform.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: geometry.buffer.gpu_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: palette.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: weights_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: output.as_entire_binding(),
+                    },
+                ],
+            });
+        Deformed {
+            skin,
+            vertex_amt,
+            weights,
+            weights_dirty: true,
+            weights_buffer,
+            output,
+            bind_group,
+        }
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    pub(crate) fn write_weights(&mut self, graphics: &Graphics) {
+        if self.weights_dirty && !self.weights.is_empty() {
+            graphics.queue.write_buffer(
+                &self.weights_buffer,
+                0,
+                bytemuck::cast_slice(&self.weights),
+            );
+        }
+        self.weights_dirty = false;
+    }
+
+    pub fn vertex(&self) -> Range<u64> {
+        0..self.vertex_amt as u64 * 12
+    }
+    pub fn normal(&self) -> Range<u64> {
+        self.vertex_amt as u64 * 12..self.vertex_amt as u64 * 24
+    }
+    pub fn tangent(&self) -> Range<u64> {
+        self.vertex_amt as u64 * 24..self.vertex_amt as u64 * 40
+    }
+}
+
+pub fn bind_group_layout(graphics: &Graphics) -> wgpu::BindGroupLayout {
+    let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    graphics
+        .device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("deform bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, true),
+                storage(3, true),
+                storage(4, false),
+            ],
+        })
+}
@@ -124,7 +124,8 @@ impl GBuffer {
     pub fn new(graphics: &Graphics) -> Self {
         let g_usage = wgpu::TextureUsages::STORAGE_BINDING
             | wgpu::TextureUsages::RENDER_ATTACHMENT
-            | wgpu::TextureUsages::TEXTURE_BINDING;
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC;
         let albedo_texture =
             Texture::create_texture(graphics, "g albedo texture", TX_FORMAT_COLOR, g_usage);
         let emission_texture =
@@ -137,7 +138,9 @@ impl GBuffer {
             graphics,
             "depth texture",
             TX_FORMAT_DEPTH,
-            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
         );
         let read_bind_group = graphics
             .device
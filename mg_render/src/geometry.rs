@@ -3,10 +3,18 @@ use mg_core::*;
 use std::ops::Range;
 
 #[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Ranges {
     pub index: [u32; 2],
     pub vertex: [u32; 2],
     pub uv: [u32; 2],
+    pub normal: [u32; 2],
+    pub tangent: [u32; 2],
+    // u32 joint indices and f32 weights, four per vertex, empty unless skinned
+    pub joints: [u32; 2],
+    pub weights: [u32; 2],
+    // per morph target position, normal and tangent deltas, f32x3 each
+    pub targets: [u32; 2],
 }
 
 impl Ranges {
@@ -19,12 +27,66 @@ impl Ranges {
     pub fn uv(&self) -> Range<u64> {
         self.uv[0] as u64..self.uv[1] as u64
     }
+    pub fn normal(&self) -> Range<u64> {
+        self.normal[0] as u64..self.normal[1] as u64
+    }
+    pub fn tangent(&self) -> Range<u64> {
+        self.tangent[0] as u64..self.tangent[1] as u64
+    }
+    pub fn joints(&self) -> Range<u64> {
+        self.joints[0] as u64..self.joints[1] as u64
+    }
+    pub fn weights(&self) -> Range<u64> {
+        self.weights[0] as u64..self.weights[1] as u64
+    }
+    pub fn targets(&self) -> Range<u64> {
+        self.targets[0] as u64..self.targets[1] as u64
+    }
+
+    pub fn target_amt(&self) -> u32 {
+        match (self.vertex[1] - self.vertex[0]) / 12 {
+            0 => 0,
+            amt => (self.targets[1] - self.targets[0]) / (amt * 36),
+        }
+    }
+}
+
+// Mirrors GeometryRanges in raytrace.wgsl
+#[repr(C)]
+pub struct RangesUniform {
+    pub ranges: Ranges,
+    pub index_size: u32,
 }
 
 pub struct Geometry {
     pub elm_amt: u32,
     pub ranges: Ranges,
+    pub index_format: wgpu::IndexFormat,
     pub buffer: Arc<Buffer>,
     pub g_pipeline: Option<wgpu::RenderPipeline>,
     pub ray_pipeline: Option<wgpu::ComputePipeline>,
 }
+
+impl Geometry {
+    pub fn vertex_amt(&self) -> u32 {
+        (self.ranges.vertex[1] - self.ranges.vertex[0]) / 12
+    }
+
+    pub fn is_skinned(&self) -> bool {
+        !self.ranges.joints().is_empty()
+    }
+
+    pub fn target_amt(&self) -> u32 {
+        self.ranges.target_amt()
+    }
+
+    pub fn ranges_uniform(&self) -> RangesUniform {
+        RangesUniform {
+            ranges: self.ranges,
+            index_size: match self.index_format {
+                wgpu::IndexFormat::Uint16 => 2,
+                wgpu::IndexFormat::Uint32 => 4,
+            },
+        }
+    }
+}
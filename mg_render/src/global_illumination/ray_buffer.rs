@@ -154,7 +154,9 @@ impl RayBuffer {
                 .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: Some("world instances buffer"),
                     contents: bytemuck::cast_slice(&world_tsfs[..]),
-                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+                    usage: wgpu::BufferUsages::VERTEX
+                        | wgpu::BufferUsages::STORAGE
+                        | wgpu::BufferUsages::COPY_DST,
                 });
         let read_bind_group = graphics
             .device
@@ -1,52 +1,586 @@
 use crate::{
+    animation::{self, Channel, Clip},
     buffer::Buffer, geometry, geometry::Geometry, graphics::Graphics, material, material::Material,
-    mesh::Mesh, texture::Texture,
+    mesh::Mesh, model::{Light, LightKind, Model, Node, Skin}, sampler::SamplerDesc, tangent_space,
+    texture::{ColorSpace, DecodedImage, Texture, TextureOptions},
 };
+use base64::Engine;
+use gltf::accessor::{DataType, Dimensions};
+use gltf::texture::MinFilter;
 use gltf::Gltf;
 use mg_core::*;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+use std::ops::Range;
 use wgpu::util::DeviceExt;
 
-enum ImgSrc<'a> {
-    Slice(&'a [u8]),
-    Array(Box<[u8]>),
+fn accessor_name(accessor: &gltf::Accessor) -> String {
+    match accessor.name() {
+        Some(name) => format!("{} \"{}\"", accessor.index(), name),
+        None => accessor.index().to_string(),
+    }
+}
+
+fn unsupported(accessor: &gltf::Accessor, asset: &str, reason: &str) -> anyhow::Error {
+    AssetError::UnsupportedAccessor {
+        asset: asset.to_string(),
+        accessor: accessor_name(accessor),
+        reason: reason.to_string(),
+    }
+    .into()
+}
+
+// Checks an accessor's elements fit inside its buffer view
+fn check_accessor(accessor: &gltf::Accessor, offsets: &[usize], asset: &str) -> Result<()> {
+    let Some(view) = accessor.view() else {
+        return Ok(());
+    };
+    view_range(&view, offsets, asset)?;
+    let stride = view.stride().unwrap_or(accessor.size());
+    let end = accessor.offset() + stride * accessor.count().saturating_sub(1) + accessor.size();
+    if stride < accessor.size() || end > view.length() {
+        let reason = format!("ends at {} past its view length {}", end, view.length());
+        return Err(unsupported(accessor, asset, &reason));
+    }
+    Ok(())
+}
+
+// Byte range of an accessor that can be bound as is,
+// tightly packed, not sparse and 4 byte aligned
+fn packed_range(
+    accessor: &gltf::Accessor,
+    data_type: DataType,
+    dimensions: Dimensions,
+    offsets: &[usize],
+) -> Option<[u32; 2]> {
+    let view = accessor.view()?;
+    let start = offsets[view.buffer().index()] + view.offset() + accessor.offset();
+    let packed = accessor.sparse().is_none()
+        && !accessor.normalized()
+        && accessor.data_type() == data_type
+        && accessor.dimensions() == dimensions
+        && view.stride().is_none_or(|stride| stride == accessor.size())
+        && start.is_multiple_of(4);
+    packed.then(|| {
+        let end = start + accessor.count() * accessor.size();
+        [start as u32, end as u32]
+    })
+}
+
+fn read<T: gltf::accessor::Item>(
+    accessor: &gltf::Accessor,
+    bin: &[u8],
+    offsets: &[usize],
+    asset: &str,
+) -> Result<Vec<T>> {
+    let buffer_data = |buffer: gltf::Buffer| {
+        let start = offsets[buffer.index()];
+        bin.get(start..start + buffer.length())
+    };
+    gltf::accessor::Iter::<T>::new(accessor.clone(), buffer_data)
+        .map(|iter| iter.collect())
+        .ok_or_else(|| unsupported(accessor, asset, "can't read its data"))
+}
+
+fn check_float(accessor: &gltf::Accessor, dimensions: Dimensions, asset: &str) -> Result<()> {
+    if accessor.data_type() != DataType::F32 || accessor.dimensions() != dimensions {
+        let reason = format!("expected f32 {:?}", dimensions);
+        return Err(unsupported(accessor, asset, &reason));
+    }
+    Ok(())
+}
+
+fn read_uvs(
+    accessor: &gltf::Accessor,
+    bin: &[u8],
+    offsets: &[usize],
+    asset: &str,
+) -> Result<Vec<[f32; 2]>> {
+    match (
+        accessor.data_type(),
+        accessor.normalized(),
+        accessor.dimensions(),
+    ) {
+        (DataType::F32, _, Dimensions::Vec2) => read(accessor, bin, offsets, asset),
+        (DataType::U8, true, Dimensions::Vec2) => {
+            Ok(read::<[u8; 2]>(accessor, bin, offsets, asset)?
+                .into_iter()
+                .map(|uv| uv.map(|c| c as f32 / u8::MAX as f32))
+                .collect())
+        }
+        (DataType::U16, true, Dimensions::Vec2) => {
+            Ok(read::<[u16; 2]>(accessor, bin, offsets, asset)?
+                .into_iter()
+                .map(|uv| uv.map(|c| c as f32 / u16::MAX as f32))
+                .collect())
+        }
+        _ => Err(unsupported(
+            accessor,
+            asset,
+            "expected f32 or normalized u8/u16 Vec2",
+        )),
+    }
+}
+
+fn read_joints(
+    accessor: &gltf::Accessor,
+    bin: &[u8],
+    offsets: &[usize],
+    asset: &str,
+) -> Result<Vec<[u32; 4]>> {
+    match (accessor.data_type(), accessor.dimensions()) {
+        (DataType::U8, Dimensions::Vec4) => Ok(read::<[u8; 4]>(accessor, bin, offsets, asset)?
+            .into_iter()
+            .map(|joints| joints.map(u32::from))
+            .collect()),
+        (DataType::U16, Dimensions::Vec4) => Ok(read::<[u16; 4]>(accessor, bin, offsets, asset)?
+            .into_iter()
+            .map(|joints| joints.map(u32::from))
+            .collect()),
+        _ => Err(unsupported(accessor, asset, "expected u8/u16 Vec4")),
+    }
+}
+
+fn read_weights(
+    accessor: &gltf::Accessor,
+    bin: &[u8],
+    offsets: &[usize],
+    asset: &str,
+) -> Result<Vec<[f32; 4]>> {
+    match (
+        accessor.data_type(),
+        accessor.normalized(),
+        accessor.dimensions(),
+    ) {
+        (DataType::F32, _, Dimensions::Vec4) => read(accessor, bin, offsets, asset),
+        (DataType::U8, true, Dimensions::Vec4) => {
+            Ok(read::<[u8; 4]>(accessor, bin, offsets, asset)?
+                .into_iter()
+                .map(|w| w.map(|c| c as f32 / u8::MAX as f32))
+                .collect())
+        }
+        (DataType::U16, true, Dimensions::Vec4) => {
+            Ok(read::<[u16; 4]>(accessor, bin, offsets, asset)?
+                .into_iter()
+                .map(|w| w.map(|c| c as f32 / u16::MAX as f32))
+                .collect())
+        }
+        _ => Err(unsupported(
+            accessor,
+            asset,
+            "expected f32 or normalized u8/u16 Vec4",
+        )),
+    }
+}
+
+// Flattened components, normalized integers mapped to [0, 1] or [-1, 1]
+fn read_floats(
+    accessor: &gltf::Accessor,
+    bin: &[u8],
+    offsets: &[usize],
+    asset: &str,
+) -> Result<Vec<f32>> {
+    fn components<T: gltf::accessor::Item + Copy>(
+        accessor: &gltf::Accessor,
+        bin: &[u8],
+        offsets: &[usize],
+        asset: &str,
+    ) -> Result<Vec<T>> {
+        Ok(match accessor.dimensions() {
+            Dimensions::Scalar => read::<T>(accessor, bin, offsets, asset)?,
+            Dimensions::Vec3 => read::<[T; 3]>(accessor, bin, offsets, asset)?.concat(),
+            Dimensions::Vec4 => read::<[T; 4]>(accessor, bin, offsets, asset)?.concat(),
+            _ => return Err(unsupported(accessor, asset, "expected Scalar, Vec3 or Vec4")),
+        })
+    }
+    fn normalized<T: gltf::accessor::Item + Copy + Into<f32>>(
+        accessor: &gltf::Accessor,
+        bin: &[u8],
+        offsets: &[usize],
+        asset: &str,
+        max: f32,
+    ) -> Result<Vec<f32>> {
+        Ok(components::<T>(accessor, bin, offsets, asset)?
+            .into_iter()
+            .map(|c| (c.into() / max).max(-1.0))
+            .collect())
+    }
+    match (accessor.data_type(), accessor.normalized()) {
+        (DataType::F32, _) => components(accessor, bin, offsets, asset),
+        (DataType::I8, true) => normalized::<i8>(accessor, bin, offsets, asset, 127.0),
+        (DataType::U8, true) => normalized::<u8>(accessor, bin, offsets, asset, 255.0),
+        (DataType::I16, true) => normalized::<i16>(accessor, bin, offsets, asset, 32767.0),
+        (DataType::U16, true) => normalized::<u16>(accessor, bin, offsets, asset, 65535.0),
+        _ => Err(unsupported(
+            accessor,
+            asset,
+            "expected f32 or normalized integers",
+        )),
+    }
+}
+
+// A morph target's deltas for one attribute, zeros for attributes it
+// doesn't move
+fn read_deltas(
+    accessor: Option<gltf::Accessor>,
+    count: usize,
+    bin: &[u8],
+    offsets: &[usize],
+    asset: &str,
+) -> Result<Vec<[f32; 3]>> {
+    let Some(accessor) = accessor else {
+        return Ok(vec![[0.0; 3]; count]);
+    };
+    check_accessor(&accessor, offsets, asset)?;
+    if accessor.dimensions() != Dimensions::Vec3 || accessor.count() != count {
+        let reason = format!("expected {} Vec3 elements", count);
+        return Err(unsupported(&accessor, asset, &reason));
+    }
+    Ok(read_floats(&accessor, bin, offsets, asset)?
+        .chunks_exact(3)
+        .map(|delta| [delta[0], delta[1], delta[2]])
+        .collect())
+}
+
+fn read_indices(
+    accessor: &gltf::Accessor,
+    bin: &[u8],
+    offsets: &[usize],
+    asset: &str,
+) -> Result<Vec<u32>> {
+    fn widen<T: Into<u32>>(indices: Vec<T>) -> Vec<u32> {
+        indices.into_iter().map(Into::into).collect()
+    }
+    match (accessor.data_type(), accessor.dimensions()) {
+        (DataType::U8, Dimensions::Scalar) => Ok(widen(read::<u8>(accessor, bin, offsets, asset)?)),
+        (DataType::U16, Dimensions::Scalar) => {
+            Ok(widen(read::<u16>(accessor, bin, offsets, asset)?))
+        }
+        (DataType::U32, Dimensions::Scalar) => read(accessor, bin, offsets, asset),
+        _ => Err(unsupported(accessor, asset, "expected unsigned Scalar")),
+    }
+}
+
+// Appends decoded attributes after the glTF buffers
+pub(crate) fn append(bin: &mut Vec<u8>, bytes: &[u8]) -> [u32; 2] {
+    bin.resize(bin.len().next_multiple_of(4), 0);
+    let start = bin.len();
+    bin.extend_from_slice(bytes);
+    [start as u32, bin.len() as u32]
+}
+
+fn append_indices(bin: &mut Vec<u8>, indices: &[u32], format: wgpu::IndexFormat) -> [u32; 2] {
+    match format {
+        wgpu::IndexFormat::Uint16 => {
+            let indices: Vec<u16> = indices.iter().map(|&i| i as u16).collect();
+            append(bin, bytemuck::cast_slice(&indices))
+        }
+        wgpu::IndexFormat::Uint32 => append(bin, bytemuck::cast_slice(indices)),
+    }
+}
+
+// Binds a f32 attribute in place when packed, otherwise decodes and appends it
+fn float_range<T: gltf::accessor::Item + bytemuck::Pod>(
+    accessor: &gltf::Accessor,
+    dimensions: Dimensions,
+    bin: &mut Vec<u8>,
+    offsets: &[usize],
+    asset: &str,
+) -> Result<[u32; 2]> {
+    check_float(accessor, dimensions, asset)?;
+    match packed_range(accessor, DataType::F32, dimensions, offsets) {
+        Some(range) => Ok(range),
+        None => {
+            let data = read::<T>(accessor, bin, offsets, asset)?;
+            Ok(append(bin, bytemuck::cast_slice(&data)))
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NormalMode {
+    // one normal per face, as the glTF spec asks for
+    Flat,
+    // faces sharing a vertex average their normals
+    Smooth,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LoadOptions {
+    // how to generate normals for primitives without them
+    pub normals: NormalMode,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            normals: NormalMode::Flat,
+        }
+    }
+}
+
+// Where a primitive's attributes sit in the model bin
+pub(crate) struct Layout {
+    pub(crate) elm_amt: u32,
+    pub(crate) ranges: geometry::Ranges,
+    pub(crate) index_format: wgpu::IndexFormat,
+}
+
+// Attributes the renderer can't bind directly (interleaved, offset,
+// normalized or sparse) are decoded to packed f32s and appended to bin,
+// as are generated normals and tangents
+type Skinning = Option<(Vec<[u32; 4]>, Vec<[f32; 4]>)>;
+
+// Copies the vertices listed and their uvs, joints, weights and morph
+// deltas, one copy per entry
+fn copy_vertices(
+    list: &[u32],
+    vertices: &mut Vec<[f32; 3]>,
+    uvs: &mut Vec<[f32; 2]>,
+    skin: &mut Skinning,
+    targets: &mut [[Vec<[f32; 3]>; 3]],
+) {
+    *vertices = tangent_space::unweld(vertices, list);
+    *uvs = tangent_space::unweld(uvs, list);
+    if let Some((joints, weights)) = skin {
+        *joints = tangent_space::unweld(joints, list);
+        *weights = tangent_space::unweld(weights, list);
+    }
+    for deltas in targets.iter_mut().flatten() {
+        *deltas = tangent_space::unweld(deltas, list);
+    }
+}
+
+fn parse_layout(
+    p: &gltf::Primitive,
+    mesh_name: &str,
+    bin: &mut Vec<u8>,
+    offsets: &[usize],
+    asset: &str,
+    options: &LoadOptions,
+) -> Result<Layout> {
+    let missing = |semantic: &str| AssetError::UnsupportedAccessor {
+        asset: asset.to_string(),
+        accessor: format!("{} of mesh \"{}\"", semantic, mesh_name),
+        reason: "primitive has none".to_string(),
+    };
+    let positions = p
+        .get(&gltf::Semantic::Positions)
+        .ok_or_else(|| missing("POSITION"))?;
+    let indices = p.indices().ok_or_else(|| missing("indices"))?;
+    // points, lines and strips would be drawn as triangle lists
+    if p.mode() != gltf::mesh::Mode::Triangles {
+        return Err(AssetError::UnsupportedAccessor {
+            asset: asset.to_string(),
+            accessor: format!("primitive {} of mesh \"{}\"", p.index(), mesh_name),
+            reason: format!("{:?} mode, expected Triangles", p.mode()),
+        }
+        .into());
+    }
+    if indices.count() % 3 != 0 {
+        let reason = format!("{} isn't a multiple of 3 indices", indices.count());
+        return Err(unsupported(&indices, asset, &reason));
+    }
+    let uvs = p.get(&gltf::Semantic::TexCoords(0));
+    let normals = p.get(&gltf::Semantic::Normals);
+    // tangents are ignored when normals have to be generated
+    let tangents = normals.as_ref().and(p.get(&gltf::Semantic::Tangents));
+    for accessor in [&positions, &indices]
+        .into_iter()
+        .chain(uvs.as_ref())
+        .chain(normals.as_ref())
+        .chain(tangents.as_ref())
+    {
+        check_accessor(accessor, offsets, asset)?;
+    }
+    // every attribute has an element per vertex
+    let check_count = |accessor: &gltf::Accessor| match accessor.count() == positions.count() {
+        true => Ok(()),
+        false => {
+            let reason = format!("expected {} elements", positions.count());
+            Err(unsupported(accessor, asset, &reason))
+        }
+    };
+    for accessor in uvs.iter().chain(&normals).chain(&tangents) {
+        check_count(accessor)?;
+    }
+    check_float(&positions, Dimensions::Vec3, asset)?;
+    if let Some(normals) = &normals {
+        check_float(normals, Dimensions::Vec3, asset)?;
+    }
+    if let Some(tangents) = &tangents {
+        check_float(tangents, Dimensions::Vec4, asset)?;
+    }
+
+    let mut layout = Layout {
+        elm_amt: indices.count() as u32,
+        ranges: geometry::Ranges::default(),
+        index_format: match indices.data_type() {
+            DataType::U32 => wgpu::IndexFormat::Uint32,
+            // u8 indices can't be drawn and are widened
+            _ => wgpu::IndexFormat::Uint16,
+        },
+    };
+    // joints are widened to u32 for the skinning shader
+    let mut skin = match (
+        p.get(&gltf::Semantic::Joints(0)),
+        p.get(&gltf::Semantic::Weights(0)),
+    ) {
+        (Some(joints), Some(weights)) => {
+            for accessor in [&joints, &weights] {
+                check_accessor(accessor, offsets, asset)?;
+                check_count(accessor)?;
+            }
+            Some((
+                read_joints(&joints, bin, offsets, asset)?,
+                read_weights(&weights, bin, offsets, asset)?,
+            ))
+        }
+        _ => None,
+    };
+    // position, normal and tangent deltas of each target. Normal and
+    // tangent deltas are dropped with the normals and tangents they move
+    let mut targets = p
+        .morph_targets()
+        .map(|target| {
+            Ok([
+                read_deltas(target.positions(), positions.count(), bin, offsets, asset)?,
+                read_deltas(
+                    normals.as_ref().and(target.normals()),
+                    positions.count(),
+                    bin,
+                    offsets,
+                    asset,
+                )?,
+                read_deltas(
+                    tangents.as_ref().and(target.tangents()),
+                    positions.count(),
+                    bin,
+                    offsets,
+                    asset,
+                )?,
+            ])
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // set once vertices are copied, for flat normals or split tangents
+    let mut unwelded = false;
+    if let (Some(normals), Some(tangents)) = (&normals, &tangents) {
+        layout.ranges.normal =
+            float_range::<[f32; 3]>(normals, Dimensions::Vec3, bin, offsets, asset)?;
+        layout.ranges.tangent =
+            float_range::<[f32; 4]>(tangents, Dimensions::Vec4, bin, offsets, asset)?;
+    } else {
+        let mut vertices = read::<[f32; 3]>(&positions, bin, offsets, asset)?;
+        let mut uv_data = match &uvs {
+            Some(uvs) => read_uvs(uvs, bin, offsets, asset)?,
+            None => vec![[0.0; 2]; vertices.len()],
+        };
+        let mut index_data = read_indices(&indices, bin, offsets, asset)?;
+        if let Some(&i) = index_data.iter().find(|&&i| i as usize >= vertices.len()) {
+            let reason = format!("index {} past {} vertices", i, vertices.len());
+            return Err(unsupported(&indices, asset, &reason));
+        }
+        let mut normal_data = match &normals {
+            Some(normals) => read::<[f32; 3]>(normals, bin, offsets, asset)?,
+            None if options.normals == NormalMode::Smooth => {
+                tangent_space::smooth_normals(&vertices, &index_data)
+            }
+            None => {
+                // flat normals need every triangle to have its own vertices
+                copy_vertices(&index_data, &mut vertices, &mut uv_data, &mut skin, &mut targets);
+                index_data = (0..vertices.len() as u32).collect();
+                unwelded = true;
+                tangent_space::flat_normals(&vertices)
+            }
+        };
+        let split = tangent_space::tangents(&vertices, &normal_data, &uv_data, &index_data);
+        if split.vertices.len() > vertices.len() {
+            copy_vertices(&split.vertices, &mut vertices, &mut uv_data, &mut skin, &mut targets);
+            normal_data = tangent_space::unweld(&normal_data, &split.vertices);
+            index_data = split.indices;
+            unwelded = true;
+        }
+        if unwelded {
+            layout.index_format = match vertices.len() > 1 << 16 {
+                true => wgpu::IndexFormat::Uint32,
+                false => wgpu::IndexFormat::Uint16,
+            };
+            layout.ranges.vertex = append(bin, bytemuck::cast_slice(&vertices));
+            layout.ranges.uv = append(bin, bytemuck::cast_slice(&uv_data));
+            layout.ranges.index = append_indices(bin, &index_data, layout.index_format);
+        }
+        layout.ranges.normal = match normals
+            .as_ref()
+            .filter(|_| !unwelded)
+            .and_then(|normals| packed_range(normals, DataType::F32, Dimensions::Vec3, offsets))
+        {
+            Some(range) => range,
+            None => append(bin, bytemuck::cast_slice(&normal_data)),
+        };
+        layout.ranges.tangent = append(bin, bytemuck::cast_slice(&split.tangents));
+    }
+    if let Some((joints, weights)) = &skin {
+        layout.ranges.joints = append(bin, bytemuck::cast_slice(joints));
+        layout.ranges.weights = append(bin, bytemuck::cast_slice(weights));
+    }
+    if !targets.is_empty() {
+        let deltas: Vec<[f32; 3]> = targets.concat().concat();
+        layout.ranges.targets = append(bin, bytemuck::cast_slice(&deltas));
+    }
+    if unwelded {
+        return Ok(layout);
+    }
+
+    layout.ranges.vertex =
+        float_range::<[f32; 3]>(&positions, Dimensions::Vec3, bin, offsets, asset)?;
+    layout.ranges.uv = match &uvs {
+        Some(uvs) => match packed_range(uvs, DataType::F32, Dimensions::Vec2, offsets) {
+            Some(range) => range,
+            None => {
+                let data = read_uvs(uvs, bin, offsets, asset)?;
+                append(bin, bytemuck::cast_slice(&data))
+            }
+        },
+        // zeroed so every mesh has a uv buffer to bind
+        None => append(bin, &vec![0; positions.count() * size_of::<[f32; 2]>()]),
+    };
+    let packed = match indices.data_type() {
+        DataType::U8 => None,
+        data_type => packed_range(&indices, data_type, Dimensions::Scalar, offsets),
+    };
+    layout.ranges.index = match packed {
+        Some(range) => range,
+        None => {
+            let data = read_indices(&indices, bin, offsets, asset)?;
+            append_indices(bin, &data, layout.index_format)
+        }
+    };
+    Ok(layout)
+}
+
+// Default morph target weights, zero when the mesh has none or too few
+pub(crate) fn mesh_weights(mesh: &gltf::Mesh, ranges: &geometry::Ranges) -> Vec<f32> {
+    let mut weights = mesh.weights().unwrap_or_default().to_vec();
+    weights.resize(ranges.target_amt() as usize, 0.0);
+    weights
 }
 
 fn parse_meshes(
-    graphics: &Graphics,
     defaults: &material::Defaults,
     mesh: &gltf::Mesh,
+    layouts: Vec<Layout>,
     buffer: Arc<Buffer>,
-    materials: &Vec<Arc<Material>>,
+    materials: &[Arc<Material>],
 ) -> Vec<Mesh> {
+    let mesh_name = mesh.name().unwrap_or("").to_string();
     mesh.primitives()
-        .map(|p| {
-            let vertex_view = p.get(&gltf::Semantic::Positions).unwrap().view().unwrap();
-            let mut ranges = geometry::Ranges {
-                vertex: [
-                    vertex_view.offset() as u32,
-                    (vertex_view.offset() + vertex_view.length()) as u32,
-                ],
-                index: [0, 0],
-                uv: [0, 0],
-            };
-
-            let elm_amt = {
-                if let Some(view) = p.indices().unwrap().view() {
-                    ranges.index[0] = view.offset() as u32;
-                    ranges.index[1] = (view.offset() + view.length()) as u32;
-                    view.length()
-                } else {
-                    vertex_view.length()
-                }
-            } as u32;
-            if let Some(uvs) = p.get(&gltf::Semantic::TexCoords(0)) {
-                let view = uvs.view().unwrap();
-                ranges.uv[0] = view.offset() as u32;
-                ranges.uv[1] = (view.offset() + view.length()) as u32;
-            }
+        .zip(layouts)
+        .map(|(p, layout)| {
             let geometry = Arc::new(Geometry {
-                elm_amt,
-                ranges,
+                elm_amt: layout.elm_amt,
+                ranges: layout.ranges,
+                index_format: layout.index_format,
                 buffer: buffer.clone(),
                 g_pipeline: None,
                 ray_pipeline: None,
@@ -57,141 +591,656 @@ fn parse_meshes(
                 .map_or(defaults.material.clone(), |i| materials[i].clone());
 
             Mesh {
-                name: mesh.name().unwrap_or("").to_string(),
+                name: mesh_name.clone(),
                 geometry,
                 material,
+                weights: mesh_weights(mesh, &layout.ranges),
             }
         })
         .collect()
 }
 
+// A material's albedo, emission, metallic roughness, normal and occlusion
+// textures, with the uv set and color space each is read in
+pub(crate) fn material_textures<'a>(
+    m: &gltf::Material<'a>,
+) -> [Option<(gltf::Texture<'a>, u32, ColorSpace)>; 5] {
+    let pbr = m.pbr_metallic_roughness();
+    [
+        pbr.base_color_texture()
+            .map(|t| (t.texture(), t.tex_coord(), ColorSpace::Srgb)),
+        m.emissive_texture()
+            .map(|t| (t.texture(), t.tex_coord(), ColorSpace::Srgb)),
+        pbr.metallic_roughness_texture()
+            .map(|t| (t.texture(), t.tex_coord(), ColorSpace::Linear)),
+        m.normal_texture()
+            .map(|t| (t.texture(), t.tex_coord(), ColorSpace::Linear)),
+        m.occlusion_texture()
+            .map(|t| (t.texture(), t.tex_coord(), ColorSpace::Linear)),
+    ]
+}
+
+pub(crate) fn material_params(m: &gltf::Material) -> material::Params {
+    let pbr = m.pbr_metallic_roughness();
+    let (alpha_mode, alpha_cutoff) = match m.alpha_mode() {
+        gltf::material::AlphaMode::Opaque => (material::AlphaMode::Opaque, 0.5),
+        gltf::material::AlphaMode::Mask => {
+            (material::AlphaMode::Mask, m.alpha_cutoff().unwrap_or(0.5))
+        }
+        gltf::material::AlphaMode::Blend => (material::AlphaMode::Blend, 0.5),
+    };
+    material::Params {
+        base_color_factor: pbr.base_color_factor(),
+        emissive_factor: m.emissive_factor(),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        normal_scale: m.normal_texture().map_or(1.0, |t| t.scale()),
+        occlusion_strength: m.occlusion_texture().map_or(1.0, |t| t.strength()),
+        alpha_cutoff,
+        alpha_mode: alpha_mode as u32,
+        double_sided: m.double_sided() as u32,
+        ..Default::default()
+    }
+}
+
 fn parse_materials(
     graphics: &Graphics,
     defaults: &material::Defaults,
     doc: &gltf::Document,
-    buffer: Arc<Buffer>,
-    path: &str,
+    textures: &HashMap<(usize, ColorSpace), Arc<Texture>>,
+    asset: &str,
 ) -> Vec<Arc<Material>> {
-    let img_sources: Vec<_> = doc
-        .images()
-        .map(|i| match i.source() {
-            gltf::image::Source::View { view, .. } => {
-                ImgSrc::Slice(&buffer.bin[view.offset()..view.offset() + view.length()])
-            }
-            gltf::image::Source::Uri { uri, .. } => {
-                let filename = path.to_string() + uri;
-                let bytes = read_file_to_end(filename.as_str());
-                ImgSrc::Array(bytes.into_boxed_slice())
-            }
-        })
-        .collect();
-    let textures: Vec<_> = doc
-        .textures()
-        .map(|t| {
-            Arc::new(Texture::create_image_texture(
-                graphics,
-                (path.to_string() + " img").as_str(),
-                match &img_sources[t.source().index()] {
-                    ImgSrc::Slice(slice) => slice,
-                    ImgSrc::Array(array) => &array[..],
-                },
-            ))
-        })
-        .collect();
+    let default_textures = defaults.material.bindings.textures();
     doc.materials()
         .map(|m| {
-            let albedo_tx = match m.pbr_metallic_roughness().base_color_texture() {
-                Some(t) => textures[t.texture().index()].clone(),
-                None => defaults.material.bindings.albedo_tx.clone(),
-            };
-            let emission_tx = match m.emissive_texture() {
-                Some(t) => textures[t.texture().index()].clone(),
-                None => defaults.material.bindings.emission_tx.clone(),
-            };
-            Arc::new(Material::new(
-                graphics,
-                m.name(),
-                material::Bindings {
-                    albedo_tx,
-                    emission_tx,
-                    ..defaults.material.bindings.clone()
-                },
-            ))
+            // only TEXCOORD_0 is imported, other sets fall back to it
+            let slots = material_textures(&m);
+            for (t, tex_coord, _) in slots.iter().flatten() {
+                if *tex_coord != 0 {
+                    log::warn!("{} texture {} uses TEXCOORD_{}", asset, t.index(), tex_coord);
+                }
+            }
+            let textures = std::array::from_fn(|i| match &slots[i] {
+                Some((t, _, color_space)) => (
+                    textures[&(t.index(), *color_space)].clone(),
+                    graphics.sampler(sampler_desc(&t.sampler())),
+                ),
+                None => default_textures[i].clone(),
+            });
+            let bindings = material::Bindings::from_textures(material_params(&m), textures);
+            Arc::new(Material::new(graphics, m.name(), bindings))
         })
         .collect()
 }
 
-// pub fn meshes_from_embeded(graphics: &Graphics, name: &str) {
-//     let gltf = Gltf::open(name).expect(format!("file not found \"{:?}\"", name).as_str());
-// }
+// Every texture materials sample, with the color space it's read in
+pub(crate) fn texture_uses(doc: &gltf::Document) -> Vec<(usize, ColorSpace)> {
+    let mut seen = HashSet::new();
+    doc.materials()
+        .flat_map(|m| material_textures(&m))
+        .flatten()
+        .map(|(t, _, color_space)| (t.index(), color_space))
+        .filter(|u| seen.insert(*u))
+        .collect()
+}
+
+// Images a texture can be read from in order of preference, the KTX2 image
+// of KHR_texture_basisu before its fallback source, and whether its
+// sampler reads mip levels
+pub(crate) fn texture_images(t: &gltf::Texture, image_count: usize) -> (Vec<usize>, bool) {
+    let basisu = t
+        .extension_value("KHR_texture_basisu")
+        .and_then(|ext| ext.get("source")?.as_u64())
+        .map(|image| image as usize)
+        .filter(|&image| image < image_count);
+    let mipmaps = !matches!(
+        t.sampler().min_filter(),
+        Some(MinFilter::Nearest | MinFilter::Linear)
+    );
+    (basisu.into_iter().chain([t.source().index()]).collect(), mipmaps)
+}
+
+pub(crate) fn sampler_desc(sampler: &gltf::texture::Sampler) -> SamplerDesc {
+    use gltf::texture::{MagFilter, WrappingMode};
+    use wgpu::{AddressMode, FilterMode};
+    let address_mode = |mode| match mode {
+        WrappingMode::ClampToEdge => AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => AddressMode::Repeat,
+    };
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => FilterMode::Nearest,
+        Some(MagFilter::Linear) | None => FilterMode::Linear,
+    };
+    // filters without a mipmap part only sample the base level
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) => (FilterMode::Nearest, FilterMode::Nearest),
+        Some(MinFilter::Linear) => (FilterMode::Linear, FilterMode::Nearest),
+        Some(MinFilter::NearestMipmapNearest) => (FilterMode::Nearest, FilterMode::Nearest),
+        Some(MinFilter::LinearMipmapNearest) => (FilterMode::Linear, FilterMode::Nearest),
+        Some(MinFilter::NearestMipmapLinear) => (FilterMode::Nearest, FilterMode::Linear),
+        Some(MinFilter::LinearMipmapLinear) | None => (FilterMode::Linear, FilterMode::Linear),
+    };
+    SamplerDesc {
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        address_mode_w: AddressMode::Repeat,
+        mag_filter,
+        min_filter,
+        mipmap_filter,
+    }
+}
+
+pub enum GltfSrc<'a> {
+    // .gltf or .glb file, external uris are relative to its directory
+    Path(&'a str),
+    // .gltf or .glb contents, external uris are relative to the working directory
+    Slice(&'a [u8]),
+}
+
+fn view_range(view: &gltf::buffer::View, offsets: &[usize], asset: &str) -> Result<[u32; 2]> {
+    let end = view.offset() + view.length();
+    if end > view.buffer().length() {
+        return Err(AssetError::BufferViewOutOfRange {
+            asset: asset.to_string(),
+            view: view.index(),
+            end,
+            len: view.buffer().length(),
+        }
+        .into());
+    }
+    let start = offsets[view.buffer().index()] + view.offset();
+    Ok([start as u32, (start + view.length()) as u32])
+}
+
+fn read_uri(path: &str, uri: &str, asset: &str) -> Result<Vec<u8>> {
+    match uri.strip_prefix("data:") {
+        // data:[<media type>];base64,<data>
+        Some(data) => {
+            let encoded = data
+                .split_once(";base64,")
+                .map_or(data, |(_, encoded)| encoded);
+            base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|_| {
+                    AssetError::InvalidUri {
+                        asset: asset.to_string(),
+                        uri: uri.to_string(),
+                    }
+                    .into()
+                })
+        }
+        None => read_file_to_end(&(path.to_string() + uri)),
+    }
+}
+
+// Concatenates every glTF buffer into one bin, returning where each starts
+fn read_buffers(gltf: &Gltf, path: &str, asset: &str) -> Result<(Vec<u8>, Vec<usize>)> {
+    let mut bin = vec![];
+    let offsets = gltf
+        .buffers()
+        .map(|buffer| {
+            let data = match buffer.source() {
+                gltf::buffer::Source::Bin => Cow::Borrowed(gltf.blob.as_deref().unwrap_or(&[])),
+                gltf::buffer::Source::Uri(uri) => Cow::Owned(read_uri(path, uri, asset)?),
+            };
+            if data.len() < buffer.length() {
+                return Err(AssetError::InvalidGltf {
+                    asset: asset.to_string(),
+                    reason: format!(
+                        "buffer {} holds {} bytes of its {} byte length",
+                        buffer.index(),
+                        data.len(),
+                        buffer.length()
+                    ),
+                }
+                .into());
+            }
+            // keep vertex and index ranges 4 byte aligned
+            Ok(append(&mut bin, &data)[0] as usize)
+        })
+        .collect::<Result<_>>()?;
+    Ok((bin, offsets))
+}
 
 pub fn meshes_from_separated(
     graphics: &Graphics,
     defaults: &material::Defaults,
     path: &str,
     name: &str,
-) -> Vec<Mesh> {
-    // Create GLTF file paths
-    let file_path = format!("{}{}", path, name);
-    let gltf_path = format!("{}.gltf", file_path);
-    let gltf_bin_path = format!("{}.bin", file_path);
-
-    // Load in GLTF data
-    // Attempt to load the GLTF file
-    let gltf = match Gltf::open(&gltf_path) {
-        Ok(gltf) => gltf,
-        Err(err) => {
-            println!("Error opening GLTF file {}: {}", &gltf_path, err);
-            return Vec::new();
+) -> Result<Vec<Mesh>> {
+    let gltf_path = format!("{}{}.gltf", path, name);
+    Ok(model_from_source(graphics, defaults, GltfSrc::Path(&gltf_path))?.meshes)
+}
+
+pub fn model_from_source(
+    graphics: &Graphics,
+    defaults: &material::Defaults,
+    src: GltfSrc,
+) -> Result<Model> {
+    model_from_source_with(graphics, defaults, src, &LoadOptions::default())
+}
+
+pub fn model_from_source_with(
+    graphics: &Graphics,
+    defaults: &material::Defaults,
+    src: GltfSrc,
+    options: &LoadOptions,
+) -> Result<Model> {
+    model_from_prepared(graphics, defaults, prepare(src, options)?)
+}
+
+// A glTF document with its files read, accessors unpacked and images
+// decoded. Preparing doesn't touch the gpu, so it can run off the main
+// thread, leaving texture uploads and model_from_prepared for it
+pub struct Prepared {
+    pub(crate) doc: gltf::Document,
+    pub(crate) asset: String,
+    pub(crate) bin: Vec<u8>,
+    pub(crate) layouts: Vec<Vec<Layout>>,
+    pub(crate) skins: Vec<Skin>,
+    // per node, see parse_instances
+    pub(crate) instances: Vec<Vec<Mat4>>,
+    pub(crate) animations: Vec<Arc<Clip>>,
+    // image names and their decoded texels or why they couldn't be decoded
+    pub(crate) images: Vec<(String, std::result::Result<DecodedImage, String>)>,
+    // textures left to upload in the color space they're read in
+    pending: Vec<(usize, ColorSpace)>,
+    textures: HashMap<(usize, ColorSpace), Arc<Texture>>,
+    // the document and the external buffers and images it read
+    files: Vec<String>,
+}
+
+pub fn prepare(src: GltfSrc, options: &LoadOptions) -> Result<Prepared> {
+    let (bytes, path, asset, document) = match src {
+        GltfSrc::Path(file_path) => {
+            let path = match file_path.rfind('/') {
+                Some(i) => &file_path[..i + 1],
+                None => "",
+            };
+            let bytes = read_file_to_end(file_path)?;
+            (Cow::Owned(bytes), path, file_path, Some(file_path))
         }
+        GltfSrc::Slice(slice) => (Cow::Borrowed(slice), "", "gltf", None),
     };
 
-    // Assuming read_file_to_end() returns a Result<Vec<u8>, Error>
-    let bytes = read_file_to_end(&gltf_bin_path);
-    let bin = bytes.into_boxed_slice();
-    meshes_from_gltf(graphics, defaults, &gltf, bin, path, name)
+    // Parses both .gltf json and .glb containers
+    let gltf = Gltf::from_slice(&bytes).map_err(|err| AssetError::InvalidGltf {
+        asset: asset.to_string(),
+        reason: err.to_string(),
+    })?;
+    let (mut bin, offsets) = read_buffers(&gltf, path, asset)?;
+    let offsets = &offsets[..];
+    let layouts = gltf
+        .meshes()
+        .map(|mesh| {
+            let name = mesh.name().unwrap_or("");
+            mesh.primitives()
+                .map(|p| parse_layout(&p, name, &mut bin, offsets, asset, options))
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let skins = parse_skins(&gltf.document, &bin, offsets, asset)?;
+    let instances = parse_instances(&gltf.document, &bin, offsets, asset)?;
+    let animations = parse_animations(&gltf.document, &bin, offsets, asset)?;
+    let images = gltf
+        .images()
+        .map(|i| {
+            let (name, bytes) = match i.source() {
+                gltf::image::Source::View { view, .. } => {
+                    let [start, end] = view_range(&view, offsets, asset)?;
+                    let name = format!("{} image {}", asset, i.index());
+                    (name, Cow::Borrowed(&bin[start as usize..end as usize]))
+                }
+                gltf::image::Source::Uri { uri, .. } => {
+                    let name = match uri.starts_with("data:") {
+                        true => format!("{} image {}", asset, i.index()),
+                        false => path.to_string() + uri,
+                    };
+                    (name, Cow::Owned(read_uri(path, uri, asset)?))
+                }
+            };
+            // failures surface when a material uses the image
+            let image = DecodedImage::decode(&name, &bytes).map_err(|err| match err {
+                AssetError::BadImage { reason, .. } => reason,
+                err => err.to_string(),
+            });
+            Ok((name, image))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut files = vec![];
+    if let Some(document) = document {
+        let uris = gltf
+            .buffers()
+            .filter_map(|b| match b.source() {
+                gltf::buffer::Source::Uri(uri) => Some(uri),
+                gltf::buffer::Source::Bin => None,
+            })
+            .chain(gltf.images().filter_map(|i| match i.source() {
+                gltf::image::Source::Uri { uri, .. } => Some(uri),
+                gltf::image::Source::View { .. } => None,
+            }))
+            .filter(|uri| !uri.starts_with("data:"))
+            .map(|uri| path.to_string() + uri);
+        files = std::iter::once(document.to_string()).chain(uris).collect();
+    }
+
+    let mut pending = texture_uses(&gltf.document);
+    pending.reverse();
+    Ok(Prepared {
+        doc: gltf.document,
+        asset: asset.to_string(),
+        bin,
+        layouts,
+        skins,
+        instances,
+        animations,
+        images,
+        pending,
+        textures: HashMap::new(),
+        files,
+    })
 }
 
-//let f = std::fs::File::open(name).unwrap();
-//let reader = std::io::BufReader::new(f);
-//let glb = Glb::from_reader(reader).unwrap();
-//let gltf = Gltf::from_slice_without_validation(glb.json.as_ref()).unwrap();
-//let bin = glb.bin.unwrap().into_owned().into_boxed_slice();
+impl Prepared {
+    // Textures materials read, each once per color space
+    pub fn texture_count(&self) -> usize {
+        self.pending.len() + self.textures.len()
+    }
+
+    pub fn pending_textures(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    // Uploads the next texture, false once every one is uploaded
+    pub fn upload_texture(&mut self, graphics: &Graphics) -> Result<bool> {
+        let Some((index, color_space)) = self.pending.pop() else {
+            return Ok(false);
+        };
+        let t = self.doc.textures().nth(index).unwrap();
+        let (images, mipmaps) = texture_images(&t, self.images.len());
+        let options = TextureOptions {
+            mipmaps,
+            color_space,
+        };
+        let load = |image: usize| match &self.images[image] {
+            (name, Ok(image)) => graphics
+                .textures
+                .get_decoded(graphics, name, image, &options),
+            (name, Err(reason)) => Err(AssetError::BadImage {
+                asset: name.clone(),
+                reason: reason.clone(),
+            }
+            .into()),
+        };
+        let texture = match &images[..] {
+            [basisu, source] => load(*basisu).or_else(|err| {
+                log::warn!("{:#}, falling back to image {}", err, source);
+                load(*source)
+            })?,
+            _ => load(images[0])?,
+        };
+        self.textures.insert((index, color_space), texture);
+        Ok(true)
+    }
+}
+
+// Inverse binds are identity for skins without them
+fn parse_skins(
+    doc: &gltf::Document,
+    bin: &[u8],
+    offsets: &[usize],
+    asset: &str,
+) -> Result<Vec<Skin>> {
+    doc.skins()
+        .map(|skin| {
+            let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+            let inverse_binds = match skin.inverse_bind_matrices() {
+                Some(accessor) => {
+                    check_accessor(&accessor, offsets, asset)?;
+                    check_float(&accessor, Dimensions::Mat4, asset)?;
+                    if accessor.count() < joints.len() {
+                        let reason = format!("expected {} matrices", joints.len());
+                        return Err(unsupported(&accessor, asset, &reason));
+                    }
+                    read::<[[f32; 4]; 4]>(&accessor, bin, offsets, asset)?
+                        .into_iter()
+                        .take(joints.len())
+                        .map(Mat4::from)
+                        .collect()
+                }
+                None => vec![Mat4::identity(); joints.len()],
+            };
+            Ok(Skin {
+                name: skin.name().unwrap_or("").to_string(),
+                joints,
+                inverse_binds,
+            })
+        })
+        .collect()
+}
 
-fn meshes_from_gltf(
+// Per node transforms of EXT_mesh_gpu_instancing instances relative to the
+// node, empty for nodes that aren't instanced. Missing attributes leave
+// instances unmoved, unrotated or unscaled
+fn parse_instances(
+    doc: &gltf::Document,
+    bin: &[u8],
+    offsets: &[usize],
+    asset: &str,
+) -> Result<Vec<Vec<Mat4>>> {
+    doc.nodes()
+        .map(|node| {
+            let Some(attributes) = node
+                .extension_value("EXT_mesh_gpu_instancing")
+                .and_then(|ext| ext.get("attributes"))
+            else {
+                return Ok(vec![]);
+            };
+            let read_attribute = |name: &str, dimensions| -> Result<Option<Vec<f32>>> {
+                let Some(index) = attributes.get(name) else {
+                    return Ok(None);
+                };
+                let accessor = index
+                    .as_u64()
+                    .and_then(|index| doc.accessors().nth(index as usize))
+                    .ok_or_else(|| AssetError::InvalidGltf {
+                        asset: asset.to_string(),
+                        reason: format!("node {} {} isn't an accessor", node.index(), name),
+                    })?;
+                check_accessor(&accessor, offsets, asset)?;
+                if accessor.dimensions() != dimensions {
+                    let reason = format!("expected {:?} instance {}", dimensions, name);
+                    return Err(unsupported(&accessor, asset, &reason));
+                }
+                read_floats(&accessor, bin, offsets, asset).map(Some)
+            };
+            let translations = read_attribute("TRANSLATION", Dimensions::Vec3)?;
+            let rotations = read_attribute("ROTATION", Dimensions::Vec4)?;
+            let scales = read_attribute("SCALE", Dimensions::Vec3)?;
+            let counts = [(&translations, 3), (&rotations, 4), (&scales, 3)]
+                .map(|(values, n)| values.as_ref().map(|values| values.len() / n));
+            let amt = counts.iter().flatten().copied().max().unwrap_or(0);
+            if counts.iter().flatten().any(|&count| count != amt) {
+                return Err(AssetError::InvalidGltf {
+                    asset: asset.to_string(),
+                    reason: format!("node {} instance attribute counts differ", node.index()),
+                }
+                .into());
+            }
+            Ok((0..amt)
+                .map(|i| {
+                    let (t, r) = (i * 3, i * 4);
+                    gltf::scene::Transform::Decomposed {
+                        translation: translations
+                            .as_ref()
+                            .map_or([0.0; 3], |v| [v[t], v[t + 1], v[t + 2]]),
+                        rotation: rotations
+                            .as_ref()
+                            .map_or([0.0, 0.0, 0.0, 1.0], |v| [v[r], v[r + 1], v[r + 2], v[r + 3]]),
+                        scale: scales
+                            .as_ref()
+                            .map_or([1.0; 3], |v| [v[t], v[t + 1], v[t + 2]]),
+                    }
+                    .matrix()
+                })
+                .map(Mat4::from)
+                .collect())
+        })
+        .collect()
+}
+
+fn parse_animations(
+    doc: &gltf::Document,
+    bin: &[u8],
+    offsets: &[usize],
+    asset: &str,
+) -> Result<Vec<Arc<Clip>>> {
+    use gltf::animation::{Interpolation, Property};
+    doc.animations()
+        .map(|a| {
+            let channels = a
+                .channels()
+                .map(|c| {
+                    let sampler = c.sampler();
+                    let (input, output) = (sampler.input(), sampler.output());
+                    check_accessor(&input, offsets, asset)?;
+                    check_accessor(&output, offsets, asset)?;
+                    check_float(&input, Dimensions::Scalar, asset)?;
+                    let channel = Channel {
+                        node: c.target().node().index(),
+                        property: match c.target().property() {
+                            Property::Translation => animation::Property::Translation,
+                            Property::Rotation => animation::Property::Rotation,
+                            Property::Scale => animation::Property::Scale,
+                            Property::MorphTargetWeights => animation::Property::Weights,
+                        },
+                        interpolation: match sampler.interpolation() {
+                            Interpolation::Step => animation::Interpolation::Step,
+                            Interpolation::Linear => animation::Interpolation::Linear,
+                            Interpolation::CubicSpline => animation::Interpolation::CubicSpline,
+                        },
+                        times: read(&input, bin, offsets, asset)?,
+                        values: read_floats(&output, bin, offsets, asset)?,
+                    };
+                    match channel.check() {
+                        Some(reason) => Err(unsupported(&output, asset, &reason)),
+                        None => Ok(channel),
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let name = a.name().unwrap_or("").to_string();
+            Ok(Arc::new(Clip::new(name, channels)))
+        })
+        .collect()
+}
+
+pub(crate) fn parse_lights(doc: &gltf::Document) -> Vec<Light> {
+    use gltf::khr_lights_punctual::Kind;
+    doc.lights().map_or(vec![], |lights| {
+        lights
+            .map(|light| Light {
+                name: light.name().unwrap_or("").to_string(),
+                kind: match light.kind() {
+                    Kind::Directional => LightKind::Directional,
+                    Kind::Point => LightKind::Point,
+                    Kind::Spot {
+                        inner_cone_angle,
+                        outer_cone_angle,
+                    } => LightKind::Spot {
+                        inner_cone: inner_cone_angle,
+                        outer_cone: outer_cone_angle,
+                    },
+                },
+                color: Vec3f::from(light.color()),
+                intensity: light.intensity(),
+                range: light.range(),
+            })
+            .collect()
+    })
+}
+
+pub(crate) fn parse_nodes(
+    doc: &gltf::Document,
+    mesh_ranges: &[Range<usize>],
+    instances: &[Vec<Mat4>],
+) -> (Vec<Node>, Vec<usize>) {
+    let mut nodes: Vec<_> = doc
+        .nodes()
+        .zip(instances)
+        .map(|(node, instances)| Node {
+            name: node.name().unwrap_or("").to_string(),
+            tsf: Mat4::from(node.transform().matrix()),
+            parent: None,
+            children: node.children().map(|child| child.index()).collect(),
+            meshes: node
+                .mesh()
+                .map_or(vec![], |mesh| mesh_ranges[mesh.index()].clone().collect()),
+            skin: node.skin().map(|skin| skin.index()),
+            light: node.light().map(|light| light.index()),
+            instances: instances.clone(),
+        })
+        .collect();
+    for i in 0..nodes.len() {
+        for child in nodes[i].children.clone() {
+            nodes[child].parent = Some(i);
+        }
+    }
+    let roots = doc
+        .default_scene()
+        .or_else(|| doc.scenes().next())
+        .map_or(vec![], |scene| {
+            scene.nodes().map(|node| node.index()).collect()
+        });
+    (nodes, roots)
+}
+
+// Builds the model once every texture is uploaded
+pub fn model_from_prepared(
     graphics: &Graphics,
     defaults: &material::Defaults,
-    gltf: &Gltf,
-    bin: Box<[u8]>,
-    path: &str,
-    name: &str,
-) -> Vec<Mesh> {
+    mut prepared: Prepared,
+) -> Result<Model> {
+    while prepared.upload_texture(graphics)? {}
+    let asset = &prepared.asset[..];
     let gpu_buffer = graphics
         .device
         .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some(format!("{} buffer", name).as_str()),
-            contents: bin.as_ref(),
+            label: Some(format!("{} buffer", asset).as_str()),
+            contents: &prepared.bin,
             usage: wgpu::BufferUsages::VERTEX
                 | wgpu::BufferUsages::INDEX
                 | wgpu::BufferUsages::STORAGE,
         });
 
-    let texture_buffer = Arc::new(Buffer { bin, gpu_buffer });
-    let mesh_buffer = texture_buffer.clone();
-    let materials = parse_materials(graphics, defaults, &gltf.document, texture_buffer, path);
-    gltf.scenes()
-        .map(|scene| {
-            scene
-                .nodes()
-                .filter_map(|node| match node.mesh() {
-                    Some(mesh) => Some(
-                        parse_meshes(graphics, defaults, &mesh, mesh_buffer.clone(), &materials)
-                            .into_iter(),
-                    ),
-                    None => None,
-                })
-                .flatten()
+    let buffer = Arc::new(Buffer {
+        bin: prepared.bin.into(),
+        gpu_buffer,
+    });
+    let materials = parse_materials(graphics, defaults, &prepared.doc, &prepared.textures, asset);
+    let mut meshes = vec![];
+    let mesh_ranges: Vec<_> = prepared
+        .doc
+        .meshes()
+        .zip(prepared.layouts)
+        .map(|(mesh, layouts)| {
+            let start = meshes.len();
+            meshes.extend(parse_meshes(defaults, &mesh, layouts, buffer.clone(), &materials));
+            start..meshes.len()
         })
-        .flatten()
-        .collect()
+        .collect();
+    let (nodes, roots) = parse_nodes(&prepared.doc, &mesh_ranges, &prepared.instances);
+    Ok(Model {
+        meshes,
+        nodes,
+        roots,
+        skins: prepared.skins,
+        lights: parse_lights(&prepared.doc),
+        animations: prepared.animations,
+    })
 }
@@ -1,28 +1,43 @@
 use wgpu;
+
+#[cfg(target_arch = "wasm32")]
+fn adapter_limits(adapter: &wgpu::Adapter) -> wgpu::Limits {
+    wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn adapter_limits(adapter: &wgpu::Adapter) -> wgpu::Limits {
+    // software adapters (lavapipe, llvmpipe) fall short of the default limits
+    if adapter.get_info().device_type == wgpu::DeviceType::Cpu {
+        wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits())
+    } else {
+        wgpu::Limits::default()
+    }
+}
+
+pub fn instance(default_backend: wgpu::Backends) -> wgpu::Instance {
+    let backend = wgpu::util::backend_bits_from_env().unwrap_or(default_backend);
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: backend,
+        ..Default::default()
+    })
+}
+
+// Device context shared by windowed and headless graphics
 pub struct Gpu {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub instance: wgpu::Instance,
     pub adapter: wgpu::Adapter,
 }
-impl Gpu {
-    pub fn new() -> Self {
-        #[cfg(target_arch = "wasm32")]
-        let default_backend = wgpu::util::backend_bits_from_env()
-            .unwrap_or(wgpu::Backends::PRIMARY | wgpu::Backends::GL);
-        #[cfg(not(target_arch = "wasm32"))]
-        let default_backend = wgpu::Backends::PRIMARY;
 
-        let backend = wgpu::util::backend_bits_from_env().unwrap_or(default_backend);
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: backend,
-            ..Default::default()
-        });
+impl Gpu {
+    pub async fn new(instance: wgpu::Instance, surface: Option<&wgpu::Surface>) -> Self {
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::util::power_preference_from_env()
                     .unwrap_or(wgpu::PowerPreference::HighPerformance),
-                compatible_surface: Some(&surface),
+                compatible_surface: surface,
                 force_fallback_adapter: false,
             })
             .await
@@ -33,20 +48,26 @@ impl Gpu {
                         .enumerate_adapters(wgpu::Backends::all())
                         .find(|adapter| {
                             // Check if this adapter supports our surface
-                            adapter.is_surface_supported(&surface)
+                            surface.is_none_or(|surface| adapter.is_surface_supported(surface))
                         })
                 }
 
                 #[cfg(target_arch = "wasm32")]
                 panic!("no adapter")
             })
-            .unwrap();
+            .expect("no adapter");
+        log::info!("adapter {:?}", adapter.get_info());
 
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    features: adapter.features() & wgpu::Features::default(),
+                    // compressed formats are picked per texture when available
+                    features: adapter.features()
+                        & (wgpu::Features::default()
+                            | wgpu::Features::TEXTURE_COMPRESSION_BC
+                            | wgpu::Features::TEXTURE_COMPRESSION_ETC2
+                            | wgpu::Features::TEXTURE_COMPRESSION_ASTC),
                     limits: adapter_limits(&adapter),
                 },
                 None,
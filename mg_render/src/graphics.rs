@@ -1,4 +1,10 @@
-use mg_core::*;
+use crate::{
+    gpu::{self, Gpu},
+    pipeline::PipelineCache,
+    sampler::SamplerCache,
+    texture::{Texture, TextureCache},
+    TX_FORMAT_OFFSCREEN,
+};
 use std::cell::Ref;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -56,25 +62,61 @@ fn window_size(window: Ref<Window>) -> (u32, u32) {
     (physical_size.width, physical_size.height)
 }
 
-#[cfg(target_arch = "wasm32")]
-fn adapter_limits(adapter: &wgpu::Adapter) -> wgpu::Limits {
-    wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+pub enum Target {
+    Surface {
+        window: Rc<RefCell<Window>>,
+        surface: wgpu::Surface,
+        config: wgpu::SurfaceConfiguration,
+    },
+    Offscreen(Texture),
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-fn adapter_limits(_: &wgpu::Adapter) -> wgpu::Limits {
-    wgpu::Limits::default()
+// View of the current target texture, surface frames are shown by present
+pub struct Frame {
+    surface_texture: Option<wgpu::SurfaceTexture>,
+    pub view: wgpu::TextureView,
+}
+
+impl Frame {
+    pub fn present(self) {
+        if let Some(surface_texture) = self.surface_texture {
+            surface_texture.present();
+        }
+    }
+}
+
+fn offscreen_texture(device: &wgpu::Device, width: u32, height: u32) -> Texture {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("offscreen texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: TX_FORMAT_OFFSCREEN,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&Default::default());
+    Texture { texture, view }
 }
 
 pub struct Graphics {
-    pub window: Rc<RefCell<Window>>,
-    pub surface: wgpu::Surface,
+    pub target: Target,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
-    pub config: wgpu::SurfaceConfiguration,
+    pub adapter: wgpu::Adapter,
     pub tx_format_surface: wgpu::TextureFormat,
     pub width: u32,
     pub height: u32,
+    pub samplers: SamplerCache,
+    pub pipelines: PipelineCache,
+    pub textures: TextureCache,
     #[cfg(target_arch = "wasm32")]
     on_resize: Closure<dyn FnMut(web_sys::Event)>,
 }
@@ -99,8 +141,7 @@ impl Graphics {
             Rc::new(RefCell::new(builder.build(event_loop).unwrap()))
         };
         #[cfg(target_arch = "wasm32")]
-        let default_backend = wgpu::util::backend_bits_from_env()
-            .unwrap_or(wgpu::Backends::PRIMARY | wgpu::Backends::GL);
+        let default_backend = wgpu::Backends::PRIMARY | wgpu::Backends::GL;
         #[cfg(not(target_arch = "wasm32"))]
         let default_backend = wgpu::Backends::PRIMARY;
 
@@ -108,49 +149,14 @@ impl Graphics {
         #[cfg(target_arch = "wasm32")]
         let on_resize = web_add_resize(window.clone());
 
-        let backend = wgpu::util::backend_bits_from_env().unwrap_or(default_backend);
-
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: backend,
-            ..Default::default()
-        });
+        let instance = gpu::instance(default_backend);
         let surface = unsafe { instance.create_surface(&*window.borrow()) }.unwrap();
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::util::power_preference_from_env()
-                    .unwrap_or(wgpu::PowerPreference::HighPerformance),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .or_else(|| {
-                #[cfg(not(target_arch = "wasm32"))]
-                {
-                    instance
-                        .enumerate_adapters(wgpu::Backends::all())
-                        .find(|adapter| {
-                            // Check if this adapter supports our surface
-                            adapter.is_surface_supported(&surface)
-                        })
-                }
-
-                #[cfg(target_arch = "wasm32")]
-                panic!("no adapter")
-            })
-            .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: adapter.features() & wgpu::Features::default(),
-                    limits: adapter_limits(&adapter),
-                },
-                None,
-            )
-            .await
-            .expect("Request device");
+        let Gpu {
+            device,
+            queue,
+            adapter,
+            ..
+        } = Gpu::new(instance, Some(&surface)).await;
 
         let capabilities = surface.get_capabilities(&adapter);
         let tx_format_surface = capabilities
@@ -173,25 +179,93 @@ impl Graphics {
 
         surface.configure(&device, &config);
         Graphics {
-            surface,
+            target: Target::Surface {
+                window,
+                surface,
+                config,
+            },
             device,
             queue,
-            config,
+            adapter,
             width,
             height,
             tx_format_surface,
-            window,
+            samplers: SamplerCache::default(),
+            pipelines: PipelineCache::default(),
+            textures: TextureCache::default(),
             #[cfg(target_arch = "wasm32")]
             on_resize,
         }
     }
+
+    // Windowless graphics rendering into an offscreen texture, also picks
+    // software adapters (lavapipe, llvmpipe) so it can run without a gpu
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn headless(width: u32, height: u32) -> Graphics {
+        let instance = gpu::instance(wgpu::Backends::PRIMARY | wgpu::Backends::GL);
+        let Gpu {
+            device,
+            queue,
+            adapter,
+            ..
+        } = Gpu::new(instance, None).await;
+        let texture = offscreen_texture(&device, width, height);
+        Graphics {
+            target: Target::Offscreen(texture),
+            device,
+            queue,
+            adapter,
+            width,
+            height,
+            tx_format_surface: TX_FORMAT_OFFSCREEN,
+            samplers: SamplerCache::default(),
+            pipelines: PipelineCache::default(),
+            textures: TextureCache::default(),
+        }
+    }
+
+    pub fn window(&self) -> Ref<'_, Window> {
+        match &self.target {
+            Target::Surface { window, .. } => window.borrow(),
+            Target::Offscreen(_) => panic!("headless graphics has no window"),
+        }
+    }
+
+    pub fn frame(&self) -> Result<Frame, wgpu::SurfaceError> {
+        match &self.target {
+            Target::Surface { surface, .. } => {
+                let surface_texture = surface.get_current_texture()?;
+                let view = surface_texture
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                Ok(Frame {
+                    surface_texture: Some(surface_texture),
+                    view,
+                })
+            }
+            Target::Offscreen(texture) => Ok(Frame {
+                surface_texture: None,
+                view: texture.texture.create_view(&Default::default()),
+            }),
+        }
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         self.width = width;
         self.height = height;
         //let sf = self.window.borrow().scale_factor();
 
-        self.config.width = width;
-        self.config.height = height;
-        self.surface.configure(&self.device, &self.config);
+        match &mut self.target {
+            Target::Surface {
+                surface, config, ..
+            } => {
+                config.width = width;
+                config.height = height;
+                surface.configure(&self.device, config);
+            }
+            Target::Offscreen(texture) => {
+                *texture = offscreen_texture(&self.device, width, height);
+            }
+        }
     }
 }
@@ -0,0 +1,157 @@
+use crate::{
+    assets::{AssetServer, Reloaded},
+    graphics::Graphics,
+    scene::Scene,
+    wgsl::{Preprocessor, MODULES},
+    Renderer, SHADERS,
+};
+use mg_core::*;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// Where the renderer's shaders are in the source tree
+pub const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader");
+
+// How often HotReload::update checks its files
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Last modified time and length of a file, None while it's missing
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+// Notices changes to files by polling their metadata
+#[derive(Default)]
+pub struct Watcher {
+    files: HashMap<PathBuf, Stamp>,
+}
+
+impl Watcher {
+    pub fn watch(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        let stamp = stamp(&path);
+        self.files.entry(path).or_insert(stamp);
+    }
+
+    pub fn is_watching(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    // Files written, created or removed since the last call
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        self.files
+            .iter_mut()
+            .filter_map(|(path, last)| {
+                let stamp = stamp(path);
+                (stamp != *last).then(|| {
+                    *last = stamp;
+                    path.clone()
+                })
+            })
+            .collect()
+    }
+}
+
+enum Target {
+    Shader(&'static str),
+    Module(&'static str),
+    Asset(String),
+}
+
+// Reloads edited shaders and asset files during development. Shaders and
+// the modules they include are read from SHADER_DIR, assets from wherever
+// their native mount is
+pub struct HotReload {
+    watcher: Watcher,
+    targets: HashMap<PathBuf, Target>,
+    shaders: Preprocessor,
+    last_poll: Instant,
+}
+
+impl Default for HotReload {
+    fn default() -> Self {
+        let mut hot_reload = Self {
+            watcher: Watcher::default(),
+            targets: HashMap::new(),
+            shaders: Preprocessor::default(),
+            last_poll: Instant::now(),
+        };
+        let shaders = SHADERS.map(|name| (name, Target::Shader(name)));
+        let modules = MODULES.map(|(name, _)| (name, Target::Module(name)));
+        for (name, target) in shaders.into_iter().chain(modules) {
+            let path = Path::new(SHADER_DIR).join(name);
+            hot_reload.watcher.watch(&path);
+            hot_reload.targets.insert(path, target);
+        }
+        hot_reload
+    }
+}
+
+impl HotReload {
+    // Reloads what changed since the last poll into the renderer and the
+    // scene. Returns the reloaded assets so handles to them can be updated
+    pub fn update(
+        &mut self,
+        graphics: &Graphics,
+        renderer: &mut Renderer,
+        assets: &AssetServer,
+        scene: &mut Scene,
+    ) -> Vec<Reloaded> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return vec![];
+        }
+        self.last_poll = Instant::now();
+        for file in assets.source_files() {
+            if let Some(path) = vfs::VFS.native_path(&file) {
+                if !self.watcher.is_watching(&path) {
+                    self.watcher.watch(&path);
+                    self.targets.insert(path, Target::Asset(file));
+                }
+            }
+        }
+
+        let mut reloaded = vec![];
+        let mut shaders = BTreeSet::new();
+        for path in self.watcher.changed() {
+            match &self.targets[&path] {
+                Target::Shader(name) => {
+                    shaders.insert(*name);
+                }
+                // every shader could include it
+                Target::Module(name) => match fs::read_to_string(&path) {
+                    Ok(source) => {
+                        self.shaders.add_module(name, &source);
+                        shaders.extend(SHADERS);
+                    }
+                    Err(err) => log::error!("{}: {}", name, err),
+                },
+                Target::Asset(file) => {
+                    log::info!("reloading {}", file);
+                    reloaded.extend(assets.reload(graphics, file));
+                }
+            }
+        }
+        for name in shaders {
+            let result = fs::read_to_string(Path::new(SHADER_DIR).join(name))
+                .map_err(anyhow::Error::from)
+                .and_then(|source| renderer.reload_shader(graphics, &self.shaders, name, &source));
+            match result {
+                Ok(_) => log::info!("reloaded {}", name),
+                Err(err) => log::error!("{:#}", err),
+            }
+        }
+        for asset in &reloaded {
+            match asset {
+                Reloaded::Model { old, new } => scene.swap_model(graphics, old, new),
+                Reloaded::Texture { old, new } => {
+                    scene.swap_texture(graphics, &old.arc(), &new.arc())
+                }
+            };
+        }
+        reloaded
+    }
+}
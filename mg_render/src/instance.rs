@@ -1,3 +1,5 @@
+use crate::deform::Deformed;
+use mg_core::*;
 use std::mem::size_of;
 use std::ops::Range;
 #[repr(C)]
@@ -37,17 +39,34 @@ impl Inst {
 
 pub struct Params {
     pub amt: u32,
+    // one Inst per instance relative to the mesh's node, placed in the
+    // world transforms by Scene::update
     pub bin: Option<Box<[u8]>>,
     pub buffer: Option<wgpu::Buffer>,
     pub range: Option<[u32; 2]>,
 }
 
+impl Params {
+    // A mesh drawn once per transform, like the instances of an
+    // EXT_mesh_gpu_instancing node
+    pub fn from_tsfs(tsfs: &[Mat4]) -> Params {
+        let insts: Vec<Inst> = tsfs.iter().map(|&tsf| Inst(tsf.into())).collect();
+        Params {
+            amt: insts.len() as u32,
+            bin: Some(bytemuck::cast_slice(&insts).into()),
+            buffer: None,
+            range: None,
+        }
+    }
+}
+
 pub struct Properties {
     pub amt: u32,
     pub bin: Option<Box<[u8]>>,
     pub buffer: Option<wgpu::Buffer>,
     pub(super) range: [u32; 2],
     pub(super) bind_group: wgpu::BindGroup,
+    pub deformed: Option<Deformed>,
 }
 
 impl Properties {
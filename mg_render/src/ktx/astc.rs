@@ -0,0 +1,505 @@
+use super::Bits;
+
+// Decoders show blocks they can't decode in magenta, including the HDR ones
+// of an LDR texture
+const ERROR: [u8; 4] = [255, 0, 255, 255];
+
+// Trits, quints and bits of each integer sequence range, smallest first
+const RANGES: [(u32, u32, u32); 21] = [
+    (0, 0, 1),
+    (1, 0, 0),
+    (0, 0, 2),
+    (0, 1, 0),
+    (1, 0, 1),
+    (0, 0, 3),
+    (0, 1, 1),
+    (1, 0, 2),
+    (0, 0, 4),
+    (0, 1, 2),
+    (1, 0, 3),
+    (0, 0, 5),
+    (0, 1, 3),
+    (1, 0, 4),
+    (0, 0, 6),
+    (0, 1, 4),
+    (1, 0, 5),
+    (0, 0, 7),
+    (0, 1, 5),
+    (1, 0, 6),
+    (0, 0, 8),
+];
+
+fn sequence_bits(count: u32, range: usize) -> u32 {
+    match RANGES[range] {
+        (1, _, bits) => (8 * count).div_ceil(5) + count * bits,
+        (_, 1, bits) => (7 * count).div_ceil(3) + count * bits,
+        (_, _, bits) => count * bits,
+    }
+}
+
+fn bit(value: u32, i: u32) -> u32 {
+    value >> i & 1
+}
+
+fn trits(t: u32) -> [u32; 5] {
+    let (c, t3, t4) = match t >> 2 & 7 {
+        7 => ((t >> 5 & 7) << 2 | t & 3, 2, 2),
+        _ => match t >> 5 & 3 {
+            3 => (t & 31, bit(t, 7), 2),
+            t3 => (t & 31, t3, bit(t, 7)),
+        },
+    };
+    let (t0, t1, t2) = if c & 3 == 3 {
+        (bit(c, 3) << 1 | (bit(c, 2) & !bit(c, 3) & 1), bit(c, 4), 2)
+    } else if c >> 2 & 3 == 3 {
+        (c & 3, 2, 2)
+    } else {
+        (
+            bit(c, 1) << 1 | (bit(c, 0) & !bit(c, 1) & 1),
+            c >> 2 & 3,
+            bit(c, 4),
+        )
+    };
+    [t0, t1, t2, t3, t4]
+}
+
+fn quints(q: u32) -> [u32; 3] {
+    if q >> 1 & 3 == 3 && q >> 5 & 3 == 0 {
+        let not0 = !q & 1;
+        let q2 = bit(q, 0) << 2 | (bit(q, 4) & not0) << 1 | (bit(q, 3) & not0);
+        return [4, 4, q2];
+    }
+    let (c, q2) = match q >> 1 & 3 {
+        3 => ((q >> 3 & 3) << 3 | (!q >> 5 & 3) << 1 | bit(q, 0), 4),
+        _ => (q & 31, q >> 5 & 3),
+    };
+    match c & 7 {
+        5 => [c >> 3 & 3, 4, q2],
+        _ => [c & 7, c >> 3 & 3, q2],
+    }
+}
+
+// Values of an integer sequence as (trit or quint, low bits) pairs
+fn read_sequence(bits: &mut Bits, count: usize, range: usize) -> Vec<(u32, u32)> {
+    let (t, q, n) = RANGES[range];
+    // a last group cut short reads its missing bits as zeros
+    let mut bits = bits.take(sequence_bits(count as u32, range) as usize);
+    let mut values = Vec::with_capacity(count + 4);
+    while values.len() < count {
+        match (t, q) {
+            (1, _) => {
+                let mut low = [0; 5];
+                let mut packed = 0;
+                for (i, shift) in [(0, 2), (1, 2), (2, 1), (3, 2), (4, 1)] {
+                    low[i] = bits.read(n);
+                    let at = [0, 2, 4, 5, 7][i];
+                    packed |= bits.read(shift) << at;
+                }
+                let digits = trits(packed);
+                values.extend((0..5).map(|i| (digits[i], low[i])));
+            }
+            (_, 1) => {
+                let mut low = [0; 3];
+                let mut packed = 0;
+                for (i, (shift, at)) in [(3, 0), (2, 3), (2, 5)].into_iter().enumerate() {
+                    low[i] = bits.read(n);
+                    packed |= bits.read(shift) << at;
+                }
+                let digits = quints(packed);
+                values.extend((0..3).map(|i| (digits[i], low[i])));
+            }
+            _ => values.push((0, bits.read(n))),
+        }
+    }
+    values.truncate(count);
+    values
+}
+
+// Repeats the value's bits to fill the wider field
+fn replicate(value: u32, bits: u32, to: u32) -> u32 {
+    let mut result = 0;
+    let mut filled = 0;
+    while filled < to {
+        result = result << bits | value;
+        filled += bits;
+    }
+    result >> (filled - to)
+}
+
+fn unquantize_color(range: usize, (digit, low): (u32, u32)) -> u8 {
+    let (t, q, n) = RANGES[range];
+    if t + q == 0 {
+        return replicate(low, n, 8) as u8;
+    }
+    let a = match low & 1 {
+        1 => 0x1ff,
+        _ => 0,
+    };
+    let (b, c, d, e, f) = (
+        bit(low, 1),
+        bit(low, 2),
+        bit(low, 3),
+        bit(low, 4),
+        bit(low, 5),
+    );
+    let (bb, cc) = match (t, n) {
+        (1, 1) => (0, 204),
+        (1, 2) => (b << 8 | b << 4 | b << 2 | b << 1, 93),
+        (1, 3) => (c << 8 | b << 7 | c << 3 | b << 2 | c << 1 | b, 44),
+        (1, 4) => (d << 8 | c << 7 | b << 6 | d << 2 | c << 1 | b, 22),
+        (1, 5) => (e << 8 | d << 7 | c << 6 | b << 5 | e << 1 | d, 11),
+        (1, _) => (f << 8 | e << 7 | d << 6 | c << 5 | b << 4 | f, 5),
+        (_, 1) => (0, 113),
+        (_, 2) => (b << 8 | b << 3 | b << 2, 54),
+        (_, 3) => (c << 8 | b << 7 | c << 2 | b << 1 | c, 26),
+        (_, 4) => (d << 8 | c << 7 | b << 6 | d << 1 | c, 13),
+        (_, _) => (e << 8 | d << 7 | c << 6 | b << 5 | e, 6),
+    };
+    let value = (digit * cc + bb) ^ a;
+    ((a & 0x80) | value >> 2) as u8
+}
+
+// A weight from 0 to 64
+fn unquantize_weight(range: usize, (digit, low): (u32, u32)) -> u32 {
+    let (t, q, n) = RANGES[range];
+    let value = match (t, q, n) {
+        (0, 0, n) => replicate(low, n, 6),
+        (1, _, 0) => [0, 32, 63][digit as usize],
+        (_, 1, 0) => [0, 16, 32, 47, 63][digit as usize],
+        _ => {
+            let a = match low & 1 {
+                1 => 0x7f,
+                _ => 0,
+            };
+            let (b, c) = (bit(low, 1), bit(low, 2));
+            let (bb, cc) = match (t, n) {
+                (1, 1) => (0, 50),
+                (1, 2) => (b << 6 | b << 2 | b, 23),
+                (1, _) => (c << 6 | b << 5 | c << 1 | b, 11),
+                (_, 1) => (0, 28),
+                (_, _) => (b << 6 | b << 1, 13),
+            };
+            let value = (digit * cc + bb) ^ a;
+            (a & 0x20) | value >> 2
+        }
+    };
+    value + (value > 32) as u32
+}
+
+fn hash52(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+fn partition(seed: u32, partitions: u32, mut x: u32, mut y: u32, small: bool) -> usize {
+    if small {
+        x <<= 1;
+        y <<= 1;
+    }
+    let seed = seed + (partitions - 1) * 1024;
+    let rnum = hash52(seed);
+    let mut seeds: [u32; 8] = std::array::from_fn(|i| (rnum >> (4 * i)) & 0xf).map(|s| s * s);
+    let (sh1, sh2) = match seed & 1 {
+        1 => (
+            if seed & 2 != 0 { 4 } else { 5 },
+            if partitions == 3 { 6 } else { 5 },
+        ),
+        _ => (
+            if partitions == 3 { 6 } else { 5 },
+            if seed & 2 != 0 { 4 } else { 5 },
+        ),
+    };
+    for (i, s) in seeds.iter_mut().enumerate() {
+        *s >>= [sh1, sh2][i % 2];
+    }
+    let sums = [
+        seeds[0] * x + seeds[1] * y + (rnum >> 14),
+        seeds[2] * x + seeds[3] * y + (rnum >> 10),
+        seeds[4] * x + seeds[5] * y + (rnum >> 6),
+        seeds[6] * x + seeds[7] * y + (rnum >> 2),
+    ]
+    .map(|s| s & 0x3f);
+    let sums: [u32; 4] = std::array::from_fn(|i| match i < partitions as usize {
+        true => sums[i],
+        false => 0,
+    });
+    // the first of the largest
+    (0..4).rev().max_by_key(|&i| sums[i]).unwrap()
+}
+
+fn bit_transfer_signed(a: i32, b: i32) -> (i32, i32) {
+    let b = (b >> 1) | (a & 0x80);
+    let a = (a >> 1) & 0x3f;
+    let a = match a & 0x20 {
+        0 => a,
+        _ => a - 0x40,
+    };
+    (a, b)
+}
+
+fn blue_contract([r, g, b, a]: [i32; 4]) -> [i32; 4] {
+    [(r + b) >> 1, (g + b) >> 1, b, a]
+}
+
+// The two LDR endpoints of a color endpoint mode, or None for HDR modes
+fn endpoints(mode: u32, v: &[i32]) -> Option<[[u8; 4]; 2]> {
+    let clamp = |e: [i32; 4]| e.map(|c| c.clamp(0, 255) as u8);
+    let (e0, e1) = match mode {
+        0 => ([v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]),
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xc0);
+            let l1 = (l0 + (v[1] & 0x3f)).min(255);
+            ([l0, l0, l0, 255], [l1, l1, l1, 255])
+        }
+        4 => ([v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]),
+        5 => {
+            let (d1, l0) = bit_transfer_signed(v[1], v[0]);
+            let (d3, a0) = bit_transfer_signed(v[3], v[2]);
+            let l1 = l0 + d1;
+            ([l0, l0, l0, a0], [l1, l1, l1, a0 + d3])
+        }
+        6 | 10 => {
+            let alpha = match mode {
+                6 => [255, 255],
+                _ => [v[4], v[5]],
+            };
+            let scale = |c: i32| (c * v[3]) >> 8;
+            (
+                [scale(v[0]), scale(v[1]), scale(v[2]), alpha[0]],
+                [v[0], v[1], v[2], alpha[1]],
+            )
+        }
+        8 | 12 => {
+            let alpha = match mode {
+                8 => [255, 255],
+                _ => [v[6], v[7]],
+            };
+            let e0 = [v[0], v[2], v[4], alpha[0]];
+            let e1 = [v[1], v[3], v[5], alpha[1]];
+            match v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                true => (e0, e1),
+                false => (blue_contract(e1), blue_contract(e0)),
+            }
+        }
+        9 | 13 => {
+            let pairs: [(i32, i32); 4] = std::array::from_fn(|c| match (c, mode) {
+                (3, 9) => (0, 255),
+                _ => bit_transfer_signed(v[2 * c + 1], v[2 * c]),
+            });
+            let e0 = pairs.map(|(_, base)| base);
+            let e1 = pairs.map(|(offset, base)| base + offset);
+            match pairs[..3].iter().map(|(offset, _)| offset).sum::<i32>() >= 0 {
+                true => (e0, e1),
+                false => (blue_contract(e1), blue_contract(e0)),
+            }
+        }
+        _ => return None,
+    };
+    Some([clamp(e0), clamp(e1)])
+}
+
+// Weight grid width, height, range and dual plane of a block mode
+fn block_mode(mode: u32) -> Option<(u32, u32, usize, bool)> {
+    let a = mode >> 5 & 3;
+    let b = mode >> 7 & 3;
+    let (high, dual) = (bit(mode, 9), bit(mode, 10) == 1);
+    let (r, width, height, high, dual) = match mode & 3 {
+        0 => {
+            let r = (mode >> 2 & 3) << 1 | bit(mode, 4);
+            let (width, height, high, dual) = match b {
+                0 => (12, a + 2, high, dual),
+                1 => (a + 2, 12, high, dual),
+                2 => (a + 6, (mode >> 9 & 3) + 6, 0, false),
+                _ => match a {
+                    0 => (6, 10, high, dual),
+                    1 => (10, 6, high, dual),
+                    _ => return None,
+                },
+            };
+            (r, width, height, high, dual)
+        }
+        low => {
+            let r = low << 1 | bit(mode, 4);
+            let (width, height) = match mode >> 2 & 3 {
+                0 => (b + 4, a + 2),
+                1 => (b + 8, a + 2),
+                2 => (a + 2, b + 8),
+                _ => match bit(mode, 8) {
+                    0 => (a + 2, bit(mode, 7) + 6),
+                    _ => (bit(mode, 7) + 2, a + 2),
+                },
+            };
+            (r, width, height, high, dual)
+        }
+    };
+    if r < 2 {
+        return None;
+    }
+    Some((width, height, (r - 2 + 6 * high) as usize, dual))
+}
+
+// A sixteen bit channel in eight, srgb ones keeping their top byte
+fn to_unorm8(c: u32, srgb: bool) -> u8 {
+    match srgb {
+        true => (c >> 8) as u8,
+        false => ((c * 255 + 32767) / 65535) as u8,
+    }
+}
+
+// Texels of an LDR ASTC block, row by row
+pub fn decode_block(block: &[u8], block_width: u32, block_height: u32, srgb: bool) -> Vec<[u8; 4]> {
+    let texel_count = (block_width * block_height) as usize;
+    decode_texels(block, block_width, block_height, srgb)
+        .unwrap_or_else(|| vec![ERROR; texel_count])
+}
+
+fn decode_texels(
+    block: &[u8],
+    block_width: u32,
+    block_height: u32,
+    srgb: bool,
+) -> Option<Vec<[u8; 4]>> {
+    let texel_count = (block_width * block_height) as usize;
+    let mut bits = Bits::new(block);
+    let mode = bits.read(11);
+    // void extent blocks are one color. Their extent is either all ones or
+    // runs from low to high coordinates
+    if mode & 0x1ff == 0x1fc {
+        let reserved = bits.read(1);
+        let extent: [u32; 4] = std::array::from_fn(|_| bits.read(13));
+        let unbounded = extent.iter().all(|&e| e == 0x1fff);
+        let ordered = extent[0] < extent[1] && extent[2] < extent[3];
+        if bit(mode, 9) == 1 || bit(mode, 10) & reserved == 0 || !(unbounded || ordered) {
+            return None;
+        }
+        let color: [u8; 4] = std::array::from_fn(|_| to_unorm8(bits.read(16), srgb));
+        return Some(vec![color; texel_count]);
+    }
+    let (grid_width, grid_height, weight_range, dual) = block_mode(mode)?;
+    let partitions = bits.read(2) + 1;
+    let weight_count = (grid_width * grid_height * (dual as u32 + 1)) as usize;
+    let weight_bits = sequence_bits(weight_count as u32, weight_range);
+    if grid_width > block_width
+        || grid_height > block_height
+        || weight_count > 64
+        || !(24..=96).contains(&weight_bits)
+        || (dual && partitions == 4)
+    {
+        return None;
+    }
+
+    let (seed, modes, extra_bits) = match partitions {
+        1 => (0, vec![bits.read(4)], 0),
+        _ => {
+            let seed = bits.read(10);
+            let selector = bits.read(2);
+            let field = bits.read(4);
+            match selector {
+                0 => (seed, vec![field; partitions as usize], 0),
+                _ => {
+                    let extra_bits = 3 * partitions - 4;
+                    let mut high = Bits::new(block);
+                    high.skip((128 - weight_bits - extra_bits) as usize);
+                    let field = field | high.read(extra_bits) << 4;
+                    let modes = (0..partitions)
+                        .map(|i| {
+                            let class = selector - 1 + bit(field, i);
+                            class << 2 | (field >> (partitions + 2 * i) & 3)
+                        })
+                        .collect();
+                    (seed, modes, extra_bits)
+                }
+            }
+        }
+    };
+    let config_bits = bits.position() as u32;
+    let plane_bits = 2 * dual as u32;
+    let plane_component = {
+        let mut high = Bits::new(block);
+        high.skip((128 - weight_bits - extra_bits - plane_bits) as usize);
+        high.read(plane_bits) as usize
+    };
+
+    // endpoints use the largest range that fits the bits left
+    let value_count: u32 = modes.iter().map(|mode| (mode / 4 + 1) * 2).sum();
+    let color_bits = 128u32.checked_sub(weight_bits + extra_bits + plane_bits + config_bits)?;
+    if value_count > 18 {
+        return None;
+    }
+    let color_range = (4..RANGES.len())
+        .rev()
+        .find(|&range| sequence_bits(value_count, range) <= color_bits)?;
+    let values = read_sequence(&mut bits, value_count as usize, color_range)
+        .into_iter()
+        .map(|value| unquantize_color(color_range, value) as i32)
+        .collect::<Vec<_>>();
+    let mut offset = 0;
+    let endpoints = modes
+        .iter()
+        .map(|&mode| {
+            let count = (mode / 4 + 1) as usize * 2;
+            offset += count;
+            endpoints(mode, &values[offset - count..offset])
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    // weights are stored from the end of the block backwards
+    let reversed = u128::from_le_bytes(block[..16].try_into().unwrap()).reverse_bits();
+    let reversed = reversed.to_le_bytes();
+    let weights = read_sequence(&mut Bits::new(&reversed), weight_count, weight_range)
+        .into_iter()
+        .map(|value| unquantize_weight(weight_range, value))
+        .collect::<Vec<_>>();
+    let planes = dual as usize + 1;
+    let grid = |plane: usize, x: u32, y: u32| {
+        let i = (y * grid_width + x) as usize * planes + plane;
+        weights.get(i).copied().unwrap_or(0)
+    };
+    let ds = (1024 + block_width / 2) / (block_width - 1);
+    let dt = (1024 + block_height / 2) / (block_height - 1);
+    let small = texel_count < 31;
+
+    let mut texels = Vec::with_capacity(texel_count);
+    for t in 0..block_height {
+        for s in 0..block_width {
+            let gs = (ds * s * (grid_width - 1) + 32) >> 6;
+            let gt = (dt * t * (grid_height - 1) + 32) >> 6;
+            let (js, fs, jt, ft) = (gs >> 4, gs & 15, gt >> 4, gt & 15);
+            let w11 = (fs * ft + 8) >> 4;
+            let infill = |plane: usize| {
+                let (w10, w01) = (ft - w11, fs - w11);
+                let w00 = 16 + w11 - fs - ft;
+                (grid(plane, js, jt) * w00
+                    + grid(plane, js + 1, jt) * w01
+                    + grid(plane, js, jt + 1) * w10
+                    + grid(plane, js + 1, jt + 1) * w11
+                    + 8)
+                    >> 4
+            };
+            let weight = [infill(0), infill(dual as usize)];
+            let [e0, e1] = match partitions {
+                1 => endpoints[0],
+                _ => endpoints[partition(seed, partitions, s, t, small)],
+            };
+            texels.push(std::array::from_fn(|c| {
+                let w = weight[(dual && c == plane_component) as usize];
+                let expand = |e: u8| match srgb {
+                    true => (e as u32) << 8 | 0x80,
+                    false => e as u32 * 257,
+                };
+                let c = (expand(e0[c]) * (64 - w) + expand(e1[c]) * w + 32) >> 6;
+                to_unorm8(c, srgb)
+            }));
+        }
+    }
+    Some(texels)
+}
@@ -0,0 +1,586 @@
+use super::{bad_image, encode_bc1_block, encode_bc4_block, etc, wgpu_format, Bits, Levels};
+use image::RgbaImage;
+use mg_core::*;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+// Lengths of the code length code come in this order
+const LENGTH_ORDER: [usize; 21] = [
+    17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16,
+];
+
+// The endpoint prediction symbol that repeats the last one
+const REPEAT_PREDICTION: u32 = 256;
+
+// Symbols of the selector run length table, the last one for long runs
+const RUN_SYMBOLS: u32 = 64;
+
+// ETC1 selectors of ETC1S ones, which ascend from the darkest texel
+const ETC1_SELECTORS: [u64; 4] = [3, 2, 0, 1];
+
+// Canonical prefix code, read a bit at a time
+struct Huffman {
+    counts: [u16; 17],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; 17];
+        lengths.iter().for_each(|&l| counts[l as usize] += 1);
+        counts[0] = 0;
+        let mut symbols = (0..lengths.len() as u16)
+            .filter(|&s| lengths[s as usize] > 0)
+            .collect::<Vec<_>>();
+        symbols.sort_by_key(|&s| lengths[s as usize]);
+        Huffman { counts, symbols }
+    }
+
+    fn read(&self, bits: &mut Bits) -> Option<u32> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &self.counts[1..] {
+            code |= bits.read(1) as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Some(self.symbols[(index + code - first) as usize] as u32);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+// A table's code lengths, themselves coded with runs of zeros and repeats
+fn read_table(bits: &mut Bits) -> Option<Huffman> {
+    let symbol_count = bits.read(14) as usize;
+    if symbol_count == 0 {
+        return Some(Huffman::new(&[]));
+    }
+    let length_count = bits.read(5) as usize;
+    if !(1..=21).contains(&length_count) {
+        return None;
+    }
+    let mut length_lengths = [0; 21];
+    for &symbol in &LENGTH_ORDER[..length_count] {
+        length_lengths[symbol] = bits.read(3) as u8;
+    }
+    let length_code = Huffman::new(&length_lengths);
+    let mut lengths = Vec::with_capacity(symbol_count);
+    while lengths.len() < symbol_count {
+        let (length, run) = match length_code.read(bits)? {
+            length @ 0..=16 => (length as u8, 1),
+            17 => (0, bits.read(3) + 3),
+            18 => (0, bits.read(7) + 11),
+            19 => (*lengths.last()?, bits.read(2) + 3),
+            _ => (*lengths.last()?, bits.read(7) + 7),
+        };
+        if lengths.len() + run as usize > symbol_count {
+            return None;
+        }
+        lengths.resize(lengths.len() + run as usize, length);
+    }
+    Some(Huffman::new(&lengths))
+}
+
+// An integer in chunks, each followed by a bit that's set when more follow
+fn read_vlc(bits: &mut Bits, chunk: u32) -> u32 {
+    let mut value = 0;
+    for shift in (0..32).step_by(chunk as usize) {
+        let bits = bits.read(chunk + 1);
+        value |= (bits & ((1 << chunk) - 1)) << shift;
+        if bits >> chunk == 0 {
+            break;
+        }
+    }
+    value
+}
+
+// Five bit color and intensity table shared by both halves of an ETC1 block
+#[derive(Clone, Copy)]
+struct Endpoint {
+    color: [u8; 3],
+    intensity: u8,
+}
+
+// Endpoints are deltas from the last, coded by how bright it was
+fn read_endpoints(data: &[u8], count: usize) -> Option<Vec<Endpoint>> {
+    let mut bits = Bits::new(data);
+    let models = [
+        read_table(&mut bits)?,
+        read_table(&mut bits)?,
+        read_table(&mut bits)?,
+    ];
+    let intensity_model = read_table(&mut bits)?;
+    let grayscale = bits.read(1) == 1;
+    let (mut color, mut intensity) = ([16; 3], 0);
+    (0..count)
+        .map(|_| {
+            intensity = (intensity + intensity_model.read(&mut bits)?) & 7;
+            for channel in color.iter_mut().take(if grayscale { 1 } else { 3 }) {
+                let model = match channel {
+                    0..=9 => &models[0],
+                    10..=21 => &models[1],
+                    _ => &models[2],
+                };
+                *channel = (*channel + model.read(&mut bits)?) & 31;
+            }
+            if grayscale {
+                color = [color[0]; 3];
+            }
+            Some(Endpoint {
+                color: color.map(|c| c as u8),
+                intensity: intensity as u8,
+            })
+        })
+        .collect()
+}
+
+// Selectors row by row, each row a byte stored raw or xored with the last
+fn read_selectors(data: &[u8], count: usize) -> Option<Vec<[u8; 16]>> {
+    let mut bits = Bits::new(data);
+    // global and hybrid codebooks are from older versions of the format
+    if bits.read(2) != 0 {
+        return None;
+    }
+    let delta_model = match bits.read(1) {
+        1 => None,
+        _ => Some(read_table(&mut bits)?),
+    };
+    let mut rows = [0; 4];
+    (0..count)
+        .map(|i| {
+            for row in &mut rows {
+                *row = match (&delta_model, i) {
+                    (Some(model), 1..) => *row ^ model.read(&mut bits)?,
+                    _ => bits.read(8),
+                };
+            }
+            Some(std::array::from_fn(|t| {
+                (rows[t / 4] >> (2 * (t % 4)) & 3) as u8
+            }))
+        })
+        .collect()
+}
+
+// Codes every slice of a texture is read with
+struct Tables {
+    prediction: Huffman,
+    delta: Huffman,
+    selector: Huffman,
+    run: Huffman,
+    history_size: usize,
+}
+
+fn read_tables(data: &[u8]) -> Option<Tables> {
+    let mut bits = Bits::new(data);
+    let tables = Tables {
+        prediction: read_table(&mut bits)?,
+        delta: read_table(&mut bits)?,
+        selector: read_table(&mut bits)?,
+        run: read_table(&mut bits)?,
+        history_size: bits.read(13) as usize,
+    };
+    (tables.history_size > 0).then_some(tables)
+}
+
+// Recently used selectors. Adding one replaces the back half in turn, and
+// using one moves it halfway to the front
+struct History {
+    selectors: Vec<usize>,
+    rover: usize,
+}
+
+impl History {
+    fn add(&mut self, selector: usize) {
+        self.selectors[self.rover] = selector;
+        self.rover += 1;
+        if self.rover == self.selectors.len() {
+            self.rover = self.selectors.len() / 2;
+        }
+    }
+
+    fn get(&mut self, index: usize) -> Option<usize> {
+        let selector = *self.selectors.get(index)?;
+        self.selectors.swap(index / 2, index);
+        Some(selector)
+    }
+}
+
+// Endpoint and selector of each block of a slice, row by row. Endpoints
+// are predicted from the left, upper or upper left block, or are a delta
+// from the last, two bits per block with one symbol for each 2x2 blocks
+fn read_slice(
+    data: &[u8],
+    tables: &Tables,
+    (endpoints, selectors): (usize, usize),
+    (blocks_wide, blocks_high): (usize, usize),
+) -> Option<Vec<(usize, usize)>> {
+    if endpoints == 0 {
+        return None;
+    }
+    let mut bits = Bits::new(data);
+    let mut history = History {
+        selectors: vec![0; tables.history_size],
+        rover: tables.history_size / 2,
+    };
+    let run_symbol = (selectors + tables.history_size) as u32;
+    let mut blocks: Vec<(usize, usize)> = Vec::with_capacity(blocks_wide * blocks_high);
+    let mut odd_row = vec![0; blocks_wide];
+    let (mut predictions, mut last_prediction, mut repeats) = (0, 0, 0);
+    let (mut endpoint, mut run) = (0, 0);
+    for y in 0..blocks_high {
+        for (x, odd_predictions) in odd_row.iter_mut().enumerate() {
+            let i = blocks.len();
+            if x % 2 == 0 {
+                predictions = match y % 2 {
+                    0 if repeats > 0 => {
+                        repeats -= 1;
+                        last_prediction
+                    }
+                    0 => match tables.prediction.read(&mut bits)? {
+                        REPEAT_PREDICTION => {
+                            repeats = read_vlc(&mut bits, 4) + 2;
+                            last_prediction
+                        }
+                        symbol => {
+                            last_prediction = symbol;
+                            symbol
+                        }
+                    },
+                    _ => *odd_predictions,
+                };
+                *odd_predictions = predictions >> 4;
+            }
+            endpoint = match predictions & 3 {
+                0 if x > 0 => endpoint,
+                1 if y > 0 => blocks[i - blocks_wide].0,
+                2 if x > 0 && y > 0 => blocks[i - blocks_wide - 1].0,
+                3 => (endpoint + tables.delta.read(&mut bits)? as usize) % endpoints,
+                _ => return None,
+            };
+            predictions >>= 2;
+
+            // runs repeat the most recent selector in the history
+            let symbol = match run {
+                0 => match tables.selector.read(&mut bits)? {
+                    symbol if symbol == run_symbol => {
+                        run = match tables.run.read(&mut bits)? {
+                            symbol if symbol == RUN_SYMBOLS - 1 => read_vlc(&mut bits, 7),
+                            symbol => symbol,
+                        } + 2;
+                        selectors
+                    }
+                    symbol => symbol as usize,
+                },
+                _ => {
+                    run -= 1;
+                    selectors
+                }
+            };
+            let selector = match symbol.checked_sub(selectors) {
+                None => {
+                    history.add(symbol);
+                    symbol
+                }
+                Some(index) => history.get(index)?,
+            };
+            if endpoint >= endpoints || selector >= selectors {
+                return None;
+            }
+            blocks.push((endpoint, selector));
+        }
+    }
+    Some(blocks)
+}
+
+// A differential ETC1 block whose halves share the endpoint
+fn etc1_block(endpoint: Endpoint, selectors: &[u8; 16]) -> [u8; 8] {
+    let [r, g, b] = endpoint.color.map(u64::from);
+    let table = endpoint.intensity as u64;
+    let mut bits = r << 59 | g << 51 | b << 43 | table << 37 | table << 34 | 1 << 33;
+    for (t, &selector) in selectors.iter().enumerate() {
+        // ETC1 numbers its texels down columns
+        let i = t % 4 * 4 + t / 4;
+        let selector = ETC1_SELECTORS[selector as usize];
+        bits |= (selector >> 1) << (16 + i) | (selector & 1) << i;
+    }
+    bits.to_be_bytes()
+}
+
+// The colors of an endpoint's selectors, darkest first
+fn palette(endpoint: Endpoint) -> [[u8; 4]; 4] {
+    // the first four texels take selectors 0, 1, 2, 3
+    let ramp = std::array::from_fn(|t| (t % 4) as u8);
+    let texels = etc::decode_block(&etc1_block(endpoint, &ramp), false);
+    std::array::from_fn(|s| texels[s])
+}
+
+// A format ETC1S blocks are transcoded to, by an encoder of its blocks and
+// where a texel's index sits in one read as an integer of its byte order
+struct Target {
+    encode: fn(&[[u8; 4]; 16]) -> [u8; 8],
+    index_bits: u32,
+    shift: fn(usize) -> u32,
+    big_endian: bool,
+}
+
+const BC1: Target = Target {
+    encode: encode_bc1_block,
+    index_bits: 2,
+    shift: |t| 32 + 2 * t as u32,
+    big_endian: false,
+};
+
+// alpha slices keep it in green
+const BC4: Target = Target {
+    encode: |texels| encode_bc4_block(texels.map(|t| t[1])),
+    index_bits: 3,
+    shift: |t| 16 + 3 * t as u32,
+    big_endian: false,
+};
+
+const EAC: Target = Target {
+    encode: |texels| etc::encode_eac_block(texels.map(|t| t[1])),
+    index_bits: 3,
+    shift: |t| 45 - 3 * etc::raster(t) as u32,
+    big_endian: true,
+};
+
+// Blocks of a slice transcoded straight from their endpoints and selectors,
+// as basisu does. The colors of an endpoint's range of selectors are
+// encoded once, and a block's texels take the index of their selector
+fn transcode_blocks(
+    blocks: &[(usize, usize)],
+    endpoints: &[Endpoint],
+    selectors: &[[u8; 16]],
+    target: &Target,
+) -> Vec<[u8; 8]> {
+    let read = |block: [u8; 8]| match target.big_endian {
+        true => u64::from_be_bytes(block),
+        false => u64::from_le_bytes(block),
+    };
+    let mask = (1 << target.index_bits) - 1;
+    let indices = (0..16).fold(0, |bits, t| bits | mask << (target.shift)(t));
+    let mut fits = HashMap::new();
+    blocks
+        .iter()
+        .map(|&(endpoint, selector)| {
+            let selectors = &selectors[selector];
+            let low = *selectors.iter().min().unwrap() as usize;
+            let high = *selectors.iter().max().unwrap() as usize;
+            let (header, chosen) = *fits.entry((endpoint, low, high)).or_insert_with(|| {
+                // the first texels are the range's colors, the rest repeat them
+                let colors = &palette(endpoints[endpoint])[low..=high];
+                let block = (target.encode)(&std::array::from_fn(|t| colors[t % colors.len()]));
+                let block = read(block);
+                let chosen: [u64; 4] =
+                    std::array::from_fn(|s| block >> (target.shift)(s % colors.len()) & mask);
+                (block & !indices, chosen)
+            });
+            let block = (0..16).fold(header, |block, t| {
+                block | chosen[selectors[t] as usize - low] << (target.shift)(t)
+            });
+            match target.big_endian {
+                true => block.to_be_bytes(),
+                false => block.to_le_bytes(),
+            }
+        })
+        .collect()
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<usize> {
+    let bytes = data.get(at..at.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<usize> {
+    let bytes = data.get(at..at.checked_add(2)?)?;
+    Some(u16::from_le_bytes(bytes.try_into().unwrap()) as usize)
+}
+
+// The color model and whether the texels are srgb, from the container's
+// basic data format descriptor
+fn descriptor(reader: &ktx2::Reader<&[u8]>) -> (Option<ktx2::ColorModel>, bool) {
+    if read_u32(reader.data(), 52).unwrap_or(0) < 4 {
+        return (None, false);
+    }
+    reader
+        .data_format_descriptors()
+        .find(|d| d.header == ktx2::DataFormatDescriptorHeader::BASIC)
+        .and_then(|d| ktx2::BasicDataFormatDescriptor::parse(d.data).ok())
+        .map_or((None, false), |basic| {
+            let srgb = basic.transfer_function == Some(ktx2::TransferFunction::SRGB);
+            (basic.color_model, srgb)
+        })
+}
+
+// Transcodes ETC1S textures supercompressed with BasisLZ for a device with
+// the given features. Their colors stay ETC1 blocks where ETC2 is supported,
+// with EAC alpha, otherwise they become BC1 or BC3, and without either
+// they're decoded to RGBA8. A second slice of a level holds its alpha.
+// UASTC, basis universal's other codec, has no transcoder
+pub fn transcode<'a>(
+    name: &str,
+    reader: &ktx2::Reader<&[u8]>,
+    features: wgpu::Features,
+) -> Result<Levels<'a>> {
+    use ktx2::Format as K;
+    use wgpu::Features as Fe;
+    let header = reader.header();
+    let (model, srgb) = descriptor(reader);
+    if header.supercompression_scheme != Some(ktx2::SupercompressionScheme::BasisLZ) {
+        return Err(bad_image(
+            name,
+            match model {
+                Some(ktx2::ColorModel::UASTC) => "UASTC textures can't be transcoded, only ETC1S",
+                _ => "textures without a format must be ETC1S or UASTC",
+            },
+        ));
+    }
+    let corrupt = || bad_image(name, "corrupt BasisLZ data");
+
+    // the global data's sizes, a description of each level's slices, then
+    // the palettes and tables
+    let sgd = read_u32(reader.data(), 64)
+        .zip(read_u32(reader.data(), 72))
+        .and_then(|(start, len)| reader.data().get(start..start.checked_add(len)?))
+        .ok_or_else(corrupt)?;
+    let [endpoint_count, selector_count] = [0, 2].map(|at| read_u16(sgd, at));
+    let [endpoints_len, selectors_len, tables_len] = [4, 8, 12].map(|at| read_u32(sgd, at));
+    let (endpoint_count, selector_count) =
+        endpoint_count.zip(selector_count).ok_or_else(corrupt)?;
+    let endpoints_start = 20 + 20 * header.level_count.max(1) as usize;
+    let after = |start: usize, len: Option<usize>| {
+        len.and_then(|len| start.checked_add(len))
+            .ok_or_else(corrupt)
+    };
+    let selectors_start = after(endpoints_start, endpoints_len)?;
+    let tables_start = after(selectors_start, selectors_len)?;
+    let tables_end = after(tables_start, tables_len)?;
+    let endpoints = sgd
+        .get(endpoints_start..selectors_start)
+        .and_then(|data| read_endpoints(data, endpoint_count))
+        .ok_or_else(corrupt)?;
+    let selectors = sgd
+        .get(selectors_start..tables_start)
+        .and_then(|data| read_selectors(data, selector_count))
+        .ok_or_else(corrupt)?;
+    let tables = sgd
+        .get(tables_start..tables_end)
+        .and_then(read_tables)
+        .ok_or_else(corrupt)?;
+
+    let alpha = read_u32(sgd, 20 + 16).unwrap_or(0) > 0;
+    let fits = header.pixel_width.is_multiple_of(4) && header.pixel_height.max(1).is_multiple_of(4);
+    let (unorm, srgb_format) = match () {
+        _ if !fits => (K::R8G8B8A8_UNORM, K::R8G8B8A8_SRGB),
+        _ if features.contains(Fe::TEXTURE_COMPRESSION_ETC2) => match alpha {
+            true => (K::ETC2_R8G8B8A8_UNORM_BLOCK, K::ETC2_R8G8B8A8_SRGB_BLOCK),
+            false => (K::ETC2_R8G8B8_UNORM_BLOCK, K::ETC2_R8G8B8_SRGB_BLOCK),
+        },
+        _ if features.contains(Fe::TEXTURE_COMPRESSION_BC) => match alpha {
+            true => (K::BC3_UNORM_BLOCK, K::BC3_SRGB_BLOCK),
+            false => (K::BC1_RGBA_UNORM_BLOCK, K::BC1_RGBA_SRGB_BLOCK),
+        },
+        _ => (K::R8G8B8A8_UNORM, K::R8G8B8A8_SRGB),
+    };
+    let vk_format = if srgb { srgb_format } else { unorm };
+    let format = wgpu_format(vk_format).unwrap();
+
+    let size = wgpu::Extent3d {
+        width: header.pixel_width,
+        height: header.pixel_height.max(1),
+        depth_or_array_layers: 1,
+    };
+    let levels = reader
+        .levels()
+        .enumerate()
+        .map(|(level, data)| {
+            let level_size = size.mip_level_size(level as u32, wgpu::TextureDimension::D2);
+            let (width, height) = (level_size.width, level_size.height);
+            let blocks_wide = width.div_ceil(4) as usize;
+            let blocks_high = height.div_ceil(4) as usize;
+            let desc: [Option<usize>; 5] =
+                std::array::from_fn(|i| read_u32(sgd, 20 + 20 * level + 4 * i));
+            // p frames belong to videos
+            if desc[0].ok_or_else(corrupt)? & 2 != 0 {
+                return Err(bad_image(name, "BasisLZ video frames aren't supported"));
+            }
+            let slice = |offset: Option<usize>, len: Option<usize>| {
+                let (offset, len) = offset.zip(len).ok_or_else(corrupt)?;
+                offset
+                    .checked_add(len)
+                    .and_then(|end| data.get(offset..end))
+                    .and_then(|data| {
+                        read_slice(
+                            data,
+                            &tables,
+                            (endpoints.len(), selectors.len()),
+                            (blocks_wide, blocks_high),
+                        )
+                    })
+                    .ok_or_else(corrupt)
+            };
+            let colors = slice(desc[1], desc[2])?;
+            let alphas = match alpha {
+                true => Some(slice(desc[3], desc[4])?),
+                false => None,
+            };
+            let transcode =
+                |blocks: &[_], target| transcode_blocks(blocks, &endpoints, &selectors, target);
+            let etc1 = |blocks: &[(usize, usize)]| {
+                blocks
+                    .iter()
+                    .map(|&(e, s)| etc1_block(endpoints[e], &selectors[s]))
+                    .collect::<Vec<_>>()
+            };
+            let (colors, alphas) = match format.required_features() {
+                Fe::TEXTURE_COMPRESSION_ETC2 => {
+                    (etc1(&colors), alphas.map(|a| transcode(&a, &EAC)))
+                }
+                Fe::TEXTURE_COMPRESSION_BC => (
+                    transcode(&colors, &BC1),
+                    alphas.map(|a| transcode(&a, &BC4)),
+                ),
+                _ => {
+                    let texels = |blocks: &[(usize, usize)]| {
+                        etc1(blocks)
+                            .iter()
+                            .map(|block| etc::decode_block(block, false))
+                            .collect::<Vec<_>>()
+                    };
+                    let colors = texels(&colors);
+                    let alphas = alphas.map(|a| texels(&a));
+                    let image = RgbaImage::from_fn(width, height, |x, y| {
+                        let block = (y / 4) as usize * blocks_wide + (x / 4) as usize;
+                        let texel = (y % 4 * 4 + x % 4) as usize;
+                        let mut color = colors[block][texel];
+                        if let Some(alphas) = &alphas {
+                            color[3] = alphas[block][texel][1];
+                        }
+                        image::Rgba(color)
+                    });
+                    return Ok(Cow::Owned(image.into_raw()));
+                }
+            };
+            // alpha blocks come first
+            Ok(Cow::Owned(match alphas {
+                Some(alphas) => alphas
+                    .iter()
+                    .zip(&colors)
+                    .flat_map(|(a, c)| a.iter().chain(c))
+                    .copied()
+                    .collect(),
+                None => colors.concat(),
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Levels {
+        vk_format,
+        format,
+        size,
+        levels,
+    })
+}
@@ -0,0 +1,177 @@
+use super::Bits;
+
+// Subsets, partition bits, rotation bits, index selection bits, color bits,
+// alpha bits, per endpoint p-bits, shared p-bits, index bits, second index
+// bits of each mode
+const MODES: [[u32; 10]; 8] = [
+    [3, 4, 0, 0, 4, 0, 1, 0, 3, 0],
+    [2, 6, 0, 0, 6, 0, 0, 1, 3, 0],
+    [3, 6, 0, 0, 5, 0, 0, 0, 2, 0],
+    [2, 6, 0, 0, 7, 0, 1, 0, 2, 0],
+    [1, 0, 2, 1, 5, 6, 0, 0, 2, 3],
+    [1, 0, 2, 0, 7, 8, 0, 0, 2, 2],
+    [1, 0, 0, 0, 7, 7, 1, 0, 4, 0],
+    [2, 6, 0, 0, 5, 5, 1, 0, 2, 0],
+];
+
+// Texels of the second subset in each two subset partition
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+// Subsets of each texel in the three subset partitions, two bits apiece
+const PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+// The texel holding the implicit high bit of the second subset's indices
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+// The same for the second and third subsets of three
+const ANCHORS_3: [[u8; 64]; 2] = [
+    [
+        3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6,
+        8, 5, 15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8,
+        5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+    ],
+    [
+        15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3,
+        15, 6, 10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15,
+        15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+    ],
+];
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weights(bits: u32) -> &'static [u32] {
+    match bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+fn interpolate(a: u8, b: u8, weight: u32) -> u8 {
+    (((64 - weight) * a as u32 + weight * b as u32 + 32) >> 6) as u8
+}
+
+fn subset(mode_subsets: u32, partition: usize, texel: usize) -> usize {
+    match mode_subsets {
+        2 => (PARTITIONS_2[partition] >> texel) as usize & 1,
+        3 => (PARTITIONS_3[partition] >> (2 * texel)) as usize & 3,
+        _ => 0,
+    }
+}
+
+fn is_anchor(mode_subsets: u32, partition: usize, texel: usize) -> bool {
+    texel == 0
+        || match mode_subsets {
+            2 => ANCHORS_2[partition] as usize == texel,
+            3 => ANCHORS_3
+                .iter()
+                .any(|anchors| anchors[partition] as usize == texel),
+            _ => false,
+        }
+}
+
+// 4x4 texels of a BC7 block. Blocks of the reserved mode are transparent black
+pub fn decode_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let Some(mode) = (0..8).find(|&m| block[0] & (1 << m) != 0) else {
+        return [[0; 4]; 16];
+    };
+    let [subsets, partition_bits, rotation_bits, selection_bits, ..] = MODES[mode];
+    let [.., color_bits, alpha_bits, endpoint_pbits, shared_pbits, index_bits, index2_bits] =
+        MODES[mode];
+    let mut bits = Bits::new(block);
+    bits.skip(mode + 1);
+    let partition = bits.read(partition_bits) as usize;
+    let rotation = bits.read(rotation_bits);
+    let selection = bits.read(selection_bits);
+
+    let ends = 2 * subsets as usize;
+    // each channel of every endpoint, then the next channel
+    let channels: [[u32; 6]; 4] = std::array::from_fn(|c| {
+        let precision = if c == 3 { alpha_bits } else { color_bits };
+        std::array::from_fn(|e| if e < ends { bits.read(precision) } else { 0 })
+    });
+    let pbits: [u32; 6] = match (endpoint_pbits, shared_pbits) {
+        (1, _) => std::array::from_fn(|e| if e < ends { bits.read(1) } else { 0 }),
+        (_, 1) => {
+            let shared = [bits.read(1), bits.read(1)];
+            std::array::from_fn(|e| shared[e / 2 % 2])
+        }
+        _ => [0; 6],
+    };
+    let has_pbits = endpoint_pbits + shared_pbits > 0;
+    // expanded to eight bits by repeating their highest bits
+    let endpoints: [[u8; 4]; 6] = std::array::from_fn(|e| {
+        std::array::from_fn(|c| {
+            let mut value = channels[c][e];
+            let mut precision = if c == 3 { alpha_bits } else { color_bits };
+            if precision == 0 {
+                return 255;
+            }
+            if has_pbits {
+                value = value << 1 | pbits[e];
+                precision += 1;
+            }
+            value <<= 8 - precision;
+            (value | value >> precision) as u8
+        })
+    });
+
+    // anchor texels drop the high bit of their index
+    let mut read_indices = |count: u32, anchor: &dyn Fn(usize) -> bool| -> [u32; 16] {
+        std::array::from_fn(|t| bits.read(count - anchor(t) as u32))
+    };
+    let indices = read_indices(index_bits, &|t| is_anchor(subsets, partition, t));
+    let indices2 = match index2_bits {
+        0 => None,
+        _ => Some(read_indices(index2_bits, &|t| t == 0)),
+    };
+
+    std::array::from_fn(|t| {
+        let s = subset(subsets, partition, t);
+        let (a, b) = (endpoints[2 * s], endpoints[2 * s + 1]);
+        let (color, alpha) = match indices2 {
+            None => {
+                let w = weights(index_bits)[indices[t] as usize];
+                (w, w)
+            }
+            Some(indices2) => {
+                let w = weights(index_bits)[indices[t] as usize];
+                let w2 = weights(index2_bits)[indices2[t] as usize];
+                match selection {
+                    0 => (w, w2),
+                    _ => (w2, w),
+                }
+            }
+        };
+        let mut texel: [u8; 4] = std::array::from_fn(|c| match c {
+            3 => interpolate(a[3], b[3], alpha),
+            _ => interpolate(a[c], b[c], color),
+        });
+        if rotation > 0 {
+            texel.swap(3, rotation as usize - 1);
+        }
+        texel
+    })
+}
@@ -0,0 +1,220 @@
+// ETC2 and EAC blocks are big endian, their texels numbered down columns
+const MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+fn field(bits: u64, high: u32, count: u32) -> i32 {
+    ((bits >> (high + 1 - count)) & ((1 << count) - 1)) as i32
+}
+
+fn extend(value: i32, bits: u32) -> i32 {
+    let value = value << (8 - bits);
+    value | value >> bits
+}
+
+fn add(color: [i32; 3], amount: i32) -> [u8; 4] {
+    let [r, g, b] = color.map(|c| (c + amount).clamp(0, 255) as u8);
+    [r, g, b, 255]
+}
+
+// Texel index in raster order of the bit for column major texel i
+pub fn raster(i: usize) -> usize {
+    i % 4 * 4 + i / 4
+}
+
+// 4x4 texels of an ETC2 RGB block. Punch through alpha blocks mark
+// transparent texels with their differential bit unset, and ETC1 blocks
+// are ETC2 blocks that never overflow into the T, H or planar modes
+pub fn decode_block(block: &[u8], punch_through: bool) -> [[u8; 4]; 16] {
+    let bits = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let differential = bits >> 33 & 1 == 1;
+    let opaque = !punch_through || differential;
+    let base = [63, 55, 47].map(|high| field(bits, high, 5));
+    let delta = [58, 50, 42].map(|high| field(bits, high, 3) << 29 >> 29);
+    let overflow = |c: usize| !(0..32).contains(&(base[c] + delta[c]));
+    let selectors =
+        |i: usize| (field(bits, 16 + i as u32, 1) << 1 | field(bits, i as u32, 1)) as usize;
+
+    let mut texels = [[0; 4]; 16];
+    if (differential || punch_through) && (overflow(0) || overflow(1)) {
+        let (c1, c2, d) = match overflow(0) {
+            // T mode
+            true => {
+                let r1 = field(bits, 60, 2) << 2 | field(bits, 57, 2);
+                let c1 = [r1, field(bits, 55, 4), field(bits, 51, 4)];
+                let c2 = [47, 43, 39].map(|high| field(bits, high, 4));
+                let d = DISTANCES[(field(bits, 35, 2) << 1 | field(bits, 32, 1)) as usize];
+                (c1.map(|c| c * 17), c2.map(|c| c * 17), d)
+            }
+            // H mode
+            false => {
+                let g1 = field(bits, 58, 3) << 1 | field(bits, 52, 1);
+                let b1 = field(bits, 51, 1) << 3 | field(bits, 49, 3);
+                let c1 = [field(bits, 62, 4), g1, b1];
+                let c2 = [46, 42, 38].map(|high| field(bits, high, 4));
+                let order = |c: [i32; 3]| c[0] << 8 | c[1] << 4 | c[2];
+                let index = field(bits, 34, 1) << 2
+                    | field(bits, 32, 1) << 1
+                    | (order(c1) >= order(c2)) as i32;
+                (
+                    c1.map(|c| c * 17),
+                    c2.map(|c| c * 17),
+                    DISTANCES[index as usize],
+                )
+            }
+        };
+        let paint = match overflow(0) {
+            true => [add(c1, 0), add(c2, d), add(c2, 0), add(c2, -d)],
+            false => [add(c1, d), add(c1, -d), add(c2, d), add(c2, -d)],
+        };
+        for i in 0..16 {
+            texels[raster(i)] = match selectors(i) {
+                2 if !opaque => [0; 4],
+                s => paint[s],
+            };
+        }
+        return texels;
+    }
+    if (differential || punch_through) && overflow(2) {
+        // planar mode
+        let o = [
+            extend(field(bits, 62, 6), 6),
+            extend(field(bits, 56, 1) << 6 | field(bits, 54, 6), 7),
+            extend(
+                field(bits, 48, 1) << 5 | field(bits, 44, 2) << 3 | field(bits, 41, 3),
+                6,
+            ),
+        ];
+        let h = [
+            extend(field(bits, 38, 5) << 1 | field(bits, 32, 1), 6),
+            extend(field(bits, 31, 7), 7),
+            extend(field(bits, 24, 6), 6),
+        ];
+        let v = [
+            extend(field(bits, 18, 6), 6),
+            extend(field(bits, 12, 7), 7),
+            extend(field(bits, 5, 6), 6),
+        ];
+        for (i, texel) in texels.iter_mut().enumerate() {
+            let (x, y) = ((i % 4) as i32, (i / 4) as i32);
+            *texel = add(
+                std::array::from_fn(|c| {
+                    (x * (h[c] - o[c]) + y * (v[c] - o[c]) + 4 * o[c] + 2) >> 2
+                }),
+                0,
+            );
+        }
+        return texels;
+    }
+
+    let colors = match differential || punch_through {
+        true => {
+            [base, std::array::from_fn(|c| base[c] + delta[c])].map(|c| c.map(|c| extend(c, 5)))
+        }
+        false => {
+            [[63, 55, 47], [59, 51, 43]].map(|highs| highs.map(|high| field(bits, high, 4) * 17))
+        }
+    };
+    let tables = [field(bits, 39, 3), field(bits, 36, 3)];
+    let flip = bits >> 32 & 1 == 1;
+    for i in 0..16 {
+        let (x, y) = (i / 4, i % 4);
+        let sub = match flip {
+            true => y >= 2,
+            false => x >= 2,
+        } as usize;
+        let [small, large] = MODIFIERS[tables[sub] as usize];
+        texels[raster(i)] = match (selectors(i), opaque) {
+            (0, true) => add(colors[sub], small),
+            (0, false) => add(colors[sub], 0),
+            (1, _) => add(colors[sub], large),
+            (2, true) => add(colors[sub], -small),
+            (2, false) => [0; 4],
+            _ => add(colors[sub], -large),
+        };
+    }
+    texels
+}
+
+// 4x4 values of an EAC block as eleven bit integers, also the alpha of
+// ETC2 RGBA blocks shifted down to eight bits
+pub fn eac_block(block: &[u8], eleven_bits: bool) -> [u16; 16] {
+    let bits = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let base = field(bits, 63, 8);
+    let multiplier = field(bits, 55, 4);
+    let modifiers = EAC_MODIFIERS[field(bits, 51, 4) as usize];
+    let mut values = [0; 16];
+    for i in 0..16 {
+        let modifier = modifiers[field(bits, 47 - 3 * i as u32, 3) as usize];
+        values[raster(i)] = match eleven_bits {
+            true => {
+                let step = match multiplier {
+                    0 => modifier,
+                    m => modifier * m * 8,
+                };
+                (base * 8 + 4 + step).clamp(0, 2047) as u16
+            }
+            false => (base + modifier * multiplier).clamp(0, 255) as u16,
+        };
+    }
+    values
+}
+
+// An EAC alpha block trying every table, with multipliers and bases that
+// put the smallest or largest value on its furthest modifier
+pub fn encode_eac_block(values: [u8; 16]) -> [u8; 8] {
+    let low = *values.iter().min().unwrap() as i32;
+    let high = *values.iter().max().unwrap() as i32;
+    let mut best = (u32::MAX, 0);
+    for (table, modifiers) in EAC_MODIFIERS.iter().enumerate() {
+        let (least, most) = (modifiers[3], modifiers[7]);
+        let multiplier = (high - low + (most - least) / 2) / (most - least);
+        for multiplier in (multiplier - 1..=multiplier + 1).map(|m| m.clamp(1, 15)) {
+            for base in [low - least * multiplier, high - most * multiplier] {
+                let base = base.clamp(0, 255);
+                let palette = modifiers.map(|m| (base + m * multiplier).clamp(0, 255));
+                let (indices, error) = (0..16).fold((0, 0), |(indices, error), t| {
+                    let v = values[t] as i32;
+                    let index = super::nearest(&palette, |p| (p - v).pow(2) as u32);
+                    (
+                        indices | index << (45 - 3 * raster(t)),
+                        error + (palette[index as usize] - v).pow(2) as u32,
+                    )
+                });
+                if error < best.0 {
+                    let header = (base << 8 | multiplier << 4 | table as i32) as u64;
+                    best = (error, header << 48 | indices);
+                }
+            }
+        }
+    }
+    best.1.to_be_bytes()
+}
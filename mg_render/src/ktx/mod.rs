@@ -0,0 +1,621 @@
+use crate::{
+    graphics::Graphics,
+    texture::{Texture, TextureOptions},
+};
+use image::RgbaImage;
+use mg_core::*;
+use std::borrow::Cow;
+use std::io::Read;
+
+mod astc;
+mod basis;
+mod bc7;
+mod etc;
+
+const MAGIC: [u8; 12] = [
+    0xab, b'K', b'T', b'X', b' ', b'2', b'0', 0xbb, b'\r', b'\n', 0x1a, b'\n',
+];
+
+pub fn is_ktx2(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+fn bad_image(name: &str, reason: impl ToString) -> anyhow::Error {
+    AssetError::BadImage {
+        asset: name.to_string(),
+        reason: reason.to_string(),
+    }
+    .into()
+}
+
+// The wgpu equivalent of a container's vulkan format
+pub fn wgpu_format(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+    use ktx2::Format as K;
+    use wgpu::TextureFormat as F;
+    Some(match format {
+        K::R8G8B8A8_UNORM => F::Rgba8Unorm,
+        K::R8G8B8A8_SRGB => F::Rgba8UnormSrgb,
+        K::R8_UNORM => F::R8Unorm,
+        K::R8G8_UNORM => F::Rg8Unorm,
+        K::B8G8R8A8_UNORM => F::Bgra8Unorm,
+        K::B8G8R8A8_SRGB => F::Bgra8UnormSrgb,
+        K::R16_SFLOAT => F::R16Float,
+        K::R16G16_SFLOAT => F::Rg16Float,
+        K::R16G16B16A16_SFLOAT => F::Rgba16Float,
+        K::R32_SFLOAT => F::R32Float,
+        K::R32G32B32A32_SFLOAT => F::Rgba32Float,
+        K::BC1_RGB_UNORM_BLOCK | K::BC1_RGBA_UNORM_BLOCK => F::Bc1RgbaUnorm,
+        K::BC1_RGB_SRGB_BLOCK | K::BC1_RGBA_SRGB_BLOCK => F::Bc1RgbaUnormSrgb,
+        K::BC2_UNORM_BLOCK => F::Bc2RgbaUnorm,
+        K::BC2_SRGB_BLOCK => F::Bc2RgbaUnormSrgb,
+        K::BC3_UNORM_BLOCK => F::Bc3RgbaUnorm,
+        K::BC3_SRGB_BLOCK => F::Bc3RgbaUnormSrgb,
+        K::BC4_UNORM_BLOCK => F::Bc4RUnorm,
+        K::BC4_SNORM_BLOCK => F::Bc4RSnorm,
+        K::BC5_UNORM_BLOCK => F::Bc5RgUnorm,
+        K::BC5_SNORM_BLOCK => F::Bc5RgSnorm,
+        K::BC6H_UFLOAT_BLOCK => F::Bc6hRgbUfloat,
+        K::BC6H_SFLOAT_BLOCK => F::Bc6hRgbFloat,
+        K::BC7_UNORM_BLOCK => F::Bc7RgbaUnorm,
+        K::BC7_SRGB_BLOCK => F::Bc7RgbaUnormSrgb,
+        K::ETC2_R8G8B8_UNORM_BLOCK => F::Etc2Rgb8Unorm,
+        K::ETC2_R8G8B8_SRGB_BLOCK => F::Etc2Rgb8UnormSrgb,
+        K::ETC2_R8G8B8A1_UNORM_BLOCK => F::Etc2Rgb8A1Unorm,
+        K::ETC2_R8G8B8A1_SRGB_BLOCK => F::Etc2Rgb8A1UnormSrgb,
+        K::ETC2_R8G8B8A8_UNORM_BLOCK => F::Etc2Rgba8Unorm,
+        K::ETC2_R8G8B8A8_SRGB_BLOCK => F::Etc2Rgba8UnormSrgb,
+        K::EAC_R11_UNORM_BLOCK => F::EacR11Unorm,
+        K::EAC_R11_SNORM_BLOCK => F::EacR11Snorm,
+        K::EAC_R11G11_UNORM_BLOCK => F::EacRg11Unorm,
+        K::EAC_R11G11_SNORM_BLOCK => F::EacRg11Snorm,
+        // astc formats come in unorm, srgb pairs ordered by block size
+        _ => {
+            use wgpu::{AstcBlock as B, AstcChannel};
+            const BLOCKS: [B; 14] = [
+                B::B4x4,
+                B::B5x4,
+                B::B5x5,
+                B::B6x5,
+                B::B6x6,
+                B::B8x5,
+                B::B8x6,
+                B::B8x8,
+                B::B10x5,
+                B::B10x6,
+                B::B10x8,
+                B::B10x10,
+                B::B12x10,
+                B::B12x12,
+            ];
+            let i = format
+                .0
+                .get()
+                .checked_sub(K::ASTC_4x4_UNORM_BLOCK.0.get())?;
+            F::Astc {
+                block: *BLOCKS.get(i as usize / 2)?,
+                channel: match i % 2 {
+                    0 => AstcChannel::Unorm,
+                    _ => AstcChannel::UnormSrgb,
+                },
+            }
+        }
+    })
+}
+
+fn rgb565(c: u16) -> [u8; 3] {
+    let (r, g, b) = ((c >> 11) & 31, (c >> 5) & 63, c & 31);
+    [
+        ((r << 3) | (r >> 2)) as u8,
+        ((g << 2) | (g >> 4)) as u8,
+        ((b << 3) | (b >> 2)) as u8,
+    ]
+}
+
+// 4x4 texels of a BC1 color block. BC2 and BC3 color blocks always
+// use four colors
+fn bc1_block(block: &[u8], four_colors: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let mix = |wa: u16, wb: u16| -> [u8; 4] {
+        let c =
+            |i: usize| ((a[i] as u16 * wa + b[i] as u16 * wb + (wa + wb) / 2) / (wa + wb)) as u8;
+        [c(0), c(1), c(2), 255]
+    };
+    let palette = match four_colors || c0 > c1 {
+        true => [
+            [a[0], a[1], a[2], 255],
+            [b[0], b[1], b[2], 255],
+            mix(2, 1),
+            mix(1, 2),
+        ],
+        false => [
+            [a[0], a[1], a[2], 255],
+            [b[0], b[1], b[2], 255],
+            mix(1, 1),
+            [0; 4],
+        ],
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|i| palette[(indices >> (2 * i)) as usize & 3])
+}
+
+// 4x4 values of a BC4 block, also the alpha of BC3 and channels of BC5
+fn bc4_block(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+    match a0 > a1 {
+        true => (1..7).for_each(|i| palette[i + 1] = ((7 - i as u32) * a0 + i as u32 * a1 + 3) / 7),
+        false => {
+            (1..5).for_each(|i| palette[i + 1] = ((5 - i as u32) * a0 + i as u32 * a1 + 2) / 5)
+        }
+    }
+    let mut bits = [0; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    std::array::from_fn(|i| palette[(indices >> (3 * i)) as usize & 7] as u8)
+}
+
+// Reads the fields of a little endian bit stream, lowest bit first. Bits
+// past its end read as zeros
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    end: usize,
+}
+
+impl<'a> Bits<'a> {
+    fn new(data: &'a [u8]) -> Bits<'a> {
+        Bits {
+            data,
+            position: 0,
+            end: data.len() * 8,
+        }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        (0..count).fold(0, |value, i| {
+            let p = self.position;
+            self.position += 1;
+            let bit = match p < self.end {
+                true => self.data[p / 8] >> (p % 8) & 1,
+                false => 0,
+            };
+            value | (bit as u32) << i
+        })
+    }
+
+    fn skip(&mut self, count: usize) {
+        self.position += count;
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    // The next count bits as a stream of their own
+    fn take(&mut self, count: usize) -> Bits<'a> {
+        let bits = Bits {
+            data: self.data,
+            position: self.position,
+            end: (self.position + count).min(self.end),
+        };
+        self.position += count;
+        bits
+    }
+}
+
+fn eleven_to_eight(value: u16) -> u8 {
+    ((value as u32 * 255 + 1023) / 2047) as u8
+}
+
+// Decodes a level of a compressed format for devices without support for
+// it. Signed and HDR formats have no cpu decoder
+pub fn decode(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    data: &[u8],
+) -> Option<RgbaImage> {
+    use wgpu::TextureFormat as F;
+    if matches!(format, F::Rgba8Unorm | F::Rgba8UnormSrgb) {
+        return RgbaImage::from_raw(width, height, data.to_vec());
+    }
+    let block_size = format.block_size(None)? as usize;
+    let (block_width, block_height) = format.block_dimensions();
+    let decode_block = |block: &[u8]| -> Option<Vec<[u8; 4]>> {
+        Some(match format {
+            F::Bc1RgbaUnorm | F::Bc1RgbaUnormSrgb => bc1_block(block, false).to_vec(),
+            F::Bc2RgbaUnorm | F::Bc2RgbaUnormSrgb => {
+                let mut texels = bc1_block(&block[8..], true);
+                (0..16).for_each(|i| texels[i][3] = (block[i / 2] >> (4 * (i % 2)) & 15) * 17);
+                texels.to_vec()
+            }
+            F::Bc3RgbaUnorm | F::Bc3RgbaUnormSrgb => {
+                let alpha = bc4_block(&block[..8]);
+                let mut texels = bc1_block(&block[8..], true);
+                (0..16).for_each(|i| texels[i][3] = alpha[i]);
+                texels.to_vec()
+            }
+            F::Bc4RUnorm => bc4_block(block).map(|r| [r, 0, 0, 255]).to_vec(),
+            F::Bc5RgUnorm => {
+                let (r, g) = (bc4_block(&block[..8]), bc4_block(&block[8..]));
+                (0..16).map(|i| [r[i], g[i], 0, 255]).collect()
+            }
+            F::Bc7RgbaUnorm | F::Bc7RgbaUnormSrgb => bc7::decode_block(block).to_vec(),
+            F::Etc2Rgb8Unorm | F::Etc2Rgb8UnormSrgb => etc::decode_block(block, false).to_vec(),
+            F::Etc2Rgb8A1Unorm | F::Etc2Rgb8A1UnormSrgb => etc::decode_block(block, true).to_vec(),
+            F::Etc2Rgba8Unorm | F::Etc2Rgba8UnormSrgb => {
+                let alpha = etc::eac_block(&block[..8], false);
+                let mut texels = etc::decode_block(&block[8..], false);
+                (0..16).for_each(|i| texels[i][3] = alpha[i] as u8);
+                texels.to_vec()
+            }
+            F::EacR11Unorm => etc::eac_block(block, true)
+                .map(|r| [eleven_to_eight(r), 0, 0, 255])
+                .to_vec(),
+            F::EacRg11Unorm => {
+                let (r, g) = (
+                    etc::eac_block(&block[..8], true),
+                    etc::eac_block(&block[8..], true),
+                );
+                (0..16)
+                    .map(|i| [eleven_to_eight(r[i]), eleven_to_eight(g[i]), 0, 255])
+                    .collect()
+            }
+            F::Astc {
+                channel: wgpu::AstcChannel::Unorm | wgpu::AstcChannel::UnormSrgb,
+                ..
+            } => astc::decode_block(block, block_width, block_height, format.is_srgb()),
+            _ => return None,
+        })
+    };
+    let blocks_wide = width.div_ceil(block_width);
+    let blocks_high = height.div_ceil(block_height);
+    if data.len() < (blocks_wide * blocks_high) as usize * block_size {
+        return None;
+    }
+    let mut image = RgbaImage::new(width, height);
+    for (i, block) in data.chunks_exact(block_size).enumerate() {
+        let bx = i as u32 % blocks_wide * block_width;
+        let by = i as u32 / blocks_wide * block_height;
+        if by >= height {
+            break;
+        }
+        for (j, texel) in decode_block(block)?.into_iter().enumerate() {
+            let x = bx + j as u32 % block_width;
+            let y = by + j as u32 / block_width;
+            if x < width && y < height {
+                image.put_pixel(x, y, image::Rgba(texel));
+            }
+        }
+    }
+    Some(image)
+}
+
+fn nearest<T: Copy>(palette: &[T], distance: impl Fn(T) -> u32) -> u64 {
+    (0..palette.len())
+        .min_by_key(|&i| distance(palette[i]))
+        .unwrap_or(0) as u64
+}
+
+fn distance(a: [u8; 4], b: [u8; 4]) -> u32 {
+    (0..3)
+        .map(|c| (a[c] as i32 - b[c] as i32).pow(2) as u32)
+        .sum()
+}
+
+// Four color BC1 block of two endpoints, the texels picking the nearest
+// color, and its squared error
+fn bc1_fit(texels: &[[u8; 4]; 16], e0: [f32; 3], e1: [f32; 3]) -> ([u8; 8], u32) {
+    let quantize = |e: [f32; 3]| {
+        let c = |i: usize, max: f32| (e[i].clamp(0.0, 255.0) * max / 255.0).round() as u16;
+        c(0, 31.0) << 11 | c(1, 63.0) << 5 | c(2, 31.0)
+    };
+    let (mut c0, mut c1) = (quantize(e0), quantize(e1));
+    if c0 < c1 {
+        std::mem::swap(&mut c0, &mut c1);
+    }
+    let mut block = [0; 8];
+    block[..2].copy_from_slice(&c0.to_le_bytes());
+    block[2..4].copy_from_slice(&c1.to_le_bytes());
+    // the first four texels of indices 0, 1, 2, 3 decode to the palette,
+    // which has transparent black last when the endpoints are the same
+    block[4] = 0b11100100;
+    let palette = &bc1_block(&block, false)[..if c0 == c1 { 1 } else { 4 }];
+    let (indices, error) = texels
+        .iter()
+        .enumerate()
+        .fold((0, 0), |(indices, error), (i, t)| {
+            let index = nearest(palette, |p| distance(p, *t));
+            (
+                indices | index << (2 * i),
+                error + distance(palette[index as usize], *t),
+            )
+        });
+    block[4..].copy_from_slice(&(indices as u32).to_le_bytes());
+    (block, error)
+}
+
+// BC1 block between the texels' extremes along their principal axis, its
+// endpoints then refit by least squares to the colors the texels pick
+fn encode_bc1_block(texels: &[[u8; 4]; 16]) -> [u8; 8] {
+    let colors = texels.map(|t| [t[0] as f32, t[1] as f32, t[2] as f32]);
+    let mean: [f32; 3] = std::array::from_fn(|c| colors.iter().map(|t| t[c]).sum::<f32>() / 16.0);
+    let covariance: [[f32; 3]; 3] = std::array::from_fn(|i| {
+        std::array::from_fn(|j| {
+            colors
+                .iter()
+                .map(|t| (t[i] - mean[i]) * (t[j] - mean[j]))
+                .sum::<f32>()
+        })
+    });
+    // power iteration from the channel that varies most
+    let widest = (0..3)
+        .max_by(|&a, &b| covariance[a][a].total_cmp(&covariance[b][b]))
+        .unwrap();
+    let mut axis = covariance[widest];
+    for _ in 0..8 {
+        let next: [f32; 3] =
+            std::array::from_fn(|i| (0..3).map(|j| covariance[i][j] * axis[j]).sum());
+        let length = next.iter().map(|c| c * c).sum::<f32>().sqrt();
+        if length < 1e-6 {
+            break;
+        }
+        axis = next.map(|c| c / length);
+    }
+    let projections = colors.map(|t| (0..3).map(|c| (t[c] - mean[c]) * axis[c]).sum::<f32>());
+    let (low, high) = projections
+        .iter()
+        .fold((0f32, 0f32), |(low, high), &p| (low.min(p), high.max(p)));
+    let along = |p: f32| std::array::from_fn(|c| mean[c] + axis[c] * p);
+    let mut best = bc1_fit(texels, along(high), along(low));
+    for _ in 0..2 {
+        // how far each texel's color is from the first endpoint to the second
+        let block = best.0;
+        let c0 = u16::from_le_bytes([block[0], block[1]]);
+        let c1 = u16::from_le_bytes([block[2], block[3]]);
+        if c0 == c1 {
+            break;
+        }
+        let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+        let weights: [f32; 16] = std::array::from_fn(|i| {
+            [0.0, 1.0, 1.0 / 3.0, 2.0 / 3.0][(indices >> (2 * i) & 3) as usize]
+        });
+        let (mut a, mut b, mut c) = (0.0, 0.0, 0.0);
+        let (mut x0, mut x1) = ([0.0; 3], [0.0; 3]);
+        for (w, t) in weights.iter().zip(&colors) {
+            a += (1.0 - w) * (1.0 - w);
+            b += (1.0 - w) * w;
+            c += w * w;
+            for i in 0..3 {
+                x0[i] += (1.0 - w) * t[i];
+                x1[i] += w * t[i];
+            }
+        }
+        let determinant = a * c - b * b;
+        if determinant.abs() < 1e-6 {
+            break;
+        }
+        let e0 = std::array::from_fn(|i| (c * x0[i] - b * x1[i]) / determinant);
+        let e1 = std::array::from_fn(|i| (a * x1[i] - b * x0[i]) / determinant);
+        let fit = bc1_fit(texels, e0, e1);
+        if fit.1 >= best.1 {
+            break;
+        }
+        best = fit;
+    }
+    best.0
+}
+
+// Eight value BC4 block between the smallest and largest value
+fn encode_bc4_block(values: [u8; 16]) -> [u8; 8] {
+    let (a0, a1) = (*values.iter().max().unwrap(), *values.iter().min().unwrap());
+    let mut block = [a0, a1, 0, 0, 0, 0, 0, 0];
+    if a0 == a1 {
+        return block;
+    }
+    let identity = (0..8u64).fold(0, |bits, i| bits | i << (3 * i));
+    block[2..].copy_from_slice(&identity.to_le_bytes()[..6]);
+    let palette = &bc4_block(&block)[..8];
+    let indices = values.iter().enumerate().fold(0, |indices, (i, &v)| {
+        indices | nearest(palette, |p| p.abs_diff(v) as u32) << (3 * i)
+    });
+    block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    block
+}
+
+// Compresses RGBA8 texels to BC1, or BC3 to keep alpha. Blocks past the
+// edge of the image repeat its last row and column
+pub fn encode(format: wgpu::TextureFormat, image: &RgbaImage) -> Option<Vec<u8>> {
+    use wgpu::TextureFormat as F;
+    let encode_block: fn(&[[u8; 4]; 16]) -> Vec<u8> = match format {
+        F::Bc1RgbaUnorm | F::Bc1RgbaUnormSrgb => |texels| encode_bc1_block(texels).to_vec(),
+        F::Bc3RgbaUnorm | F::Bc3RgbaUnormSrgb => |texels| {
+            let mut block = encode_bc4_block(texels.map(|t| t[3])).to_vec();
+            block.extend(encode_bc1_block(texels));
+            block
+        },
+        _ => return None,
+    };
+    let (width, height) = image.dimensions();
+    let mut data = vec![];
+    for by in (0..height).step_by(4) {
+        for bx in (0..width).step_by(4) {
+            let texels: [[u8; 4]; 16] = std::array::from_fn(|i| {
+                let x = (bx + i as u32 % 4).min(width - 1);
+                let y = (by + i as u32 / 4).min(height - 1);
+                image.get_pixel(x, y).0
+            });
+            data.extend(encode_block(&texels));
+        }
+    }
+    Some(data)
+}
+
+// A container's levels, largest first, with supercompression undone
+pub struct Levels<'a> {
+    pub vk_format: ktx2::Format,
+    pub format: wgpu::TextureFormat,
+    pub size: wgpu::Extent3d,
+    pub levels: Vec<Cow<'a, [u8]>>,
+}
+
+// Reads a container's levels. Basis universal ones, which have no format of
+// their own, are transcoded to one the features support. Only their ETC1S
+// codec is, UASTC ones are an error
+pub fn read_levels<'a>(
+    name: &str,
+    bytes: &'a [u8],
+    features: wgpu::Features,
+) -> Result<Levels<'a>> {
+    let reader = ktx2::Reader::new(bytes).map_err(|err| bad_image(name, err))?;
+    let header = reader.header();
+    if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+        return Err(bad_image(name, "only 2d ktx2 textures are supported"));
+    }
+    let (vk_format, format) = match header.format {
+        Some(format) => (
+            format,
+            wgpu_format(format)
+                .ok_or_else(|| bad_image(name, format!("unsupported format {:?}", format)))?,
+        ),
+        // KHR_texture_basisu payloads, ETC1S or the unsupported UASTC
+        None => return basis::transcode(name, &reader, features),
+    };
+    let levels = reader
+        .levels()
+        .map(|level| match header.supercompression_scheme {
+            // the reader's levels borrow it, but they're slices of bytes
+            None => {
+                let start = level.as_ptr() as usize - bytes.as_ptr() as usize;
+                Ok(Cow::Borrowed(&bytes[start..start + level.len()]))
+            }
+            Some(ktx2::SupercompressionScheme::Zstandard) => {
+                let mut data = vec![];
+                ruzstd::StreamingDecoder::new(level)
+                    .map_err(|err| bad_image(name, err))?
+                    .read_to_end(&mut data)
+                    .map_err(|err| bad_image(name, err))?;
+                Ok(Cow::Owned(data))
+            }
+            Some(scheme) => Err(bad_image(
+                name,
+                format!("unsupported supercompression {:?}", scheme),
+            )),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Levels {
+        vk_format,
+        format,
+        size: wgpu::Extent3d {
+            width: header.pixel_width,
+            height: header.pixel_height.max(1),
+            depth_or_array_layers: 1,
+        },
+        levels,
+    })
+}
+
+// Uploads a KTX2 container's levels in their own format when the device
+// supports it, otherwise decodes them to RGBA8. The container states its
+// own transfer function, so the color space hint isn't used
+pub fn create_texture(
+    graphics: &Graphics,
+    name: &str,
+    bytes: &[u8],
+    options: &TextureOptions,
+) -> Result<Texture> {
+    let Levels {
+        format,
+        size,
+        levels,
+        ..
+    } = read_levels(name, bytes, graphics.device.features())?;
+    let levels = levels.iter().map(|l| &l[..]).collect::<Vec<_>>();
+    upload_levels(graphics, name, format, size, &levels, options)
+}
+
+// Creates a texture from levels in any format the container or a cooked
+// asset holds. Without mipmaps in the options a lone level is kept as is
+pub fn upload_levels(
+    graphics: &Graphics,
+    name: &str,
+    format: wgpu::TextureFormat,
+    size: wgpu::Extent3d,
+    levels: &[&[u8]],
+    options: &TextureOptions,
+) -> Result<Texture> {
+    let (block_width, block_height) = format.block_dimensions();
+    let native = graphics
+        .device
+        .features()
+        .contains(format.required_features())
+        && size.width.is_multiple_of(block_width)
+        && size.height.is_multiple_of(block_height);
+    let block_size = format.block_size(None).unwrap_or(0);
+    if levels.is_empty() {
+        return Err(bad_image(name, "no levels"));
+    }
+    for (level, data) in levels.iter().enumerate() {
+        let size = size
+            .mip_level_size(level as u32, wgpu::TextureDimension::D2)
+            .physical_size(format);
+        let expected = [
+            size.width / block_width,
+            size.height / block_height,
+            block_size,
+        ]
+        .iter()
+        .try_fold(1usize, |len, &n| len.checked_mul(n as usize))
+        .ok_or_else(|| bad_image(name, format!("level {} is too large", level)))?;
+        if data.len() < expected {
+            return Err(bad_image(name, format!("level {} is too short", level)));
+        }
+    }
+    // a lone uncompressed level still gets a generated mip chain
+    if native && (format.is_compressed() || levels.len() > 1 || !options.mipmaps) {
+        return Ok(Texture::create_levels_texture(
+            graphics, name, format, size, levels,
+        ));
+    }
+    // rgba8 is decoded below so its mips can be built on the cpu, other
+    // formats only get them where the gpu can render to them
+    let rgba8 = matches!(
+        format,
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb
+    );
+    if native && !rgba8 {
+        return Ok(Texture::create_uncompressed_texture(
+            graphics, name, format, size, levels[0], options,
+        ));
+    }
+
+    let decoded = levels
+        .iter()
+        .enumerate()
+        .map(|(level, data)| {
+            let size = size.mip_level_size(level as u32, wgpu::TextureDimension::D2);
+            decode(format, size.width, size.height, data).ok_or_else(|| {
+                bad_image(
+                    name,
+                    format!("{:?} isn't supported by the device or decodable", format),
+                )
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let rgba_format = match format.is_srgb() {
+        true => wgpu::TextureFormat::Rgba8UnormSrgb,
+        false => wgpu::TextureFormat::Rgba8Unorm,
+    };
+    Ok(match decoded.len() {
+        1 => Texture::create_rgba_texture(graphics, name, &decoded[0], rgba_format, options),
+        _ => {
+            let levels = decoded
+                .iter()
+                .map(|i| i.as_raw().as_slice())
+                .collect::<Vec<_>>();
+            Texture::create_levels_texture(graphics, name, rgba_format, size, &levels)
+        }
+    })
+}
@@ -1,12 +1,20 @@
 #[macro_use]
 // Render
 pub mod camera;
+pub mod assets;
 pub mod buffer;
+pub mod capture;
+pub mod cooked;
+pub mod cubemap;
 pub mod g_buffer;
 pub mod geometry;
 pub mod gltf_loader;
+pub mod gpu;
 pub mod graphics;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod hot_reload;
 pub mod instance;
+pub mod ktx;
 
 use camera::Camera;
 use g_buffer::GBuffer;
@@ -14,10 +22,19 @@ use graphics::Graphics;
 use instance::Inst;
 
 // Model data
+pub mod animation;
+pub mod deform;
+pub mod light;
 pub mod mesh;
 pub mod material;
+pub mod mipmap;
+pub mod model;
+pub mod pipeline;
+pub mod sampler;
 pub mod scene;
+pub mod tangent_space;
 pub mod texture;
+pub mod wgsl;
 
 // Global Illumination
 pub mod global_illumination;
@@ -33,6 +50,7 @@ pub const TX_FORMAT_COLOR: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm
 pub const TX_FORMAT_POSITION: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
 pub const TX_FORMAT_NORMAL: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
 pub const TX_FORMAT_DEPTH: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+pub const TX_FORMAT_OFFSCREEN: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
 
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -42,7 +60,7 @@ impl Vertex {
         wgpu::VertexBufferLayout {
             array_stride: size_of::<Vertex>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+            attributes: &wgpu::vertex_attr_array![0 => Float32x3],
         }
     }
 }
@@ -61,6 +79,35 @@ impl UV {
     }
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Normal([f32; 3]);
+
+impl Normal {
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Normal>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![6 => Float32x3],
+        }
+    }
+}
+
+// xyz tangent, w bitangent sign
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Tangent([f32; 4]);
+
+impl Tangent {
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Tangent>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![7 => Float32x4],
+        }
+    }
+}
+
 pub fn background_bind_group_layout(graphics: &Graphics) -> wgpu::BindGroupLayout {
     graphics
         .device
@@ -118,6 +165,95 @@ pub fn texture_bind_group_layout(graphics: &Graphics) -> wgpu::BindGroupLayout {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
+                // material params
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // metallic roughness texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // normal texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // occlusion texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 9,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 10,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        })
+}
+
+// A sampled 3D texture, like the terrain volume of prism.wgsl
+pub fn volume_bind_group_layout(graphics: &Graphics) -> wgpu::BindGroupLayout {
+    graphics
+        .device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("volume bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
             ],
         })
 }
@@ -126,36 +262,60 @@ pub struct Renderer {
     g_buffer: GBuffer,
     irradiance_cache: IrradianceCache,
     g_pipeline: wgpu::RenderPipeline,
+    g_pipeline_double_sided: wgpu::RenderPipeline,
     ray_pipeline: wgpu::ComputePipeline,
     comp_pipeline: wgpu::RenderPipeline,
+    deform_pipeline: wgpu::ComputePipeline,
 }
 
-impl Renderer {
-    pub fn new(graphics: &Graphics) -> Renderer {
-        let g_pipeline_layout =
-            graphics
-                .device
-                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: Some("geometry pipeline layout"),
-                    bind_group_layouts: &[
-                        &Camera::bind_group_layout(graphics),
-                        &texture_bind_group_layout(graphics),
-                    ],
-                    push_constant_ranges: &[],
-                });
+// The renderer's shaders by file name in shader/, see Renderer::reload_shader
+pub const SHADERS: [&str; 4] = [
+    "geometry.wgsl",
+    "raytrace.wgsl",
+    "composition.wgsl",
+    "deform.wgsl",
+];
 
-        let g_shader = graphics
+// Back face culled and double sided geometry pipelines
+fn geometry_pipelines(graphics: &Graphics, source: &str) -> [wgpu::RenderPipeline; 2] {
+    let g_pipeline_layout =
+        graphics
             .device
-            .create_shader_module(wgpu::include_wgsl!("shader/geometry.wgsl"));
-        let g_pipeline = graphics
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("geometry pipeline layout"),
+                bind_group_layouts: &[
+                    &Camera::bind_group_layout(graphics),
+                    &texture_bind_group_layout(graphics),
+                ],
+                push_constant_ranges: &[],
+            });
+
+    let g_shader = graphics
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("geometry.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+    // double sided materials skip back face culling
+    let g_pipeline = |cull_mode: Option<wgpu::Face>| {
+        graphics
             .device
             .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("geometry pipeline"),
+                label: Some(match cull_mode {
+                    Some(_) => "geometry pipeline",
+                    None => "double sided geometry pipeline",
+                }),
                 layout: Some(&g_pipeline_layout),
                 vertex: wgpu::VertexState {
                     module: &g_shader,
                     entry_point: "vs_main",
-                    buffers: &[Vertex::layout(), UV::layout(), Inst::layout()],
+                    buffers: &[
+                        Vertex::layout(),
+                        UV::layout(),
+                        Inst::layout(),
+                        Normal::layout(),
+                        Tangent::layout(),
+                    ],
                 },
                 fragment: Some(wgpu::FragmentState {
                     module: &g_shader,
@@ -187,7 +347,7 @@ impl Renderer {
                     topology: wgpu::PrimitiveTopology::TriangleList,
                     strip_index_format: None,
                     front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
+                    cull_mode,
                     polygon_mode: wgpu::PolygonMode::Fill,
                     unclipped_depth: false,
                     conservative: false,
@@ -205,93 +365,211 @@ impl Renderer {
                     alpha_to_coverage_enabled: false,
                 },
                 multiview: None,
+            })
+    };
+    [g_pipeline(Some(wgpu::Face::Back)), g_pipeline(None)]
+}
+
+fn ray_pipeline(graphics: &Graphics, source: &str) -> wgpu::ComputePipeline {
+    let ray_pipeline_layout =
+        graphics
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("ray pipeline layout"),
+                bind_group_layouts: &[
+                    &ray_buffer::read_bind_group_layout(graphics),
+                    &mesh::bind_group_layout(graphics),
+                    &g_buffer::read_bind_group_layout(graphics),
+                    &g_buffer::write_bind_group_layout(graphics),
+                ],
+                push_constant_ranges: &[],
             });
-        let ray_pipeline_layout =
-            graphics
-                .device
-                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: Some("ray pipeline layout"),
-                    bind_group_layouts: &[
-                        &ray_buffer::read_bind_group_layout(graphics),
-                        &mesh::bind_group_layout(graphics),
-                        &g_buffer::read_bind_group_layout(graphics),
-                        &g_buffer::write_bind_group_layout(graphics),
-                    ],
-                    push_constant_ranges: &[],
-                });
-        let ray_shader = graphics
+    let ray_shader = graphics
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("raytrace.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+    graphics
+        .device
+        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("ray pipeline"),
+            layout: Some(&ray_pipeline_layout),
+            module: &ray_shader,
+            entry_point: "main",
+        })
+}
+
+fn deform_pipeline(graphics: &Graphics, source: &str) -> wgpu::ComputePipeline {
+    let deform_pipeline_layout =
+        graphics
             .device
-            .create_shader_module(wgpu::include_wgsl!("shader/raytrace.wgsl"));
-        let ray_pipeline =
-            graphics
-                .device
-                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                    label: Some("ray pipeline"),
-                    layout: Some(&ray_pipeline_layout),
-                    module: &ray_shader,
-                    entry_point: "main",
-                });
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("deform pipeline layout"),
+                bind_group_layouts: &[&deform::bind_group_layout(graphics)],
+                push_constant_ranges: &[],
+            });
+    let deform_shader = graphics
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("deform.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+    graphics
+        .device
+        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("deform pipeline"),
+            layout: Some(&deform_pipeline_layout),
+            module: &deform_shader,
+            entry_point: "main",
+        })
+}
 
-        let comp_pipeline_layout =
-            graphics
-                .device
-                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: Some("composition pipeline layout"),
-                    bind_group_layouts: &[
-                        &g_buffer::read_bind_group_layout(graphics),
-                        &IrradianceCache::bind_group_layout(graphics),
-                    ],
-                    push_constant_ranges: &[],
-                });
-        let comp_shader = graphics
+fn composition_pipeline(graphics: &Graphics, source: &str) -> wgpu::RenderPipeline {
+    let comp_pipeline_layout =
+        graphics
             .device
-            .create_shader_module(wgpu::include_wgsl!("shader/composition.wgsl"));
-        let comp_pipeline =
-            graphics
-                .device
-                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("composition pipeline"),
-                    layout: Some(&comp_pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        module: &comp_shader,
-                        entry_point: "vs_main",
-                        buffers: &[],
-                    },
-                    fragment: Some(wgpu::FragmentState {
-                        module: &comp_shader,
-                        entry_point: "fs_main",
-                        targets: &[Some(wgpu::ColorTargetState {
-                            format: graphics.tx_format_surface,
-                            blend: Some(wgpu::BlendState::REPLACE),
-                            write_mask: wgpu::ColorWrites::ALL,
-                        })],
-                    }),
-                    primitive: wgpu::PrimitiveState {
-                        topology: wgpu::PrimitiveTopology::TriangleStrip,
-                        strip_index_format: None,
-                        front_face: wgpu::FrontFace::Ccw,
-                        cull_mode: Some(wgpu::Face::Back),
-                        polygon_mode: wgpu::PolygonMode::Fill,
-                        unclipped_depth: false,
-                        conservative: false,
-                    },
-                    depth_stencil: None,
-                    multisample: wgpu::MultisampleState {
-                        count: 1,
-                        mask: !0,
-                        alpha_to_coverage_enabled: false,
-                    },
-                    multiview: None,
-                });
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("composition pipeline layout"),
+                bind_group_layouts: &[
+                    &g_buffer::read_bind_group_layout(graphics),
+                    &IrradianceCache::bind_group_layout(graphics),
+                    &light::bind_group_layout(graphics),
+                ],
+                push_constant_ranges: &[],
+            });
+    let comp_shader = graphics
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("composition.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+    graphics
+        .device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("composition pipeline"),
+            layout: Some(&comp_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &comp_shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &comp_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: graphics.tx_format_surface,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+}
+
+// Builds with validation errors returned instead of raised by the device
+#[cfg(not(target_arch = "wasm32"))]
+fn validated<T>(graphics: &Graphics, name: &str, build: impl FnOnce() -> T) -> Result<T> {
+    graphics
+        .device
+        .push_error_scope(wgpu::ErrorFilter::Validation);
+    let built = build();
+    match pollster::block_on(graphics.device.pop_error_scope()) {
+        Some(err) => Err(anyhow::anyhow!("{} failed validation: {}", name, err)),
+        None => Ok(built),
+    }
+}
+
+impl Renderer {
+    pub fn new(graphics: &Graphics) -> Renderer {
+        let shaders = wgsl::Preprocessor::default();
+        let source = |name, source| {
+            shaders
+                .preprocess(name, source, &[])
+                .expect("built in shaders preprocess")
+        };
+        let [g_pipeline, g_pipeline_double_sided] = geometry_pipelines(
+            graphics,
+            &source("geometry.wgsl", include_str!("shader/geometry.wgsl")),
+        );
+        let ray_pipeline = ray_pipeline(
+            graphics,
+            &source("raytrace.wgsl", include_str!("shader/raytrace.wgsl")),
+        );
+        let comp_pipeline = composition_pipeline(
+            graphics,
+            &source("composition.wgsl", include_str!("shader/composition.wgsl")),
+        );
+        let deform_pipeline = deform_pipeline(
+            graphics,
+            &source("deform.wgsl", include_str!("shader/deform.wgsl")),
+        );
         let irradiance_cache = IrradianceCache::new(graphics);
 
         Renderer {
             g_buffer: GBuffer::new(graphics),
             ray_pipeline,
             g_pipeline,
+            g_pipeline_double_sided,
             irradiance_cache,
             comp_pipeline,
+            deform_pipeline,
+        }
+    }
+
+    pub fn g_buffer(&self) -> &GBuffer {
+        &self.g_buffer
+    }
+
+    // Rebuilds the pipelines built from one of SHADERS with new WGSL. The
+    // old pipelines stay when it fails to preprocess or validate, false for
+    // other shaders
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload_shader(
+        &mut self,
+        graphics: &Graphics,
+        shaders: &wgsl::Preprocessor,
+        name: &str,
+        source: &str,
+    ) -> Result<bool> {
+        if !SHADERS.contains(&name) {
+            return Ok(false);
         }
+        let source = &shaders.preprocess(name, source, &[])?;
+        match name {
+            "geometry.wgsl" => {
+                [self.g_pipeline, self.g_pipeline_double_sided] =
+                    validated(graphics, name, || geometry_pipelines(graphics, source))?
+            }
+            "raytrace.wgsl" => {
+                self.ray_pipeline = validated(graphics, name, || ray_pipeline(graphics, source))?
+            }
+            "composition.wgsl" => {
+                self.comp_pipeline =
+                    validated(graphics, name, || composition_pipeline(graphics, source))?
+            }
+            "deform.wgsl" => {
+                self.deform_pipeline =
+                    validated(graphics, name, || deform_pipeline(graphics, source))?
+            }
+            _ => unreachable!(),
+        }
+        Ok(true)
     }
 
     pub fn resize(&mut self, graphics: &Graphics) {
@@ -301,6 +579,22 @@ impl Renderer {
         self.g_buffer = GBuffer::new(graphics);
     }
 
+    // Morphs and skins the vertices of deformed instances. The geometry and
+    // ray passes read what it writes, so callers driving the passes
+    // themselves run it first, like render does
+    pub fn deform_pass(&mut self, encoder: &mut wgpu::CommandEncoder, scene: &Scene) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("deform pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.deform_pipeline);
+        for deformed in scene.inst_props.iter().filter_map(|ip| ip.deformed.as_ref()) {
+            compute_pass.set_bind_group(0, &deformed.bind_group, &[]);
+            compute_pass.dispatch_workgroups(deformed.vertex_amt.div_ceil(64), 1, 1);
+        }
+    }
+
+    // after deform_pass
     pub fn ray_pass(&mut self, encoder: &mut wgpu::CommandEncoder, scene: &mut Scene) {
         let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
             label: Some("ray pass"),
@@ -332,6 +626,7 @@ impl Renderer {
                 compute_pass.dispatch_workgroups(1, 1, 1);
             });
     }
+    // after deform_pass
     pub fn geometry_pass(&mut self, encoder: &mut wgpu::CommandEncoder, scene: &Scene) {
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("g pass"),
@@ -356,7 +651,8 @@ impl Renderer {
                     view: &self.g_buffer.position_texture.view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
+                        // a zero alpha tells composition nothing was drawn
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
                         store: wgpu::StoreOp::Store,
                     },
                 }),
@@ -387,32 +683,55 @@ impl Renderer {
             .for_each(|(mesh, inst_prop)| {
                 match &mesh.geometry.g_pipeline {
                     Some(pipeline) => render_pass.set_pipeline(&pipeline),
+                    None if mesh.material.bindings.params.double_sided != 0 => {
+                        render_pass.set_pipeline(&self.g_pipeline_double_sided)
+                    }
                     None => render_pass.set_pipeline(&self.g_pipeline),
                 }
                 render_pass.set_bind_group(0, &scene.camera.bind_group, &[]);
                 render_pass.set_bind_group(1, &mesh.material.bind_group, &[]);
+                let vertex = match &inst_prop.deformed {
+                    Some(deformed) => deformed.output.slice(deformed.vertex()),
+                    None => mesh
+                        .geometry
+                        .buffer
+                        .gpu_buffer
+                        .slice(mesh.geometry.ranges.vertex()),
+                };
+                render_pass.set_vertex_buffer(0, vertex);
                 render_pass.set_vertex_buffer(
-                    0,
+                    1,
                     mesh.geometry
                         .buffer
                         .gpu_buffer
-                        .slice(mesh.geometry.ranges.vertex()),
+                        .slice(mesh.geometry.ranges.uv()),
                 );
                 if let Some(ib) = &inst_prop.buffer {
-                    render_pass.set_vertex_buffer(1, ib.slice(inst_prop.range()));
+                    render_pass.set_vertex_buffer(2, ib.slice(inst_prop.range()));
                 } else {
                     render_pass.set_vertex_buffer(
-                        1,
+                        2,
                         scene.ray_buffer.world_tsfs_buffer.slice(inst_prop.range()),
                     );
                 }
-                render_pass.set_vertex_buffer(
-                    2,
-                    mesh.geometry
-                        .buffer
-                        .gpu_buffer
-                        .slice(mesh.geometry.ranges.uv()),
-                );
+                let (normal, tangent) = match &inst_prop.deformed {
+                    Some(deformed) => (
+                        deformed.output.slice(deformed.normal()),
+                        deformed.output.slice(deformed.tangent()),
+                    ),
+                    None => (
+                        mesh.geometry
+                            .buffer
+                            .gpu_buffer
+                            .slice(mesh.geometry.ranges.normal()),
+                        mesh.geometry
+                            .buffer
+                            .gpu_buffer
+                            .slice(mesh.geometry.ranges.tangent()),
+                    ),
+                };
+                render_pass.set_vertex_buffer(3, normal);
+                render_pass.set_vertex_buffer(4, tangent);
 
                 //if mesh.geometry.ranges.index() {
                 render_pass.set_index_buffer(
@@ -420,7 +739,7 @@ impl Renderer {
                         .buffer
                         .gpu_buffer
                         .slice(mesh.geometry.ranges.index()),
-                    wgpu::IndexFormat::Uint16,
+                    mesh.geometry.index_format,
                 );
                 render_pass.draw_indexed(0..mesh.geometry.elm_amt, 0, 0..inst_prop.amt);
                 //} else {
@@ -428,10 +747,37 @@ impl Renderer {
                 //}
             });
     }
-    pub fn compose_pass<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+    pub fn render(&mut self, graphics: &Graphics, scene: &mut Scene, view: &wgpu::TextureView) {
+        let mut encoder = graphics
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.deform_pass(&mut encoder, scene);
+        self.geometry_pass(&mut encoder, scene);
+        self.ray_pass(&mut encoder, scene);
+        {
+            let mut comp_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("comp pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.compose_pass(&mut comp_pass, scene);
+        }
+        graphics.queue.submit(Some(encoder.finish()));
+    }
+    pub fn compose_pass<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, scene: &'a Scene) {
         render_pass.set_pipeline(&self.comp_pipeline);
         render_pass.set_bind_group(0, &self.g_buffer.read_bind_group, &[]);
         render_pass.set_bind_group(1, &self.irradiance_cache.bind_group, &[]);
+        render_pass.set_bind_group(2, &scene.light_buffer.bind_group, &[]);
         render_pass.draw(0..4, 0..1);
     }
 }
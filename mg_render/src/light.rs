@@ -0,0 +1,155 @@
+use crate::{
+    graphics::Graphics,
+    model::{Light, LightKind},
+};
+use mg_core::*;
+use std::mem::size_of;
+
+// A light placed in the scene, tsf is relative to node when attached to
+// one and the light's world transform otherwise
+pub struct SceneLight {
+    pub light: Light,
+    pub node: Option<usize>,
+    pub tsf: Mat4,
+}
+
+// mirrors Light in light.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightUniform {
+    position: [f32; 3],
+    kind: u32,
+    direction: [f32; 3],
+    range: f32,
+    color: [f32; 3],
+    intensity: f32,
+    cone_scale: f32,
+    cone_offset: f32,
+    _pad: [f32; 2],
+}
+
+impl LightUniform {
+    fn new(light: &Light, world: &Mat4) -> LightUniform {
+        let position = world.transform_point(&Point3f::origin());
+        let direction = world
+            .transform_vector(&-Vec3f::z())
+            .try_normalize(0.0)
+            .unwrap_or(-Vec3f::z());
+        // kinds are LIGHT_DIRECTIONAL, LIGHT_POINT and LIGHT_SPOT in
+        // light.wgsl, spots fade between their cones as KHR_lights_punctual
+        // suggests
+        let (kind, cone_scale, cone_offset) = match light.kind {
+            LightKind::Directional => (0, 0.0, 1.0),
+            LightKind::Point => (1, 0.0, 1.0),
+            LightKind::Spot {
+                inner_cone,
+                outer_cone,
+            } => {
+                let scale = 1.0 / (inner_cone.cos() - outer_cone.cos()).max(0.001);
+                (2, scale, -outer_cone.cos() * scale)
+            }
+        };
+        LightUniform {
+            position: position.coords.into(),
+            kind,
+            direction: direction.into(),
+            range: light.range.unwrap_or(0.0),
+            color: light.color.into(),
+            intensity: light.intensity,
+            cone_scale,
+            cone_offset,
+            _pad: [0.0; 2],
+        }
+    }
+}
+
+// mirrors the fields of Lights in light.wgsl before its array
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Header {
+    eye: [f32; 3],
+    amt: u32,
+}
+
+// Every scene light in one storage buffer, grown as lights are added
+pub struct LightBuffer {
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl LightBuffer {
+    pub fn new(graphics: &Graphics, capacity: usize) -> LightBuffer {
+        let capacity = capacity.max(1);
+        let buffer = graphics.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("light buffer"),
+            size: (size_of::<Header>() + capacity * size_of::<LightUniform>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = graphics
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("light bind group"),
+                layout: &bind_group_layout(graphics),
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            });
+        LightBuffer {
+            buffer,
+            capacity,
+            bind_group,
+        }
+    }
+
+    // Places the lights by the world transforms of the nodes they're
+    // attached to
+    pub(crate) fn write(
+        &mut self,
+        graphics: &Graphics,
+        eye: &Point3f,
+        lights: &[SceneLight],
+        world: impl Fn(usize) -> Mat4,
+    ) {
+        if lights.len() > self.capacity {
+            *self = LightBuffer::new(graphics, lights.len().next_power_of_two());
+        }
+        let header = Header {
+            eye: eye.coords.into(),
+            amt: lights.len() as u32,
+        };
+        let uniforms: Vec<_> = lights
+            .iter()
+            .map(|placed| {
+                let world = match placed.node {
+                    Some(node) => world(node) * placed.tsf,
+                    None => placed.tsf,
+                };
+                LightUniform::new(&placed.light, &world)
+            })
+            .collect();
+        let mut bytes = bytemuck::bytes_of(&header).to_vec();
+        bytes.extend_from_slice(bytemuck::cast_slice(&uniforms));
+        graphics.queue.write_buffer(&self.buffer, 0, &bytes);
+    }
+}
+
+pub fn bind_group_layout(graphics: &Graphics) -> wgpu::BindGroupLayout {
+    graphics
+        .device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("light bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        })
+}
@@ -1,21 +1,119 @@
-use crate::{graphics::Graphics, texture::Texture, texture_bind_group_layout};
+use crate::{
+    graphics::Graphics, sampler::SamplerDesc, texture::Texture, texture_bind_group_layout,
+};
 use mg_core::*;
+use wgpu::util::DeviceExt;
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AlphaMode {
+    Opaque = 0,
+    Mask = 1,
+    // the G-buffer can't blend, blended materials are masked at 0.5
+    Blend = 2,
+}
+
+// glTF metallic roughness factors, mirrors MaterialParams in geometry.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Params {
+    pub base_color_factor: [f32; 4],
+    pub emissive_factor: [f32; 3],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub alpha_cutoff: f32,
+    pub alpha_mode: u32,
+    pub double_sided: u32,
+    pub _pad: [u32; 2],
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0; 4],
+            emissive_factor: [0.0; 3],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            alpha_cutoff: 0.5,
+            alpha_mode: AlphaMode::Opaque as u32,
+            double_sided: 0,
+            _pad: [0; 2],
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Bindings {
+    pub params: Params,
     pub albedo_tx: Arc<Texture>,
     pub albedo_sampler: Arc<wgpu::Sampler>,
     pub emission_tx: Arc<Texture>,
     pub emission_sampler: Arc<wgpu::Sampler>,
+    // roughness in g, metalness in b
+    pub metallic_roughness_tx: Arc<Texture>,
+    pub metallic_roughness_sampler: Arc<wgpu::Sampler>,
+    pub normal_tx: Arc<Texture>,
+    pub normal_sampler: Arc<wgpu::Sampler>,
+    pub occlusion_tx: Arc<Texture>,
+    pub occlusion_sampler: Arc<wgpu::Sampler>,
+}
+
+impl Bindings {
+    // Textures and samplers ordered albedo, emission, metallic roughness,
+    // normal and occlusion
+    pub fn from_textures(
+        params: Params,
+        textures: [(Arc<Texture>, Arc<wgpu::Sampler>); 5],
+    ) -> Self {
+        let [albedo, emission, metallic_roughness, normal, occlusion] = textures;
+        Self {
+            params,
+            albedo_tx: albedo.0,
+            albedo_sampler: albedo.1,
+            emission_tx: emission.0,
+            emission_sampler: emission.1,
+            metallic_roughness_tx: metallic_roughness.0,
+            metallic_roughness_sampler: metallic_roughness.1,
+            normal_tx: normal.0,
+            normal_sampler: normal.1,
+            occlusion_tx: occlusion.0,
+            occlusion_sampler: occlusion.1,
+        }
+    }
+
+    pub fn textures(&self) -> [(Arc<Texture>, Arc<wgpu::Sampler>); 5] {
+        [
+            (self.albedo_tx.clone(), self.albedo_sampler.clone()),
+            (self.emission_tx.clone(), self.emission_sampler.clone()),
+            (
+                self.metallic_roughness_tx.clone(),
+                self.metallic_roughness_sampler.clone(),
+            ),
+            (self.normal_tx.clone(), self.normal_sampler.clone()),
+            (self.occlusion_tx.clone(), self.occlusion_sampler.clone()),
+        ]
+    }
 }
 
 pub struct Material {
     pub bindings: Bindings,
+    pub params_buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
 }
 
 impl Material {
     pub fn new(graphics: &Graphics, name: Option<&str>, bindings: Bindings) -> Material {
+        let params_buffer = graphics
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: name,
+                contents: bytemuck::bytes_of(&bindings.params),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
         let bind_group = graphics
             .device
             .create_bind_group(&wgpu::BindGroupDescriptor {
@@ -38,21 +136,54 @@ impl Material {
                         binding: 3,
                         resource: wgpu::BindingResource::Sampler(&bindings.emission_sampler),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: wgpu::BindingResource::TextureView(
+                            &bindings.metallic_roughness_tx.view,
+                        ),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 6,
+                        resource: wgpu::BindingResource::Sampler(
+                            &bindings.metallic_roughness_sampler,
+                        ),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 7,
+                        resource: wgpu::BindingResource::TextureView(&bindings.normal_tx.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 8,
+                        resource: wgpu::BindingResource::Sampler(&bindings.normal_sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 9,
+                        resource: wgpu::BindingResource::TextureView(&bindings.occlusion_tx.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 10,
+                        resource: wgpu::BindingResource::Sampler(&bindings.occlusion_sampler),
+                    },
                 ],
             });
         Material {
             bindings,
+            params_buffer,
             bind_group,
         }
     }
-    //address_mode_u: wgpu::AddressMode::Repeat,
-    //address_mode_v: wgpu::AddressMode::Repeat,
-    //address_mode_w: wgpu::AddressMode::Repeat,
-    //mag_filter: wgpu::FilterMode::Nearest,
-    //min_filter: wgpu::FilterMode::Nearest,
-    //mipmap_filter: wgpu::FilterMode::Nearest,
-    //..Default::default()
-    //},
+
+    // Factors can change without rebuilding the bind group
+    pub fn set_params(&mut self, graphics: &Graphics, params: Params) {
+        self.bindings.params = params;
+        graphics
+            .queue
+            .write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+    }
 }
 
 pub struct Defaults {
@@ -61,28 +192,47 @@ pub struct Defaults {
 
 impl Defaults {
     pub fn new(graphics: &Graphics) -> Self {
-        let albedo_tx = Arc::new(Texture::create_image_texture(
-            &graphics,
+        let albedo_tx = Arc::new(Self::texture(
+            graphics,
             "default albedo",
             include_bytes!("textures/defaultA.png"),
         ));
-        let emission_tx = Arc::new(Texture::create_image_texture(
-            &graphics,
+        let emission_tx = Arc::new(Self::texture(
+            graphics,
             "default emission",
             include_bytes!("textures/defaultA.png"),
         ));
-        let default_sampler = Arc::new(
-            graphics
-                .device
-                .create_sampler(&wgpu::SamplerDescriptor::default()),
-        );
+        // neutral data textures, the material factors apply as is
+        let white = Arc::new(Self::pixel(graphics, "default white", [255, 255, 255, 255]));
+        let normal_tx = Arc::new(Self::pixel(graphics, "default normal", [128, 128, 255, 255]));
+        let default_sampler = graphics.sampler(SamplerDesc::default());
         let bindings = Bindings {
+            params: Params::default(),
             albedo_tx,
             albedo_sampler: default_sampler.clone(),
             emission_tx,
             emission_sampler: default_sampler.clone(),
+            metallic_roughness_tx: white.clone(),
+            metallic_roughness_sampler: default_sampler.clone(),
+            normal_tx,
+            normal_sampler: default_sampler.clone(),
+            occlusion_tx: white,
+            occlusion_sampler: default_sampler.clone(),
         };
         let material = Arc::new(Material::new(graphics, Some("default"), bindings));
         Self { material }
     }
+
+    fn pixel(graphics: &Graphics, name: &str, rgba: [u8; 4]) -> Texture {
+        let rgba = image::ImageBuffer::from_pixel(1, 1, image::Rgba(rgba));
+        Texture::create_blank_texture(graphics, name, rgba, wgpu::TextureFormat::Rgba8Unorm)
+    }
+
+    // Built in textures fall back to black so a bad checkout still renders
+    fn texture(graphics: &Graphics, name: &str, bytes: &[u8]) -> Texture {
+        Texture::create_image_texture(graphics, name, bytes).unwrap_or_else(|err| {
+            log::error!("{}", err);
+            Self::pixel(graphics, name, [0, 0, 0, 255])
+        })
+    }
 }
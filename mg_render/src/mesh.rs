@@ -7,6 +7,8 @@ pub struct Mesh {
     pub name: String,
     pub geometry: Arc<Geometry>,
     pub material: Arc<Material>,
+    // default morph target weights, one per target
+    pub weights: Vec<f32>,
 }
 
 pub fn bind_group_layout(graphics: &Graphics) -> wgpu::BindGroupLayout {
@@ -62,6 +64,17 @@ pub fn bind_group_layout(graphics: &Graphics) -> wgpu::BindGroupLayout {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
+                // positions, the deformed ones for skinned or morphed instances
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         })
 }
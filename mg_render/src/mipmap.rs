@@ -0,0 +1,154 @@
+use crate::{graphics::Graphics, sampler::SamplerDesc};
+use image::RgbaImage;
+
+// Whether the gpu can render into and filter the format, otherwise
+// mip levels are built on the cpu
+pub fn gpu_supported(graphics: &Graphics, format: wgpu::TextureFormat) -> bool {
+    let features = graphics.adapter.get_texture_format_features(format);
+    features
+        .allowed_usages
+        .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        && features
+            .flags
+            .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE)
+}
+
+// Renders every level from the one above it. Level 0 has to be written
+// and the texture needs RENDER_ATTACHMENT usage
+pub fn generate_gpu(graphics: &Graphics, texture: &wgpu::Texture) {
+    let device = &graphics.device;
+    let pipeline = graphics.pipelines.get("mipmap", texture.format(), || {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shader/mipmap.wgsl"));
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("mipmap pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(texture.format().into())],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    });
+    let sampler = graphics.sampler(SamplerDesc {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+    let views = (0..texture.mip_level_count())
+        .map(|level| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("mipmap view"),
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("mipmap encoder"),
+    });
+    for pair in views.windows(2) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("mipmap bind group"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&pair[0]),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("mipmap pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &pair[1],
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.draw(0..4, 0..1);
+    }
+    graphics.queue.submit(Some(encoder.finish()));
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    match c <= 0.04045 {
+        true => c / 12.92,
+        false => ((c + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+fn linear_to_srgb(c: f32) -> u8 {
+    let c = match c <= 0.0031308 {
+        true => c * 12.92,
+        false => 1.055 * c.powf(1.0 / 2.4) - 0.055,
+    };
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// Box filters the image to half its size, in linear space when srgb.
+// Odd edges repeat their last texel
+pub fn downsample(image: &RgbaImage, srgb: bool) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let decode: Vec<f32> = (0..=255u8)
+        .map(|c| match srgb {
+            true => srgb_to_linear(c),
+            false => c as f32 / 255.0,
+        })
+        .collect();
+    RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+        let mut sum = [0.0; 4];
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let texel = image.get_pixel((x * 2 + dx).min(width - 1), (y * 2 + dy).min(height - 1));
+            for c in 0..4 {
+                sum[c] += match c {
+                    // alpha is always linear
+                    3 => texel[c] as f32 / 255.0,
+                    _ => decode[texel[c] as usize],
+                };
+            }
+        }
+        image::Rgba(std::array::from_fn(|c| match (c, srgb) {
+            (0..=2, true) => linear_to_srgb(sum[c] / 4.0),
+            _ => (sum[c] / 4.0 * 255.0).round() as u8,
+        }))
+    })
+}
+
+// Every level below the base image
+pub fn generate_cpu(image: &RgbaImage, srgb: bool, levels: u32) -> Vec<RgbaImage> {
+    let mut chain: Vec<RgbaImage> = vec![];
+    for _ in 1..levels {
+        let next = downsample(chain.last().unwrap_or(image), srgb);
+        chain.push(next);
+    }
+    chain
+}
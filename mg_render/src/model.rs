@@ -0,0 +1,78 @@
+use crate::{animation::Clip, mesh::Mesh};
+use mg_core::*;
+
+pub struct Node {
+    pub name: String,
+    pub tsf: Mat4,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    // indices into Model::meshes, one per primitive
+    pub meshes: Vec<usize>,
+    // index into Model::skins deforming the node's meshes
+    pub skin: Option<usize>,
+    // index into Model::lights, placed at the node
+    pub light: Option<usize>,
+    // EXT_mesh_gpu_instancing transforms relative to the node, each
+    // drawing its meshes once. Empty for nodes drawn once as usual
+    pub instances: Vec<Mat4>,
+}
+
+// Joints of a skinned mesh and the inverse of each one's world transform
+// when the mesh was bound to it
+pub struct Skin {
+    pub name: String,
+    pub joints: Vec<usize>,
+    pub inverse_binds: Vec<Mat4>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightKind {
+    // shines down the node's -z
+    Directional,
+    Point,
+    // down -z, angles in radians from it
+    Spot { inner_cone: f32, outer_cone: f32 },
+}
+
+// A KHR_lights_punctual light. Intensity is in lux for directional lights
+// and candela for the others
+#[derive(Clone, Debug, PartialEq)]
+pub struct Light {
+    pub name: String,
+    pub kind: LightKind,
+    // linear rgb
+    pub color: Vec3f,
+    pub intensity: f32,
+    // distance it fades out by, unlimited when None
+    pub range: Option<f32>,
+}
+
+// Node tree of an imported asset, instantiated with Scene::instantiate_model
+#[derive(Default)]
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    pub nodes: Vec<Node>,
+    pub roots: Vec<usize>,
+    pub skins: Vec<Skin>,
+    pub lights: Vec<Light>,
+    pub animations: Vec<Arc<Clip>>,
+}
+
+impl Model {
+    pub fn find_node(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    pub fn find_animation(&self, name: &str) -> Option<&Arc<Clip>> {
+        self.animations.iter().find(|clip| clip.name == name)
+    }
+
+    pub fn world_tsf(&self, mut node: usize) -> Mat4 {
+        let mut tsf = self.nodes[node].tsf;
+        while let Some(parent) = self.nodes[node].parent {
+            tsf = self.nodes[parent].tsf * tsf;
+            node = parent;
+        }
+        tsf
+    }
+}
@@ -0,0 +1,32 @@
+use mg_core::*;
+use std::collections::HashMap;
+
+// Pipelines of the passes that render into textures of any format, like
+// mipmap and cubemap generation. One per pass and format, built on first use
+#[derive(Default)]
+pub struct PipelineCache {
+    pipelines: Mutex<HashMap<(&'static str, wgpu::TextureFormat), Arc<wgpu::RenderPipeline>>>,
+}
+
+impl PipelineCache {
+    pub fn get(
+        &self,
+        pass: &'static str,
+        format: wgpu::TextureFormat,
+        build: impl FnOnce() -> wgpu::RenderPipeline,
+    ) -> Arc<wgpu::RenderPipeline> {
+        self.pipelines
+            .lock()
+            .entry((pass, format))
+            .or_insert_with(|| Arc::new(build()))
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.lock().is_empty()
+    }
+}
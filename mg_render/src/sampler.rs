@@ -0,0 +1,69 @@
+use crate::graphics::Graphics;
+use mg_core::*;
+use std::collections::HashMap;
+
+// Hashable subset of wgpu::SamplerDescriptor, enough to express glTF samplers
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerDesc {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub address_mode_w: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+}
+
+// glTF's default, repeating with linear filtering
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+// One wgpu::Sampler per distinct description, shared by every material
+#[derive(Default)]
+pub struct SamplerCache {
+    samplers: Mutex<HashMap<SamplerDesc, Arc<wgpu::Sampler>>>,
+}
+
+impl SamplerCache {
+    pub fn get(&self, device: &wgpu::Device, desc: SamplerDesc) -> Arc<wgpu::Sampler> {
+        self.samplers
+            .lock()
+            .entry(desc)
+            .or_insert_with(|| {
+                Arc::new(device.create_sampler(&wgpu::SamplerDescriptor {
+                    label: Some("cached sampler"),
+                    address_mode_u: desc.address_mode_u,
+                    address_mode_v: desc.address_mode_v,
+                    address_mode_w: desc.address_mode_w,
+                    mag_filter: desc.mag_filter,
+                    min_filter: desc.min_filter,
+                    mipmap_filter: desc.mipmap_filter,
+                    ..Default::default()
+                }))
+            })
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samplers.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.lock().is_empty()
+    }
+}
+
+impl Graphics {
+    pub fn sampler(&self, desc: SamplerDesc) -> Arc<wgpu::Sampler> {
+        self.samplers.get(&self.device, desc)
+    }
+}
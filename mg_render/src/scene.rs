@@ -1,8 +1,10 @@
 use crate::{
-    camera::Camera, graphics::Graphics, instance, Vertex, mesh::Mesh, mesh
+    camera::Camera, graphics::Graphics, instance, Vertex, mesh::Mesh, mesh, model::Model,
+    material::Material, deform::{self, Deformed}, texture::Texture,
+    light::{LightBuffer, SceneLight}, model::Light,
 };
 use mg_core::*;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::mem::size_of;
 use wgpu::util::DeviceExt;
 
@@ -10,13 +12,33 @@ use crate::global_illumination::ray_buffer::*;
 // use crate::mesh::{Mesh, bind_group_layout};
 
 struct LocalNode {
+    pub first_child: usize,
     pub child_count: usize,
     pub tsf: Mat4,
-    pub mesh: usize,
+    pub mesh: Option<usize>,
+    // one of a node's several primitives
+    pub primitive: bool,
     pub dirty: bool,
+    // world transform as of the last update
+    pub world: Mat4,
+}
+
+impl LocalNode {
+    fn new(tsf: Mat4, mesh: Option<usize>) -> LocalNode {
+        LocalNode {
+            first_child: 0,
+            child_count: 0,
+            tsf,
+            mesh,
+            primitive: false,
+            dirty: true,
+            world: Mat4::identity(),
+        }
+    }
 }
 
 struct WorldNode {
+    pub first_child: usize,
     pub child_count: usize,
     pub tsf: Mat4,
     pub dirty: bool,
@@ -25,6 +47,7 @@ struct WorldNode {
 impl WorldNode {
     pub fn from_local(local: &LocalNode) -> WorldNode {
         WorldNode {
+            first_child: local.first_child,
             child_count: local.child_count,
             tsf: local.tsf,
             dirty: local.dirty,
@@ -32,6 +55,7 @@ impl WorldNode {
     }
     pub fn add_local(&self, local: &LocalNode) -> WorldNode {
         WorldNode {
+            first_child: local.first_child,
             child_count: local.child_count,
             tsf: self.tsf * local.tsf,
             dirty: self.dirty || local.dirty,
@@ -43,11 +67,14 @@ pub struct Scene {
     pub camera: Camera,
     //pub collections: Vec<geometry::Collection>,
     pub background: Vertex,
-    pub root_count: usize,
+    roots: Vec<usize>,
     world_deque: VecDeque<WorldNode>,
     nodes: Vec<LocalNode>,
     pub meshes: Vec<Mesh>,
     pub inst_props: Vec<instance::Properties>,
+    pub skins: Vec<deform::Skin>,
+    pub lights: Vec<SceneLight>,
+    pub(crate) light_buffer: LightBuffer,
     pub ray_buffer: RayBuffer,
     accel_struct: Box<[u32]>,
     pub accel_struct_buffer: wgpu::Buffer,
@@ -69,46 +96,238 @@ impl Scene {
         Scene {
             background,
             camera,
-            root_count: 0,
+            roots: vec![],
             world_deque: VecDeque::with_capacity(128),
             nodes: Vec::with_capacity(1024),
             ray_buffer: RayBuffer::new(graphics, &accel_struct_buffer, 0),
             meshes: vec![],
             inst_props: vec![],
+            skins: vec![],
+            lights: vec![],
+            light_buffer: LightBuffer::new(graphics, 0),
             accel_struct,
             accel_struct_buffer,
         }
     }
 
-    pub fn update(&mut self) {
+    pub fn update(&mut self, graphics: &Graphics) {
         self.world_deque.clear();
-        for i in 0..self.root_count {
-            let node = &self.nodes[i];
-            if node.dirty {
-                self.ray_buffer.world_tsfs[i].0 = node.tsf.into();
-            }
-            if node.child_count != 0 {
-                self.world_deque.push_back(WorldNode::from_local(node));
+        let mut changed = false;
+        for r in 0..self.roots.len() {
+            let i = self.roots[r];
+            let world = WorldNode::from_local(&self.nodes[i]);
+            changed |= self.write_world(i, &world);
+            if world.child_count != 0 {
+                self.world_deque.push_back(world);
             }
         }
-        let mut offset = self.root_count;
         while let Some(parent) = self.world_deque.pop_front() {
-            offset = self.update_children(offset, parent);
+            changed |= self.update_children(parent);
+        }
+        self.nodes.iter_mut().for_each(|node| node.dirty = false);
+        if changed {
+            graphics.queue.write_buffer(
+                &self.ray_buffer.world_tsfs_buffer,
+                0,
+                bytemuck::cast_slice(&self.ray_buffer.world_tsfs[..]),
+            );
+            let nodes = &self.nodes;
+            for skin in self.skins.iter_mut() {
+                skin.pose(|node| nodes[node].world);
+                skin.write(graphics);
+            }
         }
+        for deformed in self.inst_props.iter_mut().filter_map(|ip| ip.deformed.as_mut()) {
+            deformed.write_weights(graphics);
+        }
+        // lights are few, so they're rewritten whether they moved or not
+        let nodes = &self.nodes;
+        self.light_buffer
+            .write(graphics, &self.camera.eye, &self.lights, |node| nodes[node].world);
     }
 
-    fn update_children(&mut self, offset: usize, parent: WorldNode) -> usize {
-        for i in offset..offset + parent.child_count {
-            let node = &self.nodes[i];
-            let world = parent.add_local(node);
-            if world.dirty {
-                self.ray_buffer.world_tsfs[i].0 = world.tsf.into();
-            }
-            if node.child_count != 0 {
+    fn update_children(&mut self, parent: WorldNode) -> bool {
+        let mut changed = false;
+        for i in parent.first_child..parent.first_child + parent.child_count {
+            let world = parent.add_local(&self.nodes[i]);
+            changed |= self.write_world(i, &world);
+            if world.child_count != 0 {
                 self.world_deque.push_back(world)
             }
         }
-        offset + parent.child_count
+        changed
+    }
+
+    fn write_world(&mut self, i: usize, world: &WorldNode) -> bool {
+        if !world.dirty {
+            return false;
+        }
+        self.nodes[i].world = world.tsf;
+        if let Some(mesh) = self.nodes[i].mesh {
+            let inst_prop = &self.inst_props[mesh];
+            let inst = inst_prop.range[0] as usize / size_of::<instance::Inst>();
+            match &inst_prop.bin {
+                Some(bin) => {
+                    // instances are relative to the node
+                    let locals = bin.chunks_exact(size_of::<instance::Inst>());
+                    for (k, local) in locals.enumerate() {
+                        let local: instance::Inst = bytemuck::pod_read_unaligned(local);
+                        let tsf = world.tsf * Mat4::from(local.0);
+                        self.ray_buffer.world_tsfs[inst + k].0 = tsf.into();
+                    }
+                }
+                None => self.ray_buffer.world_tsfs[inst].0 = world.tsf.into(),
+            }
+        }
+        true
+    }
+
+    pub fn node_tsf(&self, node: usize) -> Mat4 {
+        self.nodes[node].tsf
+    }
+
+    pub fn set_node_tsf(&mut self, node: usize, tsf: Mat4) {
+        self.nodes[node].tsf = tsf;
+        self.nodes[node].dirty = true;
+    }
+
+    // Places a light at the node so it follows it, or at the origin
+    // shining down -z until its tsf is set
+    pub fn add_light(&mut self, light: Light, node: Option<usize>) -> usize {
+        self.lights.push(SceneLight {
+            light,
+            node,
+            tsf: Mat4::identity(),
+        });
+        self.lights.len() - 1
+    }
+
+    // Sets the morph target weights of an instantiated mesh, extra weights
+    // are ignored and missing ones left as they were
+    pub fn set_weights(&mut self, mesh: usize, weights: &[f32]) {
+        if let Some(deformed) = &mut self.inst_props[mesh].deformed {
+            let amt = weights.len().min(deformed.weights.len());
+            deformed.weights[..amt].copy_from_slice(&weights[..amt]);
+            deformed.weights_dirty = true;
+        }
+    }
+
+    // Sets the weights of the node's mesh or every one of its primitives
+    pub fn set_node_weights(&mut self, node: usize, weights: &[f32]) {
+        let LocalNode { first_child, child_count, mesh, .. } = self.nodes[node];
+        let primitives = (first_child..first_child + child_count)
+            .filter(|&child| self.nodes[child].primitive)
+            .filter_map(|child| self.nodes[child].mesh);
+        let meshes: Vec<_> = mesh.into_iter().chain(primitives).collect();
+        for mesh in meshes {
+            self.set_weights(mesh, weights);
+        }
+    }
+
+    // Adds the model's node tree, keeping each node's children contiguous.
+    // Nodes with several primitives get a child node per primitive.
+    // Returns the scene indices of the model's nodes
+    pub fn instantiate_model(&mut self, graphics: &Graphics, model: &Model) -> Vec<usize> {
+        let mut indices = vec![0; model.nodes.len()];
+        let mut instanced = vec![vec![]; model.nodes.len()];
+        let mut queue = VecDeque::with_capacity(model.nodes.len());
+        for &root in model.roots.iter() {
+            let i = self.nodes.len();
+            self.nodes.push(LocalNode::new(model.nodes[root].tsf, None));
+            self.roots.push(i);
+            queue.push_back((root, i));
+        }
+        while let Some((m, i)) = queue.pop_front() {
+            indices[m] = i;
+            let node = &model.nodes[m];
+            let meshes: Vec<_> = node
+                .meshes
+                .iter()
+                .map(|&mesh| {
+                    let inst_param = match node.instances.is_empty() {
+                        true => instance::Params {
+                            amt: 1,
+                            bin: None,
+                            buffer: None,
+                            range: None,
+                        },
+                        false => instance::Params::from_tsfs(&node.instances),
+                    };
+                    self.instantiate_mesh(graphics, model.meshes[mesh].clone(), inst_param)
+                })
+                .collect();
+            instanced[m] = meshes.clone();
+            if let Some(light) = node.light.and_then(|light| model.lights.get(light)) {
+                self.add_light(light.clone(), Some(i));
+            }
+            let first_child = self.nodes.len();
+            if let [mesh] = meshes[..] {
+                self.nodes[i].mesh = Some(mesh);
+            } else {
+                for mesh in meshes {
+                    let mut primitive = LocalNode::new(Mat4::identity(), Some(mesh));
+                    primitive.primitive = true;
+                    self.nodes.push(primitive);
+                }
+            }
+            for &child in node.children.iter() {
+                queue.push_back((child, self.nodes.len()));
+                self.nodes.push(LocalNode::new(model.nodes[child].tsf, None));
+            }
+            self.nodes[i].first_child = first_child;
+            self.nodes[i].child_count = self.nodes.len() - first_child;
+        }
+        // joints can be anywhere in the tree, so skins wait for every node
+        for (m, node) in model.nodes.iter().enumerate() {
+            let Some(skin) = node.skin.and_then(|skin| model.skins.get(skin)) else {
+                continue;
+            };
+            if instanced[m].is_empty() {
+                continue;
+            }
+            let joints = skin.joints.iter().map(|&joint| indices[joint]).collect();
+            self.skins.push(deform::Skin::new(
+                graphics,
+                joints,
+                skin.inverse_binds.clone(),
+                indices[m],
+            ));
+            for &mesh in &instanced[m] {
+                self.skin_mesh(graphics, mesh, self.skins.len() - 1);
+            }
+        }
+        indices
+    }
+
+    // Deforms an instantiated mesh by one of skins, ignored for meshes
+    // without joints and weights
+    pub fn skin_mesh(&mut self, graphics: &Graphics, mesh: usize, skin: usize) {
+        if !self.meshes[mesh].geometry.is_skinned() {
+            return;
+        }
+        let weights = self.weights(mesh);
+        self.deform(graphics, mesh, Some(skin), weights);
+    }
+
+    // The mesh's current morph target weights, its defaults if not morphed
+    fn weights(&self, mesh: usize) -> Vec<f32> {
+        match &self.inst_props[mesh].deformed {
+            Some(deformed) => deformed.weights.clone(),
+            None => self.meshes[mesh].weights.clone(),
+        }
+    }
+
+    // Rebuilds the mesh's deformation and bind group, dropping the
+    // deformation of meshes neither skinned nor morphed
+    fn deform(&mut self, graphics: &Graphics, mesh: usize, skin: Option<usize>, weights: Vec<f32>) {
+        let geometry = &self.meshes[mesh].geometry;
+        let skin = skin.filter(|_| geometry.is_skinned());
+        let deformed = (skin.is_some() || geometry.target_amt() > 0)
+            .then(|| Deformed::new(graphics, geometry, skin, weights, &self.skins));
+        let inst_prop = &mut self.inst_props[mesh];
+        inst_prop.bind_group =
+            Self::mesh_bind_group(graphics, &self.meshes[mesh], inst_prop.range, deformed.as_ref());
+        inst_prop.deformed = deformed;
     }
 
     pub fn instantiate_mesh(
@@ -117,22 +336,56 @@ impl Scene {
         mesh: Mesh,
         inst_param: instance::Params,
     ) -> usize {
-        let ranges_uniform =
-            graphics
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("geometry buffer"),
-                    usage: wgpu::BufferUsages::UNIFORM,
-                    contents: as_u8_slice(&mesh.geometry.ranges),
-                });
         let range = match inst_param.range {
             Some(range) => range,
             None => {
                 let size = size_of::<instance::Inst>() as u32;
-                let start = self.ray_buffer.world_tsfs.len() as u32 * size;
+                let start = self.inst_props.last().map_or(0, |ip| ip.range[1]);
                 [start, start + inst_param.amt * size]
             }
         };
+        let bind_group = Self::mesh_bind_group(graphics, &mesh, range, None);
+        self.inst_props.push(instance::Properties {
+            amt: inst_param.amt,
+            bin: inst_param.bin,
+            buffer: inst_param.buffer,
+            range,
+            bind_group,
+            deformed: None,
+        });
+        log::warn!("{}", range[1]);
+        self.meshes.push(mesh);
+        let i = self.meshes.len() - 1;
+        if self.meshes[i].geometry.target_amt() > 0 {
+            self.deform(graphics, i, None, self.meshes[i].weights.clone());
+        }
+        self.resize(graphics);
+        i
+    }
+
+    fn mesh_bind_group(
+        graphics: &Graphics,
+        mesh: &Mesh,
+        range: [u32; 2],
+        deformed: Option<&Deformed>,
+    ) -> wgpu::BindGroup {
+        // deformed positions are read from the start of their own buffer
+        let mut ranges = mesh.geometry.ranges_uniform();
+        let positions = match deformed {
+            Some(deformed) => {
+                ranges.ranges.vertex = [0, deformed.vertex().end as u32];
+                &deformed.output
+            }
+            None => &mesh.geometry.buffer.gpu_buffer,
+        };
+        let ranges_uniform =
+            graphics
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("geometry buffer"),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                    contents: as_u8_slice(&ranges),
+                });
         let inst_range_uniform =
             graphics
                 .device
@@ -141,7 +394,7 @@ impl Scene {
                     usage: wgpu::BufferUsages::UNIFORM,
                     contents: as_u8_slice(&range),
                 });
-        let bind_group = graphics
+        graphics
             .device
             .create_bind_group(&wgpu::BindGroupDescriptor {
                 label: Some(format!("{} bind group", mesh.name).as_str()),
@@ -171,27 +424,88 @@ impl Scene {
                             &mesh.material.bindings.emission_sampler,
                         ),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: positions.as_entire_binding(),
+                    },
                 ],
+            })
+    }
+
+    // Swaps meshes instantiated from the old model for the new model's
+    // meshes in the same primitive order, like after a hot reload. Changed
+    // node trees need instantiating again. Returns the meshes swapped
+    pub fn swap_model(&mut self, graphics: &Graphics, old: &Model, new: &Model) -> usize {
+        let mut swapped = 0;
+        for i in 0..self.meshes.len() {
+            let mesh = &self.meshes[i];
+            let j = old.meshes.iter().position(|m| {
+                Arc::ptr_eq(&m.geometry, &mesh.geometry) && Arc::ptr_eq(&m.material, &mesh.material)
             });
-        self.inst_props.push(instance::Properties {
-            amt: inst_param.amt,
-            bin: inst_param.bin,
-            buffer: inst_param.buffer,
-            range,
-            bind_group,
-        });
-        log::warn!("{}", range[1]);
-        self.meshes.push(mesh);
-        self.resize(graphics);
-        self.meshes.len()
+            if let Some(new_mesh) = j.and_then(|j| new.meshes.get(j)) {
+                let skin = self.inst_props[i].deformed.as_ref().and_then(|d| d.skin);
+                let weights = self.weights(i);
+                self.meshes[i] = new_mesh.clone();
+                self.deform(graphics, i, skin, weights);
+                swapped += 1;
+            }
+        }
+        swapped
+    }
+
+    // Rebuilds the materials of meshes reading the old texture with the
+    // new one. Returns the meshes swapped
+    pub fn swap_texture(
+        &mut self,
+        graphics: &Graphics,
+        old: &Arc<Texture>,
+        new: &Arc<Texture>,
+    ) -> usize {
+        let mut materials: HashMap<usize, Arc<Material>> = HashMap::new();
+        let mut swapped = 0;
+        for (mesh, inst_prop) in self.meshes.iter_mut().zip(self.inst_props.iter_mut()) {
+            let mut bindings = mesh.material.bindings.clone();
+            let mut reads = false;
+            for texture in [
+                &mut bindings.albedo_tx,
+                &mut bindings.emission_tx,
+                &mut bindings.metallic_roughness_tx,
+                &mut bindings.normal_tx,
+                &mut bindings.occlusion_tx,
+            ] {
+                if Arc::ptr_eq(texture, old) {
+                    *texture = new.clone();
+                    reads = true;
+                }
+            }
+            if !reads {
+                continue;
+            }
+            // meshes sharing a material keep sharing its replacement
+            mesh.material = materials
+                .entry(Arc::as_ptr(&mesh.material) as usize)
+                .or_insert_with(|| Arc::new(Material::new(graphics, Some(&mesh.name), bindings)))
+                .clone();
+            inst_prop.bind_group = Self::mesh_bind_group(
+                graphics,
+                mesh,
+                inst_prop.range,
+                inst_prop.deformed.as_ref(),
+            );
+            swapped += 1;
+        }
+        swapped
     }
 
     pub fn resize(&mut self, graphics: &Graphics) {
         self.camera.resize(graphics);
+        // world transforms are rewritten into the new ray buffer on update
+        self.nodes.iter_mut().for_each(|node| node.dirty = true);
         self.ray_buffer = RayBuffer::new(
             graphics,
             &self.accel_struct_buffer,
-            self.inst_props.last().map_or(0, |ip| ip.range[1]) as usize,
+            self.inst_props.last().map_or(0, |ip| ip.range[1]) as usize
+                / size_of::<instance::Inst>(),
         );
     }
 }
@@ -0,0 +1,10 @@
+// Camera::bind_group_layout
+#ifndef CAMERA_GROUP
+#define CAMERA_GROUP 0
+#endif
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+}
+
+@group(CAMERA_GROUP) @binding(0) var<uniform> camera: CameraUniform;
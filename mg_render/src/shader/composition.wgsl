@@ -1,7 +1,7 @@
-@group(0) @binding(0) var t_albedo: texture_2d<f32>;
-@group(0) @binding(1) var t_emissive: texture_2d<f32>;
-@group(0) @binding(2) var t_position: texture_2d<f32>;
-@group(0) @binding(3) var t_normal: texture_2d<f32>;
+#define G_BUFFER_READ_GROUP 0
+#include "g_buffer_read.wgsl"
+#include "hash.wgsl"
+#include "light.wgsl"
 
 struct Entry {
   lifetime: u32,
@@ -10,38 +10,7 @@ struct Entry {
 const CACHE_BUCKET_SIZE = 16;
 @group(1) @binding(0) var<storage> irradiance_cache: array<Entry>;
 
-fn pcg3d(p: vec3u) -> vec3u {
-
-  var v = p * 1664525u + 1013904223u;
-
-  v.x += v.y*v.z;
-  v.y += v.z*v.x;
-  v.z += v.x*v.y;
-
-  let s = vec3u(v.x >> 16u, v.y >> 16u, v.z >> 16u); 
-  v ^= s;
-  
-  v.x += v.y*v.z;
-  v.y += v.z*v.x;
-  v.z += v.x*v.y;
-
-  return v;
-}
-
-
-fn xxhash32(p: vec3u) -> u32 {
-  let PRIME32_2 = 2246822519u; 
-  let PRIME32_3 = 3266489917u;
-  let PRIME32_4 = 668265263u;
-  let PRIME32_5 = 374761393u;
-  var h32 =  p.z + PRIME32_5 + p.x*PRIME32_3;
-  h32 = PRIME32_4*((h32 << 17u) | (h32 >> (32u - 17u)));
-  h32 += p.y * PRIME32_3;
-  h32 = PRIME32_4*((h32 << 17u) | (h32 >> (32u - 17u)));
-  h32 = PRIME32_2*(h32^(h32 >> 15u));
-  h32 = PRIME32_3*(h32^(h32 >> 13u));
-  return h32^(h32 >> 16u);
-}
+const PI = 3.14159265;
 
 @vertex
 fn vs_main(
@@ -54,14 +23,69 @@ fn vs_main(
   return vec4f(pos[i], 0.0, 1.0);
 }
 
+// Light reaching position from the light and the direction to it
+fn incoming(light: Light, position: vec3f) -> vec4f {
+  if light.kind == LIGHT_DIRECTIONAL {
+    return vec4f(-light.direction, light.intensity);
+  }
+  let to_light = light.position - position;
+  let distance = max(length(to_light), 0.0001);
+  let l = to_light / distance;
+  var attenuation = 1.0 / (distance * distance);
+  if light.range > 0.0 {
+    attenuation *= clamp(1.0 - pow(distance / light.range, 4.0), 0.0, 1.0);
+  }
+  if light.kind == LIGHT_SPOT {
+    let cone = clamp(dot(light.direction, -l) * light.cone_scale + light.cone_offset, 0.0, 1.0);
+    attenuation *= cone * cone;
+  }
+  return vec4f(l, light.intensity * attenuation);
+}
+
+// Lambert diffuse and GGX specular of the metallic roughness model
+fn brdf(n: vec3f, v: vec3f, l: vec3f, albedo: vec3f, metallic: f32, roughness: f32) -> vec3f {
+  let h = normalize(v + l);
+  let n_dot_l = max(dot(n, l), 0.0001);
+  let n_dot_v = max(dot(n, v), 0.0001);
+  let n_dot_h = max(dot(n, h), 0.0);
+  let alpha = max(roughness * roughness, 0.002);
+  let alpha_2 = alpha * alpha;
+  let d = alpha_2 / (PI * pow(n_dot_h * n_dot_h * (alpha_2 - 1.0) + 1.0, 2.0));
+  let k = alpha / 2.0;
+  let g = n_dot_l / (n_dot_l * (1.0 - k) + k) * n_dot_v / (n_dot_v * (1.0 - k) + k);
+  let f0 = mix(vec3f(0.04), albedo, metallic);
+  let f = f0 + (1.0 - f0) * pow(1.0 - max(dot(v, h), 0.0), 5.0);
+  let diffuse = (1.0 - f) * (1.0 - metallic) * albedo / PI;
+  return diffuse + d * g * f / (4.0 * n_dot_l * n_dot_v);
+}
+
 @fragment
 fn fs_main(@builtin(position) pos: vec4f) -> @location(0) vec4f {
-  let dim = vec2f(textureDimensions(t_albedo));
+  let dim = vec2f(textureDimensions(g_albedo_tx));
   let coord = vec2i(floor(pos.xy));
 
   // last param is mip level
-  let albedo = textureLoad(t_albedo, coord, 0);
-  let position = textureLoad(t_position, coord, 0);
-  let normal = textureLoad(t_normal, coord, 0);
-  return albedo;
+  let albedo = textureLoad(g_albedo_tx, coord, 0);
+  let emission = textureLoad(g_emissive_tx, coord, 0);
+  let position = textureLoad(g_position_tx, coord, 0);
+  let normal = textureLoad(g_normal_tx, coord, 0);
+  // unlit without lights
+  if lights.amt == 0u || position.a == 0.0 {
+    return albedo;
+  }
+
+  let n = normalize(normal.xyz * 2.0 - 1.0);
+  let v = normalize(lights.eye - position.xyz);
+  var color = emission.rgb;
+  for (var i = 0u; i < lights.amt; i++) {
+    let light = lights.lights[i];
+    let l = incoming(light, position.xyz);
+    let n_dot_l = dot(n, l.xyz);
+    if n_dot_l <= 0.0 {
+      continue;
+    }
+    let radiance = light.color * l.w;
+    color += brdf(n, v, l.xyz, albedo.rgb, emission.a, normal.a) * radiance * n_dot_l;
+  }
+  return vec4f(color, 1.0);
 }
@@ -0,0 +1,92 @@
+// deform::bind_group_layout
+// mirrors deform::DeformRanges, byte offsets into mesh_buf
+struct DeformRanges {
+  vert_start: u32,
+  normal_start: u32,
+  tangent_start: u32,
+  joints_start: u32,
+  weights_start: u32,
+  targets_start: u32,
+  target_amt: u32,
+  vert_amt: u32,
+  skinned: u32,
+}
+
+@group(0) @binding(0) var<uniform> deform_ranges: DeformRanges;
+@group(0) @binding(1) var<storage> mesh_buf: array<u32>;
+@group(0) @binding(2) var<storage> palette: array<mat4x4f>;
+@group(0) @binding(3) var<storage> morph_weights: array<f32>;
+// positions, then normals, then tangents
+@group(0) @binding(4) var<storage, read_write> deformed_buf: array<f32>;
+
+fn loadVec3(i: u32) -> vec3f {
+  return vec3f(
+    bitcast<f32>(mesh_buf[i]),
+    bitcast<f32>(mesh_buf[i + 1u]),
+    bitcast<f32>(mesh_buf[i + 2u]),
+  );
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3u) {
+  let v = id.x;
+  let amt = deform_ranges.vert_amt;
+  if v >= amt {
+    return;
+  }
+
+  var position = loadVec3(deform_ranges.vert_start / 4u + v * 3u);
+  var normal = loadVec3(deform_ranges.normal_start / 4u + v * 3u);
+  let tangent_i = deform_ranges.tangent_start / 4u + v * 4u;
+  var tangent = loadVec3(tangent_i);
+  let tangent_w = bitcast<f32>(mesh_buf[tangent_i + 3u]);
+
+  // each target holds position, normal and tangent deltas, vec3 each
+  for (var t = 0u; t < deform_ranges.target_amt; t++) {
+    let weight = morph_weights[t];
+    if weight == 0.0 {
+      continue;
+    }
+    let target_i = deform_ranges.targets_start / 4u + t * amt * 9u + v * 3u;
+    position += loadVec3(target_i) * weight;
+    normal += loadVec3(target_i + amt * 3u) * weight;
+    tangent += loadVec3(target_i + amt * 6u) * weight;
+  }
+
+  var skin = mat4x4f(
+      vec4f(1.0, 0.0, 0.0, 0.0),
+      vec4f(0.0, 1.0, 0.0, 0.0),
+      vec4f(0.0, 0.0, 1.0, 0.0),
+      vec4f(0.0, 0.0, 0.0, 1.0),
+  );
+  if deform_ranges.skinned != 0u {
+    let joints_i = deform_ranges.joints_start / 4u + v * 4u;
+    let weights_i = deform_ranges.weights_start / 4u + v * 4u;
+    skin = mat4x4f(vec4f(0.0), vec4f(0.0), vec4f(0.0), vec4f(0.0));
+    for (var i = 0u; i < 4u; i++) {
+      let weight = bitcast<f32>(mesh_buf[weights_i + i]);
+      skin += palette[mesh_buf[joints_i + i]] * weight;
+    }
+  }
+
+  // cofactor matrix, the inverse transpose up to scale
+  let m = mat3x3f(skin[0].xyz, skin[1].xyz, skin[2].xyz);
+  let cofactor = mat3x3f(
+      cross(m[1], m[2]),
+      cross(m[2], m[0]),
+      cross(m[0], m[1]),
+  );
+  let det_sign = sign(dot(m[0], cross(m[1], m[2])));
+  let deformed_position = (skin * vec4f(position, 1.0)).xyz;
+  let deformed_normal = normalize(cofactor * normal) * det_sign;
+  let deformed_tangent = normalize(m * tangent);
+
+  let normals = amt * 3u;
+  let tangents = amt * 6u;
+  for (var i = 0u; i < 3u; i++) {
+    deformed_buf[v * 3u + i] = deformed_position[i];
+    deformed_buf[normals + v * 3u + i] = deformed_normal[i];
+    deformed_buf[tangents + v * 4u + i] = deformed_tangent[i];
+  }
+  deformed_buf[tangents + v * 4u + 3u] = tangent_w * det_sign;
+}
@@ -0,0 +1,49 @@
+@group(0) @binding(0) var t_equirect: texture_2d<f32>;
+@group(0) @binding(1) var s_equirect: sampler;
+@group(0) @binding(2) var<uniform> face: u32;
+
+const PI = 3.14159265359;
+
+struct VertexOutput {
+  @builtin(position) pos: vec4f,
+  @location(0) uv: vec2f,
+}
+
+@vertex
+fn vs_main(
+  @builtin(vertex_index) i: u32,
+) -> VertexOutput {
+  var pos = array(
+    vec2(-1.0, 1.0), vec2(-1.0, -1.0), 
+    vec2(1.0, 1.0), vec2(1.0, -1.0)
+  );
+  var out: VertexOutput;
+  out.pos = vec4f(pos[i], 0.0, 1.0);
+  out.uv = pos[i] * vec2(0.5, -0.5) + 0.5;
+  return out;
+}
+
+// Direction through a texel of a cube face, faces ordered
+// +x, -x, +y, -y, +z, -z with v pointing down each face
+fn face_direction(uv: vec2f) -> vec3f {
+  let s = uv.x * 2.0 - 1.0;
+  let t = uv.y * 2.0 - 1.0;
+  switch face {
+    case 0u: { return vec3(1.0, -t, -s); }
+    case 1u: { return vec3(-1.0, -t, s); }
+    case 2u: { return vec3(s, 1.0, t); }
+    case 3u: { return vec3(s, -1.0, -t); }
+    case 4u: { return vec3(s, -t, 1.0); }
+    default: { return vec3(-s, -t, -1.0); }
+  }
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+  let d = normalize(face_direction(in.uv));
+  let uv = vec2(
+    atan2(d.z, d.x) / (2.0 * PI) + 0.5,
+    acos(clamp(d.y, -1.0, 1.0)) / PI,
+  );
+  return textureSampleLevel(t_equirect, s_equirect, uv, 0.0);
+}
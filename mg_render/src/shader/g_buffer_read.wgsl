@@ -0,0 +1,9 @@
+// g_buffer::read_bind_group_layout
+#ifndef G_BUFFER_READ_GROUP
+#define G_BUFFER_READ_GROUP 2
+#endif
+
+@group(G_BUFFER_READ_GROUP) @binding(0) var g_albedo_tx: texture_2d<f32>;
+@group(G_BUFFER_READ_GROUP) @binding(1) var g_emissive_tx: texture_2d<f32>;
+@group(G_BUFFER_READ_GROUP) @binding(2) var g_position_tx: texture_2d<f32>;
+@group(G_BUFFER_READ_GROUP) @binding(3) var g_normal_tx: texture_2d<f32>;
@@ -0,0 +1,9 @@
+// g_buffer::write_bind_group_layout
+#ifndef G_BUFFER_WRITE_GROUP
+#define G_BUFFER_WRITE_GROUP 3
+#endif
+
+@group(G_BUFFER_WRITE_GROUP) @binding(0) var g_albedo_stx: texture_storage_2d<rgba8unorm, write>;
+@group(G_BUFFER_WRITE_GROUP) @binding(1) var g_emissive_stx: texture_storage_2d<rgba8unorm, write>;
+@group(G_BUFFER_WRITE_GROUP) @binding(2) var g_position_stx: texture_storage_2d<rgba16float, write>;
+@group(G_BUFFER_WRITE_GROUP) @binding(3) var g_normal_stx: texture_storage_2d<rgba8unorm, write>;
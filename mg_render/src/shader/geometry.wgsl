@@ -2,18 +2,14 @@
 //@group(0) @binding(1) var t_prev_position: texture_2d<f32>;
 //@group(0) @binding(2) var t_prev_normal: texture_2d<f32>;
 
-struct CameraUniform {
-    view_proj: mat4x4<f32>,
-}
-
-@group(0) @binding(0) var<uniform> camera: CameraUniform;
-
-@group(1) @binding(0) var t_albedo: texture_2d<f32>;
-@group(1) @binding(1) var s_albedo: sampler;
+#include "camera.wgsl"
+#include "material.wgsl"
 
 struct VertexInput {
   @location(0) position: vec3f,
   @location(1) uv: vec2f,
+  @location(6) normal: vec3f,
+  @location(7) tangent: vec4f,
   @builtin(vertex_index) index: u32,
 }
 
@@ -28,6 +24,8 @@ struct VertexOutput {
   @builtin(position) clip_position: vec4f,
   @location(0) tex_coords: vec2f,
   @location(1) normal: vec3f,
+  @location(2) tangent: vec4f,
+  @location(3) world_position: vec3f,
 }
 
 @vertex
@@ -44,9 +42,22 @@ fn vs_main(
       instance.m3,
   );
 
-  out.clip_position = camera.view_proj * model_matrix * vec4<f32>(vert.position, 1.0);
+  let world_position = model_matrix * vec4<f32>(vert.position, 1.0);
+  out.clip_position = camera.view_proj * world_position;
+  out.world_position = world_position.xyz;
   out.tex_coords = vert.uv;
 
+  // cofactor matrix, the inverse transpose up to scale
+  let m = mat3x3f(instance.m0.xyz, instance.m1.xyz, instance.m2.xyz);
+  let cofactor = mat3x3f(
+      cross(m[1], m[2]),
+      cross(m[2], m[0]),
+      cross(m[0], m[1]),
+  );
+  let det_sign = sign(dot(m[0], cross(m[1], m[2])));
+  out.normal = normalize(cofactor * vert.normal) * det_sign;
+  out.tangent = vec4f(normalize(m * vert.tangent.xyz), vert.tangent.w * det_sign);
+
   return out;
 }
 
@@ -57,19 +68,44 @@ struct FragmentOutput {
   @location(3) normal: vec4f,
 }
 
+// G-buffer packing:
+// albedo.a occlusion, emission.a metalness, normal.a roughness,
+// position.a is one where geometry was drawn
 @fragment
-fn fs_main(in: VertexOutput) -> FragmentOutput {
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> FragmentOutput {
   //let coord = vec2i(
 //i32(round(in.clip_position.x / in.clip_position.w)),
 //  i32(round(in.clip_position.y / in.clip_position.w)));
   let view_position = in.clip_position.xyz / in.clip_position.w;
-  let color = textureSample(t_albedo, s_albedo, in.tex_coords);
+  // sampled up front, textureSample needs uniform control flow
+  let color = textureSample(t_albedo, s_albedo, in.tex_coords) * material.base_color_factor;
+  let emission = textureSample(t_emission, s_emission, in.tex_coords).rgb;
+  let metallic_roughness = textureSample(t_metallic_roughness, s_metallic_roughness, in.tex_coords);
+  let normal_sample = textureSample(t_normal, s_normal, in.tex_coords).xyz;
+  let occlusion_sample = textureSample(t_occlusion, s_occlusion, in.tex_coords).r;
+
+  if material.alpha_mode != ALPHA_OPAQUE && color.a < material.alpha_cutoff {
+    discard;
+  }
+
+  let tangent_normal = (normal_sample * 2.0 - 1.0) * vec3f(vec2f(material.normal_scale), 1.0);
+  var n = normalize(in.normal);
+  let t = normalize(in.tangent.xyz - n * dot(n, in.tangent.xyz));
+  let b = cross(n, t) * in.tangent.w;
+  n = normalize(mat3x3f(t, b, n) * tangent_normal);
+  if !front_facing {
+    n = -n;
+  }
+  let occlusion = mix(1.0, occlusion_sample, material.occlusion_strength);
 
   //textureStore(ts_albedo, coord, color);
   //textureStore(ts_position, coord, vec4f(view_position, 0.0));
   //textureStore(ts_normal, coord, vec4f(in.normal, 0.0));
   var out: FragmentOutput;
-  out.albedo = color;
+  out.albedo = vec4f(color.rgb, occlusion);
+  out.emission = vec4f(emission * material.emissive_factor, metallic_roughness.b * material.metallic_factor);
+  out.position = vec4f(in.world_position, 1.0);
+  out.normal = vec4f(n * 0.5 + 0.5, metallic_roughness.g * material.roughness_factor);
   return out;
 
 }
@@ -0,0 +1,31 @@
+fn pcg3d(p: vec3u) -> vec3u {
+
+  var v = p * 1664525u + 1013904223u;
+
+  v.x += v.y*v.z;
+  v.y += v.z*v.x;
+  v.z += v.x*v.y;
+
+  let s = vec3u(v.x >> 16u, v.y >> 16u, v.z >> 16u); 
+  v ^= s;
+  
+  v.x += v.y*v.z;
+  v.y += v.z*v.x;
+  v.z += v.x*v.y;
+
+  return v;
+}
+
+fn xxhash32(p: vec3u) -> u32 {
+  let PRIME32_2 = 2246822519u; 
+  let PRIME32_3 = 3266489917u;
+  let PRIME32_4 = 668265263u;
+  let PRIME32_5 = 374761393u;
+  var h32 =  p.z + PRIME32_5 + p.x*PRIME32_3;
+  h32 = PRIME32_4*((h32 << 17u) | (h32 >> (32u - 17u)));
+  h32 += p.y * PRIME32_3;
+  h32 = PRIME32_4*((h32 << 17u) | (h32 >> (32u - 17u)));
+  h32 = PRIME32_2*(h32^(h32 >> 15u));
+  h32 = PRIME32_3*(h32^(h32 >> 13u));
+  return h32^(h32 >> 16u);
+}
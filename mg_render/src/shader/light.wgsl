@@ -0,0 +1,31 @@
+// light::bind_group_layout
+#ifndef LIGHT_GROUP
+#define LIGHT_GROUP 2
+#endif
+
+const LIGHT_DIRECTIONAL = 0u;
+const LIGHT_POINT = 1u;
+const LIGHT_SPOT = 2u;
+
+// mirrors light::LightUniform, in world space
+struct Light {
+  position: vec3f,
+  kind: u32,
+  // the way it shines
+  direction: vec3f,
+  // zero when unlimited
+  range: f32,
+  color: vec3f,
+  intensity: f32,
+  // spot cones fade by cos_angle * cone_scale + cone_offset
+  cone_scale: f32,
+  cone_offset: f32,
+}
+
+struct Lights {
+  eye: vec3f,
+  amt: u32,
+  lights: array<Light>,
+}
+
+@group(LIGHT_GROUP) @binding(0) var<storage> lights: Lights;
@@ -0,0 +1,31 @@
+// texture_bind_group_layout
+#ifndef MATERIAL_GROUP
+#define MATERIAL_GROUP 1
+#endif
+
+// mirrors material::Params
+struct MaterialParams {
+  base_color_factor: vec4f,
+  emissive_factor: vec3f,
+  metallic_factor: f32,
+  roughness_factor: f32,
+  normal_scale: f32,
+  occlusion_strength: f32,
+  alpha_cutoff: f32,
+  alpha_mode: u32,
+  double_sided: u32,
+}
+
+const ALPHA_OPAQUE = 0u;
+
+@group(MATERIAL_GROUP) @binding(0) var t_albedo: texture_2d<f32>;
+@group(MATERIAL_GROUP) @binding(1) var s_albedo: sampler;
+@group(MATERIAL_GROUP) @binding(2) var t_emission: texture_2d<f32>;
+@group(MATERIAL_GROUP) @binding(3) var s_emission: sampler;
+@group(MATERIAL_GROUP) @binding(4) var<uniform> material: MaterialParams;
+@group(MATERIAL_GROUP) @binding(5) var t_metallic_roughness: texture_2d<f32>;
+@group(MATERIAL_GROUP) @binding(6) var s_metallic_roughness: sampler;
+@group(MATERIAL_GROUP) @binding(7) var t_normal: texture_2d<f32>;
+@group(MATERIAL_GROUP) @binding(8) var s_normal: sampler;
+@group(MATERIAL_GROUP) @binding(9) var t_occlusion: texture_2d<f32>;
+@group(MATERIAL_GROUP) @binding(10) var s_occlusion: sampler;
@@ -0,0 +1,45 @@
+// mesh::bind_group_layout
+#ifndef MESH_GROUP
+#define MESH_GROUP 1
+#endif
+
+struct InstRange {
+  start: u32,
+  end: u32,
+}
+
+// mirrors geometry::RangesUniform, byte ranges into mesh_buf
+struct GeometryRanges {
+  ind_start: u32,
+  ind_end: u32,
+
+  vert_start: u32,
+  vert_end: u32,
+
+  uv_start: u32,
+  uv_end: u32,
+
+  normal_start: u32,
+  normal_end: u32,
+
+  tangent_start: u32,
+  tangent_end: u32,
+
+  joints_start: u32,
+  joints_end: u32,
+
+  weights_start: u32,
+  weights_end: u32,
+  targets_start: u32,
+  targets_end: u32,
+
+  ind_size: u32,
+}
+
+@group(MESH_GROUP) @binding(0) var<uniform> inst_range: InstRange;
+@group(MESH_GROUP) @binding(1) var<uniform> g_ranges: GeometryRanges;
+@group(MESH_GROUP) @binding(2) var<storage> mesh_buf: array<u32>;
+@group(MESH_GROUP) @binding(3) var emissive_tx: texture_2d<f32>;
+@group(MESH_GROUP) @binding(4) var emissive_s: sampler;
+// vert ranges index vert_buf, the mesh_buf or deformed positions
+@group(MESH_GROUP) @binding(5) var<storage> vert_buf: array<u32>;
@@ -0,0 +1,28 @@
+@group(0) @binding(0) var t_src: texture_2d<f32>;
+@group(0) @binding(1) var s_src: sampler;
+
+struct VertexOutput {
+  @builtin(position) pos: vec4f,
+  @location(0) uv: vec2f,
+}
+
+@vertex
+fn vs_main(
+  @builtin(vertex_index) i: u32,
+) -> VertexOutput {
+  var pos = array(
+    vec2(-1.0, 1.0), vec2(-1.0, -1.0), 
+    vec2(1.0, 1.0), vec2(1.0, -1.0)
+  );
+  var out: VertexOutput;
+  out.pos = vec4f(pos[i], 0.0, 1.0);
+  out.uv = pos[i] * vec2(0.5, -0.5) + 0.5;
+  return out;
+}
+
+// Sampling between four texels of the larger level averages them,
+// srgb views decode before filtering and encode on write
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+  return textureSampleLevel(t_src, s_src, in.uv, 0.0);
+}
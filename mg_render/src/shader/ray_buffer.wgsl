@@ -0,0 +1,9 @@
+// ray_buffer::read_bind_group_layout
+#ifndef RAY_BUFFER_GROUP
+#define RAY_BUFFER_GROUP 0
+#endif
+
+@group(RAY_BUFFER_GROUP) @binding(0) var origin_tx: texture_2d<f32>;
+@group(RAY_BUFFER_GROUP) @binding(1) var direction_tx: texture_2d<f32>;
+@group(RAY_BUFFER_GROUP) @binding(2) var<storage> accel_struct_buf: array<u32>;
+@group(RAY_BUFFER_GROUP) @binding(3) var<storage> inst_buf: array<mat4x4f>;
@@ -0,0 +1,7 @@
+// volume_bind_group_layout
+#ifndef VOLUME_GROUP
+#define VOLUME_GROUP 1
+#endif
+
+@group(VOLUME_GROUP) @binding(0) var volume_tx: texture_3d<f32>;
+@group(VOLUME_GROUP) @binding(1) var volume_s: sampler;
@@ -0,0 +1,165 @@
+use mg_core::*;
+use std::collections::HashMap;
+
+fn vec3(v: [f32; 3]) -> Vec3f {
+    Vec3f::new(v[0], v[1], v[2])
+}
+
+// Any unit vector perpendicular to n
+fn orthogonal(n: Vec3f) -> Vec3f {
+    let axis = match n.x.abs() < 0.9 {
+        true => Vec3f::x(),
+        false => Vec3f::y(),
+    };
+    n.cross(&axis).normalize()
+}
+
+// Gives every triangle its own vertices, so faces can have their own normals
+pub fn unweld<T: Copy>(attribute: &[T], indices: &[u32]) -> Vec<T> {
+    indices.iter().map(|&i| attribute[i as usize]).collect()
+}
+
+// Face normals of unwelded triangles
+pub fn flat_normals(positions: &[[f32; 3]]) -> Vec<[f32; 3]> {
+    positions
+        .chunks(3)
+        .flat_map(|tri| {
+            let [a, b, c] = [vec3(tri[0]), vec3(tri[1]), vec3(tri[2])];
+            let n = (b - a).cross(&(c - a));
+            let n = match n.norm() > f32::EPSILON {
+                true => n.normalize(),
+                false => Vec3f::z(),
+            };
+            [n.into(); 3]
+        })
+        .collect()
+}
+
+// Vertex normals averaged from the faces sharing each vertex, weighted by area
+pub fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3f::zeros(); positions.len()];
+    for tri in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| vec3(positions[tri[i] as usize]));
+        // cross product length is twice the area
+        let n = (b - a).cross(&(c - a));
+        for &i in tri {
+            normals[i as usize] += n;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|n| match n.norm() > f32::EPSILON {
+            true => n.normalize().into(),
+            false => [0.0, 0.0, 1.0],
+        })
+        .collect()
+}
+
+// Tangents with the bitangent sign in w and the vertices they belong to
+pub struct Tangents {
+    pub tangents: Vec<[f32; 4]>,
+    // the input vertex each output vertex copies, the input vertices in
+    // order followed by the split ones
+    pub vertices: Vec<u32>,
+    pub indices: Vec<u32>,
+}
+
+struct Corners<'a> {
+    positions: &'a [[f32; 3]],
+    normals: &'a [[f32; 3]],
+    uvs: &'a [[f32; 2]],
+    indices: &'a [u32],
+    tangents: Vec<[f32; 4]>,
+}
+
+impl Corners<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> usize {
+        self.indices[face * 3 + vert] as usize
+    }
+}
+
+impl bevy_mikktspace::Geometry for Corners<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.positions[self.vertex(face, vert)]
+    }
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[self.vertex(face, vert)]
+    }
+    // glTF v points down the image, tangent space y points up it
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let [u, v] = self.uvs[self.vertex(face, vert)];
+        [u, 1.0 - v]
+    }
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = tangent;
+    }
+}
+
+// Tangent perpendicular to n, or any one when there's none
+fn fallback(n: [f32; 3], tangent: Option<[f32; 4]>) -> [f32; 4] {
+    let n = vec3(n);
+    let t = tangent.map_or(Vec3f::zeros(), |t| Vec3f::new(t[0], t[1], t[2]));
+    let t = t - n * n.dot(&t);
+    let t = match t.norm() > f32::EPSILON {
+        true => t.normalize(),
+        false => orthogonal(n),
+    };
+    let w = tangent.map_or(1.0, |t| t[3]);
+    [t.x, t.y, t.z, w]
+}
+
+// MikkTSpace tangents, so normal maps baked with it match. Vertices whose
+// corners get diverging tangents, like at uv seams and mirrors, are split
+pub fn tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    uvs: &[[f32; 2]],
+    indices: &[u32],
+) -> Tangents {
+    let mut corners = Corners {
+        positions,
+        normals,
+        uvs,
+        indices,
+        tangents: vec![[0.0; 4]; indices.len()],
+    };
+    // corners it can't give a tangent keep zeros and get the fallback
+    bevy_mikktspace::generate_tangents(&mut corners);
+    let mut tangents: Vec<Option<[f32; 4]>> = vec![None; positions.len()];
+    let mut vertices: Vec<u32> = (0..positions.len() as u32).collect();
+    let mut split = HashMap::new();
+    let mut out = Vec::with_capacity(indices.len());
+    for (&i, tangent) in indices.iter().zip(corners.tangents) {
+        let tangent = fallback(normals[i as usize], Some(tangent));
+        let index = match tangents[i as usize] {
+            None => {
+                tangents[i as usize] = Some(tangent);
+                i
+            }
+            Some(first) if first == tangent => i,
+            Some(_) => *split
+                .entry((i, tangent.map(f32::to_bits)))
+                .or_insert_with(|| {
+                    vertices.push(i);
+                    tangents.push(Some(tangent));
+                    vertices.len() as u32 - 1
+                }),
+        };
+        out.push(index);
+    }
+    Tangents {
+        tangents: tangents
+            .into_iter()
+            .zip(&vertices)
+            .map(|(tangent, &i)| tangent.unwrap_or_else(|| fallback(normals[i as usize], None)))
+            .collect(),
+        vertices,
+        indices: out,
+    }
+}
@@ -1,42 +1,290 @@
-use crate::graphics::Graphics;
-use image::GenericImageView;
+use crate::{graphics::Graphics, ktx, mipmap};
 use mg_core::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Weak;
+
+// How texel values are meant to be read. Colors are stored srgb encoded,
+// data such as normals, roughness or occlusion is linear
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureOptions {
+    // ui and data textures that are never minified can skip the mip chain
+    pub mipmaps: bool,
+    pub color_space: ColorSpace,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            mipmaps: true,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+}
+
+impl TextureOptions {
+    pub fn format(&self) -> wgpu::TextureFormat {
+        match self.color_space {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+// Image textures keyed on their encoded contents and options, so an image
+// used as both color and data is uploaded once per color space. Entries
+// don't keep textures alive
+#[derive(Default)]
+pub struct TextureCache {
+    textures: Mutex<HashMap<(ContentKey, TextureOptions), Weak<Texture>>>,
+}
+
+// Encoded contents by their hash and length, without keeping them around
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ContentKey {
+    hash: u64,
+    len: usize,
+}
+
+impl ContentKey {
+    pub fn new(bytes: &[u8]) -> Self {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        Self {
+            hash: hasher.finish(),
+            len: bytes.len(),
+        }
+    }
+}
+
+impl TextureCache {
+    pub fn get(
+        &self,
+        graphics: &Graphics,
+        name: &str,
+        bytes: &[u8],
+        options: &TextureOptions,
+    ) -> Result<Arc<Texture>> {
+        let key = (ContentKey::new(bytes), *options);
+        if let Some(texture) = self.textures.lock().get(&key).and_then(Weak::upgrade) {
+            return Ok(texture);
+        }
+        self.get_decoded(graphics, name, &DecodedImage::decode(name, bytes)?, options)
+    }
+
+    // Same as get for an image decoded ahead of time
+    pub fn get_decoded(
+        &self,
+        graphics: &Graphics,
+        name: &str,
+        image: &DecodedImage,
+        options: &TextureOptions,
+    ) -> Result<Arc<Texture>> {
+        let key = (image.key, *options);
+        if let Some(texture) = self.textures.lock().get(&key).and_then(Weak::upgrade) {
+            return Ok(texture);
+        }
+        let texture = Arc::new(Texture::create_decoded_texture(
+            graphics, name, image, options,
+        )?);
+        let mut textures = self.textures.lock();
+        textures.retain(|_, texture| texture.strong_count() > 0);
+        textures.insert(key, Arc::downgrade(&texture));
+        Ok(texture)
+    }
+
+    // Textures still alive
+    pub fn len(&self) -> usize {
+        let textures = self.textures.lock();
+        textures.values().filter(|t| t.strong_count() > 0).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Graphics {
+    pub fn image_texture(
+        &self,
+        name: &str,
+        bytes: &[u8],
+        options: &TextureOptions,
+    ) -> Result<Arc<Texture>> {
+        self.textures.get(self, name, bytes, options)
+    }
+}
+
+// An encoded image decoded to texels, which is the slow part of loading
+// and can happen off the main thread. KTX2 containers are kept as is
+pub struct DecodedImage {
+    // of the encoded bytes
+    pub key: ContentKey,
+    pub texels: Texels,
+}
+
+pub enum Texels {
+    Ktx2(Vec<u8>),
+    Rgba8(image::RgbaImage),
+    Float(image::Rgba32FImage),
+}
+
+impl DecodedImage {
+    // Decoding only fails with AssetError::BadImage
+    pub fn decode(name: &str, bytes: &[u8]) -> std::result::Result<Self, AssetError> {
+        let texels = match ktx::is_ktx2(bytes) {
+            true => Texels::Ktx2(bytes.to_vec()),
+            false => {
+                let img = decode_image(name, bytes)?;
+                match is_hdr(&img) {
+                    true => Texels::Float(img.into_rgba32f()),
+                    false => Texels::Rgba8(img.into_rgba8()),
+                }
+            }
+        };
+        Ok(Self {
+            key: ContentKey::new(bytes),
+            texels,
+        })
+    }
+}
 
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
 }
 
+fn decode_image(name: &str, bytes: &[u8]) -> std::result::Result<image::DynamicImage, AssetError> {
+    let bad_image = |err: image::ImageError| AssetError::BadImage {
+        asset: name.to_string(),
+        reason: err.to_string(),
+    };
+    // the generic loader tone maps radiance images down to 8 bits
+    if bytes.starts_with(b"#?RADIANCE") || bytes.starts_with(b"#?RGBE") {
+        let decoder = image::codecs::hdr::HdrDecoder::new(bytes).map_err(bad_image)?;
+        let meta = decoder.metadata();
+        let texels = decoder.read_image_hdr().map_err(bad_image)?;
+        let rgb = image::Rgb32FImage::from_raw(
+            meta.width,
+            meta.height,
+            texels.into_iter().flat_map(|t| t.0).collect(),
+        );
+        return Ok(image::DynamicImage::ImageRgb32F(rgb.ok_or_else(|| {
+            AssetError::BadImage {
+                asset: name.to_string(),
+                reason: "truncated radiance image".to_string(),
+            }
+        })?));
+    }
+    image::load_from_memory(bytes).map_err(bad_image)
+}
+
+// radiance and openexr images decode to 32 bit floats
+fn is_hdr(img: &image::DynamicImage) -> bool {
+    matches!(
+        img,
+        image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_)
+    )
+}
+
+pub(crate) fn f16_bytes(rgba: &image::Rgba32FImage) -> Vec<u8> {
+    rgba.as_raw()
+        .iter()
+        .flat_map(|&c| half::f16::from_f32(c).to_le_bytes())
+        .collect()
+}
+
 impl Texture {
-    pub fn create_image_texture(graphics: &Graphics, name: &str, bytes: &[u8]) -> Self {
-        let img_result = image::load_from_memory(bytes);
-        let img = match img_result {
-            Ok(img) => img,
-            Err(_) => {
-                // Create a blank RGBA8 image with dimensions 1x1
-                let rgba = image::ImageBuffer::from_pixel(1, 1, image::Rgba([0, 0, 0, 255]));
-                return Self::create_blank_texture(graphics, name, rgba);
+    pub fn create_image_texture(graphics: &Graphics, name: &str, bytes: &[u8]) -> Result<Self> {
+        Self::create_image_texture_with(graphics, name, bytes, &TextureOptions::default())
+    }
+
+    pub fn create_image_texture_with(
+        graphics: &Graphics,
+        name: &str,
+        bytes: &[u8],
+        options: &TextureOptions,
+    ) -> Result<Self> {
+        let image = DecodedImage::decode(name, bytes)?;
+        Self::create_decoded_texture(graphics, name, &image, options)
+    }
+
+    pub fn create_decoded_texture(
+        graphics: &Graphics,
+        name: &str,
+        image: &DecodedImage,
+        options: &TextureOptions,
+    ) -> Result<Self> {
+        Ok(match &image.texels {
+            Texels::Ktx2(bytes) => ktx::create_texture(graphics, name, bytes, options)?,
+            Texels::Float(rgba) => Self::create_float_texture(graphics, name, rgba, options),
+            Texels::Rgba8(rgba) => {
+                Self::create_rgba_texture(graphics, name, rgba, options.format(), options)
             }
-        };
-        let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
+        })
+    }
 
+    // High dynamic range images are stored as Rgba16Float, which is
+    // filterable everywhere unlike Rgba32Float
+    pub fn create_float_texture(
+        graphics: &Graphics,
+        name: &str,
+        rgba: &image::Rgba32FImage,
+        options: &TextureOptions,
+    ) -> Self {
         let size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
+            width: rgba.width(),
+            height: rgba.height(),
             depth_or_array_layers: 1,
         };
+        Self::create_uncompressed_texture(
+            graphics,
+            name,
+            wgpu::TextureFormat::Rgba16Float,
+            size,
+            &f16_bytes(rgba),
+            options,
+        )
+    }
+
+    // A tightly packed level of any uncompressed format. Mipmaps are only
+    // generated on gpus that can render to it
+    pub fn create_uncompressed_texture(
+        graphics: &Graphics,
+        name: &str,
+        format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+        data: &[u8],
+        options: &TextureOptions,
+    ) -> Self {
+        let mip_level_count = match options.mipmaps && mipmap::gpu_supported(graphics, format) {
+            true => size.max_mips(wgpu::TextureDimension::D2),
+            false => 1,
+        };
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC;
+        if mip_level_count > 1 {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
         let texture = graphics.device.create_texture(&wgpu::TextureDescriptor {
             label: Some(name),
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            format,
+            usage,
             view_formats: &[],
         });
-
+        let texel_size = format.block_size(None).unwrap_or(4);
         graphics.queue.write_texture(
             wgpu::ImageCopyTexture {
                 aspect: wgpu::TextureAspect::All,
@@ -44,19 +292,149 @@ impl Texture {
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
+            data,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
+                bytes_per_row: Some(texel_size * size.width),
+                rows_per_image: Some(size.height),
             },
             size,
         );
+        if mip_level_count > 1 {
+            mipmap::generate_gpu(graphics, &texture);
+        }
 
         let view = texture.create_view(&Default::default());
         Self { texture, view }
     }
 
+    // Cubemaps and texture arrays from tightly packed layers of the same
+    // size, cube faces ordered +x, -x, +y, -y, +z, -z
+    pub fn create_layered_texture(
+        graphics: &Graphics,
+        name: &str,
+        format: wgpu::TextureFormat,
+        (width, height): (u32, u32),
+        layers: &[&[u8]],
+        dimension: wgpu::TextureViewDimension,
+    ) -> Result<Self> {
+        let bad_layers = |reason: String| AssetError::BadImage {
+            asset: name.to_string(),
+            reason,
+        };
+        let count = layers.len() as u32;
+        let valid_count = match dimension {
+            wgpu::TextureViewDimension::Cube => count == 6,
+            wgpu::TextureViewDimension::CubeArray => count > 0 && count.is_multiple_of(6),
+            wgpu::TextureViewDimension::D2Array => count > 0,
+            _ => return Err(bad_layers(format!("{:?} isn't layered", dimension)).into()),
+        };
+        if !valid_count {
+            return Err(bad_layers(format!("{} layers for a {:?}", count, dimension)).into());
+        }
+        let layer_size = width * height * format.block_size(None).unwrap_or(0);
+        if let Some(layer) = layers.iter().find(|l| l.len() != layer_size as usize) {
+            return Err(bad_layers(format!(
+                "layer of {} bytes, expected {}",
+                layer.len(),
+                layer_size
+            ))
+            .into());
+        }
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: count,
+        };
+        let texture = graphics.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(name),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        for (layer, data) in layers.iter().enumerate() {
+            graphics.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(layer_size / height),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    depth_or_array_layers: 1,
+                    ..size
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(dimension),
+            ..Default::default()
+        });
+        Ok(Self { texture, view })
+    }
+
+    // Layered texture from encoded images, float when any of them is hdr
+    pub fn create_image_layers(
+        graphics: &Graphics,
+        name: &str,
+        images: &[&[u8]],
+        dimension: wgpu::TextureViewDimension,
+        color_space: ColorSpace,
+    ) -> Result<Self> {
+        let images = images
+            .iter()
+            .map(|bytes| decode_image(name, bytes))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let size = images.first().map_or((0, 0), |i| (i.width(), i.height()));
+        if images.iter().any(|i| (i.width(), i.height()) != size) {
+            return Err(AssetError::BadImage {
+                asset: name.to_string(),
+                reason: "layers differ in size".to_string(),
+            }
+            .into());
+        }
+        let (format, layers) = match images.iter().any(is_hdr) {
+            true => (
+                wgpu::TextureFormat::Rgba16Float,
+                images
+                    .iter()
+                    .map(|i| f16_bytes(&i.to_rgba32f()))
+                    .collect::<Vec<_>>(),
+            ),
+            false => (
+                TextureOptions {
+                    color_space,
+                    ..Default::default()
+                }
+                .format(),
+                images
+                    .into_iter()
+                    .map(|i| i.into_rgba8().into_raw())
+                    .collect(),
+            ),
+        };
+        let layers = layers.iter().map(|l| &l[..]).collect::<Vec<_>>();
+        Self::create_layered_texture(graphics, name, format, size, &layers, dimension)
+    }
+
     pub fn create_texture(
         graphics: &Graphics,
         label: &str,
@@ -83,7 +461,26 @@ impl Texture {
         Self { texture, view }
     }
 
-    fn create_blank_texture(graphics: &Graphics, name: &str, rgba: image::RgbaImage) -> Self {
+    pub fn create_blank_texture(
+        graphics: &Graphics,
+        name: &str,
+        rgba: image::RgbaImage,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let options = TextureOptions {
+            mipmaps: false,
+            ..Default::default()
+        };
+        Self::create_rgba_texture(graphics, name, &rgba, format, &options)
+    }
+
+    pub fn create_rgba_texture(
+        graphics: &Graphics,
+        name: &str,
+        rgba: &image::RgbaImage,
+        format: wgpu::TextureFormat,
+        options: &TextureOptions,
+    ) -> Self {
         let dimensions = rgba.dimensions();
 
         let size = wgpu::Extent3d {
@@ -91,34 +488,279 @@ impl Texture {
             height: dimensions.1,
             depth_or_array_layers: 1,
         };
+        let mip_level_count = match options.mipmaps {
+            true => size.max_mips(wgpu::TextureDimension::D2),
+            false => 1,
+        };
+        let gpu_mipmaps = mip_level_count > 1 && mipmap::gpu_supported(graphics, format);
+        // copy src lets textures be read back
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC;
+        if gpu_mipmaps {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
         let texture = graphics.device.create_texture(&wgpu::TextureDescriptor {
             label: Some(name),
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            format,
+            usage,
             view_formats: &[],
         });
 
+        let write_level = |level: u32, rgba: &image::RgbaImage| {
+            let (width, height) = rgba.dimensions();
+            graphics.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: level,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                rgba,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                size.mip_level_size(level, wgpu::TextureDimension::D2),
+            );
+        };
+        write_level(0, rgba);
+        if gpu_mipmaps {
+            mipmap::generate_gpu(graphics, &texture);
+        } else {
+            let levels = mipmap::generate_cpu(rgba, format.is_srgb(), mip_level_count);
+            for (level, rgba) in levels.iter().enumerate() {
+                write_level(level as u32 + 1, rgba);
+            }
+        }
+
+        let view = texture.create_view(&Default::default());
+        Self { texture, view }
+    }
+
+    // Every mip level given up front, already in the texture's format
+    pub fn create_levels_texture(
+        graphics: &Graphics,
+        name: &str,
+        format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+        levels: &[&[u8]],
+    ) -> Self {
+        let texture = graphics.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(name),
+            size,
+            mip_level_count: levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let (block_width, block_height) = format.block_dimensions();
+        let block_size = format.block_size(None).unwrap_or(4);
+        for (level, data) in levels.iter().enumerate() {
+            // compressed levels are copied in whole blocks
+            let size = size
+                .mip_level_size(level as u32, wgpu::TextureDimension::D2)
+                .physical_size(format);
+            graphics.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(size.width / block_width * block_size),
+                    rows_per_image: Some(size.height / block_height),
+                },
+                size,
+            );
+        }
+
+        let view = texture.create_view(&Default::default());
+        Self { texture, view }
+    }
+
+    // Volume from tightly packed voxels, x fastest then y then z. Bind
+    // with volume_bind_group_layout
+    pub fn create_volume_texture(
+        graphics: &Graphics,
+        name: &str,
+        format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+        voxels: &[u8],
+    ) -> Result<Self> {
+        let bad_volume = |reason: String| -> anyhow::Error {
+            AssetError::BadImage {
+                asset: name.to_string(),
+                reason,
+            }
+            .into()
+        };
+        let extents = [size.width, size.height, size.depth_or_array_layers];
+        let max = graphics.device.limits().max_texture_dimension_3d;
+        if extents.iter().any(|&extent| extent == 0 || extent > max) {
+            return Err(bad_volume(format!(
+                "{}x{}x{} volume, extents run from 1 to {}",
+                extents[0], extents[1], extents[2], max
+            )));
+        }
+        let texel_size = match (format.block_dimensions(), format.block_size(None)) {
+            ((1, 1), Some(texel_size)) => texel_size as u64,
+            _ => return Err(bad_volume(format!("{:?} volume", format))),
+        };
+        let expected = extents
+            .iter()
+            .fold(texel_size, |amt, &extent| amt * extent as u64);
+        if voxels.len() as u64 != expected {
+            return Err(bad_volume(format!(
+                "{} bytes of voxels for a volume of {} bytes",
+                voxels.len(),
+                expected
+            )));
+        }
+        let texture = graphics.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(name),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D3),
+            ..Default::default()
+        });
+        let volume = Self { texture, view };
+        volume
+            .write_region(graphics, wgpu::Origin3d::ZERO, size, voxels)
+            .map_err(|err| bad_volume(err.to_string()))?;
+        Ok(volume)
+    }
+
+    // Volume from encoded images of the same size, the first is z = 0
+    pub fn create_volume_slices(
+        graphics: &Graphics,
+        name: &str,
+        slices: &[&[u8]],
+        color_space: ColorSpace,
+    ) -> Result<Self> {
+        let slices = slices
+            .iter()
+            .map(|bytes| decode_image(name, bytes))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let Some(first) = slices.first() else {
+            return Err(AssetError::BadImage {
+                asset: name.to_string(),
+                reason: "no slices".to_string(),
+            }
+            .into());
+        };
+        let (width, height) = (first.width(), first.height());
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: slices.len() as u32,
+        };
+        if slices
+            .iter()
+            .any(|i| (i.width(), i.height()) != (width, height))
+        {
+            return Err(AssetError::BadImage {
+                asset: name.to_string(),
+                reason: "slices differ in size".to_string(),
+            }
+            .into());
+        }
+        let (format, voxels) = match slices.iter().any(is_hdr) {
+            true => (
+                wgpu::TextureFormat::Rgba16Float,
+                slices
+                    .iter()
+                    .flat_map(|i| f16_bytes(&i.to_rgba32f()))
+                    .collect(),
+            ),
+            false => (
+                TextureOptions {
+                    color_space,
+                    ..Default::default()
+                }
+                .format(),
+                slices
+                    .into_iter()
+                    .flat_map(|i| i.into_rgba8().into_raw())
+                    .collect::<Vec<_>>(),
+            ),
+        };
+        Self::create_volume_texture(graphics, name, format, size, &voxels)
+    }
+
+    // Overwrites a box of mip level 0 with tightly packed texels, for
+    // editing volumes and layers in place. Compressed formats aren't
+    // supported
+    pub fn write_region(
+        &self,
+        graphics: &Graphics,
+        origin: wgpu::Origin3d,
+        size: wgpu::Extent3d,
+        data: &[u8],
+    ) -> Result<()> {
+        let format = self.texture.format();
+        let texel_size = match (format.block_dimensions(), format.block_size(None)) {
+            ((1, 1), Some(texel_size)) => texel_size,
+            _ => anyhow::bail!("can't write regions of {:?} textures", format),
+        };
+        let extent = self.texture.size();
+        // compared in u64 so large origins and sizes can't wrap
+        let outside = |origin: u32, size: u32, extent: u32| {
+            origin as u64 + size as u64 > extent as u64
+        };
+        if outside(origin.x, size.width, extent.width)
+            || outside(origin.y, size.height, extent.height)
+            || outside(origin.z, size.depth_or_array_layers, extent.depth_or_array_layers)
+        {
+            anyhow::bail!(
+                "region {:?} at {:?} is outside the texture's {:?}",
+                size,
+                origin,
+                extent
+            );
+        }
+        let expected = [size.width, size.height, size.depth_or_array_layers]
+            .iter()
+            .fold(texel_size as u64, |bytes, &n| bytes * n as u64);
+        if data.len() as u64 != expected {
+            anyhow::bail!("{} bytes for a region of {} bytes", data.len(), expected);
+        }
         graphics.queue.write_texture(
             wgpu::ImageCopyTexture {
                 aspect: wgpu::TextureAspect::All,
-                texture: &texture,
+                texture: &self.texture,
                 mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
+                origin,
             },
-            &rgba,
+            data,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
+                bytes_per_row: Some(texel_size * size.width),
+                rows_per_image: Some(size.height),
             },
             size,
         );
-
-        let view = texture.create_view(&Default::default());
-        Self { texture, view }
+        Ok(())
     }
 }
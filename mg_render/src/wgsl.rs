@@ -0,0 +1,154 @@
+use mg_core::*;
+use std::collections::{HashMap, HashSet};
+
+// The engine's shared WGSL, each declaring a bind group laid out like its
+// Rust side under a group number a #define can change
+pub const MODULES: [(&str, &str); 9] = [
+    ("hash.wgsl", include_str!("shader/hash.wgsl")),
+    ("camera.wgsl", include_str!("shader/camera.wgsl")),
+    ("material.wgsl", include_str!("shader/material.wgsl")),
+    ("mesh.wgsl", include_str!("shader/mesh.wgsl")),
+    ("ray_buffer.wgsl", include_str!("shader/ray_buffer.wgsl")),
+    (
+        "g_buffer_read.wgsl",
+        include_str!("shader/g_buffer_read.wgsl"),
+    ),
+    (
+        "g_buffer_write.wgsl",
+        include_str!("shader/g_buffer_write.wgsl"),
+    ),
+    ("volume.wgsl", include_str!("shader/volume.wgsl")),
+    ("light.wgsl", include_str!("shader/light.wgsl")),
+];
+
+// Expands #include "module", #define NAME [value], #ifdef, #ifndef, #else
+// and #endif lines in WGSL. Modules are included once per shader and
+// defined names are replaced by their values
+pub struct Preprocessor {
+    modules: HashMap<String, String>,
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        let mut preprocessor = Self {
+            modules: HashMap::new(),
+        };
+        for (name, source) in MODULES {
+            preprocessor.add_module(name, source);
+        }
+        preprocessor
+    }
+}
+
+struct State<'a> {
+    defines: HashMap<String, String>,
+    included: HashSet<&'a str>,
+    out: String,
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+impl Preprocessor {
+    // Adds or replaces a module shaders can include
+    pub fn add_module(&mut self, name: &str, source: &str) {
+        self.modules.insert(name.to_string(), source.to_string());
+    }
+
+    pub fn preprocess(&self, name: &str, source: &str, defines: &[(&str, &str)]) -> Result<String> {
+        let mut state = State {
+            defines: defines
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            included: HashSet::new(),
+            out: String::with_capacity(source.len()),
+        };
+        self.expand(name, source, &mut state)?;
+        Ok(state.out)
+    }
+
+    fn expand<'a>(&'a self, name: &str, source: &str, state: &mut State<'a>) -> Result<()> {
+        // whether each enclosing #ifdef's lines are kept
+        let mut conditions: Vec<bool> = vec![];
+        for (i, line) in source.lines().enumerate() {
+            let error = |reason: &str| anyhow::anyhow!("{}:{}: {}", name, i + 1, reason);
+            let active = conditions.iter().all(|&kept| kept);
+            let Some(directive) = line.trim_start().strip_prefix('#') else {
+                if active {
+                    self.substitute(line, state);
+                }
+                continue;
+            };
+            let (keyword, rest) = directive
+                .split_once(char::is_whitespace)
+                .unwrap_or((directive, ""));
+            let rest = rest.trim();
+            match keyword {
+                "ifdef" | "ifndef" => {
+                    let defined = state.defines.contains_key(rest);
+                    conditions.push(defined == (keyword == "ifdef"));
+                }
+                "else" => match conditions.last_mut() {
+                    Some(kept) => *kept = !*kept,
+                    None => return Err(error("#else without #ifdef")),
+                },
+                "endif" => {
+                    conditions
+                        .pop()
+                        .ok_or_else(|| error("#endif without #ifdef"))?;
+                }
+                _ if !active => {}
+                "define" => {
+                    let (define, value) =
+                        rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    if define.is_empty() || !define.chars().all(is_ident) {
+                        return Err(error(&format!("bad #define \"{}\"", rest)));
+                    }
+                    state
+                        .defines
+                        .insert(define.to_string(), value.trim().to_string());
+                }
+                "include" => {
+                    let module = rest
+                        .strip_prefix('"')
+                        .and_then(|rest| rest.strip_suffix('"'))
+                        .ok_or_else(|| error("#include needs a quoted module name"))?;
+                    let (module, source) = self
+                        .modules
+                        .get_key_value(module)
+                        .ok_or_else(|| error(&format!("no module \"{}\"", module)))?;
+                    if state.included.insert(module) {
+                        self.expand(module, source, state)?;
+                    }
+                }
+                _ => return Err(error(&format!("unknown directive #{}", keyword))),
+            }
+        }
+        match conditions.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow::anyhow!("{}: #ifdef without #endif", name)),
+        }
+    }
+
+    // Copies a line replacing identifiers that have defined values
+    fn substitute(&self, line: &str, state: &mut State) {
+        let mut rest = line;
+        while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            // identifiers can't start inside a number or another identifier
+            let (before, from) = rest.split_at(start);
+            let len = from.find(|c| !is_ident(c)).unwrap_or(from.len());
+            let (ident, after) = from.split_at(len);
+            state.out.push_str(before);
+            let glued = before.ends_with(|c: char| c.is_ascii_digit());
+            match state.defines.get(ident) {
+                Some(value) if !glued && !value.is_empty() => state.out.push_str(value),
+                _ => state.out.push_str(ident),
+            }
+            rest = after;
+        }
+        state.out.push_str(rest);
+        state.out.push('\n');
+    }
+}
@@ -0,0 +1,287 @@
+mod common;
+
+use base64::Engine;
+use common::assert_near;
+use mg_core::*;
+use mg_render::{
+    animation::{Animator, Channel, Clip, Interpolation, Pose, Property},
+    cooked::{self, CookOptions},
+    gltf_loader::{self, GltfSrc, LoadOptions},
+    graphics::Graphics,
+    material,
+    model::{Model, Node},
+    scene::Scene,
+};
+
+fn channel(
+    property: Property,
+    interpolation: Interpolation,
+    times: &[f32],
+    values: &[f32],
+) -> Channel {
+    Channel {
+        node: 0,
+        property,
+        interpolation,
+        times: times.to_vec(),
+        values: values.to_vec(),
+    }
+}
+
+fn sample(channel: &Channel, time: f32) -> Vec<f32> {
+    let mut out = vec![0.0; channel.width()];
+    channel.sample(time, &mut out);
+    out
+}
+
+// One node at rest 1 up, animated by clips built in code
+fn model() -> Model {
+    Model {
+        nodes: vec![Node {
+            name: "node".to_string(),
+            tsf: Mat4::new_translation(&Vec3f::new(0.0, 1.0, 0.0)),
+            parent: None,
+            children: vec![],
+            meshes: vec![],
+            skin: None,
+            light: None,
+            instances: vec![],
+        }],
+        roots: vec![0],
+        ..Default::default()
+    }
+}
+
+// Moves the node from x to x + 1 over a second
+fn slide(x: f32) -> Arc<Clip> {
+    let values = [x, 0.0, 0.0, x + 1.0, 0.0, 0.0];
+    let translation = channel(
+        Property::Translation,
+        Interpolation::Linear,
+        &[0.0, 1.0],
+        &values,
+    );
+    Arc::new(Clip::new("slide".to_string(), vec![translation]))
+}
+
+#[test]
+fn interpolation() {
+    let times = [0.0, 1.0, 3.0];
+    let values = [0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 3.0, 2.0, 1.0];
+    let linear = channel(
+        Property::Translation,
+        Interpolation::Linear,
+        &times,
+        &values,
+    );
+    assert!(linear.check().is_none());
+    assert_near(&sample(&linear, 0.5), &[0.5, 1.0, 1.5], 1e-5);
+    assert_near(&sample(&linear, 2.0), &[2.0, 2.0, 2.0], 1e-5);
+    // the ends hold outside the keyframes
+    assert_near(&sample(&linear, -1.0), &[0.0; 3], 1e-5);
+    assert_near(&sample(&linear, 4.0), &[3.0, 2.0, 1.0], 1e-5);
+
+    let step = channel(Property::Translation, Interpolation::Step, &times, &values);
+    assert_near(&sample(&step, 0.99), &[0.0; 3], 1e-5);
+    assert_near(&sample(&step, 1.0), &[1.0, 2.0, 3.0], 1e-5);
+
+    // in tangent, value and out tangent per key
+    let spline = channel(
+        Property::Weights,
+        Interpolation::CubicSpline,
+        &[0.0, 1.0],
+        &[0.0, 0.0, 2.0, 0.0, 1.0, 0.0],
+    );
+    assert_eq!(spline.width(), 1);
+    assert_near(&sample(&spline, 0.5), &[0.75], 1e-5);
+    assert_near(&sample(&spline, 1.0), &[1.0], 1e-5);
+
+    let quarter_turn = Quat::from_axis_angle(&Vec3f::z_axis(), FRAC_PI_2);
+    let mut values = vec![0.0, 0.0, 0.0, 1.0];
+    values.extend(quarter_turn.coords.iter());
+    let rotation = channel(
+        Property::Rotation,
+        Interpolation::Linear,
+        &[0.0, 1.0],
+        &values,
+    );
+    let eighth_turn = Quat::from_axis_angle(&Vec3f::z_axis(), FRAC_PI_4);
+    assert_near(&sample(&rotation, 0.5), eighth_turn.coords.as_slice(), 1e-5);
+
+    let short = channel(
+        Property::Scale,
+        Interpolation::Linear,
+        &[0.0, 1.0],
+        &[1.0; 5],
+    );
+    assert_eq!(short.check().unwrap(), "5 values for 2 keys");
+    let backwards = channel(
+        Property::Scale,
+        Interpolation::Linear,
+        &[1.0, 0.0],
+        &[1.0; 6],
+    );
+    assert_eq!(backwards.check().unwrap(), "times decrease");
+}
+
+#[test]
+fn clip_sample() {
+    let model = model();
+    let weights = channel(
+        Property::Weights,
+        Interpolation::Linear,
+        &[0.0, 2.0],
+        &[0.0, 1.0, 1.0, 0.0],
+    );
+    let clip = Clip::new(
+        "clip".to_string(),
+        vec![slide(0.0).channels[0].clone(), weights],
+    );
+    assert_eq!(clip.duration, 2.0);
+
+    let mut pose = Pose::rest(&model);
+    clip.sample(0.5, &mut pose);
+    // the rest translation is replaced, not added to
+    assert_near(
+        pose.transforms[0].translation.as_slice(),
+        &[0.5, 0.0, 0.0],
+        1e-5,
+    );
+    assert_eq!(pose.transforms[0].scale, Vec3f::new(1.0, 1.0, 1.0));
+    assert_near(&pose.weights[0], &[0.25, 0.75], 1e-5);
+}
+
+#[test]
+fn playback() {
+    let model = model();
+    let clip = slide(0.0);
+    let mut animator = Animator::new(&model, vec![0]);
+    let x = |animator: &Animator| animator.pose().transforms[0].translation.x;
+
+    animator.update(0.25);
+    // nothing playing leaves the rest pose
+    assert_eq!(animator.pose().transforms[0].translation.y, 1.0);
+
+    animator.play(&clip);
+    animator.update(0.25);
+    assert!((x(&animator) - 0.25).abs() < 1e-5);
+    // looping wraps past the end
+    animator.update(1.0);
+    assert!((x(&animator) - 0.25).abs() < 1e-5);
+
+    let track = animator.play(&clip);
+    track.speed = -2.0;
+    track.looping = false;
+    animator.update(0.25);
+    assert_eq!(x(&animator), 0.0);
+    assert!(animator.track.as_ref().unwrap().finished());
+
+    let track = animator.play(&clip);
+    track.speed = 2.0;
+    track.looping = false;
+    animator.update(0.25);
+    assert!((x(&animator) - 0.5).abs() < 1e-5);
+    animator.update(1.0);
+    assert_eq!(x(&animator), 1.0);
+    assert!(animator.track.as_ref().unwrap().finished());
+}
+
+#[test]
+fn cross_fade() {
+    let model = model();
+    let mut animator = Animator::new(&model, vec![0]);
+    animator.play(&slide(0.0)).speed = 0.0;
+    animator.cross_fade(&slide(10.0), 1.0).speed = 0.0;
+    let x = |animator: &Animator| animator.pose().transforms[0].translation.x;
+
+    animator.update(0.25);
+    assert!((x(&animator) - 2.5).abs() < 1e-4);
+    animator.update(0.5);
+    assert!((x(&animator) - 7.5).abs() < 1e-4);
+    // the old clip is dropped once faded out
+    animator.update(0.5);
+    assert_eq!(x(&animator), 10.0);
+}
+
+// A node spinning a quarter turn over a second while stepping up 2
+fn gltf() -> String {
+    let times = [0f32, 1.0];
+    let quarter_turn = Quat::from_axis_angle(&Vec3f::z_axis(), FRAC_PI_2);
+    let rotations = [[0.0, 0.0, 0.0, 1.0], quarter_turn.coords.into()];
+    let translations = [[0f32, 0.0, 0.0], [0.0, 2.0, 0.0]];
+    let mut bin = bytemuck::cast_slice::<_, u8>(&times).to_vec();
+    bin.extend_from_slice(bytemuck::cast_slice(&rotations));
+    bin.extend_from_slice(bytemuck::cast_slice(&translations));
+    let base64 = base64::engine::general_purpose::STANDARD.encode(&bin);
+    format!(
+        r#"{{
+  "asset": {{"version": "2.0"}},
+  "nodes": [{{"name": "spinner", "scale": [2.0, 2.0, 2.0]}}],
+  "scenes": [{{"nodes": [0]}}],
+  "animations": [{{
+    "name": "spin",
+    "samplers": [
+      {{"input": 0, "output": 1}},
+      {{"input": 0, "output": 2, "interpolation": "STEP"}}
+    ],
+    "channels": [
+      {{"sampler": 0, "target": {{"node": 0, "path": "rotation"}}}},
+      {{"sampler": 1, "target": {{"node": 0, "path": "translation"}}}}
+    ]
+  }}],
+  "buffers": [{{"byteLength": {len}, "uri": "data:application/octet-stream;base64,{base64}"}}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 8}},
+    {{"buffer": 0, "byteOffset": 8, "byteLength": 32}},
+    {{"buffer": 0, "byteOffset": 40, "byteLength": 24}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5126, "count": 2, "type": "SCALAR",
+      "min": [0.0], "max": [1.0]}},
+    {{"bufferView": 1, "componentType": 5126, "count": 2, "type": "VEC4"}},
+    {{"bufferView": 2, "componentType": 5126, "count": 2, "type": "VEC3"}}
+  ]
+}}"#,
+        len = bin.len(),
+    )
+}
+
+#[test]
+fn scene_nodes() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let defaults = material::Defaults::new(&graphics);
+    let json = gltf();
+    let src = GltfSrc::Slice(json.as_bytes());
+    let model = gltf_loader::model_from_source(&graphics, &defaults, src).unwrap();
+    let clip = model.find_animation("spin").unwrap();
+    assert_eq!(clip.channels.len(), 2);
+    assert_eq!(clip.duration, 1.0);
+
+    let mut scene = Scene::new(&graphics);
+    let nodes = scene.instantiate_model(&graphics, &model);
+    let mut animator = Animator::new(&model, nodes.clone());
+    animator.play(clip);
+    animator.update(0.5);
+    animator.apply(&mut scene);
+    scene.update(&graphics);
+    // the rest scale stays under the animated rotation
+    let expected = Mat4::from_axis_angle(&Vec3f::z_axis(), FRAC_PI_4) * Mat4::new_scaling(2.0);
+    assert_near(
+        scene.node_tsf(nodes[0]).as_slice(),
+        expected.as_slice(),
+        1e-5,
+    );
+
+    let prepared = gltf_loader::prepare(GltfSrc::Slice(json.as_bytes()), &LoadOptions::default());
+    let bytes = cooked::cook(&prepared.unwrap(), &CookOptions::default()).unwrap();
+    let cooked = cooked::load(&graphics, &defaults, "test.mgc", bytes.into()).unwrap();
+    let cooked_clip = cooked.find_animation("spin").unwrap();
+    assert_eq!(cooked_clip.duration, clip.duration);
+    for (channel, expected) in cooked_clip.channels.iter().zip(&clip.channels) {
+        assert_eq!(channel.node, expected.node);
+        assert_eq!(channel.property, expected.property);
+        assert_eq!(channel.interpolation, expected.interpolation);
+        assert_eq!(channel.times, expected.times);
+        assert_eq!(channel.values, expected.values);
+    }
+}
@@ -0,0 +1,134 @@
+// Broken assets surface as AssetError instead of panicking
+
+mod common;
+
+use mg_core::*;
+use mg_render::{
+    gltf_loader::{self, GltfSrc},
+    graphics::Graphics,
+    material,
+};
+
+fn load(json: &str) -> Result<mg_render::model::Model> {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    common::load(&graphics, json)
+}
+
+fn asset_error(res: Result<mg_render::model::Model>) -> AssetError {
+    match res {
+        Ok(_) => panic!("expected an asset error"),
+        Err(err) => err
+            .downcast::<AssetError>()
+            .expect("error should be an AssetError"),
+    }
+}
+
+#[test]
+fn missing_file() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let defaults = material::Defaults::new(&graphics);
+    let res = gltf_loader::model_from_source(&graphics, &defaults, GltfSrc::Path("missing.gltf"));
+    assert!(
+        matches!(asset_error(res), AssetError::MissingFile { path, .. } if path == "missing.gltf")
+    );
+}
+
+#[test]
+fn invalid_gltf() {
+    let err = asset_error(load("{ not json"));
+    assert!(matches!(err, AssetError::InvalidGltf { .. }));
+}
+
+#[test]
+fn invalid_data_uri() {
+    let json = r#"{
+        "asset": { "version": "2.0" },
+        "buffers": [{ "byteLength": 4, "uri": "data:application/octet-stream;base64,!!!!" }]
+    }"#;
+    let err = asset_error(load(json));
+    assert!(matches!(err, AssetError::InvalidUri { .. }));
+}
+
+#[test]
+fn buffer_view_out_of_range() {
+    // 4 byte buffer with a 12 byte view
+    let json = r#"{
+        "asset": { "version": "2.0" },
+        "buffers": [{ "byteLength": 4, "uri": "data:application/octet-stream;base64,AAAAAA==" }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 12 }],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3",
+              "min": [0, 0, 0], "max": [0, 0, 0] },
+            { "bufferView": 0, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }] }]
+    }"#;
+    let err = asset_error(load(json));
+    assert!(matches!(
+        err,
+        AssetError::BufferViewOutOfRange { view: 0, .. }
+    ));
+}
+
+#[test]
+fn missing_indices() {
+    let json = r#"{
+        "asset": { "version": "2.0" },
+        "buffers": [{ "byteLength": 12, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAA" }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 12 }],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3",
+              "min": [0, 0, 0], "max": [0, 0, 0] }
+        ],
+        "meshes": [{ "name": "unindexed", "primitives": [{ "attributes": { "POSITION": 0 } }] }]
+    }"#;
+    let err = asset_error(load(json));
+    assert!(matches!(err, AssetError::UnsupportedAccessor { .. }));
+    assert!(err.to_string().contains("unindexed"));
+}
+
+// Three zeroed vertices with a uv accessor of uv_amt elements, drawn with
+// index_amt indices in mode
+fn primitive(mode: u32, index_amt: usize, uv_amt: usize) -> String {
+    format!(
+        r#"{{
+        "asset": {{ "version": "2.0" }},
+        "buffers": [{{ "byteLength": 60, "uri": "data:application/octet-stream;base64,{zeros}" }}],
+        "bufferViews": [
+            {{ "buffer": 0, "byteLength": 36 }},
+            {{ "buffer": 0, "byteOffset": 36, "byteLength": 8 }},
+            {{ "buffer": 0, "byteOffset": 44, "byteLength": 16 }}
+        ],
+        "accessors": [
+            {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+              "min": [0, 0, 0], "max": [0, 0, 0] }},
+            {{ "bufferView": 1, "componentType": 5123, "count": {index_amt}, "type": "SCALAR" }},
+            {{ "bufferView": 2, "componentType": 5126, "count": {uv_amt}, "type": "VEC2" }}
+        ],
+        "meshes": [{{ "name": "broken", "primitives": [{{
+            "attributes": {{ "POSITION": 0, "TEXCOORD_0": 2 }}, "indices": 1, "mode": {mode}
+        }}] }}]
+    }}"#,
+        zeros = "A".repeat(80),
+    )
+}
+
+#[test]
+fn non_triangle_mode() {
+    // lines
+    let err = asset_error(load(&primitive(1, 2, 3)));
+    assert!(matches!(err, AssetError::UnsupportedAccessor { .. }));
+    assert!(err.to_string().contains("broken"));
+}
+
+#[test]
+fn partial_triangle() {
+    let err = asset_error(load(&primitive(4, 4, 3)));
+    assert!(matches!(err, AssetError::UnsupportedAccessor { accessor, .. } if accessor == "1"));
+}
+
+#[test]
+fn short_attribute() {
+    let err = asset_error(load(&primitive(4, 3, 2)));
+    assert!(matches!(err, AssetError::UnsupportedAccessor { accessor, .. } if accessor == "2"));
+}
@@ -0,0 +1,303 @@
+mod common;
+
+use base64::Engine;
+use common::solid_png;
+use mg_core::*;
+use mg_render::{
+    assets::{AssetCounts, AssetServer, LoadState, Loading, Reloaded},
+    gltf_loader::LoadOptions,
+    graphics::Graphics,
+    model::Model,
+    scene::Scene,
+    texture::TextureOptions,
+};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+// Triangle with tex.png as both its color and normal map
+fn triangle_gltf() -> String {
+    let positions: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+    let uvs: [[f32; 2]; 3] = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+    let mut bin = bytemuck::cast_slice::<_, u8>(&positions).to_vec();
+    bin.extend_from_slice(bytemuck::cast_slice(&uvs));
+    bin.extend_from_slice(bytemuck::cast_slice(&[0u16, 1, 2, 0]));
+    format!(
+        r#"{{
+  "asset": {{"version": "2.0"}},
+  "nodes": [{{"mesh": 0}}],
+  "scenes": [{{"nodes": [0]}}],
+  "meshes": [{{"primitives": [
+    {{"attributes": {{"POSITION": 0, "TEXCOORD_0": 1}}, "indices": 2, "material": 0}}
+  ]}}],
+  "materials": [{{
+    "pbrMetallicRoughness": {{"baseColorTexture": {{"index": 0}}}},
+    "normalTexture": {{"index": 0}}
+  }}],
+  "textures": [{{"source": 0}}],
+  "images": [{{"uri": "tex.png"}}],
+  "buffers": [{{"byteLength": {len}, "uri": "data:application/octet-stream;base64,{bin}"}}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 36}},
+    {{"buffer": 0, "byteOffset": 36, "byteLength": 24}},
+    {{"buffer": 0, "byteOffset": 60, "byteLength": 6}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+      "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]}},
+    {{"bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2"}},
+    {{"bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR"}}
+  ]
+}}"#,
+        len = bin.len(),
+        bin = base64::engine::general_purpose::STANDARD.encode(&bin),
+    )
+}
+
+// Two packages with their own copy of the same texture
+fn packages(test: &str) -> [PathBuf; 2] {
+    let png = solid_png(2, [255, 0, 0, 255]);
+    ["a", "b"].map(|package| {
+        let dir = std::env::temp_dir().join(format!("mg_assets_{}_{}", test, package));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tex.png"), &png).unwrap();
+        fs::write(dir.join("triangle.gltf"), triangle_gltf()).unwrap();
+        dir
+    })
+}
+
+#[test]
+fn shared_handles() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let assets = AssetServer::new(&graphics);
+    let [a, b] = packages("shared_handles");
+    let a_path = a.join("triangle.gltf");
+    let b_path = b.join("triangle.gltf");
+
+    let model = assets
+        .load_model(&graphics, a_path.to_str().unwrap())
+        .unwrap();
+    // the same path is only loaded once
+    let again = assets
+        .load_model(
+            &graphics,
+            a.join("../mg_assets_shared_handles_a/triangle.gltf")
+                .to_str()
+                .unwrap(),
+        )
+        .unwrap();
+    assert!(model == again);
+    assert_eq!(model.ref_count(), 2);
+
+    // a copy of the package shares textures and materials
+    let other = assets
+        .load_model(&graphics, b_path.to_str().unwrap())
+        .unwrap();
+    assert!(model != other);
+    assert!(Arc::ptr_eq(
+        &model.meshes[0].material,
+        &other.meshes[0].material
+    ));
+    let texture = assets
+        .load_texture(
+            &graphics,
+            b.join("tex.png").to_str().unwrap(),
+            &TextureOptions::default(),
+        )
+        .unwrap();
+    assert!(Arc::ptr_eq(
+        &texture.arc(),
+        &model.meshes[0].material.bindings.albedo_tx
+    ));
+    let mesh = assets.mesh(model.meshes[0].clone());
+    assert!(mesh == assets.mesh(model.meshes[0].clone()));
+    assert_eq!(
+        assets.counts(),
+        AssetCounts {
+            models: 2,
+            textures: 1,
+            materials: 1,
+            meshes: 1,
+        }
+    );
+}
+
+#[test]
+fn last_handle_frees() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let assets = AssetServer::new(&graphics);
+    let [a, _] = packages("last_handle_frees");
+    let path = a.join("triangle.gltf");
+    let model = assets
+        .load_model(&graphics, path.to_str().unwrap())
+        .unwrap();
+    let material = Arc::downgrade(&model.meshes[0].material);
+    assert_eq!(assets.counts().materials, 1);
+
+    drop(model);
+    assert_eq!(assets.counts(), AssetCounts::default());
+    assert!(material.upgrade().is_none());
+    assert!(graphics.textures.is_empty());
+
+    // freed assets load again
+    let model = assets
+        .load_model(&graphics, path.to_str().unwrap())
+        .unwrap();
+    assert_eq!(model.ref_count(), 1);
+    assert_eq!(assets.counts().models, 1);
+}
+
+// Updates until the load is done, recording its progress each update
+fn wait(assets: &AssetServer, graphics: &Graphics, loading: &Loading<Model>) -> Vec<f32> {
+    let start = Instant::now();
+    let mut progress = vec![];
+    while !loading.is_done() {
+        assert!(start.elapsed() < Duration::from_secs(10), "load timed out");
+        assets.update_with_budget(graphics, 1);
+        progress.push(loading.progress());
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    progress
+}
+
+#[test]
+fn background_load() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let assets = AssetServer::new(&graphics);
+    let [a, _] = packages("background_load");
+    let path = a.join("triangle.gltf");
+    let path = path.to_str().unwrap();
+    let loading = assets.load_model_async(path, &LoadOptions::default());
+    assets.load_model_async(path, &LoadOptions::default());
+    assert_eq!(assets.pending_loads(), 1);
+
+    let mut progress = wait(&assets, &graphics, &loading);
+    // a texture per update for both color spaces, then the model
+    progress.retain(|&p| p > 0.0);
+    progress.dedup();
+    assert_eq!(progress, [1.0 / 3.0, 2.0 / 3.0, 1.0]);
+    assert_eq!(loading.state(), LoadState::Ready);
+    assert_eq!(assets.pending_loads(), 0);
+    let model = loading.handle().unwrap();
+    assert!(model == assets.load_model(&graphics, path).unwrap());
+
+    // loaded models are ready straight away
+    let loaded = assets.load_model_async(path, &LoadOptions::default());
+    assert_eq!(loaded.state(), LoadState::Ready);
+    assert!(loaded.handle().unwrap() == model);
+}
+
+#[test]
+fn background_load_failure() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let assets = AssetServer::new(&graphics);
+    let loading = assets.load_model_async("missing.gltf", &LoadOptions::default());
+    wait(&assets, &graphics, &loading);
+    match loading.state() {
+        LoadState::Failed(reason) => assert!(reason.contains("missing.gltf"), "{}", reason),
+        state => panic!("expected a failure, got {:?}", state),
+    }
+    assert!(loading.handle().is_none());
+}
+
+#[test]
+fn mounted_package() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let assets = AssetServer::new(&graphics);
+    let [a, _] = packages("mounted_package");
+    let bundle = ["triangle.gltf", "tex.png"]
+        .into_iter()
+        .fold(vfs::Memory::default(), |bundle, name| {
+            bundle.with(name, fs::read(a.join(name)).unwrap())
+        });
+    vfs::VFS.mount("mounted_package", bundle);
+
+    let model = assets
+        .load_model(&graphics, "mounted_package/triangle.gltf")
+        .unwrap();
+    let texture = assets
+        .load_texture(
+            &graphics,
+            "mounted_package/./tex.png",
+            &TextureOptions::default(),
+        )
+        .unwrap();
+    assert!(Arc::ptr_eq(
+        &texture.arc(),
+        &model.meshes[0].material.bindings.albedo_tx
+    ));
+    assert!(assets
+        .load_model(&graphics, "mounted_package/missing.gltf")
+        .is_err());
+}
+
+#[test]
+fn reload() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let assets = AssetServer::new(&graphics);
+    let [a, _] = packages("reload");
+    let path = a.join("triangle.gltf");
+    let path = path.to_str().unwrap();
+    let tex = a.join("tex.png");
+    let model = assets.load_model(&graphics, path).unwrap();
+    assert!(assets
+        .source_files()
+        .contains(&vfs::normalize(tex.to_str().unwrap())));
+    let mut scene = Scene::new(&graphics);
+    scene.instantiate_model(&graphics, &model);
+
+    fs::write(&tex, solid_png(4, [0, 255, 0, 255])).unwrap();
+    let reloaded = assets.reload(&graphics, tex.to_str().unwrap());
+    let [Reloaded::Model { old, new }] = &reloaded[..] else {
+        panic!("expected the model to reload");
+    };
+    assert!(*old == model && *new != model);
+    let albedo = &new.meshes[0].material.bindings.albedo_tx;
+    assert_eq!(albedo.texture.width(), 4);
+    assert_eq!(scene.swap_model(&graphics, old, new), 1);
+    assert!(Arc::ptr_eq(
+        &scene.meshes[0].material,
+        &new.meshes[0].material
+    ));
+    assert!(assets.load_model(&graphics, path).unwrap() == *new);
+
+    // a broken file keeps the loaded model
+    fs::write(path, "{").unwrap();
+    assert!(assets.reload(&graphics, path).is_empty());
+    assert!(assets.load_model(&graphics, path).unwrap() == *new);
+}
+
+#[test]
+fn texture_reload() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let assets = AssetServer::new(&graphics);
+    let [a, _] = packages("texture_reload");
+    let model = assets
+        .load_model(&graphics, a.join("triangle.gltf").to_str().unwrap())
+        .unwrap();
+    let mut scene = Scene::new(&graphics);
+    scene.instantiate_model(&graphics, &model);
+    let path = a.join("other.png");
+    fs::write(&path, solid_png(2, [0, 0, 255, 255])).unwrap();
+    let path = path.to_str().unwrap();
+    let texture = assets
+        .load_texture(&graphics, path, &TextureOptions::default())
+        .unwrap();
+
+    fs::write(path, solid_png(4, [0, 0, 255, 255])).unwrap();
+    let reloaded = assets.reload(&graphics, path);
+    let [Reloaded::Texture { old, new }] = &reloaded[..] else {
+        panic!("expected the texture to reload");
+    };
+    assert!(*old == texture);
+    assert_eq!(new.texture.width(), 4);
+
+    // the scene's materials can take a replacement for any texture
+    let albedo = model.meshes[0].material.bindings.albedo_tx.clone();
+    assert_eq!(scene.swap_texture(&graphics, &albedo, &new.arc()), 1);
+    let bindings = &scene.meshes[0].material.bindings;
+    assert!(Arc::ptr_eq(&bindings.albedo_tx, &new.arc()));
+    assert!(Arc::ptr_eq(
+        &bindings.normal_tx,
+        &model.meshes[0].material.bindings.normal_tx
+    ));
+}
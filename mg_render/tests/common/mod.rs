@@ -0,0 +1,41 @@
+// Helpers shared by the integration tests, each uses only some of them
+#![allow(dead_code)]
+
+use mg_core::*;
+use mg_render::{
+    gltf_loader::{self, GltfSrc},
+    graphics::Graphics,
+    material,
+    model::Model,
+};
+
+// A glTF document with default materials
+pub fn load(graphics: &Graphics, json: &str) -> Result<Model> {
+    let defaults = material::Defaults::new(graphics);
+    gltf_loader::model_from_source(graphics, &defaults, GltfSrc::Slice(json.as_bytes()))
+}
+
+pub fn png(image: image::RgbaImage) -> Vec<u8> {
+    let mut png = std::io::Cursor::new(vec![]);
+    image
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+    png.into_inner()
+}
+
+// A size by size png of one color
+pub fn solid_png(size: u32, rgba: [u8; 4]) -> Vec<u8> {
+    png(image::RgbaImage::from_pixel(size, size, image::Rgba(rgba)))
+}
+
+// Components of vectors, matrices or slices of them, compared in order
+pub fn assert_near<'a, 'b>(
+    a: impl IntoIterator<Item = &'a f32>,
+    b: impl IntoIterator<Item = &'b f32>,
+    epsilon: f32,
+) {
+    let a = a.into_iter().copied().collect::<Vec<_>>();
+    let b = b.into_iter().copied().collect::<Vec<_>>();
+    let near = a.len() == b.len() && a.iter().zip(&b).all(|(a, b)| (a - b).abs() <= epsilon);
+    assert!(near, "{:?} != {:?}", a, b);
+}
@@ -0,0 +1,276 @@
+use base64::Engine;
+use mg_core::*;
+use mg_render::{
+    assets::AssetServer,
+    cooked::{self, CookOptions},
+    gltf_loader::{self, GltfSrc, LoadOptions},
+    graphics::Graphics,
+    material,
+    model::Model,
+};
+
+// Two nodes, the child drawing a textured quad and an untextured triangle
+// from one mesh. The uvs are interleaved so loading decodes them
+fn gltf() -> String {
+    let positions: [[f32; 3]; 4] = [[0.0; 3], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+    let uvs: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+    let mut bin = bytemuck::cast_slice::<_, u8>(&[0u16, 1, 2, 0, 2, 3]).to_vec();
+    bin.extend_from_slice(bytemuck::cast_slice(&positions));
+    for uv in uvs {
+        bin.extend_from_slice(bytemuck::cast_slice(&uv));
+        bin.extend_from_slice(&[0; 8]);
+    }
+    let mut png = vec![];
+    let checker = image::RgbaImage::from_fn(8, 8, |x, y| match (x / 4 + y / 4) % 2 {
+        0 => image::Rgba([250, 40, 40, 255]),
+        _ => image::Rgba([40, 40, 250, 255]),
+    });
+    image::DynamicImage::ImageRgba8(checker)
+        .write_to(
+            &mut std::io::Cursor::new(&mut png),
+            image::ImageOutputFormat::Png,
+        )
+        .unwrap();
+    let base64 = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    format!(
+        r#"{{
+  "asset": {{"version": "2.0"}},
+  "nodes": [
+    {{"name": "root", "children": [1], "translation": [1.0, 2.0, 3.0]}},
+    {{"name": "quad", "mesh": 0, "scale": [2.0, 2.0, 2.0]}}
+  ],
+  "scenes": [{{"nodes": [0]}}],
+  "meshes": [{{"name": "quad", "primitives": [
+    {{"attributes": {{"POSITION": 1, "TEXCOORD_0": 2}}, "indices": 0, "material": 0}},
+    {{"attributes": {{"POSITION": 1}}, "indices": 0, "material": 1}}
+  ]}}],
+  "materials": [
+    {{
+      "name": "checker",
+      "pbrMetallicRoughness": {{
+        "baseColorTexture": {{"index": 0}},
+        "baseColorFactor": [1.0, 0.5, 0.25, 1.0],
+        "roughnessFactor": 0.75
+      }},
+      "alphaMode": "MASK",
+      "doubleSided": true
+    }},
+    {{"name": "plain", "emissiveFactor": [1.0, 1.0, 0.0]}}
+  ],
+  "textures": [{{"source": 0, "sampler": 0}}],
+  "samplers": [{{"magFilter": 9728, "wrapS": 33071, "wrapT": 33648}}],
+  "images": [{{"uri": "data:image/png;base64,{png}"}}],
+  "buffers": [{{"byteLength": {len}, "uri": "data:application/octet-stream;base64,{bin}"}}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 12}},
+    {{"buffer": 0, "byteOffset": 12, "byteLength": 48}},
+    {{"buffer": 0, "byteOffset": 60, "byteLength": 64, "byteStride": 16}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5123, "count": 6, "type": "SCALAR"}},
+    {{"bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC3",
+      "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]}},
+    {{"bufferView": 2, "componentType": 5126, "count": 4, "type": "VEC2"}}
+  ]
+}}"#,
+        png = base64(&png),
+        len = bin.len(),
+        bin = base64(&bin),
+    )
+}
+
+fn cook_json(json: &str, options: &CookOptions) -> Vec<u8> {
+    let prepared = gltf_loader::prepare(GltfSrc::Slice(json.as_bytes()), &LoadOptions::default());
+    cooked::cook(&prepared.unwrap(), options).unwrap()
+}
+
+fn cook(options: &CookOptions) -> Vec<u8> {
+    cook_json(&gltf(), options)
+}
+
+fn bin_slice(model: &Model, mesh: usize, range: std::ops::Range<u64>) -> &[u8] {
+    &model.meshes[mesh].geometry.buffer.bin[range.start as usize..range.end as usize]
+}
+
+#[test]
+fn round_trip() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let defaults = material::Defaults::new(&graphics);
+    let json = gltf();
+    let src = GltfSrc::Slice(json.as_bytes());
+    let gltf = gltf_loader::model_from_source(&graphics, &defaults, src).unwrap();
+    let bytes = cook(&CookOptions::default());
+    assert!(cooked::is_cooked(&bytes));
+    let model = cooked::load(&graphics, &defaults, "test.mgc", bytes.into()).unwrap();
+
+    assert_eq!(model.meshes.len(), gltf.meshes.len());
+    for (i, (mesh, expected)) in model.meshes.iter().zip(&gltf.meshes).enumerate() {
+        assert_eq!(mesh.name, expected.name);
+        let (geometry, expected_geometry) = (&mesh.geometry, &expected.geometry);
+        assert_eq!(geometry.elm_amt, expected_geometry.elm_amt);
+        assert_eq!(geometry.index_format, expected_geometry.index_format);
+        let (ranges, expected_ranges) = (geometry.ranges, expected_geometry.ranges);
+        for (range, expected_range) in [
+            (ranges.index(), expected_ranges.index()),
+            (ranges.vertex(), expected_ranges.vertex()),
+            (ranges.uv(), expected_ranges.uv()),
+            (ranges.normal(), expected_ranges.normal()),
+            (ranges.tangent(), expected_ranges.tangent()),
+        ] {
+            assert_eq!(
+                bin_slice(&model, i, range),
+                bin_slice(&gltf, i, expected_range)
+            );
+        }
+        let (params, expected_params) = (
+            mesh.material.bindings.params,
+            expected.material.bindings.params,
+        );
+        assert_eq!(
+            bytemuck::bytes_of(&params),
+            bytemuck::bytes_of(&expected_params)
+        );
+    }
+    // the interleaved uvs were decoded, only the copy meshes read is kept
+    let bin_len = |model: &Model| model.meshes[0].geometry.buffer.bin.len();
+    assert!(bin_len(&model) < bin_len(&gltf));
+
+    let albedo = &model.meshes[0].material.bindings.albedo_tx.texture;
+    assert_eq!(albedo.format(), wgpu::TextureFormat::Rgba8UnormSrgb);
+    assert_eq!(albedo.mip_level_count(), 4);
+    let plain = &model.meshes[1].material.bindings;
+    assert!(Arc::ptr_eq(
+        &plain.albedo_tx,
+        &defaults.material.bindings.albedo_tx
+    ));
+
+    assert_eq!(model.roots, gltf.roots);
+    assert_eq!(model.nodes.len(), gltf.nodes.len());
+    for (node, expected) in model.nodes.iter().zip(&gltf.nodes) {
+        assert_eq!(node.name, expected.name);
+        assert_eq!(node.tsf, expected.tsf);
+        assert_eq!(node.parent, expected.parent);
+        assert_eq!(node.children, expected.children);
+        assert_eq!(node.meshes, expected.meshes);
+    }
+    assert_eq!(model.world_tsf(1), gltf.world_tsf(1));
+}
+
+#[test]
+fn parents_first() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let defaults = material::Defaults::new(&graphics);
+    // the child is listed first, cooking moves it after its parent
+    let json = gltf()
+        .replace(
+            r#"{"name": "root", "children": [1], "translation": [1.0, 2.0, 3.0]},"#,
+            "",
+        )
+        .replace(
+            r#"{"name": "quad", "mesh": 0, "scale": [2.0, 2.0, 2.0]}"#,
+            r#"{"name": "quad", "mesh": 0, "scale": [2.0, 2.0, 2.0]},
+    {"name": "root", "children": [0], "translation": [1.0, 2.0, 3.0]}"#,
+        )
+        .replace(
+            r#""scenes": [{"nodes": [0]}]"#,
+            r#""scenes": [{"nodes": [1]}]"#,
+        );
+    let bytes = cook_json(&json, &CookOptions::default());
+    let model = cooked::load(&graphics, &defaults, "test.mgc", bytes.into()).unwrap();
+    let names: Vec<_> = model.nodes.iter().map(|node| node.name.as_str()).collect();
+    assert_eq!(names, ["root", "quad"]);
+    assert_eq!(model.roots, [0]);
+    assert_eq!(model.nodes[0].children, [1]);
+    assert_eq!(model.nodes[1].parent, Some(0));
+}
+
+#[test]
+fn compressed() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let defaults = material::Defaults::new(&graphics);
+    let options = CookOptions { compress: true };
+    let bytes = cook(&options);
+    assert!(bytes.len() < cook(&CookOptions::default()).len());
+    let model = cooked::load(&graphics, &defaults, "test.mgc", bytes.into()).unwrap();
+    let albedo = &model.meshes[0].material.bindings.albedo_tx.texture;
+    // decoded back to RGBA8 where the device can't sample BC
+    let bc = wgpu::Features::TEXTURE_COMPRESSION_BC;
+    let expected = match graphics.device.features().contains(bc) {
+        true => wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+        false => wgpu::TextureFormat::Rgba8UnormSrgb,
+    };
+    assert_eq!(albedo.format(), expected);
+    assert_eq!(albedo.mip_level_count(), 4);
+}
+
+#[test]
+fn asset_server() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let assets = AssetServer::new(&graphics);
+    let bytes = cook(&CookOptions::default());
+    let model = assets
+        .load_model_slice(&graphics, &bytes, &LoadOptions::default())
+        .unwrap();
+    assert_eq!(model.meshes.len(), 2);
+
+    let dir = std::env::temp_dir().join("mg_cooked_asset_server");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("quad.{}", cooked::EXTENSION));
+    fs::write(&path, &bytes).unwrap();
+    let path = path.to_str().unwrap();
+    let model = assets.load_model(&graphics, path).unwrap();
+    assert_eq!(model.nodes.len(), 2);
+    assert!(assets.source_files().iter().any(|file| file == path));
+}
+
+#[test]
+fn invalid() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let defaults = material::Defaults::new(&graphics);
+    let bytes = cook(&CookOptions::default());
+    let reason = |bytes: &[u8]| match cooked::load(&graphics, &defaults, "test.mgc", bytes.into())
+        .err()
+        .expect("expected an error")
+        .downcast::<AssetError>()
+    {
+        Ok(AssetError::InvalidCooked { asset, reason }) => {
+            assert_eq!(asset, "test.mgc");
+            reason
+        }
+        other => panic!("expected InvalidCooked, got {:?}", other.err()),
+    };
+    assert_eq!(reason(&bytes[..4]), "not a cooked model");
+    assert_eq!(reason(gltf().as_bytes()), "not a cooked model");
+
+    let mut newer = bytes.clone();
+    newer[8..12].copy_from_slice(&(cooked::VERSION + 1).to_le_bytes());
+    assert_eq!(
+        reason(&newer),
+        format!(
+            "version {}, this build reads {}",
+            cooked::VERSION + 1,
+            cooked::VERSION
+        )
+    );
+
+    // a node claiming a parent after it could loop
+    let span = |bytes: &[u8], at: usize| {
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        (word(at) as usize, word(at + 4) as usize)
+    };
+    let (nodes, _) = span(&bytes, 52);
+    let mut looped = bytes.clone();
+    looped[nodes + 72..nodes + 76].copy_from_slice(&1u32.to_le_bytes());
+    assert_eq!(reason(&looped), "node 0 comes before its parent");
+
+    // vertices bound off their f32 alignment
+    let (meshes, _) = span(&bytes, 44);
+    let (vertex, _) = span(&bytes, meshes + 28);
+    let mut misaligned = bytes.clone();
+    misaligned[meshes + 28..meshes + 32].copy_from_slice(&(vertex as u32 + 2).to_le_bytes());
+    assert_eq!(reason(&misaligned), "mesh \"quad\" ranges aren't aligned");
+
+    // cut short, the tables at the end fall outside the file
+    let truncated = &bytes[..bytes.len() - 4];
+    assert!(reason(truncated).ends_with("out of range"));
+}
@@ -0,0 +1,217 @@
+mod common;
+
+use common::solid_png;
+use mg_core::*;
+use mg_render::{
+    capture, cubemap,
+    graphics::Graphics,
+    texture::{ColorSpace, Texture},
+};
+
+fn hdr(width: u32, height: u32, texel: impl Fn(u32, u32) -> [f32; 3]) -> Vec<u8> {
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| image::Rgb(texel(x, y)))
+        .collect::<Vec<_>>();
+    let mut bytes = vec![];
+    image::codecs::hdr::HdrEncoder::new(&mut bytes)
+        .encode(&pixels, width as usize, height as usize)
+        .unwrap();
+    bytes
+}
+
+#[test]
+fn float_images() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let radiance = hdr(4, 4, |_, _| [0.25, 2.0, 0.0]);
+    let texture = Texture::create_image_texture(&graphics, "radiance", &radiance).unwrap();
+    assert_eq!(texture.texture.format(), wgpu::TextureFormat::Rgba16Float);
+    let captured = capture::capture_texture(&graphics, &texture.texture).unwrap();
+    // values above one survive in the texture, capture clamps them
+    assert_eq!(captured.get_pixel(1, 1).0, [64, 255, 0, 255]);
+
+    let mut exr = vec![];
+    image::DynamicImage::ImageRgba32F(image::Rgba32FImage::from_pixel(
+        4,
+        4,
+        image::Rgba([0.5, 0.0, 4.0, 1.0]),
+    ))
+    .write_to(
+        &mut std::io::Cursor::new(&mut exr),
+        image::ImageOutputFormat::OpenExr,
+    )
+    .unwrap();
+    let texture = Texture::create_image_texture(&graphics, "exr", &exr).unwrap();
+    assert_eq!(texture.texture.format(), wgpu::TextureFormat::Rgba16Float);
+    let captured = capture::capture_texture(&graphics, &texture.texture).unwrap();
+    assert_eq!(captured.get_pixel(0, 0).0, [128, 0, 255, 255]);
+}
+
+#[test]
+fn texture_array() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let layers = [
+        solid_png(2, [255, 0, 0, 255]),
+        solid_png(2, [0, 0, 255, 255]),
+    ];
+    let layers = layers.iter().map(|l| &l[..]).collect::<Vec<_>>();
+    let texture = Texture::create_image_layers(
+        &graphics,
+        "array",
+        &layers,
+        wgpu::TextureViewDimension::D2Array,
+        ColorSpace::Srgb,
+    )
+    .unwrap();
+    assert_eq!(texture.texture.depth_or_array_layers(), 2);
+    let captured = capture::capture_texture_layer(&graphics, &texture.texture, 0, 1).unwrap();
+    assert_eq!(captured.get_pixel(1, 1).0, [0, 0, 255, 255]);
+
+    // cubes need exactly six faces
+    let err = Texture::create_image_layers(
+        &graphics,
+        "cube",
+        &layers,
+        wgpu::TextureViewDimension::Cube,
+        ColorSpace::Srgb,
+    )
+    .err()
+    .unwrap();
+    assert!(matches!(
+        err.downcast_ref::<AssetError>(),
+        Some(AssetError::BadImage { .. })
+    ));
+}
+
+const SAMPLE_CUBE: &str = "
+@group(0) @binding(0) var t_cube: texture_cube<f32>;
+@group(0) @binding(1) var s_cube: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4f {
+  var pos = array(vec2(-1.0, -1.0), vec2(3.0, -1.0), vec2(-1.0, 3.0));
+  return vec4f(pos[i], 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) pos: vec4f) -> @location(0) vec4f {
+  var dirs = array(
+    vec3(1.0, 0.0, 0.0), vec3(-1.0, 0.0, 0.0),
+    vec3(0.0, 1.0, 0.0), vec3(0.0, -1.0, 0.0),
+    vec3(0.0, 0.0, 1.0), vec3(0.0, 0.0, -1.0),
+  );
+  return textureSampleLevel(t_cube, s_cube, dirs[u32(pos.x)], 0.0);
+}
+";
+
+// One texel per cube axis, +x, -x, +y, -y, +z, -z
+fn sample_cube(graphics: &Graphics, cube: &Texture) -> image::RgbaImage {
+    let device = &graphics.device;
+    let format = wgpu::TextureFormat::Rgba8Unorm;
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: 6,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(SAMPLE_CUBE.into()),
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: None,
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(format.into())],
+        }),
+        primitive: Default::default(),
+        depth_stencil: None,
+        multisample: Default::default(),
+        multiview: None,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &pipeline.get_bind_group_layout(0),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&cube.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&graphics.sampler(Default::default())),
+            },
+        ],
+    });
+    let view = target.create_view(&Default::default());
+    let mut encoder = device.create_command_encoder(&Default::default());
+    {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: Default::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+    graphics.queue.submit(Some(encoder.finish()));
+    capture::capture_texture(graphics, &target).unwrap()
+}
+
+#[test]
+fn equirect_to_cube() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    // latitude bands for the poles, longitude bands around the horizon
+    let panorama = hdr(8, 4, |x, y| match (y, x) {
+        (0, _) => [0.0, 1.0, 0.0],
+        (3, _) => [1.0, 0.0, 1.0],
+        (_, 0 | 7) => [0.0, 0.0, 1.0],
+        (_, 1 | 2) => [1.0, 1.0, 0.0],
+        (_, 3 | 4) => [1.0, 0.0, 0.0],
+        _ => [0.0, 1.0, 1.0],
+    });
+    let equirect = Texture::create_image_texture(&graphics, "panorama", &panorama).unwrap();
+    let pipelines = graphics.pipelines.len();
+    let cube = cubemap::equirect_to_cube(&graphics, "cube", &equirect, 8);
+    assert_eq!(cube.texture.depth_or_array_layers(), 6);
+    // the pipeline is built once per format
+    cubemap::equirect_to_cube(&graphics, "cube", &equirect, 8);
+    assert_eq!(graphics.pipelines.len(), pipelines + 1);
+    // gl can't copy cube faces back, sample the cube along each axis instead
+    assert_eq!(
+        sample_cube(&graphics, &cube)
+            .pixels()
+            .map(|p| p.0)
+            .collect::<Vec<_>>(),
+        [
+            [255, 0, 0, 255],
+            [0, 0, 255, 255],
+            [0, 255, 0, 255],
+            [255, 0, 255, 255],
+            [0, 255, 255, 255],
+            [255, 255, 0, 255],
+        ]
+    );
+}
@@ -0,0 +1,927 @@
+// Renders reference scenes headless and compares them against the png
+// images in tests/golden. Run with MG_UPDATE_GOLDEN=1 to rewrite them.
+mod common;
+
+use base64::Engine;
+use common::png;
+use mg_core::*;
+use mg_render::{
+    buffer::Buffer,
+    capture::{self, Attachment},
+    cooked,
+    geometry::{self, Geometry},
+    gltf_loader::{self, GltfSrc, LoadOptions, NormalMode},
+    graphics::Graphics,
+    instance, material,
+    material::Material,
+    mesh::Mesh,
+    model::{Light, LightKind, Model, Node},
+    sampler::SamplerDesc,
+    scene::Scene,
+    texture::Texture,
+    Renderer,
+};
+use std::path::PathBuf;
+use wgpu::util::DeviceExt;
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 256;
+const TOLERANCE: u8 = 8;
+const MAX_MISMATCHED: f32 = 0.005;
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name))
+}
+
+fn check_golden(name: &str, image: &image::RgbaImage) {
+    let path = golden_path(name);
+    if std::env::var("MG_UPDATE_GOLDEN").is_ok() {
+        capture::save_png(image, &path).unwrap();
+        return;
+    }
+    let golden = match image::open(&path) {
+        Ok(golden) => golden.to_rgba8(),
+        Err(err) => panic!(
+            "can't read golden image {:?}: {}, run with MG_UPDATE_GOLDEN=1 to write it",
+            path, err
+        ),
+    };
+    let diff = capture::diff_images(&golden, image, TOLERANCE).unwrap();
+    if diff.mismatched_fraction() > MAX_MISMATCHED {
+        let out = std::env::temp_dir().join(format!("{}.actual.png", name));
+        capture::save_png(image, &out).unwrap();
+        panic!(
+            "{} differs from golden image {:?}, wrote {:?}",
+            name, diff, out
+        );
+    }
+}
+
+fn render(graphics: &Graphics, renderer: &mut Renderer, scene: &mut Scene, name: &str) {
+    scene.update(graphics);
+    scene.camera.update(graphics);
+    let frame = graphics.frame().unwrap();
+    renderer.render(graphics, scene, &frame.view);
+    frame.present();
+
+    check_golden(name, &capture::capture_frame(graphics).unwrap());
+    for attachment in [Attachment::Albedo, Attachment::Position, Attachment::Normal] {
+        let image = capture::capture_attachment(graphics, renderer.g_buffer(), attachment);
+        check_golden(&format!("{}_{}", name, attachment.name()), &image.unwrap());
+    }
+}
+
+const QUAD_INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+const QUAD_VERTICES: [[f32; 3]; 4] = [
+    [-5.0, -5.0, 0.0],
+    [5.0, -5.0, 0.0],
+    [5.0, 5.0, 0.0],
+    [-5.0, 5.0, 0.0],
+];
+const QUAD_UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+// keeps the magnified checkers crisp
+const NEAREST: &str = r#"{"magFilter": 9728, "minFilter": 9728}"#;
+
+// indices, vertices and uvs packed back to back
+fn quad_bin() -> Vec<u8> {
+    let mut bin = bytemuck::cast_slice::<_, u8>(&QUAD_INDICES).to_vec();
+    bin.extend_from_slice(bytemuck::cast_slice(&QUAD_VERTICES));
+    bin.extend_from_slice(bytemuck::cast_slice(&QUAD_UVS));
+    bin
+}
+
+fn checker_png() -> Vec<u8> {
+    png(image::RgbaImage::from_fn(8, 8, |x, y| {
+        if (x + y) % 2 == 0 {
+            image::Rgba([230, 60, 40, 255])
+        } else {
+            image::Rgba([40, 120, 230, 255])
+        }
+    }))
+}
+
+// Checkered quad built in code so it doesn't depend on lfs assets
+fn quad_mesh(graphics: &Graphics, defaults: &material::Defaults) -> Mesh {
+    let mut bin = quad_bin();
+    bin.extend_from_slice(bytemuck::cast_slice(&[[0f32, 0.0, 1.0]; 4]));
+    bin.extend_from_slice(bytemuck::cast_slice(&[[1f32, 0.0, 0.0, 1.0]; 4]));
+    let gpu_buffer = graphics
+        .device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("quad buffer"),
+            contents: &bin,
+            usage: wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::INDEX
+                | wgpu::BufferUsages::STORAGE,
+        });
+    let geometry = Arc::new(Geometry {
+        elm_amt: QUAD_INDICES.len() as u32,
+        ranges: geometry::Ranges {
+            index: [0, 12],
+            vertex: [12, 60],
+            uv: [60, 92],
+            normal: [92, 140],
+            tangent: [140, 204],
+            ..Default::default()
+        },
+        index_format: wgpu::IndexFormat::Uint16,
+        buffer: Arc::new(Buffer {
+            bin: bin.into(),
+            gpu_buffer,
+        }),
+        g_pipeline: None,
+        ray_pipeline: None,
+    });
+
+    let albedo_tx =
+        Arc::new(Texture::create_image_texture(graphics, "checker", &checker_png()).unwrap());
+    let albedo_sampler = graphics.sampler(SamplerDesc {
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+    let material = Arc::new(Material::new(
+        graphics,
+        Some("checker"),
+        material::Bindings {
+            albedo_tx,
+            albedo_sampler,
+            ..defaults.material.bindings.clone()
+        },
+    ));
+    Mesh {
+        name: "quad".to_string(),
+        geometry,
+        material,
+        weights: vec![],
+    }
+}
+
+// The same quad as glTF json, buffer and image are either data uris or,
+// for glb, views into the binary chunk
+fn quad_gltf(glb: bool) -> (String, Vec<u8>) {
+    let mut bin = quad_bin();
+    let png = checker_png();
+    let base64 = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    let (buffer, image) = if glb {
+        bin.extend_from_slice(&png);
+        (
+            format!(r#"{{"byteLength": {}}}"#, bin.len()),
+            r#"{"bufferView": 3, "mimeType": "image/png"}"#.to_string(),
+        )
+    } else {
+        (
+            format!(
+                r#"{{"byteLength": {}, "uri": "data:application/octet-stream;base64,{}"}}"#,
+                bin.len(),
+                base64(&bin)
+            ),
+            format!(r#"{{"uri": "data:image/png;base64,{}"}}"#, base64(&png)),
+        )
+    };
+    let json = format!(
+        r#"{{
+  "asset": {{"version": "2.0"}},
+  "scene": 0,
+  "scenes": [{{"nodes": [0]}}],
+  "nodes": [{{"mesh": 0}}],
+  "meshes": [{{"primitives": [{{
+    "attributes": {{"POSITION": 1, "TEXCOORD_0": 2}},
+    "indices": 0,
+    "material": 0
+  }}]}}],
+  "materials": [{{"pbrMetallicRoughness": {{"baseColorTexture": {{"index": 0}}}}}}],
+  "textures": [{{"source": 0, "sampler": 0}}],
+  "samplers": [{NEAREST}],
+  "images": [{image}],
+  "buffers": [{buffer}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 12}},
+    {{"buffer": 0, "byteOffset": 12, "byteLength": 48}},
+    {{"buffer": 0, "byteOffset": 60, "byteLength": 32}},
+    {{"buffer": 0, "byteOffset": 92, "byteLength": {png_len}}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5123, "count": 6, "type": "SCALAR"}},
+    {{"bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC3",
+      "min": [-5.0, -5.0, 0.0], "max": [5.0, 5.0, 0.0]}},
+    {{"bufferView": 2, "componentType": 5126, "count": 4, "type": "VEC2"}}
+  ]
+}}"#,
+        png_len = png.len()
+    );
+    (json, bin)
+}
+
+fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+    let pad = |len: usize| len.next_multiple_of(4);
+    let mut glb = vec![];
+    let total = 12 + 8 + pad(json.len()) + 8 + pad(bin.len());
+    glb.extend_from_slice(b"glTF");
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(total as u32).to_le_bytes());
+    glb.extend_from_slice(&(pad(json.len()) as u32).to_le_bytes());
+    glb.extend_from_slice(b"JSON");
+    glb.extend_from_slice(json.as_bytes());
+    glb.resize(glb.len() + pad(json.len()) - json.len(), b' ');
+    glb.extend_from_slice(&(pad(bin.len()) as u32).to_le_bytes());
+    glb.extend_from_slice(b"BIN\0");
+    glb.extend_from_slice(bin);
+    glb.resize(total, 0);
+    glb
+}
+
+#[test]
+fn quad() {
+    let graphics = pollster::block_on(Graphics::headless(WIDTH, HEIGHT));
+    let mut renderer = Renderer::new(&graphics);
+    let mut scene = Scene::new(&graphics);
+    let defaults = material::Defaults::new(&graphics);
+    scene.instantiate_mesh(
+        &graphics,
+        quad_mesh(&graphics, &defaults),
+        instance::Params {
+            amt: 1,
+            bin: None,
+            buffer: None,
+            range: None,
+        },
+    );
+    scene.camera.eye = Point3f::new(0.0, 0.0, 20.0);
+    scene.camera.target = Point3f::new(0.0, 0.0, 0.0);
+    render(&graphics, &mut renderer, &mut scene, "quad");
+}
+
+#[test]
+fn quad_embedded() {
+    let graphics = pollster::block_on(Graphics::headless(WIDTH, HEIGHT));
+    let defaults = material::Defaults::new(&graphics);
+    let (json, _) = quad_gltf(false);
+    let (glb_json, bin) = quad_gltf(true);
+    let glb = glb(&glb_json, &bin);
+    for src in [GltfSrc::Slice(json.as_bytes()), GltfSrc::Slice(&glb)] {
+        let mut renderer = Renderer::new(&graphics);
+        let mut scene = Scene::new(&graphics);
+        let model = gltf_loader::model_from_source(&graphics, &defaults, src).unwrap();
+        scene.instantiate_model(&graphics, &model);
+        scene.camera.eye = Point3f::new(0.0, 0.0, 20.0);
+        scene.camera.target = Point3f::new(0.0, 0.0, 0.0);
+        render(&graphics, &mut renderer, &mut scene, "quad");
+    }
+}
+
+// The quad with accessors the renderer can't bind directly
+fn quad_layout_gltf(interleaved: bool) -> String {
+    let mut bin = vec![];
+    let (views, accessors) = if interleaved {
+        // u8 indices behind an accessor offset, then positions and uvs interleaved
+        bin.extend_from_slice(&[0, 0]);
+        bin.extend(QUAD_INDICES.iter().map(|&i| i as u8));
+        for (v, uv) in QUAD_VERTICES.iter().zip(QUAD_UVS.iter()) {
+            bin.extend_from_slice(bytemuck::cast_slice(v));
+            bin.extend_from_slice(bytemuck::cast_slice(uv));
+        }
+        (
+            r#"{"buffer": 0, "byteOffset": 0, "byteLength": 8},
+    {"buffer": 0, "byteOffset": 8, "byteLength": 80, "byteStride": 20}"#,
+            r#"{"bufferView": 0, "byteOffset": 2, "componentType": 5121, "count": 6, "type": "SCALAR"},
+    {"bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC3",
+      "min": [-5.0, -5.0, 0.0], "max": [5.0, 5.0, 0.0]},
+    {"bufferView": 1, "byteOffset": 12, "componentType": 5126, "count": 4, "type": "VEC2"}"#,
+        )
+    } else {
+        // u32 indices and normalized u16 uvs
+        bin.extend(QUAD_INDICES.iter().flat_map(|&i| (i as u32).to_le_bytes()));
+        bin.extend_from_slice(bytemuck::cast_slice(&QUAD_VERTICES));
+        for uv in QUAD_UVS.iter().flatten() {
+            bin.extend_from_slice(&((uv * 65535.0) as u16).to_le_bytes());
+        }
+        (
+            r#"{"buffer": 0, "byteOffset": 0, "byteLength": 24},
+    {"buffer": 0, "byteOffset": 24, "byteLength": 48},
+    {"buffer": 0, "byteOffset": 72, "byteLength": 16}"#,
+            r#"{"bufferView": 0, "componentType": 5125, "count": 6, "type": "SCALAR"},
+    {"bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC3",
+      "min": [-5.0, -5.0, 0.0], "max": [5.0, 5.0, 0.0]},
+    {"bufferView": 2, "componentType": 5123, "normalized": true, "count": 4, "type": "VEC2"}"#,
+        )
+    };
+    let base64 = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    format!(
+        r#"{{
+  "asset": {{"version": "2.0"}},
+  "nodes": [{{"mesh": 0}}],
+  "scenes": [{{"nodes": [0]}}],
+  "meshes": [{{"primitives": [{{
+    "attributes": {{"POSITION": 1, "TEXCOORD_0": 2}},
+    "indices": 0,
+    "material": 0
+  }}]}}],
+  "materials": [{{"pbrMetallicRoughness": {{"baseColorTexture": {{"index": 0}}}}}}],
+  "textures": [{{"source": 0, "sampler": 0}}],
+  "samplers": [{NEAREST}],
+  "images": [{{"uri": "data:image/png;base64,{png}"}}],
+  "buffers": [{{"byteLength": {len}, "uri": "data:application/octet-stream;base64,{bin}"}}],
+  "bufferViews": [
+    {views}
+  ],
+  "accessors": [
+    {accessors}
+  ]
+}}"#,
+        png = base64(&checker_png()),
+        len = bin.len(),
+        bin = base64(&bin),
+    )
+}
+
+#[test]
+fn quad_layouts() {
+    let graphics = pollster::block_on(Graphics::headless(WIDTH, HEIGHT));
+    let defaults = material::Defaults::new(&graphics);
+    for (interleaved, index_format) in [
+        (true, wgpu::IndexFormat::Uint16),
+        (false, wgpu::IndexFormat::Uint32),
+    ] {
+        let json = quad_layout_gltf(interleaved);
+        let src = GltfSrc::Slice(json.as_bytes());
+        // flat normals would unweld the quad and rewrite its indices
+        let options = LoadOptions {
+            normals: NormalMode::Smooth,
+        };
+        let model =
+            gltf_loader::model_from_source_with(&graphics, &defaults, src, &options).unwrap();
+        assert_eq!(model.meshes[0].geometry.index_format, index_format);
+        let mut renderer = Renderer::new(&graphics);
+        let mut scene = Scene::new(&graphics);
+        scene.instantiate_model(&graphics, &model);
+        scene.camera.eye = Point3f::new(0.0, 0.0, 20.0);
+        scene.camera.target = Point3f::new(0.0, 0.0, 0.0);
+        render(&graphics, &mut renderer, &mut scene, "quad");
+    }
+}
+
+// Quad with a tinted, alpha masked checker, a normal map leaning
+// its normals right and a double sided material
+fn material_quad_gltf() -> String {
+    let bin = quad_bin();
+    let checker = png(image::RgbaImage::from_fn(8, 8, |x, y| {
+        let alpha = if (x + y) % 2 == 0 { 255 } else { 40 };
+        image::Rgba([230, 230, 230, alpha])
+    }));
+    let normals = png(image::RgbaImage::from_pixel(
+        1,
+        1,
+        image::Rgba([220, 128, 200, 255]),
+    ));
+    let base64 = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    format!(
+        r#"{{
+  "asset": {{"version": "2.0"}},
+  "nodes": [{{"mesh": 0}}],
+  "scenes": [{{"nodes": [0]}}],
+  "meshes": [{{"primitives": [{{
+    "attributes": {{"POSITION": 1, "TEXCOORD_0": 2}},
+    "indices": 0,
+    "material": 0
+  }}]}}],
+  "materials": [{{
+    "pbrMetallicRoughness": {{
+      "baseColorTexture": {{"index": 0}},
+      "baseColorFactor": [1.0, 0.5, 0.25, 1.0],
+      "metallicFactor": 0.25,
+      "roughnessFactor": 0.75
+    }},
+    "normalTexture": {{"index": 1, "scale": 1.0}},
+    "alphaMode": "MASK",
+    "alphaCutoff": 0.5,
+    "doubleSided": true
+  }}],
+  "textures": [{{"source": 0, "sampler": 0}}, {{"source": 1, "sampler": 0}}],
+  "samplers": [{NEAREST}],
+  "images": [
+    {{"uri": "data:image/png;base64,{checker}"}},
+    {{"uri": "data:image/png;base64,{normals}"}}
+  ],
+  "buffers": [{{"byteLength": {len}, "uri": "data:application/octet-stream;base64,{bin}"}}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 12}},
+    {{"buffer": 0, "byteOffset": 12, "byteLength": 48}},
+    {{"buffer": 0, "byteOffset": 60, "byteLength": 32}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5123, "count": 6, "type": "SCALAR"}},
+    {{"bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC3",
+      "min": [-5.0, -5.0, 0.0], "max": [5.0, 5.0, 0.0]}},
+    {{"bufferView": 2, "componentType": 5126, "count": 4, "type": "VEC2"}}
+  ]
+}}"#,
+        checker = base64(&checker),
+        normals = base64(&normals),
+        len = bin.len(),
+        bin = base64(&bin),
+    )
+}
+
+#[test]
+fn quad_material() {
+    let graphics = pollster::block_on(Graphics::headless(WIDTH, HEIGHT));
+    let defaults = material::Defaults::new(&graphics);
+    let json = material_quad_gltf();
+    let src = GltfSrc::Slice(json.as_bytes());
+    let model = gltf_loader::model_from_source(&graphics, &defaults, src).unwrap();
+    let params = model.meshes[0].material.bindings.params;
+    assert_eq!(params.base_color_factor, [1.0, 0.5, 0.25, 1.0]);
+    assert_eq!(params.alpha_mode, material::AlphaMode::Mask as u32);
+    assert_eq!(params.double_sided, 1);
+    let bindings = &model.meshes[0].material.bindings;
+    assert_eq!(
+        bindings.albedo_tx.texture.format(),
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    );
+    assert_eq!(
+        bindings.normal_tx.texture.format(),
+        wgpu::TextureFormat::Rgba8Unorm
+    );
+    // front and back, back faces are only drawn since the material is double sided
+    for (z, name) in [(20.0, "quad_material"), (-20.0, "quad_material_back")] {
+        let mut renderer = Renderer::new(&graphics);
+        let mut scene = Scene::new(&graphics);
+        scene.instantiate_model(&graphics, &model);
+        scene.camera.eye = Point3f::new(0.0, 0.0, z);
+        scene.camera.target = Point3f::new(0.0, 0.0, 0.0);
+        render(&graphics, &mut renderer, &mut scene, name);
+    }
+}
+
+// Cooking changes how the model is stored, not how it looks
+#[test]
+fn quad_material_cooked() {
+    let graphics = pollster::block_on(Graphics::headless(WIDTH, HEIGHT));
+    let defaults = material::Defaults::new(&graphics);
+    let json = material_quad_gltf();
+    let prepared = gltf_loader::prepare(GltfSrc::Slice(json.as_bytes()), &LoadOptions::default());
+    let bytes = cooked::cook(&prepared.unwrap(), &Default::default()).unwrap();
+    let model = cooked::load(&graphics, &defaults, "quad_material.mgc", bytes.into()).unwrap();
+    let mut renderer = Renderer::new(&graphics);
+    let mut scene = Scene::new(&graphics);
+    scene.instantiate_model(&graphics, &model);
+    scene.camera.eye = Point3f::new(0.0, 0.0, 20.0);
+    scene.camera.target = Point3f::new(0.0, 0.0, 0.0);
+    render(&graphics, &mut renderer, &mut scene, "quad_material");
+}
+
+// Quad with uvs running to 3, drawn twice through two identical samplers
+// that repeat horizontally and mirror vertically
+fn tiled_quad_gltf() -> String {
+    let mut bin = quad_bin();
+    bin.extend_from_slice(bytemuck::cast_slice(&QUAD_UVS.map(|uv| uv.map(|x| x * 3.0))));
+    let sampler = r#"{"magFilter": 9728, "minFilter": 9728, "wrapS": 10497, "wrapT": 33648}"#;
+    let base64 = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    format!(
+        r#"{{
+  "asset": {{"version": "2.0"}},
+  "nodes": [{{"mesh": 0}}],
+  "scenes": [{{"nodes": [0]}}],
+  "meshes": [{{"primitives": [
+    {{"attributes": {{"POSITION": 1, "TEXCOORD_0": 2}}, "indices": 0, "material": 0}},
+    {{"attributes": {{"POSITION": 1, "TEXCOORD_0": 2}}, "indices": 0, "material": 1}}
+  ]}}],
+  "materials": [
+    {{"pbrMetallicRoughness": {{"baseColorTexture": {{"index": 0}}}}}},
+    {{"pbrMetallicRoughness": {{"baseColorTexture": {{"index": 1}}}}}}
+  ],
+  "textures": [{{"source": 0, "sampler": 0}}, {{"source": 0, "sampler": 1}}],
+  "samplers": [{sampler}, {sampler}],
+  "images": [{{"uri": "data:image/png;base64,{png}"}}],
+  "buffers": [{{"byteLength": {len}, "uri": "data:application/octet-stream;base64,{bin}"}}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 12}},
+    {{"buffer": 0, "byteOffset": 12, "byteLength": 48}},
+    {{"buffer": 0, "byteOffset": 92, "byteLength": 32}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5123, "count": 6, "type": "SCALAR"}},
+    {{"bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC3",
+      "min": [-5.0, -5.0, 0.0], "max": [5.0, 5.0, 0.0]}},
+    {{"bufferView": 2, "componentType": 5126, "count": 4, "type": "VEC2"}}
+  ]
+}}"#,
+        png = base64(&checker_png()),
+        len = bin.len(),
+        bin = base64(&bin),
+    )
+}
+
+#[test]
+fn quad_tiled() {
+    let graphics = pollster::block_on(Graphics::headless(WIDTH, HEIGHT));
+    let defaults = material::Defaults::new(&graphics);
+    let json = tiled_quad_gltf();
+    let src = GltfSrc::Slice(json.as_bytes());
+    let model = gltf_loader::model_from_source(&graphics, &defaults, src).unwrap();
+    let [a, b] = [0, 1].map(|i| model.meshes[i].material.bindings.albedo_sampler.clone());
+    assert!(Arc::ptr_eq(&a, &b));
+    // loading again finds every sampler cached
+    let samplers = graphics.samplers.len();
+    let src = GltfSrc::Slice(json.as_bytes());
+    gltf_loader::model_from_source(&graphics, &defaults, src).unwrap();
+    assert_eq!(graphics.samplers.len(), samplers);
+    let mut renderer = Renderer::new(&graphics);
+    let mut scene = Scene::new(&graphics);
+    scene.instantiate_model(&graphics, &model);
+    scene.camera.eye = Point3f::new(0.0, 0.0, 20.0);
+    scene.camera.target = Point3f::new(0.0, 0.0, 0.0);
+    render(&graphics, &mut renderer, &mut scene, "quad_tiled");
+}
+
+// Four sided pyramid pointing at the camera, without normals
+fn pyramid_gltf() -> String {
+    let indices: [u16; 12] = [0, 1, 4, 1, 2, 4, 2, 3, 4, 3, 0, 4];
+    let vertices: [[f32; 3]; 5] = [
+        [-5.0, -5.0, 0.0],
+        [5.0, -5.0, 0.0],
+        [5.0, 5.0, 0.0],
+        [-5.0, 5.0, 0.0],
+        [0.0, 0.0, 4.0],
+    ];
+    let mut bin = bytemuck::cast_slice::<_, u8>(&indices).to_vec();
+    bin.extend_from_slice(bytemuck::cast_slice(&vertices));
+    let base64 = base64::engine::general_purpose::STANDARD.encode(&bin);
+    format!(
+        r#"{{
+  "asset": {{"version": "2.0"}},
+  "nodes": [{{"mesh": 0}}],
+  "scenes": [{{"nodes": [0]}}],
+  "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 1}}, "indices": 0}}]}}],
+  "buffers": [{{"byteLength": {}, "uri": "data:application/octet-stream;base64,{}"}}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 24}},
+    {{"buffer": 0, "byteOffset": 24, "byteLength": 60}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5123, "count": 12, "type": "SCALAR"}},
+    {{"bufferView": 1, "componentType": 5126, "count": 5, "type": "VEC3",
+      "min": [-5.0, -5.0, 0.0], "max": [5.0, 5.0, 4.0]}}
+  ]
+}}"#,
+        bin.len(),
+        base64
+    )
+}
+
+#[test]
+fn pyramid_normals() {
+    let graphics = pollster::block_on(Graphics::headless(WIDTH, HEIGHT));
+    let defaults = material::Defaults::new(&graphics);
+    let json = pyramid_gltf();
+    for (mode, name) in [
+        (NormalMode::Flat, "pyramid_flat"),
+        (NormalMode::Smooth, "pyramid_smooth"),
+    ] {
+        let options = LoadOptions { normals: mode };
+        let src = GltfSrc::Slice(json.as_bytes());
+        let model =
+            gltf_loader::model_from_source_with(&graphics, &defaults, src, &options).unwrap();
+        let mut renderer = Renderer::new(&graphics);
+        let mut scene = Scene::new(&graphics);
+        scene.instantiate_model(&graphics, &model);
+        scene.camera.eye = Point3f::new(0.0, -8.0, 20.0);
+        scene.camera.target = Point3f::new(0.0, 0.0, 0.0);
+        render(&graphics, &mut renderer, &mut scene, name);
+    }
+}
+
+#[test]
+fn quad_nodes() {
+    let graphics = pollster::block_on(Graphics::headless(WIDTH, HEIGHT));
+    let mut renderer = Renderer::new(&graphics);
+    let mut scene = Scene::new(&graphics);
+    let defaults = material::Defaults::new(&graphics);
+    let quad = quad_mesh(&graphics, &defaults);
+    let scale = Mat4::new_scaling(0.5);
+    let model = Model {
+        meshes: vec![quad],
+        nodes: vec![
+            Node {
+                name: "root".to_string(),
+                tsf: Mat4::new_translation(&Vec3f::new(-3.0, 0.0, 0.0)),
+                parent: None,
+                children: vec![1, 2],
+                meshes: vec![0],
+                skin: None,
+                light: None,
+                instances: vec![],
+            },
+            Node {
+                name: "right".to_string(),
+                tsf: Mat4::new_translation(&Vec3f::new(8.0, 4.0, 0.0)) * scale,
+                parent: Some(0),
+                children: vec![],
+                meshes: vec![0],
+                skin: None,
+                light: None,
+                instances: vec![],
+            },
+            Node {
+                name: "down".to_string(),
+                tsf: Mat4::new_translation(&Vec3f::new(8.0, -4.0, 0.0)),
+                parent: Some(0),
+                children: vec![3],
+                meshes: vec![],
+                skin: None,
+                light: None,
+                instances: vec![],
+            },
+            Node {
+                name: "leaf".to_string(),
+                tsf: Mat4::from_euler_angles(0.0, 0.0, FRAC_PI_4) * scale,
+                parent: Some(2),
+                children: vec![],
+                meshes: vec![0],
+                skin: None,
+                light: None,
+                instances: vec![],
+            },
+        ],
+        roots: vec![0],
+        ..Default::default()
+    };
+    let nodes = scene.instantiate_model(&graphics, &model);
+    scene.camera.eye = Point3f::new(0.0, 0.0, 30.0);
+    scene.camera.target = Point3f::new(0.0, 0.0, 0.0);
+    render(&graphics, &mut renderer, &mut scene, "quad_nodes");
+
+    // moving a parent moves its subtree
+    let down = nodes[model.find_node("down").unwrap()];
+    let tsf = Mat4::new_translation(&Vec3f::new(0.0, -6.0, 0.0)) * scene.node_tsf(down);
+    scene.set_node_tsf(down, tsf);
+    render(&graphics, &mut renderer, &mut scene, "quad_nodes_moved");
+}
+
+// Three instances of the quad in a row, the right one turned and the
+// left one shrunk, drawn by one mesh
+#[test]
+fn quad_instanced() {
+    let graphics = pollster::block_on(Graphics::headless(WIDTH, HEIGHT));
+    let mut renderer = Renderer::new(&graphics);
+    let mut scene = Scene::new(&graphics);
+    let defaults = material::Defaults::new(&graphics);
+    let model = Model {
+        meshes: vec![quad_mesh(&graphics, &defaults)],
+        nodes: vec![Node {
+            name: "row".to_string(),
+            tsf: Mat4::identity(),
+            parent: None,
+            children: vec![],
+            meshes: vec![0],
+            skin: None,
+            light: None,
+            instances: vec![
+                Mat4::new_translation(&Vec3f::new(-8.0, 0.0, 0.0)) * Mat4::new_scaling(0.5),
+                Mat4::identity(),
+                Mat4::new_translation(&Vec3f::new(8.0, 0.0, 0.0))
+                    * Mat4::from_euler_angles(0.0, 0.0, FRAC_PI_4),
+            ],
+        }],
+        roots: vec![0],
+        ..Default::default()
+    };
+    let nodes = scene.instantiate_model(&graphics, &model);
+    assert_eq!(scene.meshes.len(), 1);
+    assert_eq!(scene.inst_props[0].amt, 3);
+    scene.camera.eye = Point3f::new(0.0, 0.0, 30.0);
+    scene.camera.target = Point3f::new(0.0, 0.0, 0.0);
+    render(&graphics, &mut renderer, &mut scene, "quad_instanced");
+
+    // the instances follow their node
+    let tsf = Mat4::new_translation(&Vec3f::new(0.0, -6.0, 0.0));
+    scene.set_node_tsf(nodes[0], tsf);
+    render(&graphics, &mut renderer, &mut scene, "quad_instanced_moved");
+}
+
+// The quad moved 10 to the right and skinned to a joint binding it back,
+// so at rest it draws like quad. Flat normals unweld the joints and weights
+fn skinned_quad_gltf() -> String {
+    let mut bin = bytemuck::cast_slice::<_, u8>(&QUAD_INDICES).to_vec();
+    let moved = QUAD_VERTICES.map(|[x, y, z]| [x + 10.0, y, z]);
+    bin.extend_from_slice(bytemuck::cast_slice(&moved));
+    bin.extend_from_slice(bytemuck::cast_slice(&QUAD_UVS));
+    bin.extend_from_slice(&[0; 16]);
+    bin.extend_from_slice(bytemuck::cast_slice(&[[1f32, 0.0, 0.0, 0.0]; 4]));
+    let inverse_bind = Mat4::new_translation(&Vec3f::new(-10.0, 0.0, 0.0));
+    bin.extend_from_slice(bytemuck::cast_slice(inverse_bind.as_slice()));
+    let base64 = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    format!(
+        r#"{{
+  "asset": {{"version": "2.0"}},
+  "scenes": [{{"nodes": [0, 1]}}],
+  "nodes": [{{"mesh": 0, "skin": 0}}, {{"name": "joint"}}],
+  "skins": [{{"joints": [1], "inverseBindMatrices": 5}}],
+  "meshes": [{{"primitives": [{{
+    "attributes": {{"POSITION": 1, "TEXCOORD_0": 2, "JOINTS_0": 3, "WEIGHTS_0": 4}},
+    "indices": 0,
+    "material": 0
+  }}]}}],
+  "materials": [{{"pbrMetallicRoughness": {{"baseColorTexture": {{"index": 0}}}}}}],
+  "textures": [{{"source": 0, "sampler": 0}}],
+  "samplers": [{NEAREST}],
+  "images": [{{"uri": "data:image/png;base64,{png}"}}],
+  "buffers": [{{"byteLength": {len}, "uri": "data:application/octet-stream;base64,{bin}"}}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 12}},
+    {{"buffer": 0, "byteOffset": 12, "byteLength": 48}},
+    {{"buffer": 0, "byteOffset": 60, "byteLength": 32}},
+    {{"buffer": 0, "byteOffset": 92, "byteLength": 16}},
+    {{"buffer": 0, "byteOffset": 108, "byteLength": 64}},
+    {{"buffer": 0, "byteOffset": 172, "byteLength": 64}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5123, "count": 6, "type": "SCALAR"}},
+    {{"bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC3",
+      "min": [5.0, -5.0, 0.0], "max": [15.0, 5.0, 0.0]}},
+    {{"bufferView": 2, "componentType": 5126, "count": 4, "type": "VEC2"}},
+    {{"bufferView": 3, "componentType": 5121, "count": 4, "type": "VEC4"}},
+    {{"bufferView": 4, "componentType": 5126, "count": 4, "type": "VEC4"}},
+    {{"bufferView": 5, "componentType": 5126, "count": 1, "type": "MAT4"}}
+  ]
+}}"#,
+        png = base64(&checker_png()),
+        len = bin.len(),
+        bin = base64(&bin),
+    )
+}
+
+#[test]
+fn quad_skinned() {
+    let graphics = pollster::block_on(Graphics::headless(WIDTH, HEIGHT));
+    let mut renderer = Renderer::new(&graphics);
+    let mut scene = Scene::new(&graphics);
+    let defaults = material::Defaults::new(&graphics);
+    let json = skinned_quad_gltf();
+    let src = GltfSrc::Slice(json.as_bytes());
+    let model = gltf_loader::model_from_source(&graphics, &defaults, src).unwrap();
+    let nodes = scene.instantiate_model(&graphics, &model);
+    scene.camera.eye = Point3f::new(0.0, 0.0, 20.0);
+    scene.camera.target = Point3f::new(0.0, 0.0, 0.0);
+    render(&graphics, &mut renderer, &mut scene, "quad");
+
+    // turning the joint turns the quad around it
+    let joint = nodes[model.find_node("joint").unwrap()];
+    scene.set_node_tsf(joint, Mat4::from_euler_angles(0.0, 0.0, FRAC_PI_4));
+    render(&graphics, &mut renderer, &mut scene, "quad_skinned_turned");
+}
+
+// The quad moved 10 to the right, the first target moving it back and the
+// second stretching its top edge up, so at rest it draws like quad
+fn morphed_quad_gltf() -> String {
+    let mut bin = bytemuck::cast_slice::<_, u8>(&QUAD_INDICES).to_vec();
+    let moved = QUAD_VERTICES.map(|[x, y, z]| [x + 10.0, y, z]);
+    bin.extend_from_slice(bytemuck::cast_slice(&moved));
+    bin.extend_from_slice(bytemuck::cast_slice(&QUAD_UVS));
+    bin.extend_from_slice(bytemuck::cast_slice(&[[-10f32, 0.0, 0.0]; 4]));
+    let stretch = QUAD_VERTICES.map(|[_, y, _]| [0.0, if y > 0.0 { 5f32 } else { 0.0 }, 0.0]);
+    bin.extend_from_slice(bytemuck::cast_slice(&stretch));
+    let base64 = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    format!(
+        r#"{{
+  "asset": {{"version": "2.0"}},
+  "scenes": [{{"nodes": [0]}}],
+  "nodes": [{{"name": "morphed", "mesh": 0}}],
+  "meshes": [{{
+    "primitives": [{{
+      "attributes": {{"POSITION": 1, "TEXCOORD_0": 2}},
+      "targets": [{{"POSITION": 3}}, {{"POSITION": 4}}],
+      "indices": 0,
+      "material": 0
+    }}],
+    "weights": [1.0, 0.0]
+  }}],
+  "materials": [{{"pbrMetallicRoughness": {{"baseColorTexture": {{"index": 0}}}}}}],
+  "textures": [{{"source": 0, "sampler": 0}}],
+  "samplers": [{NEAREST}],
+  "images": [{{"uri": "data:image/png;base64,{png}"}}],
+  "buffers": [{{"byteLength": {len}, "uri": "data:application/octet-stream;base64,{bin}"}}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 12}},
+    {{"buffer": 0, "byteOffset": 12, "byteLength": 48}},
+    {{"buffer": 0, "byteOffset": 60, "byteLength": 32}},
+    {{"buffer": 0, "byteOffset": 92, "byteLength": 48}},
+    {{"buffer": 0, "byteOffset": 140, "byteLength": 48}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5123, "count": 6, "type": "SCALAR"}},
+    {{"bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC3",
+      "min": [5.0, -5.0, 0.0], "max": [15.0, 5.0, 0.0]}},
+    {{"bufferView": 2, "componentType": 5126, "count": 4, "type": "VEC2"}},
+    {{"bufferView": 3, "componentType": 5126, "count": 4, "type": "VEC3",
+      "min": [-10.0, 0.0, 0.0], "max": [-10.0, 0.0, 0.0]}},
+    {{"bufferView": 4, "componentType": 5126, "count": 4, "type": "VEC3",
+      "min": [0.0, 0.0, 0.0], "max": [0.0, 5.0, 0.0]}}
+  ]
+}}"#,
+        png = base64(&checker_png()),
+        len = bin.len(),
+        bin = base64(&bin),
+    )
+}
+
+#[test]
+fn quad_morphed() {
+    let graphics = pollster::block_on(Graphics::headless(WIDTH, HEIGHT));
+    let mut renderer = Renderer::new(&graphics);
+    let mut scene = Scene::new(&graphics);
+    let defaults = material::Defaults::new(&graphics);
+    let json = morphed_quad_gltf();
+    let src = GltfSrc::Slice(json.as_bytes());
+    let model = gltf_loader::model_from_source(&graphics, &defaults, src).unwrap();
+    let nodes = scene.instantiate_model(&graphics, &model);
+    scene.camera.eye = Point3f::new(0.0, 0.0, 20.0);
+    scene.camera.target = Point3f::new(0.0, 0.0, 0.0);
+    render(&graphics, &mut renderer, &mut scene, "quad");
+
+    let morphed = nodes[model.find_node("morphed").unwrap()];
+    scene.set_node_weights(morphed, &[1.0, 1.0]);
+    render(&graphics, &mut renderer, &mut scene, "quad_morphed_stretched");
+}
+
+// The quad lit by a point light on a node 5 in front of it, bright
+// enough to draw the center at about its albedo
+#[test]
+fn quad_lit() {
+    let graphics = pollster::block_on(Graphics::headless(WIDTH, HEIGHT));
+    let mut renderer = Renderer::new(&graphics);
+    let mut scene = Scene::new(&graphics);
+    let defaults = material::Defaults::new(&graphics);
+    let node = |name: &str, tsf, meshes, light| Node {
+        name: name.to_string(),
+        tsf,
+        parent: None,
+        children: vec![],
+        meshes,
+        skin: None,
+        light,
+        instances: vec![],
+    };
+    let model = Model {
+        meshes: vec![quad_mesh(&graphics, &defaults)],
+        nodes: vec![
+            node("quad", Mat4::identity(), vec![0], None),
+            node(
+                "lamp",
+                Mat4::new_translation(&Vec3f::new(0.0, 0.0, 5.0)),
+                vec![],
+                Some(0),
+            ),
+        ],
+        roots: vec![0, 1],
+        lights: vec![Light {
+            name: "lamp".to_string(),
+            kind: LightKind::Point,
+            color: Vec3f::new(1.0, 1.0, 1.0),
+            intensity: 25.0 * PI,
+            range: None,
+        }],
+        ..Default::default()
+    };
+    let nodes = scene.instantiate_model(&graphics, &model);
+    scene.camera.eye = Point3f::new(0.0, 0.0, 20.0);
+    scene.camera.target = Point3f::new(0.0, 0.0, 0.0);
+    render(&graphics, &mut renderer, &mut scene, "quad_lit_point");
+
+    // the light follows its node
+    let lamp = nodes[model.find_node("lamp").unwrap()];
+    scene.set_node_tsf(lamp, Mat4::new_translation(&Vec3f::new(3.0, 3.0, 5.0)));
+    render(&graphics, &mut renderer, &mut scene, "quad_lit_moved");
+
+    scene.lights[0].light.kind = LightKind::Spot {
+        inner_cone: 0.2,
+        outer_cone: 0.4,
+    };
+    render(&graphics, &mut renderer, &mut scene, "quad_lit_spot");
+}
+
+#[test]
+fn curtains() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/PKG_A_Curtains/");
+    let bin = fs::read(format!("{}NewSponza_Curtains_glTF.bin", path)).unwrap();
+    if bin.starts_with(b"version https://git-lfs") {
+        eprintln!("skipping curtains, lfs assets are not checked out");
+        return;
+    }
+    let graphics = pollster::block_on(Graphics::headless(WIDTH, HEIGHT));
+    let mut renderer = Renderer::new(&graphics);
+    let mut scene = Scene::new(&graphics);
+    let defaults = material::Defaults::new(&graphics);
+    let gltf_path = format!("{}NewSponza_Curtains_glTF.gltf", path);
+    let model =
+        gltf_loader::model_from_source(&graphics, &defaults, GltfSrc::Path(&gltf_path)).unwrap();
+    assert!(!model.meshes.is_empty());
+    scene.instantiate_model(&graphics, &model);
+    scene.camera.eye = Point3f::new(12.0, 6.0, 12.0);
+    scene.camera.target = Point3f::new(0.0, 2.0, 0.0);
+    render(&graphics, &mut renderer, &mut scene, "curtains");
+}
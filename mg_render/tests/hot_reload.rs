@@ -0,0 +1,59 @@
+use mg_core::*;
+use mg_render::{
+    graphics::Graphics,
+    hot_reload::{Watcher, SHADER_DIR},
+    wgsl::Preprocessor,
+    Renderer, SHADERS,
+};
+
+#[test]
+fn watcher() {
+    let dir = std::env::temp_dir().join("mg_hot_reload_watcher");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("watched.txt");
+    fs::write(&path, "a").unwrap();
+    let mut watcher = Watcher::default();
+    watcher.watch(&path);
+    assert!(watcher.changed().is_empty());
+
+    fs::write(&path, "ab").unwrap();
+    assert_eq!(watcher.changed(), std::slice::from_ref(&path));
+    assert!(watcher.changed().is_empty());
+
+    // removing and recreating count as changes too
+    fs::remove_file(&path).unwrap();
+    assert_eq!(watcher.changed(), std::slice::from_ref(&path));
+    fs::write(&path, "a").unwrap();
+    assert_eq!(watcher.changed(), [path]);
+}
+
+#[test]
+fn shader_reload() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let mut renderer = Renderer::new(&graphics);
+    let shaders = Preprocessor::default();
+    for name in SHADERS {
+        let source = fs::read_to_string(format!("{}/{}", SHADER_DIR, name)).unwrap();
+        assert!(renderer
+            .reload_shader(&graphics, &shaders, name, &source)
+            .unwrap());
+
+        // bad WGSL keeps the old pipelines
+        let err = renderer
+            .reload_shader(&graphics, &shaders, name, "fn broken(")
+            .unwrap_err();
+        assert!(err.to_string().contains(name), "{}", err);
+        let missing_entry = source.replace("fn ", "fn renamed_");
+        assert!(renderer
+            .reload_shader(&graphics, &shaders, name, &missing_entry)
+            .is_err());
+        let missing_module = format!("#include \"missing.wgsl\"\n{}", source);
+        let err = renderer
+            .reload_shader(&graphics, &shaders, name, &missing_module)
+            .unwrap_err();
+        assert!(err.to_string().contains(name), "{}", err);
+    }
+    assert!(!renderer
+        .reload_shader(&graphics, &shaders, "mipmap.wgsl", "")
+        .unwrap());
+}
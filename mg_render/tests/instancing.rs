@@ -0,0 +1,136 @@
+mod common;
+
+use base64::Engine;
+use common::{assert_near, load};
+use mg_core::*;
+use mg_render::{
+    cooked::{self, CookOptions},
+    gltf_loader::{self, GltfSrc, LoadOptions},
+    graphics::Graphics,
+    material,
+    scene::Scene,
+};
+
+// A triangle drawn twice by a node 1 up, once 2 to the left and once 3 to
+// the right turned a quarter about z by normalized i16 rotations and
+// doubled. SCALE has scale_amt elements
+fn gltf(scale_amt: usize) -> String {
+    let positions: [[f32; 3]; 3] = [[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+    let translations: [[f32; 3]; 2] = [[-2.0, 0.0, 0.0], [3.0, 0.0, 0.0]];
+    let rotations: [[i16; 4]; 2] = [[0, 0, 0, 32767], [0, 0, 23170, 23170]];
+    let scales: [[f32; 3]; 2] = [[1.0; 3], [2.0; 3]];
+    let mut bin = bytemuck::cast_slice::<_, u8>(&[0u16, 1, 2, 0]).to_vec();
+    bin.extend_from_slice(bytemuck::cast_slice(&positions));
+    bin.extend_from_slice(bytemuck::cast_slice(&translations));
+    bin.extend_from_slice(bytemuck::cast_slice(&rotations));
+    bin.extend_from_slice(bytemuck::cast_slice(&scales));
+    let base64 = base64::engine::general_purpose::STANDARD.encode(&bin);
+    format!(
+        r#"{{
+  "asset": {{"version": "2.0"}},
+  "extensionsUsed": ["EXT_mesh_gpu_instancing"],
+  "nodes": [{{"name": "pair", "mesh": 0, "translation": [0.0, 1.0, 0.0],
+    "extensions": {{"EXT_mesh_gpu_instancing": {{
+      "attributes": {{"TRANSLATION": 2, "ROTATION": 3, "SCALE": 4}}
+    }}}}
+  }}],
+  "scenes": [{{"nodes": [0]}}],
+  "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 1}}, "indices": 0}}]}}],
+  "buffers": [{{"byteLength": {len}, "uri": "data:application/octet-stream;base64,{base64}"}}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 8}},
+    {{"buffer": 0, "byteOffset": 8, "byteLength": 36}},
+    {{"buffer": 0, "byteOffset": 44, "byteLength": 24}},
+    {{"buffer": 0, "byteOffset": 68, "byteLength": 16}},
+    {{"buffer": 0, "byteOffset": 84, "byteLength": 24}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5123, "count": 3, "type": "SCALAR"}},
+    {{"bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3",
+      "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]}},
+    {{"bufferView": 2, "componentType": 5126, "count": 2, "type": "VEC3"}},
+    {{"bufferView": 3, "componentType": 5122, "count": 2, "type": "VEC4", "normalized": true}},
+    {{"bufferView": 4, "componentType": 5126, "count": {scale_amt}, "type": "VEC3"}}
+  ]
+}}"#,
+        len = bin.len(),
+    )
+}
+
+fn instances() -> [Mat4; 2] {
+    [
+        Mat4::new_translation(&Vec3f::new(-2.0, 0.0, 0.0)),
+        Mat4::new_translation(&Vec3f::new(3.0, 0.0, 0.0))
+            * Mat4::from_euler_angles(0.0, 0.0, std::f32::consts::FRAC_PI_2)
+            * Mat4::new_scaling(2.0),
+    ]
+}
+
+#[test]
+fn import() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let model = load(&graphics, &gltf(2)).unwrap();
+    assert_near(
+        model.nodes[0].instances.iter().flatten(),
+        instances().iter().flatten(),
+        1e-4,
+    );
+    // the node keeps its own transform
+    assert_eq!(
+        model.nodes[0].tsf,
+        Mat4::new_translation(&Vec3f::new(0.0, 1.0, 0.0))
+    );
+
+    let err = load(&graphics, &gltf(1)).err().unwrap();
+    assert!(matches!(
+        err.downcast::<AssetError>().unwrap(),
+        AssetError::InvalidGltf { .. }
+    ));
+}
+
+#[test]
+fn cooked() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let defaults = material::Defaults::new(&graphics);
+    let json = gltf(2);
+    let gltf = load(&graphics, &json).unwrap();
+    let prepared = gltf_loader::prepare(GltfSrc::Slice(json.as_bytes()), &LoadOptions::default());
+    let bytes = cooked::cook(&prepared.unwrap(), &CookOptions::default()).unwrap();
+    let model = cooked::load(&graphics, &defaults, "test.mgc", bytes.into()).unwrap();
+    assert_eq!(model.nodes[0].instances, gltf.nodes[0].instances);
+}
+
+#[test]
+fn placed() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let model = load(&graphics, &gltf(2)).unwrap();
+    let mut scene = Scene::new(&graphics);
+    let nodes = scene.instantiate_model(&graphics, &model);
+    // one mesh draws every instance
+    assert_eq!(scene.meshes.len(), 1);
+    assert_eq!(scene.inst_props[0].amt, 2);
+
+    let world_tsfs = |scene: &Scene| -> Vec<Mat4> {
+        scene.ray_buffer.world_tsfs[..2]
+            .iter()
+            .map(|inst| Mat4::from(inst.0))
+            .collect()
+    };
+    scene.update(&graphics);
+    let tsf = model.nodes[0].tsf;
+    assert_near(
+        world_tsfs(&scene).iter().flatten(),
+        instances().map(|instance| tsf * instance).iter().flatten(),
+        1e-4,
+    );
+
+    // instances follow their node
+    let tsf = Mat4::new_translation(&Vec3f::new(5.0, 0.0, 0.0));
+    scene.set_node_tsf(nodes[0], tsf);
+    scene.update(&graphics);
+    assert_near(
+        world_tsfs(&scene).iter().flatten(),
+        instances().map(|instance| tsf * instance).iter().flatten(),
+        1e-4,
+    );
+}
@@ -0,0 +1,93 @@
+use mg_core::*;
+use mg_render::{
+    cooked::{self, CookOptions},
+    gltf_loader::{self, GltfSrc, LoadOptions},
+    graphics::Graphics,
+    material,
+    model::{Light, LightKind},
+    scene::Scene,
+};
+
+// A sun, and a post with a spot light on a child node and a point
+// light of its own
+const GLTF: &str = r#"{
+  "asset": {"version": "2.0"},
+  "extensionsUsed": ["KHR_lights_punctual"],
+  "extensions": {"KHR_lights_punctual": {"lights": [
+    {"name": "sun", "type": "directional", "color": [1.0, 0.9, 0.8], "intensity": 3.0},
+    {"name": "bulb", "type": "point", "range": 10.0},
+    {"name": "cone", "type": "spot", "intensity": 20.0,
+      "spot": {"innerConeAngle": 0.1, "outerConeAngle": 0.5}}
+  ]}},
+  "nodes": [
+    {"name": "sun", "rotation": [-0.7071068, 0.0, 0.0, 0.7071068],
+      "extensions": {"KHR_lights_punctual": {"light": 0}}},
+    {"name": "post", "translation": [0.0, 3.0, 0.0], "children": [2],
+      "extensions": {"KHR_lights_punctual": {"light": 1}}},
+    {"name": "head", "translation": [0.0, 1.0, 0.0],
+      "extensions": {"KHR_lights_punctual": {"light": 2}}}
+  ],
+  "scenes": [{"nodes": [0, 1]}]
+}"#;
+
+#[test]
+fn import() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let defaults = material::Defaults::new(&graphics);
+    let model =
+        gltf_loader::model_from_source(&graphics, &defaults, GltfSrc::Slice(GLTF.as_bytes()))
+            .unwrap();
+    assert_eq!(
+        model.lights[0],
+        Light {
+            name: "sun".to_string(),
+            kind: LightKind::Directional,
+            color: Vec3f::new(1.0, 0.9, 0.8),
+            intensity: 3.0,
+            range: None,
+        }
+    );
+    // color and intensity default to white and one
+    assert_eq!(model.lights[1].color, Vec3f::new(1.0, 1.0, 1.0));
+    assert_eq!(model.lights[1].intensity, 1.0);
+    assert_eq!(model.lights[1].range, Some(10.0));
+    assert_eq!(
+        model.lights[2].kind,
+        LightKind::Spot {
+            inner_cone: 0.1,
+            outer_cone: 0.5
+        }
+    );
+    let lights: Vec<_> = model.nodes.iter().map(|node| node.light).collect();
+    assert_eq!(lights, [Some(0), Some(1), Some(2)]);
+
+    let prepared = gltf_loader::prepare(GltfSrc::Slice(GLTF.as_bytes()), &LoadOptions::default());
+    let bytes = cooked::cook(&prepared.unwrap(), &CookOptions::default()).unwrap();
+    let cooked = cooked::load(&graphics, &defaults, "test.mgc", bytes.into()).unwrap();
+    assert_eq!(cooked.lights, model.lights);
+    let cooked_lights: Vec<_> = cooked.nodes.iter().map(|node| node.light).collect();
+    assert_eq!(cooked_lights, lights);
+}
+
+#[test]
+fn attached() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let defaults = material::Defaults::new(&graphics);
+    let model =
+        gltf_loader::model_from_source(&graphics, &defaults, GltfSrc::Slice(GLTF.as_bytes()))
+            .unwrap();
+    let mut scene = Scene::new(&graphics);
+    let nodes = scene.instantiate_model(&graphics, &model);
+    assert_eq!(scene.lights.len(), 3);
+    for (light, node) in scene.lights.iter().zip(["sun", "post", "head"]) {
+        assert_eq!(light.node, Some(nodes[model.find_node(node).unwrap()]));
+        assert_eq!(light.tsf, Mat4::identity());
+    }
+    assert_eq!(scene.lights[2].light.name, "cone");
+
+    let lamp = scene.add_light(model.lights[1].clone(), None);
+    assert_eq!(lamp, 3);
+    assert_eq!(scene.lights[lamp].node, None);
+    // lights grow their buffer past the ones first instantiated
+    scene.update(&graphics);
+}
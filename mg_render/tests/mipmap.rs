@@ -0,0 +1,63 @@
+mod common;
+
+use common::png;
+use mg_render::{
+    capture,
+    graphics::Graphics,
+    mipmap,
+    texture::{Texture, TextureOptions},
+};
+
+// Black and white checker, its average is half intensity in linear space
+fn checker(size: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(size, size, |x, y| match (x + y) % 2 {
+        0 => image::Rgba([0, 0, 0, 255]),
+        _ => image::Rgba([255, 255, 255, 255]),
+    })
+}
+
+fn assert_near(a: u8, b: u8) {
+    assert!(a.abs_diff(b) <= 2, "{} != {}", a, b);
+}
+
+#[test]
+fn downsample() {
+    let half = mipmap::downsample(&checker(4), true);
+    assert_eq!(half.dimensions(), (2, 2));
+    // 0.5 linear encodes to 188 in srgb
+    assert_near(half.get_pixel(0, 0)[0], 188);
+    assert_eq!(half.get_pixel(0, 0)[3], 255);
+    let half = mipmap::downsample(&checker(4), false);
+    assert_near(half.get_pixel(1, 1)[0], 128);
+
+    let chain = mipmap::generate_cpu(&checker(5), true, 3);
+    let sizes = chain.iter().map(|i| i.dimensions()).collect::<Vec<_>>();
+    assert_eq!(sizes, [(2, 2), (1, 1)]);
+}
+
+#[test]
+fn image_mipmaps() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let png = png(checker(16));
+    let texture = Texture::create_image_texture(&graphics, "checker", &png).unwrap();
+    assert_eq!(texture.texture.mip_level_count(), 5);
+    for level in 1..5 {
+        let image = capture::capture_texture_level(&graphics, &texture.texture, level).unwrap();
+        assert_eq!(image.width(), 16 >> level);
+        for pixel in image.pixels() {
+            assert_near(pixel[0], 188);
+            assert_near(pixel[1], 188);
+        }
+    }
+    // the pipeline is built once per format
+    let pipelines = graphics.pipelines.len();
+    Texture::create_image_texture(&graphics, "checker", &png).unwrap();
+    assert_eq!(graphics.pipelines.len(), pipelines);
+
+    let options = TextureOptions {
+        mipmaps: false,
+        ..Default::default()
+    };
+    let texture = Texture::create_image_texture_with(&graphics, "ui", &png, &options).unwrap();
+    assert_eq!(texture.texture.mip_level_count(), 1);
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use base64::Engine;
+use common::load;
+use mg_core::*;
+use mg_render::{
+    animation::{Animator, Channel, Clip, Interpolation, Property},
+    cooked::{self, CookOptions},
+    gltf_loader::{self, GltfSrc, LoadOptions},
+    graphics::Graphics,
+    material,
+    model::Model,
+    scene::Scene,
+};
+
+// A triangle with normals and two targets, the first raising its top
+// vertex and tilting its normals, the second with normalized i8 position
+// deltas pushing the right vertex out. The mesh only has one default weight
+fn gltf() -> String {
+    let positions: [[f32; 3]; 3] = [[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+    let normals = [[0f32, 0.0, 1.0]; 3];
+    let raise: [[f32; 3]; 3] = [[0.0; 3], [0.0; 3], [0.0, 2.0, 0.0]];
+    let tilt = [[0f32, 0.5, 0.0]; 3];
+    let push: [[i8; 4]; 3] = [[0; 4], [127, 0, 0, 0], [0; 4]];
+    let mut bin = bytemuck::cast_slice::<_, u8>(&[0u16, 1, 2, 0]).to_vec();
+    bin.extend_from_slice(bytemuck::cast_slice(&positions));
+    bin.extend_from_slice(bytemuck::cast_slice(&normals));
+    bin.extend_from_slice(bytemuck::cast_slice(&raise));
+    bin.extend_from_slice(bytemuck::cast_slice(&tilt));
+    bin.extend_from_slice(bytemuck::cast_slice(&push));
+    let base64 = base64::engine::general_purpose::STANDARD.encode(&bin);
+    format!(
+        r#"{{
+  "asset": {{"version": "2.0"}},
+  "nodes": [{{"name": "face", "mesh": 0}}],
+  "scenes": [{{"nodes": [0]}}],
+  "meshes": [{{
+    "primitives": [{{
+      "attributes": {{"POSITION": 1, "NORMAL": 2}},
+      "targets": [{{"POSITION": 3, "NORMAL": 4}}, {{"POSITION": 5}}],
+      "indices": 0
+    }}],
+    "weights": [0.5]
+  }}],
+  "buffers": [{{"byteLength": {len}, "uri": "data:application/octet-stream;base64,{base64}"}}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 8}},
+    {{"buffer": 0, "byteOffset": 8, "byteLength": 36}},
+    {{"buffer": 0, "byteOffset": 44, "byteLength": 36}},
+    {{"buffer": 0, "byteOffset": 80, "byteLength": 36}},
+    {{"buffer": 0, "byteOffset": 116, "byteLength": 36}},
+    {{"buffer": 0, "byteOffset": 152, "byteLength": 12, "byteStride": 4}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5123, "count": 3, "type": "SCALAR"}},
+    {{"bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3",
+      "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]}},
+    {{"bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC3"}},
+    {{"bufferView": 3, "componentType": 5126, "count": 3, "type": "VEC3",
+      "min": [0.0, 0.0, 0.0], "max": [0.0, 2.0, 0.0]}},
+    {{"bufferView": 4, "componentType": 5126, "count": 3, "type": "VEC3"}},
+    {{"bufferView": 5, "componentType": 5120, "count": 3, "type": "VEC3", "normalized": true,
+      "min": [0.0, 0.0, 0.0], "max": [1.0, 0.0, 0.0]}}
+  ]
+}}"#,
+        len = bin.len(),
+    )
+}
+
+fn targets(model: &Model) -> Vec<[f32; 3]> {
+    let geometry = &model.meshes[0].geometry;
+    let range = geometry.ranges.targets();
+    bytemuck::cast_slice(&geometry.buffer.bin[range.start as usize..range.end as usize]).to_vec()
+}
+
+#[test]
+fn attributes() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let model = load(&graphics, &gltf()).unwrap();
+    let mesh = &model.meshes[0];
+    assert_eq!(mesh.geometry.target_amt(), 2);
+    // missing weights default to zero
+    assert_eq!(mesh.weights, [0.5, 0.0]);
+
+    // target major, then positions, normals and tangents
+    let deltas = targets(&model);
+    assert_eq!(deltas.len(), 2 * 3 * 3);
+    assert_eq!(deltas[2], [0.0, 2.0, 0.0]);
+    assert_eq!(deltas[3..6], [[0.0, 0.5, 0.0]; 3]);
+    assert_eq!(deltas[6..9], [[0.0; 3]; 3]);
+    assert_eq!(deltas[10], [1.0, 0.0, 0.0]);
+    // the second target moves neither normals nor tangents
+    assert_eq!(deltas[12..], [[0.0; 3]; 6]);
+}
+
+#[test]
+fn cooked() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let defaults = material::Defaults::new(&graphics);
+    let json = gltf();
+    let gltf = load(&graphics, &json).unwrap();
+    let prepared = gltf_loader::prepare(GltfSrc::Slice(json.as_bytes()), &LoadOptions::default());
+    let bytes = cooked::cook(&prepared.unwrap(), &CookOptions::default()).unwrap();
+    let model = cooked::load(&graphics, &defaults, "test.mgc", bytes.into()).unwrap();
+    assert_eq!(model.meshes[0].geometry.target_amt(), 2);
+    assert_eq!(model.meshes[0].weights, gltf.meshes[0].weights);
+    assert_eq!(targets(&model), targets(&gltf));
+}
+
+#[test]
+fn weights() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let model = load(&graphics, &gltf()).unwrap();
+    let mut scene = Scene::new(&graphics);
+    let nodes = scene.instantiate_model(&graphics, &model);
+    let weights = |scene: &Scene| {
+        scene.inst_props[0]
+            .deformed
+            .as_ref()
+            .unwrap()
+            .weights()
+            .to_vec()
+    };
+    assert_eq!(weights(&scene), [0.5, 0.0]);
+
+    // extra weights are ignored
+    scene.set_node_weights(nodes[0], &[0.25, 1.0, 3.0]);
+    assert_eq!(weights(&scene), [0.25, 1.0]);
+    scene.set_weights(0, &[0.75]);
+    assert_eq!(weights(&scene), [0.75, 1.0]);
+    scene.update(&graphics);
+
+    let morph = Channel {
+        node: 0,
+        property: Property::Weights,
+        interpolation: Interpolation::Linear,
+        times: vec![0.0, 1.0],
+        values: vec![0.0, 0.0, 1.0, 0.5],
+    };
+    let clip = Arc::new(Clip::new("morph".to_string(), vec![morph]));
+    let mut animator = Animator::new(&model, nodes.clone());
+    assert_eq!(animator.pose().weights[0], [0.5, 0.0]);
+    animator.play(&clip);
+    animator.update(0.5);
+    animator.apply(&mut scene);
+    assert_eq!(weights(&scene), [0.5, 0.25]);
+    // weights don't touch the node's transform
+    assert_eq!(scene.node_tsf(nodes[0]), model.nodes[0].tsf);
+}
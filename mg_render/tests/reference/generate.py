@@ -0,0 +1,163 @@
+# Regenerates the fixtures in this directory with Mesa's software
+# rasterizer. Build the helper and run it from anywhere:
+#
+#   cc gl.c -o /tmp/gl -lEGL -lGL
+#   python3 generate.py /tmp/gl
+#
+# The fixtures were made with Mesa 22.3.6 and llvmpipe
+import math, os, random, struct, subprocess, sys, zlib
+
+OUT = os.path.dirname(os.path.abspath(__file__))
+GL = sys.argv[1]
+
+def png(path, w, h, rgba):
+    raw = b''.join(b'\0' + rgba[y * w * 4:(y + 1) * w * 4] for y in range(h))
+    def chunk(t, d):
+        c = t + d
+        return struct.pack('>I', len(d)) + c + struct.pack('>I', zlib.crc32(c) & 0xffffffff)
+    data = b'\x89PNG\r\n\x1a\n' + chunk(b'IHDR', struct.pack('>IIBBBBB', w, h, 8, 6, 0, 0, 0))
+    data += chunk(b'IDAT', zlib.compress(raw, 9)) + chunk(b'IEND', b'')
+    open(path, 'wb').write(data)
+
+def gl(mode, fmt, w, h, data):
+    return subprocess.run([GL, mode, fmt, str(w), str(h)], input=data, capture_output=True, check=True).stdout
+
+def decoded(fmt, w, h, blocks):
+    floats = struct.unpack('<%df' % (w * h * 4), gl('dec', fmt, w, h, blocks))
+    return bytes(min(255, max(0, round(f * 255))) for f in floats)
+
+rng = random.Random(12)
+W = H = 64
+src = bytearray()
+for y in range(H):
+    for x in range(W):
+        if x < 32 and y < 32:
+            c = [x * 8, y * 8, 128 + int(100 * math.sin(x * 0.3 + y * 0.2)), 255]
+        elif y < 32:
+            inside = (x - 48) ** 2 + (y - 16) ** 2 < 100
+            c = [220, 40, 30, 255] if inside else ([20, 200, 60, 255] if (x // 3 + y // 5) % 2 else [240, 240, 250, 255])
+        elif x < 32:
+            c = [rng.randrange(256), rng.randrange(256), rng.randrange(256), (y - 32) * 8]
+        else:
+            v = 0.5 + 0.25 * math.sin(x * 0.7) + 0.25 * math.cos(y * 0.45 + x * 0.1)
+            c = [int(255 * v), int(180 * v * v), int(90 + 60 * math.sin(y * 0.9)), 0 if (x * y) % 7 == 0 else 255]
+        src += bytes(min(255, max(0, int(v))) for v in c)
+src = bytes(src)
+png(f'{OUT}/source.png', W, H, src)
+
+# blocks from mesa's encoders
+for name, fmt in [('bc1', '83F1'), ('bc2', '83F2'), ('bc3', '83F3'), ('bc4', '8DBB'), ('bc5', '8DBD'), ('bc7', '8E8C')]:
+    blocks = gl('enc', fmt, W, H, src)
+    open(f'{OUT}/{name}.bin', 'wb').write(blocks)
+    png(f'{OUT}/{name}.png', W, H, decoded(fmt, W, H, blocks))
+
+# random blocks for formats mesa can't encode, and every mode of bc7
+def rand_blocks(count, size):
+    return bytes(rng.randrange(256) for _ in range(count * size))
+
+def bc7_blocks(count):
+    out = bytearray()
+    for i in range(count):
+        b = bytearray(rand_blocks(1, 16))
+        mode = i % 8
+        b[0] = (b[0] & ~((1 << (mode + 1)) - 1) | (1 << mode)) & 0xff
+        out += b
+    return bytes(out)
+
+sets = [
+    ('bc7_modes', '8E8C', 4, 4, 16, bc7_blocks(64)),
+    ('etc2_rgb', '9274', 4, 4, 8, rand_blocks(64, 8)),
+    ('etc2_rgb_a1', '9276', 4, 4, 8, rand_blocks(64, 8)),
+    ('etc2_rgba', '9278', 4, 4, 16, rand_blocks(64, 16)),
+    ('eac_r11', '9270', 4, 4, 8, rand_blocks(64, 8)),
+    ('eac_rg11', '9272', 4, 4, 16, rand_blocks(64, 16)),
+]
+for name, fmt, bw, bh, size, blocks in sets:
+    w, h = 8 * bw, 8 * bh
+    open(f'{OUT}/{name}.bin', 'wb').write(blocks)
+    png(f'{OUT}/{name}.png', w, h, decoded(fmt, w, h, blocks))
+
+# astc blocks of random bits. Few random modes are valid, so the modes
+# and partition counts of ones that are seed the rest
+MAGENTA = bytes([255, 0, 255, 255])
+def void_extent():
+    bits = 0x1FC | 3 << 10 | ((1 << 52) - 1) << 12
+    color = rand_blocks(1, 8)
+    return (bits).to_bytes(8, 'little') + color
+
+# Mesa decodes HDR endpoint modes, more than 18 endpoint values and void
+# extent blocks without their reserved bits, which the specs make errors
+RANGES = [(0,0,1),(1,0,0),(0,0,2),(0,1,0),(1,0,1),(0,0,3),(0,1,1),(1,0,2),(0,0,4),(0,1,2),(1,0,3),(0,0,5),(0,1,3),(1,0,4),(0,0,6),(0,1,4),(1,0,5),(0,0,7),(0,1,5),(1,0,6),(0,0,8)]
+def seq_bits(n, r):
+    t, q, b = RANGES[r]
+    return (8 * n + 4) // 5 + n * b if t else ((7 * n + 2) // 3 + n * b if q else n * b)
+def weight_grid(mode):
+    bit = lambda i: mode >> i & 1
+    a, b = mode >> 5 & 3, mode >> 7 & 3
+    high, dual = bit(9), bit(10)
+    if mode & 3 == 0:
+        r = (mode >> 2 & 3) << 1 | bit(4)
+        if b == 0: w, h = 12, a + 2
+        elif b == 1: w, h = a + 2, 12
+        elif b == 2: w, h, high, dual = a + 6, (mode >> 9 & 3) + 6, 0, 0
+        elif a == 0: w, h = 6, 10
+        elif a == 1: w, h = 10, 6
+        else: return None
+    else:
+        r = (mode & 3) << 1 | bit(4)
+        c = mode >> 2 & 3
+        if c == 0: w, h = b + 4, a + 2
+        elif c == 1: w, h = b + 8, a + 2
+        elif c == 2: w, h = a + 2, b + 8
+        elif bit(8) == 0: w, h = a + 2, bit(7) + 6
+        else: w, h = bit(7) + 2, a + 2
+    if r < 2: return None
+    return w * h * (dual + 1), r - 2 + 6 * high
+def unsupported(block):
+    bits = int.from_bytes(block, 'little')
+    mode = bits & 0x7ff
+    if mode & 0x1ff == 0x1fc:
+        return mode >> 9 != 6
+    if weight_grid(mode) is None:
+        return False
+    count, r = weight_grid(mode)
+    parts = (bits >> 11 & 3) + 1
+    if parts == 1:
+        cems = [bits >> 13 & 15]
+    else:
+        field = bits >> 23 & 63
+        sel = field & 3
+        if sel == 0:
+            cems = [field >> 2] * parts
+        else:
+            extra = 3 * parts - 4
+            field |= (bits >> (128 - seq_bits(count, r) - extra) & ((1 << extra) - 1)) << 6
+            field >>= 2
+            cems = [(sel - 1 + (field >> i & 1)) << 2 | (field >> (parts + 2 * i) & 3) for i in range(parts)]
+    return any(c in (2, 3, 7, 11, 14, 15) for c in cems) or sum(c // 4 * 2 + 2 for c in cems) > 18
+
+for name, fmt, bw, bh in [('astc_4x4', '93B0', 4, 4), ('astc_5x4', '93B1', 5, 4), ('astc_6x6', '93B4', 6, 6), ('astc_8x8', '93B7', 8, 8), ('astc_12x12', '93BD', 12, 12)]:
+    def valid_of(pool):
+        n = len(pool) // 16
+        texels = decoded(fmt, 32 * bw, n // 32 * bh, pool)
+        def block_texels(i):
+            x, y = i % 32 * bw, i // 32 * bh
+            return b''.join(texels[((y + j) * 32 * bw + x) * 4:][:bw * 4] for j in range(bh))
+        return [i for i in range(n) if block_texels(i) != MAGENTA * (bw * bh)]
+    pool = rand_blocks(2048, 16)
+    seeds = [int.from_bytes(pool[i * 16:i * 16 + 2], 'little') & 0x1FFF for i in valid_of(pool)]
+    pool = bytearray(rand_blocks(2048, 16))
+    for i in range(2048):
+        low = int.from_bytes(pool[i * 16:i * 16 + 2], 'little') & ~0x1FFF | rng.choice(seeds)
+        pool[i * 16:i * 16 + 2] = low.to_bytes(2, 'little')
+    pool = bytes(pool)
+    decodes = set(valid_of(pool))
+    valid = [i for i in sorted(decodes) if not unsupported(pool[i * 16:(i + 1) * 16])]
+    invalid = [i for i in range(2048) if i not in decodes]
+    chosen = [pool[i * 16:(i + 1) * 16] for i in valid[:54] + invalid[:6]]
+    chosen += [void_extent() for _ in range(4)]
+    rng.shuffle(chosen)
+    blocks = b''.join(chosen)
+    w, h = 8 * bw, 8 * bh
+    open(f'{OUT}/{name}.bin', 'wb').write(blocks)
+    png(f'{OUT}/{name}.png', w, h, decoded(fmt, w, h, blocks))
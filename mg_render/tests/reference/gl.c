@@ -0,0 +1,55 @@
+// Compresses RGBA8 texels with Mesa's encoders, or decodes blocks to RGBA
+// f32 texels, through a surfaceless desktop GL context
+//
+//   gl enc <internal format in hex> <width> <height> < texels > blocks
+//   gl dec <internal format in hex> <width> <height> < blocks > texels
+#define GL_GLEXT_PROTOTYPES
+#include <EGL/egl.h>
+#include <EGL/eglext.h>
+#include <GL/gl.h>
+#include <GL/glext.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+static void ctx(void) {
+    PFNEGLGETPLATFORMDISPLAYEXTPROC gpd = (void *)eglGetProcAddress("eglGetPlatformDisplayEXT");
+    EGLDisplay d = gpd(EGL_PLATFORM_SURFACELESS_MESA, EGL_DEFAULT_DISPLAY, NULL);
+    if (!eglInitialize(d, NULL, NULL)) { fprintf(stderr, "init\n"); exit(1); }
+    eglBindAPI(EGL_OPENGL_API);
+    EGLContext c = eglCreateContext(d, EGL_NO_CONFIG_KHR, EGL_NO_CONTEXT, NULL);
+    if (!c) { fprintf(stderr, "context %x\n", eglGetError()); exit(1); }
+    if (!eglMakeCurrent(d, EGL_NO_SURFACE, EGL_NO_SURFACE, c)) { fprintf(stderr, "current\n"); exit(1); }
+}
+
+int main(int argc, char **argv) {
+    ctx();
+    if (argc > 1 && !strcmp(argv[1], "info")) {
+        printf("%s\n%s\n", glGetString(GL_RENDERER), glGetString(GL_VERSION));
+        return 0;
+    }
+    GLenum fmt = strtol(argv[2], NULL, 16);
+    int w = atoi(argv[3]), h = atoi(argv[4]);
+    static unsigned char in[1 << 24];
+    size_t len = fread(in, 1, sizeof in, stdin);
+    GLuint t; glGenTextures(1, &t); glBindTexture(GL_TEXTURE_2D, t);
+    glPixelStorei(GL_UNPACK_ALIGNMENT, 1); glPixelStorei(GL_PACK_ALIGNMENT, 1);
+    if (!strcmp(argv[1], "enc")) {
+        glTexImage2D(GL_TEXTURE_2D, 0, fmt, w, h, 0, GL_RGBA, GL_UNSIGNED_BYTE, in);
+        GLint comp = 0, size = 0;
+        glGetTexLevelParameteriv(GL_TEXTURE_2D, 0, GL_TEXTURE_COMPRESSED, &comp);
+        glGetTexLevelParameteriv(GL_TEXTURE_2D, 0, GL_TEXTURE_COMPRESSED_IMAGE_SIZE, &size);
+        if (!comp) { fprintf(stderr, "not compressed, err %x\n", glGetError()); return 1; }
+        unsigned char *out = malloc(size);
+        glGetCompressedTexImage(GL_TEXTURE_2D, 0, out);
+        fwrite(out, 1, size, stdout);
+    } else {
+        glCompressedTexImage2D(GL_TEXTURE_2D, 0, fmt, w, h, 0, len, in);
+        GLenum e = glGetError();
+        if (e) { fprintf(stderr, "upload err %x\n", e); return 1; }
+        float *out = malloc(w * h * 16);
+        glGetTexImage(GL_TEXTURE_2D, 0, GL_RGBA, GL_FLOAT, out);
+        fwrite(out, 4, w * h * 4, stdout);
+    }
+    return glGetError() != 0;
+}
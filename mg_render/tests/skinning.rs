@@ -0,0 +1,151 @@
+mod common;
+
+use base64::Engine;
+use common::{assert_near, load};
+use mg_core::*;
+use mg_render::{
+    cooked::{self, CookOptions},
+    gltf_loader::{self, GltfSrc, LoadOptions},
+    graphics::Graphics,
+    material,
+    model::Model,
+    scene::Scene,
+};
+
+// A triangle skinned to a hip and the knee below it, the last vertex
+// halfway between them. Joints are u16 and weights normalized u8 so
+// loading decodes both
+fn gltf() -> String {
+    let positions: [[f32; 3]; 3] = [[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+    let joints: [[u16; 4]; 3] = [[0; 4], [1, 0, 0, 0], [0, 1, 0, 0]];
+    let weights: [[u8; 4]; 3] = [[255, 0, 0, 0], [255, 0, 0, 0], [128, 127, 0, 0]];
+    let inverse_binds: [Mat4; 2] = [
+        Mat4::new_translation(&Vec3f::new(-1.0, 0.0, 0.0)),
+        Mat4::new_translation(&Vec3f::new(-1.0, -2.0, 0.0)),
+    ];
+    let mut bin = bytemuck::cast_slice::<_, u8>(&[0u16, 1, 2, 0]).to_vec();
+    bin.extend_from_slice(bytemuck::cast_slice(&positions));
+    bin.extend_from_slice(bytemuck::cast_slice(&joints));
+    bin.extend_from_slice(bytemuck::cast_slice(&weights));
+    for matrix in inverse_binds {
+        bin.extend_from_slice(bytemuck::cast_slice(matrix.as_slice()));
+    }
+    let base64 = base64::engine::general_purpose::STANDARD.encode(&bin);
+    format!(
+        r#"{{
+  "asset": {{"version": "2.0"}},
+  "nodes": [
+    {{"name": "body", "mesh": 0, "skin": 0, "translation": [0.0, 0.0, 5.0]}},
+    {{"name": "hip", "children": [2], "translation": [1.0, 0.0, 0.0]}},
+    {{"name": "knee", "translation": [0.0, 2.0, 0.0]}}
+  ],
+  "scenes": [{{"nodes": [0, 1]}}],
+  "skins": [{{"name": "legs", "joints": [1, 2], "inverseBindMatrices": 4}}],
+  "meshes": [{{"primitives": [{{
+    "attributes": {{"POSITION": 1, "JOINTS_0": 2, "WEIGHTS_0": 3}},
+    "indices": 0
+  }}]}}],
+  "buffers": [{{"byteLength": {len}, "uri": "data:application/octet-stream;base64,{base64}"}}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 8}},
+    {{"buffer": 0, "byteOffset": 8, "byteLength": 36}},
+    {{"buffer": 0, "byteOffset": 44, "byteLength": 24}},
+    {{"buffer": 0, "byteOffset": 68, "byteLength": 12}},
+    {{"buffer": 0, "byteOffset": 80, "byteLength": 128}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5123, "count": 3, "type": "SCALAR"}},
+    {{"bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3",
+      "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]}},
+    {{"bufferView": 2, "componentType": 5123, "count": 3, "type": "VEC4"}},
+    {{"bufferView": 3, "componentType": 5121, "count": 3, "type": "VEC4", "normalized": true}},
+    {{"bufferView": 4, "componentType": 5126, "count": 2, "type": "MAT4"}}
+  ]
+}}"#,
+        len = bin.len(),
+    )
+}
+
+#[test]
+fn attributes() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let model = load(&graphics, &gltf()).unwrap();
+    let geometry = &model.meshes[0].geometry;
+    assert!(geometry.is_skinned());
+    let bin = &geometry.buffer.bin;
+    let slice = |range: std::ops::Range<u64>| &bin[range.start as usize..range.end as usize];
+    let joints: &[[u32; 4]] = bytemuck::cast_slice(slice(geometry.ranges.joints()));
+    let weights: &[[f32; 4]] = bytemuck::cast_slice(slice(geometry.ranges.weights()));
+    // flat normals unweld the vertices, in index order here
+    assert_eq!(joints, [[0; 4], [1, 0, 0, 0], [0, 1, 0, 0]]);
+    assert_eq!(weights[0], [1.0, 0.0, 0.0, 0.0]);
+    assert_eq!(weights[2], [128.0 / 255.0, 127.0 / 255.0, 0.0, 0.0]);
+
+    assert_eq!(model.nodes[0].skin, Some(0));
+    assert_eq!(model.nodes[1].skin, None);
+    let skin = &model.skins[0];
+    assert_eq!(skin.name, "legs");
+    assert_eq!(skin.joints, [1, 2]);
+    assert_near(
+        &skin.inverse_binds[1],
+        &Mat4::new_translation(&Vec3f::new(-1.0, -2.0, 0.0)),
+        1e-5,
+    );
+}
+
+#[test]
+fn cooked() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let defaults = material::Defaults::new(&graphics);
+    let json = gltf();
+    let gltf = load(&graphics, &json).unwrap();
+    let prepared = gltf_loader::prepare(GltfSrc::Slice(json.as_bytes()), &LoadOptions::default());
+    let bytes = cooked::cook(&prepared.unwrap(), &CookOptions::default()).unwrap();
+    let model = cooked::load(&graphics, &defaults, "test.mgc", bytes.into()).unwrap();
+
+    let bin = |model: &Model, range: std::ops::Range<u64>| {
+        model.meshes[0].geometry.buffer.bin[range.start as usize..range.end as usize].to_vec()
+    };
+    let (ranges, expected) = (
+        model.meshes[0].geometry.ranges,
+        gltf.meshes[0].geometry.ranges,
+    );
+    assert_eq!(bin(&model, ranges.joints()), bin(&gltf, expected.joints()));
+    assert_eq!(bin(&model, ranges.weights()), bin(&gltf, expected.weights()));
+    assert_eq!(model.nodes[0].skin, Some(0));
+    assert_eq!(model.skins[0].name, "legs");
+    assert_eq!(model.skins[0].joints, gltf.skins[0].joints);
+    assert_eq!(model.skins[0].inverse_binds, gltf.skins[0].inverse_binds);
+}
+
+#[test]
+fn palette() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let model = load(&graphics, &gltf()).unwrap();
+    let mut scene = Scene::new(&graphics);
+    let nodes = scene.instantiate_model(&graphics, &model);
+    assert_eq!(scene.skins.len(), 1);
+    assert!(scene.inst_props[0].deformed.is_some());
+    scene.update(&graphics);
+
+    // at rest only the body's translation is undone, skinned vertices
+    // ignore their node's transform and the instance transform reapplies it
+    let undo_body = Mat4::new_translation(&Vec3f::new(0.0, 0.0, -5.0));
+    for matrix in &scene.skins[0].palette {
+        assert_near(matrix, &undo_body, 1e-5);
+    }
+
+    // bending the knee moves only the vertices weighted to it
+    let knee = nodes[model.find_node("knee").unwrap()];
+    let bend = Mat4::from_euler_angles(0.0, 0.0, FRAC_PI_2);
+    scene.set_node_tsf(knee, scene.node_tsf(knee) * bend);
+    scene.update(&graphics);
+    let palette = &scene.skins[0].palette;
+    assert_near(&palette[0], &undo_body, 1e-5);
+    let knee_world = Mat4::new_translation(&Vec3f::new(1.0, 2.0, 0.0)) * bend;
+    assert_near(
+        &palette[1],
+        &(undo_body * knee_world * model.skins[0].inverse_binds[1]),
+        1e-5,
+    );
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::assert_near;
+use mg_render::tangent_space;
+
+const QUAD_INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+const QUAD_VERTICES: [[f32; 3]; 4] = [
+    [-1.0, -1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+];
+const QUAD_UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+
+#[test]
+fn flat_normals() {
+    // a ridge, both faces leaning away from each other
+    let vertices = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 1.0],
+        [0.0, 1.0, 0.0],
+        [1.0, 0.0, 1.0],
+        [2.0, 0.0, 0.0],
+        [1.0, 1.0, 1.0],
+    ];
+    let normals = tangent_space::flat_normals(&vertices);
+    let s = std::f32::consts::FRAC_1_SQRT_2;
+    for normal in &normals[..3] {
+        assert_near(normal, &[-s, 0.0, s], 1e-5);
+    }
+    for normal in &normals[3..] {
+        assert_near(normal, &[s, 0.0, s], 1e-5);
+    }
+}
+
+#[test]
+fn smooth_normals() {
+    let normals = tangent_space::smooth_normals(&QUAD_VERTICES, &QUAD_INDICES);
+    for normal in normals {
+        assert_near(&normal, &[0.0, 0.0, 1.0], 1e-5);
+    }
+
+    // a ridge sharing its top edge averages to straight up there
+    let vertices = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 1.0],
+        [1.0, 1.0, 1.0],
+        [2.0, 0.0, 0.0],
+    ];
+    let normals = tangent_space::smooth_normals(&vertices, &[0, 1, 2, 1, 3, 2]);
+    assert_near(&normals[1], &[0.0, 0.0, 1.0], 1e-5);
+}
+
+#[test]
+fn unweld() {
+    let unwelded = tangent_space::unweld(&QUAD_VERTICES, &QUAD_INDICES);
+    assert_eq!(unwelded.len(), 6);
+    assert_eq!(unwelded[3], QUAD_VERTICES[0]);
+    assert_eq!(unwelded[5], QUAD_VERTICES[3]);
+}
+
+#[test]
+fn tangents() {
+    let normals = [[0.0, 0.0, 1.0]; 4];
+    let tangents = tangent_space::tangents(&QUAD_VERTICES, &normals, &QUAD_UVS, &QUAD_INDICES);
+    for tangent in tangents.tangents {
+        assert_near(&tangent, &[1.0, 0.0, 0.0, 1.0], 1e-5);
+    }
+
+    // mirrored uvs flip the tangent and the bitangent sign
+    let mirrored = QUAD_UVS.map(|[u, v]| [1.0 - u, v]);
+    let tangents = tangent_space::tangents(&QUAD_VERTICES, &normals, &mirrored, &QUAD_INDICES);
+    for tangent in tangents.tangents {
+        assert_near(&tangent, &[-1.0, 0.0, 0.0, -1.0], 1e-5);
+    }
+
+    // without uvs tangents still come out perpendicular to the normal
+    let tangents = tangent_space::tangents(&QUAD_VERTICES, &normals, &[[0.0; 2]; 4], &QUAD_INDICES);
+    for tangent in tangents.tangents {
+        assert!(tangent[2].abs() < 1e-5);
+        assert!((tangent[0].hypot(tangent[1]) - 1.0).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn split_tangents() {
+    // a quad keeps its vertices
+    let normals = [[0.0, 0.0, 1.0]; 4];
+    let tangents = tangent_space::tangents(&QUAD_VERTICES, &normals, &QUAD_UVS, &QUAD_INDICES);
+    assert_eq!(tangents.vertices, [0, 1, 2, 3]);
+    assert_eq!(tangents.indices, QUAD_INDICES);
+
+    // two triangles sharing an edge whose uvs mirror across it split the
+    // edge's vertices, one copy per side
+    let vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [-1.0, 0.0, 0.0]];
+    let uvs = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 0.0]];
+    let normals = [[0.0, 0.0, 1.0]; 4];
+    let tangents = tangent_space::tangents(&vertices, &normals, &uvs, &[0, 1, 2, 0, 2, 3]);
+    assert_eq!(tangents.vertices, [0, 1, 2, 3, 0, 2]);
+    assert_eq!(tangents.indices, [0, 1, 2, 4, 5, 3]);
+    for &i in &[0, 1, 2] {
+        assert_near(&tangents.tangents[i], &[1.0, 0.0, 0.0, -1.0], 1e-5);
+    }
+    for &i in &[3, 4, 5] {
+        assert_near(&tangents.tangents[i], &[-1.0, 0.0, 0.0, 1.0], 1e-5);
+    }
+}
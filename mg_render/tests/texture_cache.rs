@@ -0,0 +1,42 @@
+mod common;
+
+use common::solid_png;
+use mg_core::*;
+use mg_render::{
+    graphics::Graphics,
+    texture::{ColorSpace, TextureOptions},
+};
+
+#[test]
+fn color_spaces() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let png = solid_png(2, [128, 128, 255, 255]);
+    let srgb = TextureOptions::default();
+    let linear = TextureOptions {
+        color_space: ColorSpace::Linear,
+        ..Default::default()
+    };
+    let color = graphics.image_texture("color", &png, &srgb).unwrap();
+    let data = graphics.image_texture("data", &png, &linear).unwrap();
+    assert_eq!(color.texture.format(), wgpu::TextureFormat::Rgba8UnormSrgb);
+    assert_eq!(data.texture.format(), wgpu::TextureFormat::Rgba8Unorm);
+    assert_eq!(graphics.textures.len(), 2);
+
+    // the same image and options share a texture whatever its name
+    let again = graphics.image_texture("again", &png, &linear).unwrap();
+    assert!(Arc::ptr_eq(&data, &again));
+    let other = graphics.image_texture("other", &solid_png(2, [0; 4]), &linear).unwrap();
+    assert!(!Arc::ptr_eq(&data, &other));
+}
+
+#[test]
+fn unused_textures_are_dropped() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let png = solid_png(2, [255; 4]);
+    let options = TextureOptions::default();
+    drop(graphics.image_texture("white", &png, &options).unwrap());
+    assert!(graphics.textures.is_empty());
+    let texture = graphics.image_texture("white", &png, &options).unwrap();
+    assert_eq!(Arc::strong_count(&texture), 1);
+    assert_eq!(graphics.textures.len(), 1);
+}
@@ -0,0 +1,234 @@
+mod common;
+
+use common::solid_png;
+use mg_core::*;
+use mg_render::{
+    capture,
+    graphics::Graphics,
+    sampler::SamplerDesc,
+    texture::{ColorSpace, Texture},
+    volume_bind_group_layout,
+};
+
+const SAMPLE_VOLUME: &str = "
+@group(0) @binding(0) var t_volume: texture_3d<f32>;
+@group(0) @binding(1) var s_volume: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4f {
+  var pos = array(vec2(-1.0, -1.0), vec2(3.0, -1.0), vec2(-1.0, 3.0));
+  return vec4f(pos[i], 0.0, 1.0);
+}
+
+// slices side by side
+@fragment
+fn fs_main(@builtin(position) pos: vec4f) -> @location(0) vec4f {
+  let size = vec3f(textureDimensions(t_volume));
+  let x = floor(pos.x);
+  let uvw = vec3(x % size.x + 0.5, pos.y, floor(x / size.x) + 0.5) / size;
+  return textureSampleLevel(t_volume, s_volume, uvw, 0.0);
+}
+";
+
+// Renders every voxel through volume_bind_group_layout, slices side by side
+fn sample_volume(graphics: &Graphics, volume: &Texture) -> image::RgbaImage {
+    let device = &graphics.device;
+    let size = volume.texture.size();
+    let format = wgpu::TextureFormat::Rgba8Unorm;
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: size.width * size.depth_or_array_layers,
+            height: size.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(SAMPLE_VOLUME.into()),
+    });
+    let layout = volume_bind_group_layout(graphics);
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(
+            &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            }),
+        ),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(format.into())],
+        }),
+        primitive: Default::default(),
+        depth_stencil: None,
+        multisample: Default::default(),
+        multiview: None,
+    });
+    let sampler = graphics.sampler(SamplerDesc {
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&volume.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            },
+        ],
+    });
+    let view = target.create_view(&Default::default());
+    let mut encoder = device.create_command_encoder(&Default::default());
+    {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: Default::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+    graphics.queue.submit(Some(encoder.finish()));
+    capture::capture_texture(graphics, &target).unwrap()
+}
+
+#[test]
+fn voxels() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let size = wgpu::Extent3d {
+        width: 2,
+        height: 2,
+        depth_or_array_layers: 2,
+    };
+    // each voxel's red channel is its index
+    let voxels = (0..8u8)
+        .flat_map(|i| [i * 32, 0, 0, 255])
+        .collect::<Vec<_>>();
+    let volume = Texture::create_volume_texture(
+        &graphics,
+        "volume",
+        wgpu::TextureFormat::Rgba8Unorm,
+        size,
+        &voxels,
+    )
+    .unwrap();
+    let sampled = sample_volume(&graphics, &volume);
+    assert_eq!(sampled.dimensions(), (4, 2));
+    assert_eq!(sampled.get_pixel(1, 0).0, [32, 0, 0, 255]);
+    assert_eq!(sampled.get_pixel(2, 1).0, [192, 0, 0, 255]);
+
+    // x 1, y 0, z 1 is edited in place
+    let region = wgpu::Extent3d {
+        width: 1,
+        height: 1,
+        depth_or_array_layers: 1,
+    };
+    let origin = wgpu::Origin3d { x: 1, y: 0, z: 1 };
+    volume
+        .write_region(&graphics, origin, region, &[0, 255, 0, 255])
+        .unwrap();
+    let sampled = sample_volume(&graphics, &volume);
+    assert_eq!(sampled.get_pixel(3, 0).0, [0, 255, 0, 255]);
+    assert_eq!(sampled.get_pixel(2, 0).0, [128, 0, 0, 255]);
+
+    let outside = wgpu::Origin3d { x: 1, y: 0, z: 2 };
+    assert!(volume
+        .write_region(&graphics, outside, region, &[0; 4])
+        .is_err());
+    assert!(volume
+        .write_region(&graphics, origin, region, &[0; 3])
+        .is_err());
+    // origins near u32::MAX don't wrap back inside
+    let wrapping = wgpu::Origin3d {
+        x: u32::MAX,
+        y: 0,
+        z: 0,
+    };
+    let wide = wgpu::Extent3d { width: 2, ..region };
+    assert!(volume
+        .write_region(&graphics, wrapping, wide, &[0; 8])
+        .is_err());
+
+    let err = Texture::create_volume_texture(
+        &graphics,
+        "short",
+        wgpu::TextureFormat::Rgba8Unorm,
+        size,
+        &voxels[4..],
+    )
+    .err()
+    .unwrap();
+    assert!(matches!(
+        err.downcast_ref::<AssetError>(),
+        Some(AssetError::BadImage { .. })
+    ));
+    let empty = wgpu::Extent3d {
+        depth_or_array_layers: 0,
+        ..size
+    };
+    let err = Texture::create_volume_texture(
+        &graphics,
+        "empty",
+        wgpu::TextureFormat::Rgba8Unorm,
+        empty,
+        &[],
+    )
+    .err()
+    .unwrap();
+    assert!(matches!(
+        err.downcast_ref::<AssetError>(),
+        Some(AssetError::BadImage { .. })
+    ));
+}
+
+#[test]
+fn slices() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let slices = [
+        solid_png(2, [255, 0, 0, 255]),
+        solid_png(2, [0, 0, 255, 255]),
+    ];
+    let slices = slices.iter().map(|s| &s[..]).collect::<Vec<_>>();
+    let volume =
+        Texture::create_volume_slices(&graphics, "slices", &slices, ColorSpace::Linear).unwrap();
+    assert_eq!(volume.texture.dimension(), wgpu::TextureDimension::D3);
+    assert_eq!(volume.texture.format(), wgpu::TextureFormat::Rgba8Unorm);
+    let sampled = sample_volume(&graphics, &volume);
+    assert_eq!(sampled.get_pixel(1, 1).0, [255, 0, 0, 255]);
+    assert_eq!(sampled.get_pixel(2, 1).0, [0, 0, 255, 255]);
+
+    let err = Texture::create_volume_slices(&graphics, "none", &[], ColorSpace::Linear)
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err.downcast_ref::<AssetError>(),
+        Some(AssetError::BadImage { .. })
+    ));
+}
@@ -0,0 +1,260 @@
+use mg_render::{
+    camera::Camera, g_buffer, global_illumination::ray_buffer, graphics::Graphics, light, mesh,
+    texture_bind_group_layout, volume_bind_group_layout, wgsl::Preprocessor,
+};
+
+#[test]
+fn defines() {
+    let shaders = Preprocessor::default();
+    let source = "#define SIZE 4u\n#define FLAG\nconst a = SIZE;\nconst SIZE_2 = 2SIZE;\n";
+    let out = shaders.preprocess("test.wgsl", source, &[]).unwrap();
+    assert_eq!(out, "const a = 4u;\nconst SIZE_2 = 2SIZE;\n");
+
+    // passed defines apply like #define at the top
+    let out = shaders
+        .preprocess("test.wgsl", "let g = GROUP;\n", &[("GROUP", "2")])
+        .unwrap();
+    assert_eq!(out, "let g = 2;\n");
+}
+
+#[test]
+fn conditionals() {
+    let shaders = Preprocessor::default();
+    let source = "
+#ifdef A
+a
+#ifndef B
+a_not_b
+#else
+a_b
+#endif
+#else
+not_a
+#define C
+#endif
+#ifdef C
+c
+#endif
+";
+    let lines = |defines: &[(&str, &str)]| {
+        let out = shaders.preprocess("test.wgsl", source, defines).unwrap();
+        out.split_whitespace()
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(lines(&[]), ["not_a", "c"]);
+    assert_eq!(lines(&[("A", "")]), ["a", "a_not_b"]);
+    assert_eq!(lines(&[("A", ""), ("B", "")]), ["a", "a_b"]);
+}
+
+#[test]
+fn includes() {
+    let mut shaders = Preprocessor::default();
+    shaders.add_module("a.wgsl", "#include \"b.wgsl\"\nfn a() {}");
+    shaders.add_module("b.wgsl", "#include \"a.wgsl\"\nfn b() {}");
+    let source = "#include \"a.wgsl\"\n#include \"b.wgsl\"\nfn main() {}";
+    let out = shaders.preprocess("test.wgsl", source, &[]).unwrap();
+    assert_eq!(out, "fn b() {}\nfn a() {}\nfn main() {}\n");
+
+    // a module's default group gives way to one defined before it
+    let source = "#define CAMERA_GROUP 3\n#include \"camera.wgsl\"";
+    let out = shaders.preprocess("test.wgsl", source, &[]).unwrap();
+    assert!(
+        out.contains("@group(3) @binding(0) var<uniform> camera"),
+        "{}",
+        out
+    );
+}
+
+#[test]
+fn errors() {
+    let shaders = Preprocessor::default();
+    let error = |source| {
+        let err = shaders.preprocess("test.wgsl", source, &[]).unwrap_err();
+        err.to_string()
+    };
+    assert_eq!(
+        error("\n#include \"missing.wgsl\""),
+        "test.wgsl:2: no module \"missing.wgsl\""
+    );
+    assert_eq!(
+        error("#include camera.wgsl"),
+        "test.wgsl:1: #include needs a quoted module name"
+    );
+    assert_eq!(
+        error("#pragma once"),
+        "test.wgsl:1: unknown directive #pragma"
+    );
+    assert_eq!(error("#define 1-2"), "test.wgsl:1: bad #define \"1-2\"");
+    assert_eq!(error("#ifdef A\n"), "test.wgsl: #ifdef without #endif");
+    assert_eq!(error("#endif"), "test.wgsl:1: #endif without #ifdef");
+    assert_eq!(error("#else"), "test.wgsl:1: #else without #ifdef");
+
+    // skipped lines still need balanced blocks, not known directives
+    let out = shaders.preprocess("test.wgsl", "#ifdef A\n#pragma\n#endif", &[]);
+    assert_eq!(out.unwrap(), "");
+}
+
+// Statically uses every binding of the compute modules
+const COMPUTE: &str = "
+#include \"ray_buffer.wgsl\"
+#include \"mesh.wgsl\"
+#include \"g_buffer_write.wgsl\"
+
+@compute @workgroup_size(1)
+fn main() {
+  _ = textureLoad(origin_tx, vec2u(0u), 0);
+  _ = textureLoad(direction_tx, vec2u(0u), 0);
+  _ = accel_struct_buf[0];
+  _ = inst_buf[0];
+  _ = inst_range.start;
+  _ = g_ranges.ind_size;
+  _ = mesh_buf[0];
+  _ = vert_buf[0];
+  _ = textureSampleLevel(emissive_tx, emissive_s, vec2f(0.0), 0.0);
+  textureStore(g_albedo_stx, vec2u(0u), vec4f(0.0));
+  textureStore(g_emissive_stx, vec2u(0u), vec4f(0.0));
+  textureStore(g_position_stx, vec2u(0u), vec4f(0.0));
+  textureStore(g_normal_stx, vec2u(0u), vec4f(0.0));
+}
+";
+
+// Statically uses every binding of the render modules
+const RENDER: &str = "
+#include \"camera.wgsl\"
+#include \"material.wgsl\"
+#include \"g_buffer_read.wgsl\"
+#include \"volume.wgsl\"
+
+@vertex
+fn vs_main() -> @builtin(position) vec4f {
+  return camera.view_proj * vec4f(0.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4f {
+  let uv = vec2f(0.0);
+  return textureSample(t_albedo, s_albedo, uv)
+    + textureSample(t_emission, s_emission, uv)
+    + textureSample(t_metallic_roughness, s_metallic_roughness, uv)
+    + textureSample(t_normal, s_normal, uv)
+    + textureSample(t_occlusion, s_occlusion, uv)
+    + vec4f(f32(material.alpha_mode))
+    + textureLoad(g_albedo_tx, vec2u(0u), 0)
+    + textureLoad(g_emissive_tx, vec2u(0u), 0)
+    + textureLoad(g_position_tx, vec2u(0u), 0)
+    + textureLoad(g_normal_tx, vec2u(0u), 0)
+    + textureSample(volume_tx, volume_s, vec3f(0.0));
+}
+";
+
+// Modules past the four groups RENDER can bind
+const LIGHTING: &str = "
+#include \"light.wgsl\"
+
+@vertex
+fn vs_main() -> @builtin(position) vec4f {
+  return vec4f(0.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4f {
+  return vec4f(lights.eye, f32(lights.amt + lights.lights[0].kind));
+}
+";
+
+fn validate(graphics: &Graphics, build: impl FnOnce()) -> Option<wgpu::Error> {
+    graphics
+        .device
+        .push_error_scope(wgpu::ErrorFilter::Validation);
+    build();
+    pollster::block_on(graphics.device.pop_error_scope())
+}
+
+// The modules' declarations match the Rust layouts of their groups
+#[test]
+fn modules_match_layouts() {
+    let graphics = pollster::block_on(Graphics::headless(4, 4));
+    let device = &graphics.device;
+    let shaders = Preprocessor::default();
+    let module = |source: &str, defines: &[(&str, &str)]| {
+        let source = shaders.preprocess("test.wgsl", source, defines).unwrap();
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        })
+    };
+
+    let defines = [
+        ("RAY_BUFFER_GROUP", "0"),
+        ("MESH_GROUP", "1"),
+        ("G_BUFFER_WRITE_GROUP", "2"),
+    ];
+    let layouts = [
+        ray_buffer::read_bind_group_layout(&graphics),
+        mesh::bind_group_layout(&graphics),
+        g_buffer::write_bind_group_layout(&graphics),
+    ];
+    let err = validate(&graphics, || {
+        let module = module(COMPUTE, &defines);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &layouts.each_ref(),
+            push_constant_ranges: &[],
+        });
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: Some(&layout),
+            module: &module,
+            entry_point: "main",
+        });
+    });
+    assert!(err.is_none(), "{}", err.unwrap());
+
+    let defines = [
+        ("CAMERA_GROUP", "0"),
+        ("MATERIAL_GROUP", "1"),
+        ("G_BUFFER_READ_GROUP", "2"),
+        ("VOLUME_GROUP", "3"),
+    ];
+    let layouts = [
+        Camera::bind_group_layout(&graphics),
+        texture_bind_group_layout(&graphics),
+        g_buffer::read_bind_group_layout(&graphics),
+        volume_bind_group_layout(&graphics),
+    ];
+    let render = |source: &str, defines: &[(&str, &str)], layouts: &[&wgpu::BindGroupLayout]| {
+        let module = module(source, defines);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: layouts,
+            push_constant_ranges: &[],
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::TextureFormat::Rgba8Unorm.into())],
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+        });
+    };
+    let err = validate(&graphics, || render(RENDER, &defines, &layouts.each_ref()));
+    assert!(err.is_none(), "{}", err.unwrap());
+
+    let layout = light::bind_group_layout(&graphics);
+    let err = validate(&graphics, || {
+        render(LIGHTING, &[("LIGHT_GROUP", "0")], &[&layout])
+    });
+    assert!(err.is_none(), "{}", err.unwrap());
+}
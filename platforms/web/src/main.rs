@@ -10,6 +10,7 @@ async fn main() -> std::io::Result<()> {
 
     HttpServer::new(|| {
         App::new()
+            .service(Files::new("/assets", "../../assets"))
             .service(
                 Files::new("/", "fe")
                     .use_hidden_files()